@@ -0,0 +1,7 @@
+-- Add migration script here
+CREATE TABLE user_identities(
+  issuer TEXT NOT NULL,
+  subject TEXT NOT NULL,
+  user_id uuid NOT NULL REFERENCES users (user_id),
+  PRIMARY KEY (issuer, subject)
+);
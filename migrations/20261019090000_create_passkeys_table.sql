@@ -0,0 +1,10 @@
+-- Add migration script here
+CREATE TABLE passkeys(
+  id uuid PRIMARY KEY,
+  user_id uuid NOT NULL REFERENCES users (user_id),
+  credential_id BYTEA NOT NULL UNIQUE,
+  name TEXT NOT NULL,
+  passkey TEXT NOT NULL,
+  created_at timestamptz NOT NULL,
+  last_used_at timestamptz NULL
+);
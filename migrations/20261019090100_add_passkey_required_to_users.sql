@@ -0,0 +1,2 @@
+-- Add migration script here
+ALTER TABLE users ADD COLUMN passkey_required BOOLEAN NOT NULL DEFAULT false;
@@ -0,0 +1,21 @@
+-- Add migration script here
+CREATE TABLE audit_events(
+  id uuid PRIMARY KEY,
+  occurred_at timestamptz NOT NULL,
+  actor_user_id uuid NULL REFERENCES users (user_id),
+  action TEXT NOT NULL,
+  target TEXT NULL,
+  ip_address TEXT NULL
+);
+CREATE INDEX audit_events_occurred_at_idx ON audit_events (occurred_at);
+
+-- Audit events are append-only.
+CREATE FUNCTION reject_audit_event_changes() RETURNS trigger AS $$
+BEGIN
+  RAISE EXCEPTION 'audit_events is append-only';
+END;
+$$ LANGUAGE plpgsql;
+
+CREATE TRIGGER audit_events_append_only
+  BEFORE UPDATE OR DELETE ON audit_events
+  FOR EACH ROW EXECUTE FUNCTION reject_audit_event_changes();
@@ -0,0 +1,20 @@
+-- Add migration script here
+CREATE TABLE subscriber_imports(
+    id uuid NOT NULL PRIMARY KEY,
+    created_at timestamptz NOT NULL,
+    imported_by uuid NOT NULL REFERENCES users (user_id),
+    pre_confirmed BOOLEAN NOT NULL,
+    imported_count INTEGER NOT NULL,
+    rejected_count INTEGER NOT NULL
+);
+
+CREATE TABLE subscriber_import_rejections(
+    import_id uuid NOT NULL REFERENCES subscriber_imports (id) ON DELETE CASCADE,
+    row_number BIGINT NOT NULL,
+    email TEXT NOT NULL,
+    name TEXT NOT NULL,
+    reason TEXT NOT NULL
+);
+
+CREATE INDEX subscriber_import_rejections_import_id_idx
+    ON subscriber_import_rejections (import_id);
@@ -0,0 +1,17 @@
+-- Add migration script here
+CREATE TABLE newsletter_issues(
+    id uuid NOT NULL PRIMARY KEY,
+    title TEXT NOT NULL,
+    text_content TEXT NOT NULL,
+    html_content TEXT NOT NULL,
+    published_at timestamptz NOT NULL,
+    published_by uuid REFERENCES users (user_id)
+);
+
+CREATE TABLE issue_deliveries(
+    issue_id uuid NOT NULL REFERENCES newsletter_issues (id),
+    subscriber_id uuid NOT NULL REFERENCES subscriptions (id) ON DELETE CASCADE,
+    status TEXT NOT NULL,
+    attempted_at timestamptz NOT NULL,
+    PRIMARY KEY (issue_id, subscriber_id)
+);
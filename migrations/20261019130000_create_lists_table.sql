@@ -0,0 +1,34 @@
+CREATE TABLE lists(
+    id uuid NOT NULL PRIMARY KEY,
+    slug TEXT NOT NULL UNIQUE,
+    name TEXT NOT NULL,
+    created_at timestamptz NOT NULL
+);
+
+-- The list everyone was on before lists existed.
+INSERT INTO lists (id, slug, name, created_at)
+VALUES ('3f8d2c1e-6b7a-4e59-9c0d-1a2b3c4d5e6f', 'newsletter', 'Newsletter', now());
+
+CREATE TABLE list_memberships(
+    list_id uuid NOT NULL REFERENCES lists (id) ON DELETE CASCADE,
+    subscriber_id uuid NOT NULL REFERENCES subscriptions (id) ON DELETE CASCADE,
+    status TEXT NOT NULL,
+    subscribed_at timestamptz NOT NULL,
+    PRIMARY KEY (list_id, subscriber_id)
+);
+
+CREATE INDEX list_memberships_subscriber_id_idx ON list_memberships (subscriber_id);
+
+INSERT INTO list_memberships (list_id, subscriber_id, status, subscribed_at)
+SELECT '3f8d2c1e-6b7a-4e59-9c0d-1a2b3c4d5e6f', id, status, subscribed_at
+FROM subscriptions;
+
+ALTER TABLE subscription_tokens ADD COLUMN list_id uuid NULL REFERENCES lists (id) ON DELETE CASCADE;
+UPDATE subscription_tokens SET list_id = '3f8d2c1e-6b7a-4e59-9c0d-1a2b3c4d5e6f';
+ALTER TABLE subscription_tokens ALTER COLUMN list_id SET NOT NULL;
+
+CREATE TABLE issue_lists(
+    issue_id uuid NOT NULL REFERENCES newsletter_issues (id),
+    list_id uuid NOT NULL REFERENCES lists (id),
+    PRIMARY KEY (issue_id, list_id)
+);
@@ -0,0 +1,15 @@
+CREATE TABLE custom_fields(
+    id uuid NOT NULL PRIMARY KEY,
+    key TEXT NOT NULL UNIQUE,
+    label TEXT NOT NULL,
+    kind TEXT NOT NULL,
+    choices TEXT[] NOT NULL DEFAULT '{}',
+    created_at timestamptz NOT NULL
+);
+
+CREATE TABLE subscriber_field_values(
+    subscriber_id uuid NOT NULL REFERENCES subscriptions (id) ON DELETE CASCADE,
+    field_id uuid NOT NULL REFERENCES custom_fields (id) ON DELETE CASCADE,
+    value TEXT NOT NULL,
+    PRIMARY KEY (subscriber_id, field_id)
+);
@@ -0,0 +1,2 @@
+-- Subscribers can pause delivery from their preference page.
+ALTER TABLE subscriptions ADD COLUMN paused_until timestamptz NULL;
@@ -0,0 +1,2 @@
+-- The Markdown an issue was written in, if any; html and text are generated from it.
+ALTER TABLE newsletter_issues ADD COLUMN markdown_content TEXT NULL;
@@ -0,0 +1,14 @@
+-- Sent issues are published at /issues/{slug} unless an admin hides them.
+ALTER TABLE newsletter_issues
+    ADD COLUMN slug TEXT NULL,
+    ADD COLUMN hidden_from_archive BOOLEAN NOT NULL DEFAULT false;
+
+UPDATE newsletter_issues
+SET slug = coalesce(
+        nullif(trim(both '-' from lower(regexp_replace(title, '[^a-zA-Z0-9]+', '-', 'g'))), ''),
+        'issue'
+    ) || '-' || left(id::text, 8);
+
+ALTER TABLE newsletter_issues
+    ALTER COLUMN slug SET NOT NULL,
+    ADD CONSTRAINT newsletter_issues_slug_key UNIQUE (slug);
@@ -0,0 +1,4 @@
+-- When an issue last changed in a way readers of the archive or feeds can see.
+ALTER TABLE newsletter_issues ADD COLUMN updated_at timestamptz NULL;
+UPDATE newsletter_issues SET updated_at = published_at;
+ALTER TABLE newsletter_issues ALTER COLUMN updated_at SET NOT NULL;
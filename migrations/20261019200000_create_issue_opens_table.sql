@@ -0,0 +1,11 @@
+ALTER TABLE newsletter_issues ADD COLUMN track_opens BOOLEAN NOT NULL DEFAULT false;
+
+-- One row per recipient who opened an issue, however many times they did.
+CREATE TABLE issue_opens(
+    issue_id uuid NOT NULL REFERENCES newsletter_issues (id),
+    subscriber_id uuid NOT NULL REFERENCES subscriptions (id) ON DELETE CASCADE,
+    first_opened_at timestamptz NOT NULL,
+    last_opened_at timestamptz NOT NULL,
+    open_count INTEGER NOT NULL,
+    PRIMARY KEY (issue_id, subscriber_id)
+);
@@ -0,0 +1,11 @@
+ALTER TABLE newsletter_issues ADD COLUMN track_clicks BOOLEAN NOT NULL DEFAULT false;
+
+CREATE TABLE issue_clicks(
+    id BIGSERIAL PRIMARY KEY,
+    issue_id uuid NOT NULL REFERENCES newsletter_issues (id),
+    subscriber_id uuid NOT NULL REFERENCES subscriptions (id) ON DELETE CASCADE,
+    url TEXT NOT NULL,
+    clicked_at timestamptz NOT NULL
+);
+
+CREATE INDEX issue_clicks_issue_id_idx ON issue_clicks (issue_id);
@@ -0,0 +1,27 @@
+CREATE TABLE webhook_endpoints(
+    id uuid NOT NULL PRIMARY KEY,
+    url TEXT NOT NULL,
+    -- Payloads are signed with this, so receivers can tell they came from us.
+    secret TEXT NOT NULL,
+    events TEXT[] NOT NULL,
+    created_at timestamptz NOT NULL
+);
+
+-- The queue of payloads to send, and a log of how sending them went.
+CREATE TABLE webhook_deliveries(
+    id uuid NOT NULL PRIMARY KEY,
+    endpoint_id uuid NOT NULL REFERENCES webhook_endpoints (id) ON DELETE CASCADE,
+    event TEXT NOT NULL,
+    payload TEXT NOT NULL,
+    status TEXT NOT NULL,
+    attempts INTEGER NOT NULL,
+    next_attempt_at timestamptz NOT NULL,
+    last_attempt_at timestamptz NULL,
+    last_response_status INTEGER NULL,
+    last_error TEXT NULL,
+    created_at timestamptz NOT NULL
+);
+
+CREATE INDEX webhook_deliveries_pending_idx ON webhook_deliveries (next_attempt_at)
+    WHERE status = 'pending';
+CREATE INDEX webhook_deliveries_endpoint_id_idx ON webhook_deliveries (endpoint_id, created_at);
@@ -0,0 +1,7 @@
+-- When the address was first confirmed, for the dashboard's growth charts.
+-- Confirmations from before this was recorded are dated to the signup.
+ALTER TABLE subscriptions ADD COLUMN confirmed_at timestamptz NULL;
+UPDATE subscriptions SET confirmed_at = subscribed_at WHERE status = 'confirmed';
+
+CREATE INDEX subscriptions_subscribed_at_idx ON subscriptions (subscribed_at);
+CREATE INDEX subscriptions_confirmed_at_idx ON subscriptions (confirmed_at);
@@ -0,0 +1,4 @@
+-- Issues are only published once an admin chooses to, since they may have
+-- been sent to a segment or a private list.
+ALTER TABLE newsletter_issues ALTER COLUMN hidden_from_archive SET DEFAULT true;
+UPDATE newsletter_issues SET hidden_from_archive = true;
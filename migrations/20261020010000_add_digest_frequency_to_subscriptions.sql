@@ -0,0 +1,14 @@
+-- Subscribers on a weekly or monthly digest get the issues sent since their
+-- last digest in one email instead of one email each.
+ALTER TABLE subscriptions
+    ADD COLUMN digest_frequency TEXT NOT NULL DEFAULT 'immediate'
+        CHECK (digest_frequency IN ('immediate', 'weekly', 'monthly')),
+    ADD COLUMN last_digest_at timestamptz NULL;
+
+-- Issues waiting for a subscriber's next digest.
+CREATE TABLE digest_items(
+    subscriber_id uuid NOT NULL REFERENCES subscriptions (id) ON DELETE CASCADE,
+    issue_id uuid NOT NULL REFERENCES newsletter_issues (id),
+    queued_at timestamptz NOT NULL,
+    PRIMARY KEY (subscriber_id, issue_id)
+);
@@ -0,0 +1,4 @@
+-- The id the email provider gave the message, so bounces can be matched to
+-- the delivery they are about.
+ALTER TABLE issue_deliveries ADD COLUMN message_id TEXT;
+CREATE INDEX issue_deliveries_message_id_idx ON issue_deliveries (message_id);
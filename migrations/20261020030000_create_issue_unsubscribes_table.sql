@@ -0,0 +1,20 @@
+-- Unsubscribes put down to the last issue the subscriber had been sent,
+-- however they left: the unsubscribe button or unticking every list.
+CREATE TABLE issue_unsubscribes(
+    issue_id uuid NOT NULL REFERENCES newsletter_issues (id),
+    subscriber_id uuid NOT NULL REFERENCES subscriptions (id) ON DELETE CASCADE,
+    unsubscribed_at timestamptz NOT NULL,
+    PRIMARY KEY (issue_id, subscriber_id)
+);
+
+-- Earlier unsubscribes are only known from the audit log.
+INSERT INTO issue_unsubscribes (issue_id, subscriber_id, unsubscribed_at)
+SELECT DISTINCT ON (e.id) d.issue_id, d.subscriber_id, e.occurred_at
+FROM audit_events e
+JOIN issue_deliveries d
+    ON d.subscriber_id::text = e.target
+    AND d.attempted_at <= e.occurred_at
+WHERE e.action = 'subscriber_unsubscribed'
+AND e.actor_user_id IS NULL
+ORDER BY e.id, d.attempted_at DESC
+ON CONFLICT DO NOTHING;
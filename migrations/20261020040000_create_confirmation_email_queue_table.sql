@@ -0,0 +1,8 @@
+-- Confirmation emails for imported subscribers, sent by a background worker
+-- so a large import doesn't hold the upload request open.
+CREATE TABLE confirmation_email_queue(
+    subscription_token TEXT NOT NULL PRIMARY KEY
+        REFERENCES subscription_tokens (subscription_token) ON DELETE CASCADE,
+    attempts INT NOT NULL DEFAULT 0,
+    next_attempt_at timestamptz NOT NULL
+);
@@ -0,0 +1,155 @@
+use actix_web::HttpRequest;
+use anyhow::Context;
+use chrono::Utc;
+use sqlx::PgPool;
+use uuid::Uuid;
+
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum AuditAction {
+    LoginSucceeded,
+    LoginFailed,
+    LoggedOut,
+    PasswordChanged,
+    PasskeyRegistered,
+    PasskeyRemoved,
+    PasskeyRequirementChanged,
+    NewsletterPublished,
+    SubscriberConfirmed,
+    ConfirmationResent,
+    SubscriberUnsubscribed,
+    SubscriberDeleted,
+    SubscribersImported,
+    SubscribersExported,
+    SubscriberErased,
+    ListCreated,
+    SubscriberTagged,
+    SubscriberUntagged,
+    CustomFieldCreated,
+    PreferencesUpdated,
+    SubscriberEmailChanged,
+    IssueHidden,
+    IssueShown,
+    SubscriberBounced,
+    SubscriberComplained,
+    WebhookEndpointCreated,
+    WebhookEndpointDeleted,
+    IdentityLinked,
+}
+
+impl AuditAction {
+    pub const ALL: [AuditAction; 28] = [
+        AuditAction::LoginSucceeded,
+        AuditAction::LoginFailed,
+        AuditAction::LoggedOut,
+        AuditAction::PasswordChanged,
+        AuditAction::PasskeyRegistered,
+        AuditAction::PasskeyRemoved,
+        AuditAction::PasskeyRequirementChanged,
+        AuditAction::NewsletterPublished,
+        AuditAction::SubscriberConfirmed,
+        AuditAction::ConfirmationResent,
+        AuditAction::SubscriberUnsubscribed,
+        AuditAction::SubscriberDeleted,
+        AuditAction::SubscribersImported,
+        AuditAction::SubscribersExported,
+        AuditAction::SubscriberErased,
+        AuditAction::ListCreated,
+        AuditAction::SubscriberTagged,
+        AuditAction::SubscriberUntagged,
+        AuditAction::CustomFieldCreated,
+        AuditAction::PreferencesUpdated,
+        AuditAction::SubscriberEmailChanged,
+        AuditAction::IssueHidden,
+        AuditAction::IssueShown,
+        AuditAction::SubscriberBounced,
+        AuditAction::SubscriberComplained,
+        AuditAction::WebhookEndpointCreated,
+        AuditAction::WebhookEndpointDeleted,
+        AuditAction::IdentityLinked,
+    ];
+
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            AuditAction::LoginSucceeded => "login_succeeded",
+            AuditAction::LoginFailed => "login_failed",
+            AuditAction::LoggedOut => "logged_out",
+            AuditAction::PasswordChanged => "password_changed",
+            AuditAction::PasskeyRegistered => "passkey_registered",
+            AuditAction::PasskeyRemoved => "passkey_removed",
+            AuditAction::PasskeyRequirementChanged => "passkey_requirement_changed",
+            AuditAction::NewsletterPublished => "newsletter_published",
+            AuditAction::SubscriberConfirmed => "subscriber_confirmed",
+            AuditAction::ConfirmationResent => "confirmation_resent",
+            AuditAction::SubscriberUnsubscribed => "subscriber_unsubscribed",
+            AuditAction::SubscriberDeleted => "subscriber_deleted",
+            AuditAction::SubscribersImported => "subscribers_imported",
+            AuditAction::SubscribersExported => "subscribers_exported",
+            AuditAction::SubscriberErased => "subscriber_erased",
+            AuditAction::ListCreated => "list_created",
+            AuditAction::SubscriberTagged => "subscriber_tagged",
+            AuditAction::SubscriberUntagged => "subscriber_untagged",
+            AuditAction::CustomFieldCreated => "custom_field_created",
+            AuditAction::PreferencesUpdated => "preferences_updated",
+            AuditAction::SubscriberEmailChanged => "subscriber_email_changed",
+            AuditAction::IssueHidden => "issue_hidden",
+            AuditAction::IssueShown => "issue_shown",
+            AuditAction::SubscriberBounced => "subscriber_bounced",
+            AuditAction::SubscriberComplained => "subscriber_complained",
+            AuditAction::WebhookEndpointCreated => "webhook_endpoint_created",
+            AuditAction::WebhookEndpointDeleted => "webhook_endpoint_deleted",
+            AuditAction::IdentityLinked => "identity_linked",
+        }
+    }
+}
+
+/// A security relevant action taken in the admin area.
+pub struct AuditEvent {
+    actor: Option<Uuid>,
+    action: AuditAction,
+    target: Option<String>,
+    ip_address: Option<String>,
+}
+
+impl AuditEvent {
+    pub fn new(action: AuditAction, request: &HttpRequest) -> Self {
+        // Not `realip_remote_addr`: forwarding headers are whatever the
+        // client chose to send.
+        let ip_address = request.peer_addr().map(|addr| addr.ip().to_string());
+        Self {
+            actor: None,
+            action,
+            target: None,
+            ip_address,
+        }
+    }
+
+    pub fn actor(mut self, user_id: Uuid) -> Self {
+        self.actor = Some(user_id);
+        self
+    }
+
+    pub fn target(mut self, target: impl Into<String>) -> Self {
+        self.target = Some(target.into());
+        self
+    }
+
+    #[tracing::instrument(name = "Record audit event", skip(self, pool), fields(action = self.action.as_str()))]
+    pub async fn record(self, pool: &PgPool) -> Result<(), anyhow::Error> {
+        sqlx::query!(
+            r#"
+            INSERT INTO audit_events (id, occurred_at, actor_user_id, action, target, ip_address)
+            VALUES ($1, $2, $3, $4, $5, $6)
+            "#,
+            Uuid::new_v4(),
+            Utc::now(),
+            self.actor,
+            self.action.as_str(),
+            self.target,
+            self.ip_address,
+        )
+        .execute(pool)
+        .await
+        .context("Failed to record audit event")?;
+        Ok(())
+    }
+}
@@ -1,11 +1,20 @@
+use super::{passkey_required, validate_credentials, AuthError, Credentials};
+use crate::audit::{AuditAction, AuditEvent};
 use crate::session_state::TypedSession;
 use crate::utils::{e500, see_other};
 use actix_web::body::MessageBody;
 use actix_web::dev::{ServiceRequest, ServiceResponse};
 use actix_web::error::InternalError;
+use actix_web::http::header::{HeaderMap, WWW_AUTHENTICATE};
+use actix_web::web;
 use actix_web::FromRequest;
-use actix_web::HttpMessage;
+use actix_web::{HttpMessage, HttpResponse};
 use actix_web_lab::middleware::Next;
+use anyhow::Context;
+use base64::engine::general_purpose::STANDARD;
+use base64::Engine;
+use secrecy::Secret;
+use sqlx::PgPool;
 use std::ops::Deref;
 use uuid::Uuid;
 
@@ -47,3 +56,71 @@ pub async fn reject_anonymous_users(
         }
     }
 }
+
+/// Authenticates API requests with HTTP Basic credentials.
+///
+/// Accounts that require a passkey can't use the API, since Basic auth
+/// can't carry a second factor.
+pub async fn reject_unauthenticated_api_requests(
+    req: ServiceRequest,
+    next: Next<impl MessageBody>,
+) -> Result<ServiceResponse<impl MessageBody>, actix_web::Error> {
+    let pool = req
+        .app_data::<web::Data<PgPool>>()
+        .cloned()
+        .ok_or_else(|| e500("Missing database pool"))?;
+    let credentials = basic_authentication(req.headers()).map_err(unauthorized)?;
+    let username = credentials.username.clone();
+
+    let user_id = match validate_credentials(credentials, &pool).await {
+        Ok(user_id) => user_id,
+        Err(AuthError::InvalidCredentials(e)) => {
+            AuditEvent::new(AuditAction::LoginFailed, req.request())
+                .target(username)
+                .record(&pool)
+                .await
+                .map_err(e500)?;
+            return Err(unauthorized(e));
+        }
+        Err(AuthError::UnexpectedError(e)) => return Err(e500(e)),
+    };
+    if passkey_required(user_id, &pool).await.map_err(e500)? {
+        return Err(unauthorized(anyhow::anyhow!(
+            "A passkey is required for this account"
+        )));
+    }
+
+    req.extensions_mut().insert(UserId(user_id));
+    next.call(req).await
+}
+
+fn unauthorized(e: anyhow::Error) -> actix_web::Error {
+    let response = HttpResponse::Unauthorized()
+        .insert_header((WWW_AUTHENTICATE, r#"Basic realm="api""#))
+        .finish();
+    InternalError::from_response(e, response).into()
+}
+
+pub fn basic_authentication(headers: &HeaderMap) -> Result<Credentials, anyhow::Error> {
+    let header_value = headers
+        .get("Authorization")
+        .context("The 'Authorization' header was missing")?
+        .to_str()
+        .context("The 'Authorization' header was not a valid UTF8 string")?;
+    let base64encoded_segment = header_value
+        .strip_prefix("Basic ")
+        .context("The authorization scheme was not 'Basic'")?;
+    let decoded_bytes = STANDARD
+        .decode(base64encoded_segment)
+        .context("Failed to base64-decode 'Basic' credentials")?;
+    let decoded_credentials = String::from_utf8(decoded_bytes)
+        .context("The decoded credential string is not valid UTF8")?;
+
+    let (username, password) = decoded_credentials
+        .split_once(':')
+        .context("A password must be provided in 'Basic' auth")?;
+    Ok(Credentials {
+        username: username.to_owned(),
+        password: Secret::new(password.to_owned()),
+    })
+}
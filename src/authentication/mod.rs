@@ -1,6 +1,17 @@
 mod middleware;
+mod oidc;
+mod passkey;
 mod password;
+mod password_policy;
 
-pub use middleware::reject_anonymous_users;
-pub use middleware::UserId;
+pub use middleware::{basic_authentication, UserId};
+pub use middleware::{reject_anonymous_users, reject_unauthenticated_api_requests};
+pub use oidc::{
+    link_oidc_identity, resolve_oidc_identity, IdentityClaims, OidcClient, OidcLoginState,
+};
+pub use passkey::{
+    build_webauthn, delete_passkey, get_passkeys, get_user_id, passkey_required,
+    record_passkey_use, set_passkey_required, store_passkey, StoredPasskey,
+};
 pub use password::{change_password, validate_credentials, AuthError, Credentials};
+pub use password_policy::{PasswordPolicy, PasswordPolicyViolation};
@@ -0,0 +1,292 @@
+use crate::authentication::password::compute_password_hash;
+use crate::authentication::AuthError;
+use crate::configuration::OidcProviderSettings;
+use anyhow::Context;
+use base64::engine::general_purpose::URL_SAFE_NO_PAD;
+use base64::Engine;
+use rand::distributions::Alphanumeric;
+use rand::{thread_rng, Rng};
+use reqwest::{Client, Url};
+use secrecy::{ExposeSecret, Secret};
+use sha2::{Digest, Sha256};
+use sqlx::PgPool;
+use std::collections::HashMap;
+use uuid::Uuid;
+
+pub struct OidcClient {
+    http_client: Client,
+    redirect_url: String,
+    providers: Vec<OidcProviderSettings>,
+}
+
+/// What we need to remember between redirecting to the provider and
+/// handling the callback. Kept in the session.
+#[derive(serde::Serialize, serde::Deserialize)]
+pub struct OidcLoginState {
+    pub provider: String,
+    pub csrf_state: String,
+    pub code_verifier: String,
+    /// Set when a logged-in user started the flow to link the identity to
+    /// their account, rather than to log in.
+    #[serde(default)]
+    pub link_to: Option<Uuid>,
+}
+
+#[derive(serde::Deserialize)]
+struct TokenResponse {
+    access_token: String,
+}
+
+/// Claims returned by the provider's userinfo endpoint.
+#[derive(serde::Deserialize, Debug)]
+pub struct IdentityClaims {
+    pub sub: String,
+    #[serde(flatten)]
+    pub other: HashMap<String, serde_json::Value>,
+}
+
+impl IdentityClaims {
+    pub fn claim(&self, name: &str) -> Option<&str> {
+        if name == "sub" {
+            return Some(&self.sub);
+        }
+        self.other.get(name).and_then(|v| v.as_str())
+    }
+}
+
+impl OidcLoginState {
+    pub fn generate(provider: &str, link_to: Option<Uuid>) -> Self {
+        Self {
+            provider: provider.to_owned(),
+            csrf_state: random_string(32),
+            code_verifier: random_string(64),
+            link_to,
+        }
+    }
+
+    fn code_challenge(&self) -> String {
+        URL_SAFE_NO_PAD.encode(Sha256::digest(self.code_verifier.as_bytes()))
+    }
+}
+
+impl OidcClient {
+    pub fn new(base_url: &str, providers: Vec<OidcProviderSettings>) -> Self {
+        let http_client = Client::builder()
+            .timeout(std::time::Duration::from_secs(10))
+            .build()
+            .unwrap();
+        Self {
+            http_client,
+            redirect_url: format!("{}/login/oidc/callback", base_url),
+            providers,
+        }
+    }
+
+    pub fn providers(&self) -> &[OidcProviderSettings] {
+        &self.providers
+    }
+
+    /// Looks a provider up by name, falling back to the only configured
+    /// provider when no name is given.
+    pub fn provider(&self, name: Option<&str>) -> Option<&OidcProviderSettings> {
+        match name {
+            Some(name) => self.providers.iter().find(|p| p.name == name),
+            None if self.providers.len() == 1 => self.providers.first(),
+            None => None,
+        }
+    }
+
+    pub fn authorization_url(
+        &self,
+        provider: &OidcProviderSettings,
+        state: &OidcLoginState,
+    ) -> Result<Url, anyhow::Error> {
+        let url = Url::parse_with_params(
+            &provider.authorization_endpoint,
+            &[
+                ("response_type", "code"),
+                ("client_id", &provider.client_id),
+                ("redirect_uri", &self.redirect_url),
+                ("scope", &provider.scopes.join(" ")),
+                ("state", &state.csrf_state),
+                ("code_challenge", &state.code_challenge()),
+                ("code_challenge_method", "S256"),
+            ],
+        )
+        .context("Invalid authorization endpoint")?;
+        Ok(url)
+    }
+
+    #[tracing::instrument(name = "Exchange OIDC authorization code", skip_all)]
+    pub async fn exchange_code(
+        &self,
+        provider: &OidcProviderSettings,
+        code: &str,
+        state: &OidcLoginState,
+    ) -> Result<IdentityClaims, reqwest::Error> {
+        let token: TokenResponse = self
+            .http_client
+            .post(&provider.token_endpoint)
+            .form(&[
+                ("grant_type", "authorization_code"),
+                ("code", code),
+                ("redirect_uri", &self.redirect_url),
+                ("client_id", &provider.client_id),
+                ("client_secret", provider.client_secret.expose_secret()),
+                ("code_verifier", &state.code_verifier),
+            ])
+            .send()
+            .await?
+            .error_for_status()?
+            .json()
+            .await?;
+
+        // Claims come from the userinfo endpoint over the back channel, so
+        // there is no ID token signature to verify.
+        self.http_client
+            .get(&provider.userinfo_endpoint)
+            .bearer_auth(token.access_token)
+            .send()
+            .await?
+            .error_for_status()?
+            .json()
+            .await
+    }
+}
+
+/// Maps an identity asserted by a provider to a row in `users`.
+///
+/// Only identities that have been linked before resolve to an existing
+/// user; see [`link_oidc_identity`]. Otherwise a new user is provisioned if
+/// the provider allows it and its username claim is not taken. Usernames
+/// are never matched, since users can often choose their own at the
+/// provider.
+#[tracing::instrument(name = "Resolve OIDC identity", skip(provider, pool))]
+pub async fn resolve_oidc_identity(
+    provider: &OidcProviderSettings,
+    claims: &IdentityClaims,
+    pool: &PgPool,
+) -> Result<Uuid, AuthError> {
+    let row = sqlx::query!(
+        r#"SELECT user_id FROM user_identities WHERE issuer = $1 AND subject = $2"#,
+        provider.issuer,
+        claims.sub,
+    )
+    .fetch_optional(pool)
+    .await
+    .context("Failed to look up user identity")?;
+    if let Some(row) = row {
+        return Ok(row.user_id);
+    }
+    if !provider.jit_provisioning {
+        return Err(AuthError::InvalidCredentials(anyhow::anyhow!(
+            "No user is linked to the asserted identity"
+        )));
+    }
+
+    let username = claims.claim(&provider.username_claim).ok_or_else(|| {
+        AuthError::InvalidCredentials(anyhow::anyhow!("Missing {} claim", provider.username_claim))
+    })?;
+    let user_id = Uuid::new_v4();
+    // The user logs in through the provider, so nobody ever learns this
+    // password.
+    let password = Secret::new(random_string(64));
+    let password_hash = tokio::task::spawn_blocking(move || compute_password_hash(password))
+        .await
+        .context("Failed to spawn blocking thread")??;
+
+    let mut transaction = pool
+        .begin()
+        .await
+        .context("Failed to acquire a Postgres connection from the pool")?;
+    let inserted = sqlx::query!(
+        r#"
+        INSERT INTO users (user_id, username, password_hash) VALUES ($1, $2, $3)
+        ON CONFLICT (username) DO NOTHING
+        "#,
+        user_id,
+        username,
+        password_hash.expose_secret(),
+    )
+    .execute(&mut *transaction)
+    .await
+    .context("Failed to provision user")?;
+    if inserted.rows_affected() == 0 {
+        return Err(AuthError::InvalidCredentials(anyhow::anyhow!(
+            "The username {} belongs to a user the identity is not linked to",
+            username
+        )));
+    }
+    sqlx::query!(
+        r#"INSERT INTO user_identities (issuer, subject, user_id) VALUES ($1, $2, $3)"#,
+        provider.issuer,
+        claims.sub,
+        user_id,
+    )
+    .execute(&mut *transaction)
+    .await
+    .context("Failed to link user identity")?;
+    transaction
+        .commit()
+        .await
+        .context("Failed to commit transaction")?;
+
+    Ok(user_id)
+}
+
+/// Lets `user_id` log in with the asserted identity from now on. Fails if
+/// the identity already belongs to someone else.
+#[tracing::instrument(name = "Link OIDC identity", skip(provider, pool))]
+pub async fn link_oidc_identity(
+    provider: &OidcProviderSettings,
+    claims: &IdentityClaims,
+    user_id: Uuid,
+    pool: &PgPool,
+) -> Result<(), AuthError> {
+    let row = sqlx::query!(
+        r#"
+        INSERT INTO user_identities (issuer, subject, user_id) VALUES ($1, $2, $3)
+        ON CONFLICT (issuer, subject) DO UPDATE SET user_id = user_identities.user_id
+        RETURNING user_id
+        "#,
+        provider.issuer,
+        claims.sub,
+        user_id,
+    )
+    .fetch_one(pool)
+    .await
+    .context("Failed to link user identity")?;
+    if row.user_id != user_id {
+        return Err(AuthError::InvalidCredentials(anyhow::anyhow!(
+            "The identity is already linked to another user"
+        )));
+    }
+    Ok(())
+}
+
+fn random_string(length: usize) -> String {
+    let mut rng = thread_rng();
+    std::iter::repeat_with(|| rng.sample(Alphanumeric))
+        .map(char::from)
+        .take(length)
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::OidcLoginState;
+
+    #[test]
+    fn code_challenge_matches_rfc_7636_example() {
+        let state = OidcLoginState {
+            provider: "test".into(),
+            csrf_state: "state".into(),
+            code_verifier: "dBjftJeZ4CVP-mB92K27uhbUJU1p1r_wW1gFWFOEjXk".into(),
+            link_to: None,
+        };
+        assert_eq!(
+            state.code_challenge(),
+            "E9Melhoa2OwvFrEMTJguCHaoeK1t8URWbuGJSstw-cM"
+        );
+    }
+}
@@ -0,0 +1,186 @@
+use crate::configuration::WebauthnSettings;
+use anyhow::Context;
+use chrono::{DateTime, Utc};
+use sqlx::PgPool;
+use uuid::Uuid;
+use webauthn_rs::prelude::{AuthenticationResult, Passkey, Url};
+use webauthn_rs::{Webauthn, WebauthnBuilder};
+
+pub struct StoredPasskey {
+    pub id: Uuid,
+    pub name: String,
+    pub created_at: DateTime<Utc>,
+    pub last_used_at: Option<DateTime<Utc>>,
+    pub passkey: Passkey,
+}
+
+pub fn build_webauthn(settings: &WebauthnSettings) -> Result<Webauthn, anyhow::Error> {
+    let rp_origin = Url::parse(&settings.rp_origin).context("Invalid WebAuthn origin")?;
+    let webauthn = WebauthnBuilder::new(&settings.rp_id, &rp_origin)
+        .context("Invalid WebAuthn relying party")?
+        .rp_name(&settings.rp_name)
+        .build()
+        .context("Failed to build WebAuthn relying party")?;
+    Ok(webauthn)
+}
+
+#[tracing::instrument(name = "Get passkeys", skip(pool))]
+pub async fn get_passkeys(
+    user_id: Uuid,
+    pool: &PgPool,
+) -> Result<Vec<StoredPasskey>, anyhow::Error> {
+    let rows = sqlx::query!(
+        r#"
+        SELECT id, name, passkey, created_at, last_used_at
+        FROM passkeys
+        WHERE user_id = $1
+        ORDER BY created_at
+        "#,
+        user_id
+    )
+    .fetch_all(pool)
+    .await
+    .context("Failed to retrieve passkeys")?;
+
+    rows.into_iter()
+        .map(|r| {
+            Ok(StoredPasskey {
+                id: r.id,
+                name: r.name,
+                created_at: r.created_at,
+                last_used_at: r.last_used_at,
+                passkey: serde_json::from_str(&r.passkey).context("Corrupt stored passkey")?,
+            })
+        })
+        .collect()
+}
+
+#[tracing::instrument(name = "Store passkey", skip(passkey, pool))]
+pub async fn store_passkey(
+    user_id: Uuid,
+    name: &str,
+    passkey: &Passkey,
+    pool: &PgPool,
+) -> Result<(), anyhow::Error> {
+    sqlx::query!(
+        r#"
+        INSERT INTO passkeys (id, user_id, credential_id, name, passkey, created_at)
+        VALUES ($1, $2, $3, $4, $5, now())
+        "#,
+        Uuid::new_v4(),
+        user_id,
+        passkey.cred_id().as_ref(),
+        name,
+        serde_json::to_string(passkey)?,
+    )
+    .execute(pool)
+    .await
+    .context("Failed to store passkey")?;
+    Ok(())
+}
+
+/// Records a successful login, persisting the authenticator's new
+/// signature counter when it has moved on.
+#[tracing::instrument(name = "Update passkey after login", skip(result, pool))]
+pub async fn record_passkey_use(
+    user_id: Uuid,
+    result: &AuthenticationResult,
+    pool: &PgPool,
+) -> Result<(), anyhow::Error> {
+    for mut stored in get_passkeys(user_id, pool).await? {
+        let Some(changed) = stored.passkey.update_credential(result) else {
+            continue;
+        };
+        let passkey = changed
+            .then(|| serde_json::to_string(&stored.passkey))
+            .transpose()?;
+        sqlx::query!(
+            r#"
+            UPDATE passkeys
+            SET last_used_at = now(), passkey = COALESCE($1, passkey)
+            WHERE id = $2
+            "#,
+            passkey,
+            stored.id
+        )
+        .execute(pool)
+        .await
+        .context("Failed to update passkey")?;
+    }
+    Ok(())
+}
+
+#[tracing::instrument(name = "Delete passkey", skip(pool))]
+pub async fn delete_passkey(user_id: Uuid, id: Uuid, pool: &PgPool) -> Result<(), anyhow::Error> {
+    let mut transaction = pool
+        .begin()
+        .await
+        .context("Failed to acquire a Postgres connection from the pool")?;
+    sqlx::query!(
+        r#"DELETE FROM passkeys WHERE id = $1 AND user_id = $2"#,
+        id,
+        user_id
+    )
+    .execute(&mut *transaction)
+    .await
+    .context("Failed to delete passkey")?;
+    // Never leave a user required to present a passkey they no longer have.
+    sqlx::query!(
+        r#"
+        UPDATE users SET passkey_required = false
+        WHERE user_id = $1
+        AND NOT EXISTS (SELECT 1 FROM passkeys WHERE user_id = $1)
+        "#,
+        user_id
+    )
+    .execute(&mut *transaction)
+    .await
+    .context("Failed to update passkey requirement")?;
+    transaction
+        .commit()
+        .await
+        .context("Failed to commit transaction")?;
+    Ok(())
+}
+
+#[tracing::instrument(name = "Check if passkey is required", skip(pool))]
+pub async fn passkey_required(user_id: Uuid, pool: &PgPool) -> Result<bool, anyhow::Error> {
+    let row = sqlx::query!(
+        r#"
+        SELECT passkey_required AND EXISTS (SELECT 1 FROM passkeys WHERE user_id = $1) AS "required!"
+        FROM users
+        WHERE user_id = $1
+        "#,
+        user_id
+    )
+    .fetch_one(pool)
+    .await
+    .context("Failed to check passkey requirement")?;
+    Ok(row.required)
+}
+
+#[tracing::instrument(name = "Set passkey requirement", skip(pool))]
+pub async fn set_passkey_required(
+    user_id: Uuid,
+    required: bool,
+    pool: &PgPool,
+) -> Result<(), anyhow::Error> {
+    sqlx::query!(
+        r#"UPDATE users SET passkey_required = $1 WHERE user_id = $2"#,
+        required,
+        user_id
+    )
+    .execute(pool)
+    .await
+    .context("Failed to set passkey requirement")?;
+    Ok(())
+}
+
+#[tracing::instrument(name = "Get user id", skip(pool))]
+pub async fn get_user_id(username: &str, pool: &PgPool) -> Result<Option<Uuid>, anyhow::Error> {
+    let row = sqlx::query!(r#"SELECT user_id FROM users WHERE username = $1"#, username)
+        .fetch_optional(pool)
+        .await
+        .context("Failed to retrieve user id")?;
+    Ok(row.map(|r| r.user_id))
+}
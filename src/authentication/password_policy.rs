@@ -0,0 +1,166 @@
+use secrecy::{ExposeSecret, Secret};
+
+const BREACHED_PASSWORDS: &str = include_str!("breached_passwords.txt");
+
+#[derive(serde::Deserialize, Clone, Debug)]
+pub struct PasswordPolicy {
+    pub min_length: usize,
+    pub max_length: usize,
+    /// Minimum zxcvbn score, from 0 (guessable) to 4 (very unguessable).
+    pub min_strength: u8,
+    pub forbid_reuse: bool,
+    pub check_breached: bool,
+}
+
+#[derive(Debug, PartialEq, Eq)]
+pub enum PasswordPolicyViolation {
+    TooShort(usize),
+    TooLong(usize),
+    TooWeak,
+    ReusesCurrent,
+    Breached,
+}
+
+impl std::fmt::Display for PasswordPolicyViolation {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            PasswordPolicyViolation::TooShort(min) => {
+                write!(
+                    f,
+                    "The new password must be at least {} characters long",
+                    min
+                )
+            }
+            PasswordPolicyViolation::TooLong(max) => {
+                write!(
+                    f,
+                    "The new password must be at most {} characters long",
+                    max
+                )
+            }
+            PasswordPolicyViolation::TooWeak => write!(f, "The new password is too easy to guess"),
+            PasswordPolicyViolation::ReusesCurrent => {
+                write!(f, "The new password must differ from the current password")
+            }
+            PasswordPolicyViolation::Breached => {
+                write!(
+                    f,
+                    "The new password appears in a list of breached passwords"
+                )
+            }
+        }
+    }
+}
+
+impl PasswordPolicy {
+    /// Checks a candidate password against every rule of the policy.
+    ///
+    /// `user_inputs` are user specific strings (e.g. the username) that the
+    /// strength estimator penalises when they appear in the password.
+    pub fn validate(
+        &self,
+        password: &Secret<String>,
+        current_password: Option<&Secret<String>>,
+        user_inputs: &[&str],
+    ) -> Result<(), Vec<PasswordPolicyViolation>> {
+        let password = password.expose_secret();
+        let length = password.chars().count();
+        let mut violations = Vec::new();
+
+        if length < self.min_length {
+            violations.push(PasswordPolicyViolation::TooShort(self.min_length));
+        }
+        if length > self.max_length {
+            violations.push(PasswordPolicyViolation::TooLong(self.max_length));
+        } else if !password.is_empty() {
+            let too_weak = zxcvbn::zxcvbn(password, user_inputs)
+                .map(|entropy| entropy.score() < self.min_strength)
+                .unwrap_or(true);
+            if too_weak {
+                violations.push(PasswordPolicyViolation::TooWeak);
+            }
+        }
+        if self.forbid_reuse {
+            if let Some(current_password) = current_password {
+                if current_password.expose_secret() == password {
+                    violations.push(PasswordPolicyViolation::ReusesCurrent);
+                }
+            }
+        }
+        if self.check_breached && is_breached(password) {
+            violations.push(PasswordPolicyViolation::Breached);
+        }
+
+        if violations.is_empty() {
+            Ok(())
+        } else {
+            Err(violations)
+        }
+    }
+}
+
+fn is_breached(password: &str) -> bool {
+    let password = password.to_lowercase();
+    BREACHED_PASSWORDS.lines().any(|l| l == password)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{PasswordPolicy, PasswordPolicyViolation};
+    use claims::{assert_err, assert_ok};
+    use secrecy::Secret;
+
+    fn policy() -> PasswordPolicy {
+        PasswordPolicy {
+            min_length: 12,
+            max_length: 128,
+            min_strength: 3,
+            forbid_reuse: true,
+            check_breached: true,
+        }
+    }
+
+    #[test]
+    fn a_long_random_password_is_accepted() {
+        let password = Secret::new(uuid::Uuid::new_v4().to_string());
+        assert_ok!(policy().validate(&password, None, &[]));
+    }
+
+    #[test]
+    fn a_short_password_is_rejected() {
+        let password = Secret::new("x7#Kq!".to_string());
+        let violations = policy().validate(&password, None, &[]).unwrap_err();
+        assert!(violations.contains(&PasswordPolicyViolation::TooShort(12)));
+    }
+
+    #[test]
+    fn a_long_password_is_rejected() {
+        let password = Secret::new("a".repeat(129));
+        let violations = policy().validate(&password, None, &[]).unwrap_err();
+        assert!(violations.contains(&PasswordPolicyViolation::TooLong(128)));
+    }
+
+    #[test]
+    fn a_guessable_password_is_rejected() {
+        let password = Secret::new("aaaaaaaaaaaaaaaa".to_string());
+        let violations = policy().validate(&password, None, &[]).unwrap_err();
+        assert!(violations.contains(&PasswordPolicyViolation::TooWeak));
+    }
+
+    #[test]
+    fn reusing_the_current_password_is_rejected() {
+        let password = Secret::new(uuid::Uuid::new_v4().to_string());
+        let violations = policy()
+            .validate(&password, Some(&password), &[])
+            .unwrap_err();
+        assert_eq!(violations, vec![PasswordPolicyViolation::ReusesCurrent]);
+    }
+
+    #[test]
+    fn breached_passwords_are_rejected_regardless_of_case() {
+        let mut policy = policy();
+        policy.min_length = 0;
+        policy.min_strength = 0;
+        assert_err!(policy.validate(&Secret::new("Password123".to_string()), None, &[]));
+    }
+}
@@ -1,4 +1,7 @@
+use crate::authentication::PasswordPolicy;
 use crate::domain::SubscriberEmail;
+use crate::email_client::EmailClient;
+use crate::email_layout::EmailLayout;
 use secrecy::{ExposeSecret, Secret};
 use serde_aux::field_attributes::deserialize_number_from_string;
 use sqlx::postgres::{PgConnectOptions, PgSslMode};
@@ -19,6 +22,16 @@ pub struct Settings {
     pub application: ApplicationSettings,
     pub email_client: EmailClientSettings,
     pub redis_uri: Secret<String>,
+    pub password_policy: PasswordPolicy,
+    pub webauthn: WebauthnSettings,
+    pub email_layout: EmailLayout,
+    pub tracking: TrackingSettings,
+    pub webhooks: WebhookSettings,
+    #[serde(default)]
+    pub outbound_webhooks: OutboundWebhookSettings,
+    pub cors: CorsSettings,
+    #[serde(default)]
+    pub oidc_providers: Vec<OidcProviderSettings>,
 }
 
 #[derive(serde::Deserialize, Clone)]
@@ -39,10 +52,87 @@ pub struct EmailClientSettings {
     pub authorization_token: Secret<String>,
 }
 
+#[derive(serde::Deserialize, Clone)]
+pub struct WebauthnSettings {
+    /// Domain passkeys are scoped to, e.g. `example.com`.
+    pub rp_id: String,
+    /// Origin the browser reports, e.g. `https://example.com`.
+    pub rp_origin: String,
+    pub rp_name: String,
+}
+
+/// Switches engagement tracking off for every issue, whatever the issue says.
+#[derive(serde::Deserialize, Clone, Debug)]
+pub struct TrackingSettings {
+    /// Put a pixel in each email that records when it is opened.
+    pub opens: bool,
+    /// Send links through a redirect that records which ones get clicked.
+    pub clicks: bool,
+}
+
+/// What email providers must present to call `/webhooks/email/{provider}`:
+/// either basic auth as `username` and `secret`, or `secret` alone in the
+/// `X-Webhook-Secret` header.
+#[derive(serde::Deserialize, Clone)]
+pub struct WebhookSettings {
+    pub username: String,
+    pub secret: Secret<String>,
+}
+
+/// Where webhook endpoints admins register may point.
+#[derive(serde::Deserialize, Clone, Copy, Debug, Default)]
+pub struct OutboundWebhookSettings {
+    /// Lets endpoints use plain `http` and loopback or private addresses.
+    /// Only for tests and local development.
+    #[serde(default)]
+    pub allow_local_targets: bool,
+}
+
+/// Which sites may call the public JSON API from the browser, such as
+/// signup widgets embedded elsewhere. `"*"` allows any site.
+#[derive(serde::Deserialize, Clone, Debug)]
+pub struct CorsSettings {
+    pub allowed_origins: Vec<String>,
+}
+
+#[derive(serde::Deserialize, Clone)]
+pub struct OidcProviderSettings {
+    /// Identifies the provider in `/login/oidc?provider=<name>`.
+    pub name: String,
+    pub display_name: String,
+    pub issuer: String,
+    pub client_id: String,
+    pub client_secret: Secret<String>,
+    pub authorization_endpoint: String,
+    pub token_endpoint: String,
+    pub userinfo_endpoint: String,
+    #[serde(default = "default_oidc_scopes")]
+    pub scopes: Vec<String>,
+    /// Claim matched against `users.username`.
+    #[serde(default = "default_oidc_username_claim")]
+    pub username_claim: String,
+    /// Create a user on first login when no existing user matches.
+    #[serde(default)]
+    pub jit_provisioning: bool,
+}
+
+fn default_oidc_scopes() -> Vec<String> {
+    vec!["openid".into(), "profile".into(), "email".into()]
+}
+
+fn default_oidc_username_claim() -> String {
+    "preferred_username".into()
+}
+
 impl EmailClientSettings {
     pub fn sender(&self) -> Result<SubscriberEmail, String> {
         SubscriberEmail::parse(self.sender_email.clone())
     }
+
+    pub fn client(self) -> EmailClient {
+        let sender_email = self.sender().expect("Invalid Sender Email");
+        EmailClient::new(self.base_url, sender_email, self.authorization_token)
+    }
 }
 
 impl DatabaseSettings {
@@ -0,0 +1,173 @@
+//! Confirmation emails for imported subscribers.
+//!
+//! An import queues one per subscriber in `confirmation_email_queue`, in the
+//! transaction that adds them, and a background worker sends them, retrying
+//! failures with a growing delay.
+use crate::configuration::Settings;
+use crate::domain::{NewSubscriber, SubscriberEmail, SubscriberName};
+use crate::email_client::EmailClient;
+use crate::lists::MailingList;
+use crate::outbound_webhooks::ExecutionOutcome;
+use crate::routes::send_confirmation_email;
+use crate::startup::get_connection_pool;
+use anyhow::Context;
+use chrono::{Duration, Utc};
+use sqlx::{PgPool, Postgres, Transaction};
+use std::time::Duration as StdDuration;
+use uuid::Uuid;
+
+/// How many times an email is tried before it is given up on.
+const MAX_ATTEMPTS: i32 = 5;
+
+#[tracing::instrument(name = "Queue confirmation emails", skip_all, fields(count = tokens.len()))]
+pub async fn queue_confirmation_emails(
+    transaction: &mut Transaction<'_, Postgres>,
+    tokens: &[&str],
+) -> Result<(), sqlx::Error> {
+    sqlx::query!(
+        r#"
+        INSERT INTO confirmation_email_queue (subscription_token, next_attempt_at)
+        SELECT token, now() FROM UNNEST($1::text[]) AS t(token)
+        "#,
+        tokens as &[&str],
+    )
+    .execute(&mut **transaction)
+    .await?;
+    Ok(())
+}
+
+struct QueuedEmail {
+    subscription_token: String,
+    attempts: i32,
+    email: String,
+    name: String,
+    list_id: Uuid,
+    list_slug: String,
+    list_name: String,
+}
+
+/// Sends the next confirmation email that is due, if there is one.
+#[tracing::instrument(skip_all, err)]
+pub async fn try_execute_task(
+    pool: &PgPool,
+    email_client: &EmailClient,
+    base_url: &str,
+) -> Result<ExecutionOutcome, anyhow::Error> {
+    let mut transaction = pool
+        .begin()
+        .await
+        .context("Failed to acquire a Postgres connection from the pool")?;
+    let Some(task) = sqlx::query_as!(
+        QueuedEmail,
+        r#"
+        SELECT q.subscription_token, q.attempts, s.email, s.name,
+            l.id AS list_id, l.slug AS list_slug, l.name AS list_name
+        FROM confirmation_email_queue q
+        JOIN subscription_tokens t ON t.subscription_token = q.subscription_token
+        JOIN subscriptions s ON s.id = t.subscriber_id
+        JOIN lists l ON l.id = t.list_id
+        WHERE q.next_attempt_at <= now()
+        ORDER BY q.next_attempt_at
+        FOR UPDATE OF q SKIP LOCKED
+        LIMIT 1
+        "#
+    )
+    .fetch_optional(&mut *transaction)
+    .await
+    .context("Failed to dequeue a confirmation email")?
+    else {
+        return Ok(ExecutionOutcome::EmptyQueue);
+    };
+
+    let outcome = match (
+        SubscriberEmail::parse(task.email),
+        SubscriberName::parse(task.name),
+    ) {
+        (Ok(email), Ok(name)) => {
+            let list = MailingList {
+                id: task.list_id,
+                slug: task.list_slug,
+                name: task.list_name,
+            };
+            send_confirmation_email(
+                email_client,
+                NewSubscriber { email, name },
+                &list,
+                base_url,
+                &task.subscription_token,
+            )
+            .await
+            .map_err(|e| e.to_string())
+        }
+        (Err(e), _) | (_, Err(e)) => {
+            // Not worth retrying.
+            tracing::warn!(error = %e, "Dropping a confirmation email for an invalid subscriber");
+            Ok(())
+        }
+    };
+    let attempts = task.attempts + 1;
+    match outcome {
+        Err(e) if attempts < MAX_ATTEMPTS => {
+            tracing::warn!(error = %e, attempts, "Failed to send a confirmation email");
+            sqlx::query!(
+                r#"
+                UPDATE confirmation_email_queue
+                SET attempts = $2, next_attempt_at = $3
+                WHERE subscription_token = $1
+                "#,
+                task.subscription_token,
+                attempts,
+                Utc::now() + retry_delay(attempts),
+            )
+            .execute(&mut *transaction)
+            .await
+            .context("Failed to reschedule the confirmation email")?;
+        }
+        outcome => {
+            if let Err(e) = outcome {
+                tracing::error!(error = %e, attempts, "Gave up on a confirmation email");
+            }
+            sqlx::query!(
+                r#"DELETE FROM confirmation_email_queue WHERE subscription_token = $1"#,
+                task.subscription_token,
+            )
+            .execute(&mut *transaction)
+            .await
+            .context("Failed to remove the confirmation email from the queue")?;
+        }
+    }
+    transaction
+        .commit()
+        .await
+        .context("Failed to commit transaction")?;
+    Ok(ExecutionOutcome::TaskCompleted)
+}
+
+/// A minute after the first failure, doubling each time after that.
+fn retry_delay(attempts: i32) -> Duration {
+    Duration::minutes(2_i64.pow(attempts.clamp(1, MAX_ATTEMPTS) as u32 - 1))
+}
+
+async fn worker_loop(
+    pool: PgPool,
+    email_client: EmailClient,
+    base_url: String,
+) -> Result<(), anyhow::Error> {
+    loop {
+        match try_execute_task(&pool, &email_client, &base_url).await {
+            Ok(ExecutionOutcome::EmptyQueue) => {
+                tokio::time::sleep(StdDuration::from_secs(10)).await;
+            }
+            Err(_) => {
+                tokio::time::sleep(StdDuration::from_secs(1)).await;
+            }
+            Ok(ExecutionOutcome::TaskCompleted) => {}
+        }
+    }
+}
+
+pub async fn run_worker_until_stopped(configuration: Settings) -> Result<(), anyhow::Error> {
+    let pool = get_connection_pool(&configuration.database);
+    let email_client = configuration.email_client.client();
+    worker_loop(pool, email_client, configuration.application.base_url).await
+}
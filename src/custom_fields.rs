@@ -0,0 +1,80 @@
+use crate::domain::CustomFieldKind;
+use sqlx::{PgPool, Postgres, Transaction};
+use std::collections::HashMap;
+use uuid::Uuid;
+
+/// An admin-defined piece of information collected about subscribers.
+pub struct CustomField {
+    pub id: Uuid,
+    pub key: String,
+    pub label: String,
+    pub kind: String,
+    pub choices: Vec<String>,
+}
+
+impl CustomField {
+    pub fn kind(&self) -> Result<CustomFieldKind, String> {
+        CustomFieldKind::parse(&self.kind)
+    }
+}
+
+#[tracing::instrument(name = "Get custom fields", skip(pool))]
+pub async fn get_custom_fields(pool: &PgPool) -> Result<Vec<CustomField>, sqlx::Error> {
+    sqlx::query_as!(
+        CustomField,
+        r#"SELECT id, key, label, kind, choices FROM custom_fields ORDER BY created_at, key"#
+    )
+    .fetch_all(pool)
+    .await
+}
+
+/// Values already on file are kept, so a signup form can't overwrite them.
+#[tracing::instrument(name = "Store custom field values", skip(transaction, values))]
+pub async fn insert_field_values(
+    transaction: &mut Transaction<'_, Postgres>,
+    subscriber_id: Uuid,
+    values: &[(Uuid, String)],
+) -> Result<(), sqlx::Error> {
+    let field_ids: Vec<Uuid> = values.iter().map(|(id, _)| *id).collect();
+    let values: Vec<&str> = values.iter().map(|(_, v)| v.as_str()).collect();
+    sqlx::query!(
+        r#"
+        INSERT INTO subscriber_field_values (subscriber_id, field_id, value)
+        SELECT $1, field_id, value FROM UNNEST($2::uuid[], $3::text[]) AS t(field_id, value)
+        ON CONFLICT DO NOTHING
+        "#,
+        subscriber_id,
+        &field_ids,
+        &values as &[&str],
+    )
+    .execute(&mut **transaction)
+    .await?;
+    Ok(())
+}
+
+/// Every recipient's values keyed by field key, for merge tags.
+#[tracing::instrument(name = "Get custom field values", skip(pool, subscriber_ids))]
+pub async fn get_field_values(
+    pool: &PgPool,
+    subscriber_ids: &[Uuid],
+) -> Result<HashMap<Uuid, HashMap<String, String>>, sqlx::Error> {
+    let rows = sqlx::query!(
+        r#"
+        SELECT v.subscriber_id, f.key, v.value
+        FROM subscriber_field_values v
+        JOIN custom_fields f ON f.id = v.field_id
+        WHERE v.subscriber_id = ANY($1)
+        "#,
+        subscriber_ids,
+    )
+    .fetch_all(pool)
+    .await?;
+    let mut values: HashMap<Uuid, HashMap<String, String>> = HashMap::new();
+    for row in rows {
+        values
+            .entry(row.subscriber_id)
+            .or_default()
+            .insert(row.key, row.value);
+    }
+    Ok(values)
+}
@@ -0,0 +1,260 @@
+//! Weekly and monthly digests, for subscribers who'd rather not get every
+//! issue as it is sent.
+//!
+//! Publishing an issue queues it in `digest_items` for those subscribers; a
+//! background worker sends each of them everything queued since their last
+//! digest once the next one is due.
+use crate::configuration::Settings;
+use crate::custom_fields::get_field_values;
+use crate::domain::{DigestFrequency, SubscriberEmail};
+use crate::email_client::EmailClient;
+use crate::merge_tags::{MergeTemplate, MergeValues};
+use crate::routes::preferences_link;
+use crate::startup::{get_connection_pool, HmacSecret};
+use anyhow::Context;
+use chrono::{DateTime, Utc};
+use sqlx::{PgExecutor, PgPool};
+use std::collections::HashMap;
+use std::time::Duration as StdDuration;
+use uuid::Uuid;
+
+/// Holds an issue back for the subscriber's next digest.
+#[tracing::instrument(name = "Queue issue for digest", skip(executor))]
+pub async fn queue_for_digest(
+    executor: impl PgExecutor<'_>,
+    issue_id: Uuid,
+    subscriber_id: Uuid,
+) -> Result<(), sqlx::Error> {
+    sqlx::query!(
+        r#"
+        INSERT INTO digest_items (subscriber_id, issue_id, queued_at)
+        VALUES ($1, $2, now())
+        ON CONFLICT DO NOTHING
+        "#,
+        subscriber_id,
+        issue_id,
+    )
+    .execute(executor)
+    .await?;
+    Ok(())
+}
+
+struct Recipient {
+    id: Uuid,
+    email: String,
+    name: String,
+    digest_frequency: String,
+    /// The last digest, or the oldest queued issue if there hasn't been one.
+    since: DateTime<Utc>,
+}
+
+struct Digest<'a> {
+    recipient: &'a Recipient,
+    email: &'a SubscriberEmail,
+    frequency: DigestFrequency,
+}
+
+struct QueuedIssue {
+    id: Uuid,
+    title: String,
+    text_content: String,
+    html_content: String,
+}
+
+/// Sends every digest that is due and returns how many went out.
+///
+/// Subscribers who have switched back to getting every issue are sent
+/// whatever was still queued for them straight away.
+#[tracing::instrument(name = "Send due digests", skip_all, err)]
+pub async fn send_due_digests(
+    pool: &PgPool,
+    email_client: &EmailClient,
+    base_url: &str,
+    secret: &HmacSecret,
+) -> Result<usize, anyhow::Error> {
+    let recipients = sqlx::query_as!(
+        Recipient,
+        r#"
+        SELECT s.id, s.email, s.name, s.digest_frequency,
+            coalesce(s.last_digest_at, min(d.queued_at)) AS "since!"
+        FROM subscriptions s
+        JOIN digest_items d ON d.subscriber_id = s.id
+        WHERE s.status = 'confirmed'
+            AND (s.paused_until IS NULL OR s.paused_until <= now())
+        GROUP BY s.id
+        "#
+    )
+    .fetch_all(pool)
+    .await
+    .context("Failed to find subscribers with queued issues")?;
+
+    let mut sent = 0;
+    for recipient in recipients {
+        let frequency =
+            DigestFrequency::parse(&recipient.digest_frequency).map_err(anyhow::Error::msg)?;
+        let is_due = frequency
+            .interval()
+            .is_none_or(|interval| recipient.since + interval <= Utc::now());
+        if !is_due {
+            continue;
+        }
+        let email = match SubscriberEmail::parse(recipient.email.clone()) {
+            Ok(email) => email,
+            Err(e) => {
+                tracing::warn!(error = %e, "Skipping a digest for an invalid address");
+                continue;
+            }
+        };
+        let digest = Digest {
+            recipient: &recipient,
+            email: &email,
+            frequency,
+        };
+        if let Err(e) = send_digest(pool, email_client, base_url, secret, digest).await {
+            // It stays queued and is tried again on the next run.
+            tracing::warn!(error.cause_chain = ?e, "Failed to send a digest");
+            continue;
+        }
+        sent += 1;
+    }
+    Ok(sent)
+}
+
+#[tracing::instrument(
+    name = "Send a digest",
+    skip(pool, email_client, base_url, secret, digest),
+    fields(subscriber_id = %digest.recipient.id)
+)]
+async fn send_digest(
+    pool: &PgPool,
+    email_client: &EmailClient,
+    base_url: &str,
+    secret: &HmacSecret,
+    digest: Digest<'_>,
+) -> Result<(), anyhow::Error> {
+    let Digest {
+        recipient,
+        email,
+        frequency,
+    } = digest;
+    let issues = sqlx::query_as!(
+        QueuedIssue,
+        r#"
+        SELECT i.id, i.title, i.text_content, i.html_content
+        FROM digest_items d
+        JOIN newsletter_issues i ON i.id = d.issue_id
+        WHERE d.subscriber_id = $1
+        ORDER BY i.published_at
+        "#,
+        recipient.id
+    )
+    .fetch_all(pool)
+    .await
+    .context("Failed to retrieve queued issues")?;
+    let field_values = get_field_values(pool, &[recipient.id])
+        .await
+        .context("Failed to retrieve custom field values")?;
+    let no_values = HashMap::new();
+    let values = MergeValues {
+        name: &recipient.name,
+        email: email.as_ref(),
+        custom: field_values.get(&recipient.id).unwrap_or(&no_values),
+    };
+
+    let mut html_body = String::new();
+    let mut text_body = String::new();
+    for issue in &issues {
+        let html = MergeTemplate::parse(&issue.html_content).map_err(anyhow::Error::msg)?;
+        let text = MergeTemplate::parse(&issue.text_content).map_err(anyhow::Error::msg)?;
+        html_body.push_str(&format!(
+            "<h2>{}</h2>{}<hr>",
+            ammonia::clean_text(&issue.title),
+            html.render(&values, true)
+        ));
+        text_body.push_str(&format!(
+            "{}\n\n{}\n\n--\n",
+            issue.title,
+            text.render(&values, false)
+        ));
+    }
+    let link = preferences_link(base_url, secret, recipient.id, email.as_ref());
+    html_body.push_str(&format!(
+        "<p><a href=\"{}\">Update your preferences or unsubscribe</a></p>",
+        link
+    ));
+    text_body.push_str(&format!("Update your preferences or unsubscribe: {}", link));
+    let subject = match frequency {
+        DigestFrequency::Weekly => "Your weekly digest",
+        DigestFrequency::Monthly => "Your monthly digest",
+        DigestFrequency::Immediate => "Issues you missed",
+    };
+
+    let message_id = email_client
+        .send_email_with_message_id(email, subject, &html_body, &text_body)
+        .await
+        .context("Failed to send the digest")?;
+
+    let issue_ids: Vec<Uuid> = issues.iter().map(|i| i.id).collect();
+    let mut transaction = pool
+        .begin()
+        .await
+        .context("Failed to acquire a Postgres connection from the pool")?;
+    sqlx::query!(
+        r#"
+        INSERT INTO issue_deliveries (issue_id, subscriber_id, status, attempted_at, message_id)
+        SELECT issue_id, $1, 'delivered', now(), $3 FROM UNNEST($2::uuid[]) AS t(issue_id)
+        ON CONFLICT DO NOTHING
+        "#,
+        recipient.id,
+        &issue_ids,
+        message_id,
+    )
+    .execute(&mut *transaction)
+    .await
+    .context("Failed to record digest deliveries")?;
+    sqlx::query!(
+        r#"DELETE FROM digest_items WHERE subscriber_id = $1 AND issue_id = ANY($2)"#,
+        recipient.id,
+        &issue_ids,
+    )
+    .execute(&mut *transaction)
+    .await
+    .context("Failed to clear sent digest items")?;
+    sqlx::query!(
+        r#"UPDATE subscriptions SET last_digest_at = now() WHERE id = $1"#,
+        recipient.id,
+    )
+    .execute(&mut *transaction)
+    .await
+    .context("Failed to record the digest")?;
+    transaction
+        .commit()
+        .await
+        .context("Failed to commit transaction")?;
+    Ok(())
+}
+
+async fn worker_loop(
+    pool: PgPool,
+    email_client: EmailClient,
+    base_url: String,
+    secret: HmacSecret,
+) -> Result<(), anyhow::Error> {
+    loop {
+        // Errors are logged by `send_due_digests`; the next run retries.
+        let _ = send_due_digests(&pool, &email_client, &base_url, &secret).await;
+        tokio::time::sleep(StdDuration::from_secs(600)).await;
+    }
+}
+
+pub async fn run_worker_until_stopped(configuration: Settings) -> Result<(), anyhow::Error> {
+    let pool = get_connection_pool(&configuration.database);
+    let email_client = configuration.email_client.client();
+    worker_loop(
+        pool,
+        email_client,
+        configuration.application.base_url,
+        HmacSecret(configuration.application.hmac_secret),
+    )
+    .await
+}
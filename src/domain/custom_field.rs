@@ -0,0 +1,139 @@
+use chrono::NaiveDate;
+
+/// How a custom field is referred to in forms (`custom.<key>`) and merge
+/// tags (`{{ custom.<key> }}`).
+#[derive(Debug)]
+pub struct CustomFieldKey(String);
+
+impl CustomFieldKey {
+    pub fn parse(s: String) -> Result<CustomFieldKey, String> {
+        let mut chars = s.chars();
+        let is_valid = s.len() <= 64
+            && chars.next().is_some_and(|c| c.is_ascii_lowercase())
+            && chars.all(|c| c.is_ascii_lowercase() || c.is_ascii_digit() || c == '_');
+        if is_valid {
+            Ok(Self(s))
+        } else {
+            Err(format!("{} is not a valid field key", s))
+        }
+    }
+}
+
+impl AsRef<str> for CustomFieldKey {
+    fn as_ref(&self) -> &str {
+        &self.0
+    }
+}
+
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum CustomFieldKind {
+    Text,
+    Number,
+    Date,
+    Choice,
+}
+
+impl CustomFieldKind {
+    pub const ALL: [CustomFieldKind; 4] = [
+        CustomFieldKind::Text,
+        CustomFieldKind::Number,
+        CustomFieldKind::Date,
+        CustomFieldKind::Choice,
+    ];
+
+    pub fn parse(s: &str) -> Result<CustomFieldKind, String> {
+        Self::ALL
+            .into_iter()
+            .find(|kind| kind.as_str() == s)
+            .ok_or_else(|| format!("{} is not a valid field type", s))
+    }
+
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            CustomFieldKind::Text => "text",
+            CustomFieldKind::Number => "number",
+            CustomFieldKind::Date => "date",
+            CustomFieldKind::Choice => "choice",
+        }
+    }
+
+    /// Checks a submitted value and returns the form it is stored in.
+    pub fn normalise(&self, value: &str, choices: &[String]) -> Result<String, String> {
+        let value = value.trim();
+        match self {
+            CustomFieldKind::Text if value.chars().count() > 1024 => {
+                Err("is longer than 1024 characters".into())
+            }
+            CustomFieldKind::Text => Ok(value.to_owned()),
+            CustomFieldKind::Number => match value.parse::<f64>() {
+                Ok(n) if n.is_finite() => Ok(value.to_owned()),
+                _ => Err(format!("{} is not a number", value)),
+            },
+            CustomFieldKind::Date => NaiveDate::parse_from_str(value, "%Y-%m-%d")
+                .map(|d| d.to_string())
+                .map_err(|_| format!("{} is not a YYYY-MM-DD date", value)),
+            CustomFieldKind::Choice => choices
+                .iter()
+                .find(|c| c.as_str() == value)
+                .cloned()
+                .ok_or_else(|| format!("{} is not one of the choices", value)),
+        }
+    }
+}
+
+impl std::fmt::Display for CustomFieldKind {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str(self.as_str())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{CustomFieldKey, CustomFieldKind};
+    use claims::{assert_err, assert_ok, assert_ok_eq};
+
+    #[test]
+    fn snake_case_keys_are_valid() {
+        assert_ok!(CustomFieldKey::parse("company_size2".into()));
+    }
+
+    #[test]
+    fn keys_must_start_with_a_letter() {
+        assert_err!(CustomFieldKey::parse("".into()));
+        assert_err!(CustomFieldKey::parse("2fa".into()));
+        assert_err!(CustomFieldKey::parse("company.name".into()));
+    }
+
+    #[test]
+    fn every_kind_round_trips() {
+        for kind in CustomFieldKind::ALL {
+            assert_ok_eq!(CustomFieldKind::parse(kind.as_str()), kind);
+        }
+    }
+
+    #[test]
+    fn numbers_must_be_finite() {
+        assert_ok_eq!(CustomFieldKind::Number.normalise(" 42.5 ", &[]), "42.5");
+        assert_err!(CustomFieldKind::Number.normalise("lots", &[]));
+        assert_err!(CustomFieldKind::Number.normalise("NaN", &[]));
+    }
+
+    #[test]
+    fn dates_must_be_iso_dates() {
+        assert_ok_eq!(
+            CustomFieldKind::Date.normalise("2024-02-29", &[]),
+            "2024-02-29"
+        );
+        assert_err!(CustomFieldKind::Date.normalise("29/02/2024", &[]));
+    }
+
+    #[test]
+    fn choices_must_be_listed() {
+        let choices = vec!["small".to_string(), "large".to_string()];
+        assert_ok_eq!(
+            CustomFieldKind::Choice.normalise("large", &choices),
+            "large"
+        );
+        assert_err!(CustomFieldKind::Choice.normalise("medium", &choices));
+    }
+}
@@ -0,0 +1,74 @@
+use chrono::Duration;
+
+/// How often a subscriber wants to hear from us: every issue as it is
+/// sent, or the issues of the past week or month gathered in one email.
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum DigestFrequency {
+    Immediate,
+    Weekly,
+    Monthly,
+}
+
+impl DigestFrequency {
+    pub const ALL: [DigestFrequency; 3] = [
+        DigestFrequency::Immediate,
+        DigestFrequency::Weekly,
+        DigestFrequency::Monthly,
+    ];
+
+    pub fn parse(s: &str) -> Result<DigestFrequency, String> {
+        Self::ALL
+            .into_iter()
+            .find(|frequency| frequency.as_str() == s)
+            .ok_or_else(|| format!("{} is not a valid digest frequency", s))
+    }
+
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            DigestFrequency::Immediate => "immediate",
+            DigestFrequency::Weekly => "weekly",
+            DigestFrequency::Monthly => "monthly",
+        }
+    }
+
+    pub fn label(&self) -> &'static str {
+        match self {
+            DigestFrequency::Immediate => "Every issue, as it is sent",
+            DigestFrequency::Weekly => "A weekly digest",
+            DigestFrequency::Monthly => "A monthly digest",
+        }
+    }
+
+    /// The time between digests; `None` for subscribers who get every issue.
+    pub fn interval(&self) -> Option<Duration> {
+        match self {
+            DigestFrequency::Immediate => None,
+            DigestFrequency::Weekly => Some(Duration::days(7)),
+            DigestFrequency::Monthly => Some(Duration::days(30)),
+        }
+    }
+}
+
+impl std::fmt::Display for DigestFrequency {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str(self.as_str())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::DigestFrequency;
+    use claims::{assert_err, assert_ok_eq};
+
+    #[test]
+    fn every_frequency_round_trips() {
+        for frequency in DigestFrequency::ALL {
+            assert_ok_eq!(DigestFrequency::parse(frequency.as_str()), frequency);
+        }
+    }
+
+    #[test]
+    fn unknown_frequency_is_rejected() {
+        assert_err!(DigestFrequency::parse("daily"));
+    }
+}
@@ -0,0 +1,79 @@
+/// The permalink name of a sent issue, e.g. `spring-update`.
+#[derive(Debug)]
+pub struct IssueSlug(String);
+
+impl IssueSlug {
+    const MAX_LENGTH: usize = 64;
+
+    /// Lowercases the title and joins its words with dashes; anything other
+    /// than ASCII letters and digits separates words.
+    pub fn from_title(title: &str) -> IssueSlug {
+        let mut slug = String::new();
+        for word in title
+            .split(|c: char| !c.is_ascii_alphanumeric())
+            .filter(|w| !w.is_empty())
+        {
+            if slug.len() + word.len() + 1 > Self::MAX_LENGTH {
+                break;
+            }
+            if !slug.is_empty() {
+                slug.push('-');
+            }
+            slug.push_str(&word.to_ascii_lowercase());
+        }
+        if slug.is_empty() {
+            slug.push_str("issue");
+        }
+        Self(slug)
+    }
+
+    /// `slug`, or `slug-2`, `slug-3`, ... if it's taken.
+    pub fn first_free(&self, taken: &[String]) -> IssueSlug {
+        let mut candidate = self.0.clone();
+        let mut n = 1;
+        while taken.contains(&candidate) {
+            n += 1;
+            candidate = format!("{}-{}", self.0, n);
+        }
+        Self(candidate)
+    }
+}
+
+impl AsRef<str> for IssueSlug {
+    fn as_ref(&self) -> &str {
+        &self.0
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::domain::IssueSlug;
+
+    #[test]
+    fn titles_become_dashed_lowercase_words() {
+        assert_eq!(
+            IssueSlug::from_title("Spring Update: What's new?").as_ref(),
+            "spring-update-what-s-new"
+        );
+    }
+
+    #[test]
+    fn titles_without_letters_or_digits_get_a_placeholder() {
+        assert_eq!(IssueSlug::from_title("¡¿!?").as_ref(), "issue");
+    }
+
+    #[test]
+    fn long_titles_are_cut_at_a_word_boundary() {
+        let slug = IssueSlug::from_title(&"word ".repeat(40));
+        assert!(slug.as_ref().len() <= 64);
+        assert!(slug.as_ref().ends_with("word"));
+    }
+
+    #[test]
+    fn taken_slugs_get_a_number() {
+        let slug = IssueSlug::from_title("Weekly");
+        let taken = vec!["weekly".to_owned(), "weekly-2".to_owned()];
+        assert_eq!(slug.first_free(&taken).as_ref(), "weekly-3");
+        assert_eq!(slug.first_free(&[]).as_ref(), "weekly");
+    }
+}
@@ -0,0 +1,53 @@
+/// The URL-safe name a list is addressed by, e.g. `weekly-digest`.
+#[derive(Debug)]
+pub struct ListSlug(String);
+
+impl ListSlug {
+    pub fn parse(s: String) -> Result<ListSlug, String> {
+        let is_valid = !s.is_empty()
+            && s.len() <= 64
+            && !s.starts_with('-')
+            && !s.ends_with('-')
+            && s.chars()
+                .all(|c| c.is_ascii_lowercase() || c.is_ascii_digit() || c == '-');
+        if is_valid {
+            Ok(Self(s))
+        } else {
+            Err(format!("{} is not a valid list slug", s))
+        }
+    }
+}
+
+impl AsRef<str> for ListSlug {
+    fn as_ref(&self) -> &str {
+        &self.0
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::domain::ListSlug;
+    use claims::{assert_err, assert_ok};
+
+    #[test]
+    fn lowercase_words_joined_by_dashes_are_valid() {
+        assert_ok!(ListSlug::parse("weekly-digest-2".into()));
+    }
+
+    #[test]
+    fn empty_slug_is_rejected() {
+        assert_err!(ListSlug::parse("".into()));
+    }
+
+    #[test]
+    fn uppercase_and_spaces_are_rejected() {
+        assert_err!(ListSlug::parse("Weekly".into()));
+        assert_err!(ListSlug::parse("weekly digest".into()));
+    }
+
+    #[test]
+    fn leading_or_trailing_dashes_are_rejected() {
+        assert_err!(ListSlug::parse("-weekly".into()));
+        assert_err!(ListSlug::parse("weekly-".into()));
+    }
+}
@@ -1,7 +1,23 @@
+mod custom_field;
+mod digest_frequency;
+mod issue_slug;
+mod list_slug;
 mod new_subscriber;
+mod newsletter_html;
+mod segment;
 mod subscriber_email;
 mod subscriber_name;
+mod subscriber_tag;
+mod subscription_status;
 
+pub use custom_field::{CustomFieldKey, CustomFieldKind};
+pub use digest_frequency::DigestFrequency;
+pub use issue_slug::IssueSlug;
+pub use list_slug::ListSlug;
 pub use new_subscriber::NewSubscriber;
+pub use newsletter_html::NewsletterHtml;
+pub use segment::Segment;
 pub use subscriber_email::SubscriberEmail;
 pub use subscriber_name::SubscriberName;
+pub use subscriber_tag::SubscriberTag;
+pub use subscription_status::SubscriptionStatus;
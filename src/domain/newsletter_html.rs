@@ -0,0 +1,347 @@
+use ammonia::{Builder, UrlRelative};
+use html5ever::tendril::StrTendril;
+use html5ever::tokenizer::states::RawKind;
+use html5ever::tokenizer::{
+    BufferQueue, TagKind, Token, TokenSink, TokenSinkResult, Tokenizer, TokenizerOpts,
+};
+use std::cell::RefCell;
+use std::collections::{HashMap, HashSet};
+
+const TAGS: &[&str] = &[
+    "a",
+    "abbr",
+    "b",
+    "blockquote",
+    "br",
+    "caption",
+    "center",
+    "code",
+    "col",
+    "colgroup",
+    "dd",
+    "div",
+    "dl",
+    "dt",
+    "em",
+    "h1",
+    "h2",
+    "h3",
+    "h4",
+    "h5",
+    "h6",
+    "hr",
+    "i",
+    "img",
+    "li",
+    "ol",
+    "p",
+    "pre",
+    "s",
+    "small",
+    "span",
+    "strong",
+    "sub",
+    "sup",
+    "table",
+    "tbody",
+    "td",
+    "tfoot",
+    "th",
+    "thead",
+    "tr",
+    "u",
+    "ul",
+];
+
+/// Allowed on every tag. Email clients ignore stylesheets, so `style` stays.
+const GENERIC_ATTRIBUTES: &[&str] = &["align", "dir", "style", "title"];
+
+const TAG_ATTRIBUTES: &[(&str, &[&str])] = &[
+    ("a", &["href"]),
+    ("img", &["src", "alt", "width", "height"]),
+    ("ol", &["start", "type"]),
+    (
+        "table",
+        &["width", "border", "cellpadding", "cellspacing", "bgcolor"],
+    ),
+    ("td", &["colspan", "rowspan", "valign", "width", "bgcolor"]),
+    ("th", &["colspan", "rowspan", "valign", "width", "bgcolor"]),
+    ("col", &["span", "width"]),
+];
+
+const URL_SCHEMES: &[&str] = &["http", "https", "mailto"];
+
+/// Elements whose content goes with them.
+const CONTENT_TAGS: &[&str] = &["script", "style"];
+
+/// Newsletter HTML that is safe to send to subscribers.
+///
+/// Tags and attributes outside an allow-list are dropped, along with
+/// scripts, event handlers and links that aren't `http`, `https` or
+/// `mailto`. What was removed is reported so the editor can check the
+/// issue still reads as intended.
+#[derive(Debug)]
+pub struct NewsletterHtml {
+    html: String,
+    warnings: Vec<String>,
+}
+
+impl NewsletterHtml {
+    pub fn parse(s: &str) -> NewsletterHtml {
+        let html = builder().clean(s).to_string();
+        Self {
+            warnings: warnings(s, &html),
+            html,
+        }
+    }
+
+    pub fn warnings(&self) -> &[String] {
+        &self.warnings
+    }
+}
+
+impl AsRef<str> for NewsletterHtml {
+    fn as_ref(&self) -> &str {
+        &self.html
+    }
+}
+
+fn builder() -> Builder<'static> {
+    let tag_attributes: HashMap<&str, HashSet<&str>> = TAG_ATTRIBUTES
+        .iter()
+        .map(|(tag, attributes)| (*tag, attributes.iter().copied().collect()))
+        .collect();
+    let mut builder = Builder::default();
+    builder
+        .tags(TAGS.iter().copied().collect())
+        .clean_content_tags(CONTENT_TAGS.iter().copied().collect())
+        .generic_attributes(GENERIC_ATTRIBUTES.iter().copied().collect())
+        .tag_attributes(tag_attributes)
+        .url_schemes(URL_SCHEMES.iter().copied().collect())
+        // A relative link has nothing to be relative to in an inbox.
+        .url_relative(UrlRelative::Deny)
+        .link_rel(Some("noopener noreferrer"))
+        .attribute_filter(|_, attribute, value| match attribute {
+            "href" | "src" => Some(value.trim().into()),
+            _ => Some(value.into()),
+        });
+    builder
+}
+
+fn is_allowed_attribute(tag: &str, attribute: &str) -> bool {
+    GENERIC_ATTRIBUTES.contains(&attribute)
+        || TAG_ATTRIBUTES
+            .iter()
+            .any(|(t, attributes)| *t == tag && attributes.contains(&attribute))
+}
+
+/// Describes what `builder` removed, in the order it appears, by comparing
+/// the markup that went in with what came out.
+fn warnings(input: &str, output: &str) -> Vec<String> {
+    let mut warnings: Vec<String> = Vec::new();
+    let mut warn = |warning: String| {
+        if !warnings.contains(&warning) {
+            warnings.push(warning);
+        }
+    };
+    let mut kept: Vec<Option<Markup>> = markup(output).into_iter().map(Some).collect();
+    for item in markup(input) {
+        let tag = match item {
+            Markup::Comment(comment) if comment.trim_start().starts_with("[if") => {
+                warn("Removed conditional comments, like <!--[if mso]>".into());
+                continue;
+            }
+            Markup::Comment(_) => {
+                warn("Removed comments".into());
+                continue;
+            }
+            Markup::Tag(tag) => tag,
+        };
+        // The parser may move elements around (out of a table, say), so a
+        // tag survived if any unmatched tag of the same name came out.
+        let survivor = kept.iter_mut().find_map(|k| match k {
+            Some(Markup::Tag(t)) if t.name == tag.name => k.take(),
+            _ => None,
+        });
+        let Some(Markup::Tag(survivor)) = survivor else {
+            warn(format!("Removed <{}>", tag.name));
+            continue;
+        };
+        for (attribute, value) in &tag.attributes {
+            if survivor.attributes.iter().any(|(a, _)| a == attribute) {
+                continue;
+            }
+            if is_allowed_attribute(&tag.name, attribute)
+                && (attribute == "href" || attribute == "src")
+            {
+                warn(format!(
+                    "Removed {}=\"{}\" from <{}>: only http, https and mailto URLs are allowed",
+                    attribute, value, tag.name
+                ));
+            } else {
+                warn(format!(
+                    "Removed the {} attribute from <{}>",
+                    attribute, tag.name
+                ));
+            }
+        }
+    }
+    warnings
+}
+
+enum Markup {
+    Tag(StartTag),
+    Comment(String),
+}
+
+struct StartTag {
+    name: String,
+    attributes: Vec<(String, String)>,
+}
+
+/// The start tags and comments in `s`, as the sanitiser's HTML parser reads
+/// them.
+fn markup(s: &str) -> Vec<Markup> {
+    let input = BufferQueue::default();
+    input.push_back(StrTendril::from_slice(s));
+    let tokenizer = Tokenizer::new(MarkupSink::default(), TokenizerOpts::default());
+    let _ = tokenizer.feed(&input);
+    tokenizer.end();
+    tokenizer.sink.markup.into_inner()
+}
+
+#[derive(Default)]
+struct MarkupSink {
+    markup: RefCell<Vec<Markup>>,
+}
+
+impl TokenSink for MarkupSink {
+    type Handle = ();
+
+    fn process_token(&self, token: Token, _line_number: u64) -> TokenSinkResult<()> {
+        match token {
+            Token::TagToken(tag) if tag.kind == TagKind::StartTag => {
+                // What follows these is text, not markup, until they close.
+                let raw = match &*tag.name {
+                    "script" => Some(RawKind::ScriptData),
+                    "style" | "xmp" | "iframe" | "noembed" | "noframes" => Some(RawKind::Rawtext),
+                    "textarea" | "title" => Some(RawKind::Rcdata),
+                    _ => None,
+                };
+                self.markup.borrow_mut().push(Markup::Tag(StartTag {
+                    name: tag.name.to_string(),
+                    attributes: tag
+                        .attrs
+                        .iter()
+                        .map(|a| (a.name.local.to_string(), a.value.to_string()))
+                        .collect(),
+                }));
+                if let Some(raw) = raw {
+                    return TokenSinkResult::RawData(raw);
+                }
+            }
+            Token::CommentToken(comment) => {
+                self.markup
+                    .borrow_mut()
+                    .push(Markup::Comment(comment.to_string()));
+            }
+            _ => {}
+        }
+        TokenSinkResult::Continue
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::domain::NewsletterHtml;
+
+    #[test]
+    fn safe_markup_is_kept() {
+        let html = NewsletterHtml::parse(
+            r#"<h1 style="color:red">Hi {{ name }}</h1><p><a href="https://example.com">Read</a></p>"#,
+        );
+        assert_eq!(
+            html.as_ref(),
+            r#"<h1 style="color:red">Hi {{ name }}</h1><p><a href="https://example.com" rel="noopener noreferrer">Read</a></p>"#
+        );
+        assert!(html.warnings().is_empty());
+    }
+
+    #[test]
+    fn scripts_are_removed_with_their_content() {
+        let html = NewsletterHtml::parse("<p>Hi</p><script>if (a < b) steal()</script>");
+        assert_eq!(html.as_ref(), "<p>Hi</p>");
+        assert_eq!(html.warnings(), ["Removed <script>"]);
+    }
+
+    #[test]
+    fn event_handlers_are_removed() {
+        let html =
+            NewsletterHtml::parse(r#"<img src="https://example.com/a.png" onerror="steal()">"#);
+        assert_eq!(html.as_ref(), r#"<img src="https://example.com/a.png">"#);
+        assert_eq!(
+            html.warnings(),
+            ["Removed the onerror attribute from <img>"]
+        );
+    }
+
+    #[test]
+    fn javascript_and_relative_links_are_removed() {
+        let html =
+            NewsletterHtml::parse(r#"<a href=" JavaScript:steal()">x</a><a href="/about">y</a>"#);
+        assert_eq!(
+            html.as_ref(),
+            r#"<a rel="noopener noreferrer">x</a><a rel="noopener noreferrer">y</a>"#
+        );
+        assert_eq!(html.warnings().len(), 2);
+    }
+
+    #[test]
+    fn links_are_trimmed() {
+        let html = NewsletterHtml::parse(r#"<a href="  https://example.com  ">x</a>"#);
+        assert_eq!(
+            html.as_ref(),
+            r#"<a href="https://example.com" rel="noopener noreferrer">x</a>"#
+        );
+    }
+
+    #[test]
+    fn comments_are_reported() {
+        let html = NewsletterHtml::parse(
+            "<!--[if mso]><table><tr><td><![endif]--><p>Hi</p><!-- draft note -->",
+        );
+        assert_eq!(html.as_ref(), "<p>Hi</p>");
+        assert_eq!(
+            html.warnings(),
+            [
+                "Removed conditional comments, like <!--[if mso]>",
+                "Removed comments"
+            ]
+        );
+    }
+
+    #[test]
+    fn warnings_follow_what_was_actually_removed() {
+        // Table rows outside a table are dropped by the parser itself.
+        let html = NewsletterHtml::parse("<TD>cell</TD><P CLASS='x'>Hi</P>");
+        assert_eq!(html.as_ref(), "cell<p>Hi</p>");
+        assert_eq!(
+            html.warnings(),
+            ["Removed <td>", "Removed the class attribute from <p>"]
+        );
+    }
+
+    #[test]
+    fn elements_moved_by_the_parser_are_not_reported() {
+        let html = NewsletterHtml::parse("<table><p>Hi</p><tr><td>x</td></tr></table>");
+        assert!(html.warnings().is_empty(), "{:?}", html.warnings());
+    }
+
+    #[test]
+    fn merge_tags_in_links_survive() {
+        let html = NewsletterHtml::parse(r#"<a href="https://example.com/?e={{ email }}">x</a>"#);
+        assert!(html
+            .as_ref()
+            .contains(r#"href="https://example.com/?e={{ email }}""#));
+    }
+}
@@ -0,0 +1,266 @@
+use crate::domain::{SubscriberTag, SubscriptionStatus};
+use chrono::NaiveDate;
+
+/// How deeply `not` and parentheses may nest, so a hostile segment can't
+/// overflow the stack while it is parsed.
+const MAX_DEPTH: usize = 32;
+/// Chains of `and`/`or` nest too once parsed, so their length is capped.
+const MAX_CONDITIONS: usize = 100;
+
+/// A boolean expression picking out a subset of subscribers, e.g.
+/// `tag:beta and not (tag:region:eu or subscribed_before:2024-01-01)`.
+///
+/// `not` binds tighter than `and`, which binds tighter than `or`.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum Segment {
+    Tag(SubscriberTag),
+    Status(SubscriptionStatus),
+    /// Subscribed on or after the start of the day (UTC).
+    SubscribedSince(NaiveDate),
+    /// Subscribed before the start of the day (UTC).
+    SubscribedBefore(NaiveDate),
+    Not(Box<Segment>),
+    And(Box<Segment>, Box<Segment>),
+    Or(Box<Segment>, Box<Segment>),
+}
+
+impl Segment {
+    pub fn parse(s: &str) -> Result<Segment, String> {
+        let tokens = tokenize(s);
+        if tokens.is_empty() {
+            return Err("The segment is empty".into());
+        }
+        let mut parser = Parser {
+            tokens,
+            position: 0,
+            depth: 0,
+            conditions: 0,
+        };
+        let segment = parser.or()?;
+        match parser.next() {
+            None => Ok(segment),
+            Some(token) => Err(format!("Unexpected `{}`", token)),
+        }
+    }
+}
+
+impl std::fmt::Display for Segment {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Segment::Tag(tag) => write!(f, "tag:{}", tag),
+            Segment::Status(status) => write!(f, "status:{}", status),
+            Segment::SubscribedSince(date) => write!(f, "subscribed_since:{}", date),
+            Segment::SubscribedBefore(date) => write!(f, "subscribed_before:{}", date),
+            Segment::Not(inner) => write!(f, "not {}", inner),
+            Segment::And(lhs, rhs) => write!(f, "({} and {})", lhs, rhs),
+            Segment::Or(lhs, rhs) => write!(f, "({} or {})", lhs, rhs),
+        }
+    }
+}
+
+fn tokenize(s: &str) -> Vec<String> {
+    let mut tokens = Vec::new();
+    let mut word = String::new();
+    for c in s.chars() {
+        if c.is_whitespace() || c == '(' || c == ')' {
+            if !word.is_empty() {
+                tokens.push(std::mem::take(&mut word));
+            }
+            if !c.is_whitespace() {
+                tokens.push(c.to_string());
+            }
+        } else {
+            word.push(c);
+        }
+    }
+    if !word.is_empty() {
+        tokens.push(word);
+    }
+    tokens
+}
+
+struct Parser {
+    tokens: Vec<String>,
+    position: usize,
+    /// How many `not`s and parentheses enclose the current position.
+    depth: usize,
+    conditions: usize,
+}
+
+impl Parser {
+    fn peek(&self) -> Option<&str> {
+        self.tokens.get(self.position).map(String::as_str)
+    }
+
+    fn next(&mut self) -> Option<String> {
+        let token = self.tokens.get(self.position).cloned();
+        self.position += 1;
+        token
+    }
+
+    fn peek_keyword(&self, keyword: &str) -> bool {
+        self.peek()
+            .is_some_and(|token| token.eq_ignore_ascii_case(keyword))
+    }
+
+    fn or(&mut self) -> Result<Segment, String> {
+        let mut segment = self.and()?;
+        while self.peek_keyword("or") {
+            self.next();
+            segment = Segment::Or(Box::new(segment), Box::new(self.and()?));
+        }
+        Ok(segment)
+    }
+
+    fn and(&mut self) -> Result<Segment, String> {
+        let mut segment = self.not()?;
+        while self.peek_keyword("and") {
+            self.next();
+            segment = Segment::And(Box::new(segment), Box::new(self.not()?));
+        }
+        Ok(segment)
+    }
+
+    fn nested<T>(
+        &mut self,
+        parse: impl FnOnce(&mut Self) -> Result<T, String>,
+    ) -> Result<T, String> {
+        if self.depth == MAX_DEPTH {
+            return Err(format!(
+                "The segment nests more than {} levels deep",
+                MAX_DEPTH
+            ));
+        }
+        self.depth += 1;
+        let outcome = parse(self);
+        self.depth -= 1;
+        outcome
+    }
+
+    fn not(&mut self) -> Result<Segment, String> {
+        if self.peek_keyword("not") {
+            self.next();
+            return Ok(Segment::Not(Box::new(self.nested(Self::not)?)));
+        }
+        self.term()
+    }
+
+    fn term(&mut self) -> Result<Segment, String> {
+        let token = self.next().ok_or("The segment ends too early")?;
+        if token == "(" {
+            let segment = self.nested(Self::or)?;
+            return match self.next().as_deref() {
+                Some(")") => Ok(segment),
+                _ => Err("Missing `)`".into()),
+            };
+        }
+        self.conditions += 1;
+        if self.conditions > MAX_CONDITIONS {
+            return Err(format!(
+                "The segment has more than {} conditions",
+                MAX_CONDITIONS
+            ));
+        }
+        let Some((field, value)) = token.split_once(':') else {
+            return Err(format!(
+                "Expected a condition such as `tag:beta`, found `{}`",
+                token
+            ));
+        };
+        let date = |value: &str| {
+            NaiveDate::parse_from_str(value, "%Y-%m-%d")
+                .map_err(|_| format!("{} is not a YYYY-MM-DD date", value))
+        };
+        match field {
+            "tag" => SubscriberTag::parse(value.to_owned()).map(Segment::Tag),
+            "status" => SubscriptionStatus::parse(value).map(Segment::Status),
+            "subscribed_since" => date(value).map(Segment::SubscribedSince),
+            "subscribed_before" => date(value).map(Segment::SubscribedBefore),
+            _ => Err(format!("Unknown condition `{}`", field)),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::Segment;
+    use crate::domain::{SubscriberTag, SubscriptionStatus};
+    use chrono::NaiveDate;
+    use claims::{assert_err, assert_ok_eq};
+
+    fn tag(s: &str) -> Box<Segment> {
+        Box::new(Segment::Tag(SubscriberTag::parse(s.into()).unwrap()))
+    }
+
+    #[test]
+    fn a_single_tag_parses() {
+        assert_ok_eq!(Segment::parse("tag:region:eu"), *tag("region:eu"));
+    }
+
+    #[test]
+    fn and_binds_tighter_than_or() {
+        assert_ok_eq!(
+            Segment::parse("tag:a or tag:b and tag:c"),
+            Segment::Or(tag("a"), Box::new(Segment::And(tag("b"), tag("c"))))
+        );
+    }
+
+    #[test]
+    fn parentheses_and_not_group_conditions() {
+        assert_ok_eq!(
+            Segment::parse("NOT (tag:a OR status:confirmed)"),
+            Segment::Not(Box::new(Segment::Or(
+                tag("a"),
+                Box::new(Segment::Status(SubscriptionStatus::Confirmed))
+            )))
+        );
+    }
+
+    #[test]
+    fn dates_parse() {
+        assert_ok_eq!(
+            Segment::parse("subscribed_since:2024-01-31"),
+            Segment::SubscribedSince(NaiveDate::from_ymd_opt(2024, 1, 31).unwrap())
+        );
+    }
+
+    #[test]
+    fn display_round_trips() {
+        let segment =
+            Segment::parse("tag:a and not (tag:b or subscribed_before:2024-01-01)").unwrap();
+        assert_ok_eq!(Segment::parse(&segment.to_string()), segment);
+    }
+
+    #[test]
+    fn malformed_segments_are_rejected() {
+        for segment in [
+            "",
+            "tag:a and",
+            "(tag:a",
+            "tag:a)",
+            "beta",
+            "colour:red",
+            "status:deleted",
+            "subscribed_since:yesterday",
+            "tag:a tag:b",
+        ] {
+            assert_err!(Segment::parse(segment), "{} should be rejected", segment);
+        }
+    }
+
+    #[test]
+    fn deeply_nested_segments_are_rejected_without_overflowing() {
+        let nots = format!("{}tag:a", "not ".repeat(100_000));
+        assert_err!(Segment::parse(&nots));
+        let parentheses = format!("{}tag:a{}", "(".repeat(100_000), ")".repeat(100_000));
+        assert_err!(Segment::parse(&parentheses));
+        let chain = vec!["tag:a"; 100_000].join(" and ");
+        assert_err!(Segment::parse(&chain));
+    }
+
+    #[test]
+    fn reasonable_nesting_is_allowed() {
+        let segment = format!("{}tag:a{}", "(not ".repeat(16), ")".repeat(16));
+        assert!(Segment::parse(&segment).is_ok());
+    }
+}
@@ -0,0 +1,58 @@
+/// A label such as `beta`, `paid` or `region:eu`.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct SubscriberTag(String);
+
+impl SubscriberTag {
+    pub fn parse(s: String) -> Result<SubscriberTag, String> {
+        let is_valid = !s.is_empty()
+            && s.len() <= 64
+            && s.chars().all(|c| {
+                c.is_ascii_lowercase() || c.is_ascii_digit() || matches!(c, ':' | '_' | '-')
+            });
+        if is_valid {
+            Ok(Self(s))
+        } else {
+            Err(format!("{} is not a valid tag", s))
+        }
+    }
+}
+
+impl AsRef<str> for SubscriberTag {
+    fn as_ref(&self) -> &str {
+        &self.0
+    }
+}
+
+impl std::fmt::Display for SubscriberTag {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str(&self.0)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::domain::SubscriberTag;
+    use claims::{assert_err, assert_ok};
+
+    #[test]
+    fn namespaced_tags_are_valid() {
+        assert_ok!(SubscriberTag::parse("region:eu".into()));
+        assert_ok!(SubscriberTag::parse("early_bird-2".into()));
+    }
+
+    #[test]
+    fn empty_tag_is_rejected() {
+        assert_err!(SubscriberTag::parse("".into()));
+    }
+
+    #[test]
+    fn uppercase_and_spaces_are_rejected() {
+        assert_err!(SubscriberTag::parse("Beta".into()));
+        assert_err!(SubscriberTag::parse("paid plan".into()));
+    }
+
+    #[test]
+    fn parentheses_are_rejected() {
+        assert_err!(SubscriberTag::parse("(beta)".into()));
+    }
+}
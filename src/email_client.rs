@@ -34,6 +34,21 @@ impl EmailClient {
         html_content: &str,
         text_content: &str,
     ) -> Result<(), reqwest::Error> {
+        self.send_email_with_message_id(recipient, subject, html_content, text_content)
+            .await
+            .map(|_| ())
+    }
+
+    /// Like `send_email`, returning the id the provider gave the message so
+    /// that later bounce notifications can be matched to it. `None` if the
+    /// provider's response didn't include one.
+    pub async fn send_email_with_message_id(
+        &self,
+        recipient: &SubscriberEmail,
+        subject: &str,
+        html_content: &str,
+        text_content: &str,
+    ) -> Result<Option<String>, reqwest::Error> {
         let url = format!("{}/email", self.base_url);
         let request_body = SendEmailRequest {
             from: self.sender.as_ref(),
@@ -43,7 +58,8 @@ impl EmailClient {
             text_body: text_content,
         };
 
-        self.http_client
+        let response = self
+            .http_client
             .post(&url)
             .header(
                 "X-Postmark-Server-Token",
@@ -53,7 +69,14 @@ impl EmailClient {
             .send()
             .await?
             .error_for_status()?;
-        Ok(())
+        // The message was accepted either way; an unreadable body only
+        // means its bounces can't be traced back to it.
+        let message_id = response
+            .json::<SendEmailResponse>()
+            .await
+            .ok()
+            .and_then(|r| r.message_id);
+        Ok(message_id)
     }
 }
 
@@ -67,6 +90,12 @@ struct SendEmailRequest<'a> {
     text_body: &'a str,
 }
 
+#[derive(serde::Deserialize)]
+struct SendEmailResponse {
+    #[serde(rename = "MessageID")]
+    message_id: Option<String>,
+}
+
 #[cfg(test)]
 mod tests {
     use crate::domain::SubscriberEmail;
@@ -175,4 +204,27 @@ mod tests {
 
         //Assert
     }
+
+    #[tokio::test]
+    async fn the_message_id_given_by_the_provider_is_returned() {
+        // Arrange
+        let mock_server = MockServer::start().await;
+        let email_client = email_client(mock_server.uri());
+
+        Mock::given(any())
+            .respond_with(
+                ResponseTemplate::new(200)
+                    .set_body_json(serde_json::json!({"MessageID": "b7bc2f4a", "ErrorCode": 0})),
+            )
+            .expect(1)
+            .mount(&mock_server)
+            .await;
+
+        let outcome = email_client
+            .send_email_with_message_id(&email(), &subject(), &content(), &content())
+            .await;
+
+        //Assert
+        assert_eq!(outcome.unwrap().as_deref(), Some("b7bc2f4a"));
+    }
 }
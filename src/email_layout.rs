@@ -0,0 +1,193 @@
+//! The branded wrapper newsletters written in Markdown are sent in.
+use askama::Template;
+use html5ever::tendril::StrTendril;
+use html5ever::tokenizer::{
+    BufferQueue, TagKind, Token, TokenSink, TokenSinkResult, Tokenizer, TokenizerOpts,
+};
+use std::cell::RefCell;
+
+/// Branding for the layout, set under `email_layout` in the configuration.
+#[derive(serde::Deserialize, Clone, Debug)]
+pub struct EmailLayout {
+    pub brand_name: String,
+    /// Any CSS colour; used for the header bar and links.
+    pub accent_color: String,
+    /// Shown above the preferences link, e.g. why the reader is getting this.
+    #[serde(default)]
+    pub footer_text: Option<String>,
+}
+
+#[derive(Template)]
+#[template(path = "emails/newsletter.html")]
+struct NewsletterEmail<'a> {
+    layout: &'a EmailLayout,
+    title: &'a str,
+    body: &'a str,
+    /// `None` when the issue isn't in the public archive.
+    archive_link: Option<&'a str>,
+    preferences_link: &'a str,
+}
+
+impl EmailLayout {
+    /// Wraps a rendered body in the layout.
+    ///
+    /// Many email clients drop `<style>` blocks, so every element the
+    /// Markdown renderer produces gets its styling inline.
+    pub fn wrap(
+        &self,
+        title: &str,
+        body: &str,
+        archive_link: Option<&str>,
+        preferences_link: &str,
+    ) -> Result<String, askama::Error> {
+        NewsletterEmail {
+            layout: self,
+            title,
+            body: &self.inline_styles(body),
+            archive_link,
+            preferences_link,
+        }
+        .render()
+    }
+
+    /// Adds each element's styling to whatever `style` it already has, so
+    /// tags are styled whatever their casing or attributes.
+    fn inline_styles(&self, body: &str) -> String {
+        let link = format!("color:{}", self.accent_color);
+        let styles = [
+            ("p", "margin:0 0 16px;line-height:1.5"),
+            ("h1", "margin:0 0 16px;font-size:24px"),
+            ("h2", "margin:24px 0 12px;font-size:20px"),
+            ("h3", "margin:24px 0 12px;font-size:16px"),
+            ("a", link.as_str()),
+            (
+                "blockquote",
+                "margin:0 0 16px;padding-left:12px;border-left:3px solid #ccc;color:#555",
+            ),
+            (
+                "pre",
+                "margin:0 0 16px;padding:12px;background:#f4f4f4;overflow:auto",
+            ),
+            ("code", "font-family:monospace"),
+            ("ul", "margin:0 0 16px;padding-left:24px"),
+            ("ol", "margin:0 0 16px;padding-left:24px"),
+            ("img", "max-width:100%"),
+            ("hr", "border:0;border-top:1px solid #ddd"),
+        ];
+        let input = BufferQueue::default();
+        input.push_back(StrTendril::from_slice(body));
+        let tokenizer = Tokenizer::new(
+            StyleSink {
+                styles: &styles,
+                html: RefCell::new(String::with_capacity(body.len())),
+            },
+            TokenizerOpts::default(),
+        );
+        let _ = tokenizer.feed(&input);
+        tokenizer.end();
+        tokenizer.sink.html.into_inner()
+    }
+}
+
+/// Writes the markup back out as it is tokenized, with styles added to the
+/// start tags that have them.
+struct StyleSink<'a> {
+    styles: &'a [(&'a str, &'a str)],
+    html: RefCell<String>,
+}
+
+impl TokenSink for StyleSink<'_> {
+    type Handle = ();
+
+    fn process_token(&self, token: Token, _line_number: u64) -> TokenSinkResult<()> {
+        let mut html = self.html.borrow_mut();
+        match token {
+            Token::TagToken(tag) if tag.kind == TagKind::StartTag => {
+                let mut attributes: Vec<(String, String)> = tag
+                    .attrs
+                    .iter()
+                    .map(|a| (a.name.local.to_string(), a.value.to_string()))
+                    .collect();
+                if let Some((_, style)) = self.styles.iter().find(|(t, _)| *t == &*tag.name) {
+                    // Ours go first so the element's own declarations win.
+                    match attributes.iter_mut().find(|(name, _)| name == "style") {
+                        Some((_, existing)) => *existing = format!("{};{}", style, existing),
+                        None => attributes.insert(0, ("style".into(), style.to_string())),
+                    }
+                }
+                html.push('<');
+                html.push_str(&tag.name);
+                for (name, value) in attributes {
+                    html.push_str(&format!(" {}=\"{}\"", name, escape(&value, true)));
+                }
+                html.push_str(if tag.self_closing { " />" } else { ">" });
+            }
+            Token::TagToken(tag) => html.push_str(&format!("</{}>", tag.name)),
+            Token::CharacterTokens(text) => html.push_str(&escape(&text, false)),
+            _ => {}
+        }
+        TokenSinkResult::Continue
+    }
+}
+
+fn escape(s: &str, in_attribute: bool) -> String {
+    let mut escaped = String::with_capacity(s.len());
+    for c in s.chars() {
+        match c {
+            '&' => escaped.push_str("&amp;"),
+            '"' if in_attribute => escaped.push_str("&quot;"),
+            '<' if !in_attribute => escaped.push_str("&lt;"),
+            '>' if !in_attribute => escaped.push_str("&gt;"),
+            c => escaped.push(c),
+        }
+    }
+    escaped
+}
+
+#[cfg(test)]
+mod tests {
+    use super::EmailLayout;
+
+    #[test]
+    fn rendered_markdown_is_styled_inline() {
+        let layout = EmailLayout {
+            brand_name: "Acme Weekly".into(),
+            accent_color: "#ff6600".into(),
+            footer_text: Some("Thanks for reading.".into()),
+        };
+
+        let email = layout
+            .wrap(
+                "Issue <1>",
+                "<p>Hi <a href=\"https://example.com\">there</a></p>\n",
+                Some("https://example.com/issues/issue-1"),
+                "https://example.com/preferences?token=abc",
+            )
+            .unwrap();
+
+        assert!(email.contains("Issue &lt;1&gt;"));
+        assert!(email.contains("Acme Weekly"));
+        assert!(email.contains(r#"<p style="margin:0 0 16px;line-height:1.5">Hi <a style="color:#ff6600" href="https://example.com">there</a></p>"#));
+        assert!(email.contains("Thanks for reading."));
+        assert!(email.contains(r#"href="https://example.com/issues/issue-1""#));
+        assert!(email.contains(r#"href="https://example.com/preferences?token=abc""#));
+    }
+
+    #[test]
+    fn tags_with_attributes_or_other_casing_are_styled_too() {
+        let layout = EmailLayout {
+            brand_name: "Acme Weekly".into(),
+            accent_color: "#ff6600".into(),
+            footer_text: None,
+        };
+
+        let body = layout.inline_styles(
+            r#"<P align="center">Hi &amp; <A title="x" href="https://example.com/?a=1&amp;b=2">there</A></P><hr /><ol start="3"><li>x</li></ol><img src="https://example.com/a.png" alt="a" style="border:0">"#,
+        );
+
+        assert_eq!(
+            body,
+            r#"<p style="margin:0 0 16px;line-height:1.5" align="center">Hi &amp; <a style="color:#ff6600" title="x" href="https://example.com/?a=1&amp;b=2">there</a></p><hr style="border:0;border-top:1px solid #ddd" /><ol style="margin:0 0 16px;padding-left:24px" start="3"><li>x</li></ol><img src="https://example.com/a.png" alt="a" style="max-width:100%;border:0">"#
+        );
+    }
+}
@@ -1,9 +1,19 @@
+pub mod audit;
 pub mod authentication;
 pub mod configuration;
+pub mod confirmation_emails;
+pub mod custom_fields;
+pub mod digests;
 pub mod domain;
 pub mod email_client;
+pub mod email_layout;
+pub mod lists;
+pub mod markdown;
+pub mod merge_tags;
+pub mod outbound_webhooks;
 pub mod routes;
 pub mod session_state;
+pub mod signing;
 pub mod startup;
 pub mod telemetry;
 pub mod utils;
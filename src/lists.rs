@@ -0,0 +1,44 @@
+use sqlx::PgPool;
+use uuid::Uuid;
+
+/// The list that existed before there were lists; `/subscriptions` and
+/// imports sign people up to it.
+pub const DEFAULT_LIST_SLUG: &str = "newsletter";
+
+#[derive(Clone, Debug)]
+pub struct MailingList {
+    pub id: Uuid,
+    pub slug: String,
+    pub name: String,
+}
+
+#[tracing::instrument(name = "Get list by slug", skip(pool))]
+pub async fn get_list_by_slug(
+    pool: &PgPool,
+    slug: &str,
+) -> Result<Option<MailingList>, sqlx::Error> {
+    sqlx::query_as!(
+        MailingList,
+        r#"SELECT id, slug, name FROM lists WHERE slug = $1"#,
+        slug
+    )
+    .fetch_optional(pool)
+    .await
+}
+
+#[tracing::instrument(name = "Get default list", skip(pool))]
+pub async fn get_default_list(pool: &PgPool) -> Result<MailingList, anyhow::Error> {
+    get_list_by_slug(pool, DEFAULT_LIST_SLUG)
+        .await?
+        .ok_or_else(|| anyhow::anyhow!("The default list is missing"))
+}
+
+#[tracing::instrument(name = "Get lists", skip(pool))]
+pub async fn get_lists(pool: &PgPool) -> Result<Vec<MailingList>, sqlx::Error> {
+    sqlx::query_as!(
+        MailingList,
+        r#"SELECT id, slug, name FROM lists ORDER BY name, slug"#
+    )
+    .fetch_all(pool)
+    .await
+}
@@ -0,0 +1,262 @@
+//! Newsletter authoring in Markdown.
+//!
+//! Raw HTML in the source is shown as text and links or images that don't
+//! point at `http`, `https` or `mailto` lose their target, so the HTML that
+//! comes out is safe to send as-is. Merge tags pass through untouched.
+use pulldown_cmark::{html, Event, Options, Parser, Tag};
+
+/// The two bodies of an email generated from one Markdown source.
+#[derive(Debug)]
+pub struct RenderedMarkdown {
+    pub html: String,
+    pub text: String,
+}
+
+pub fn render(source: &str) -> RenderedMarkdown {
+    let (source, tags) = protect_merge_tags(source);
+    let events: Vec<Event> = Parser::new_ext(&source, Options::ENABLE_STRIKETHROUGH)
+        .filter_map(sanitise)
+        .collect();
+    let mut html = String::new();
+    html::push_html(&mut html, events.iter().cloned());
+    RenderedMarkdown {
+        html: restore_merge_tags(html, &tags),
+        text: restore_merge_tags(plain_text(&events), &tags),
+    }
+}
+
+/// Private-use characters mean nothing in a newsletter, so they are taken out
+/// of the source and used to mark placeholders no author can write.
+const PLACEHOLDER_START: char = '\u{E000}';
+const PLACEHOLDER_END: char = '\u{E001}';
+
+fn placeholder(index: usize) -> String {
+    format!("{}{}{}", PLACEHOLDER_START, index, PLACEHOLDER_END)
+}
+
+/// Markdown would escape the quotes in `default: "..."`, so tags are swapped
+/// for inert placeholders while it runs.
+fn protect_merge_tags(source: &str) -> (String, Vec<String>) {
+    let source: String = source
+        .chars()
+        .filter(|c| *c != PLACEHOLDER_START && *c != PLACEHOLDER_END)
+        .collect();
+    let mut protected = String::with_capacity(source.len());
+    let mut tags = Vec::new();
+    let mut rest = source.as_str();
+    while let Some(start) = rest.find("{{") {
+        let Some(end) = rest[start..].find("}}") else {
+            break;
+        };
+        let end = start + end + 2;
+        protected.push_str(&rest[..start]);
+        protected.push_str(&placeholder(tags.len()));
+        tags.push(rest[start..end].to_owned());
+        rest = &rest[end..];
+    }
+    protected.push_str(rest);
+    (protected, tags)
+}
+
+fn restore_merge_tags(mut rendered: String, tags: &[String]) -> String {
+    for (index, tag) in tags.iter().enumerate() {
+        rendered = rendered.replace(&placeholder(index), tag);
+    }
+    rendered
+}
+
+fn is_safe_url(url: &str) -> bool {
+    let url = url.trim_start().to_ascii_lowercase();
+    ["http://", "https://", "mailto:"]
+        .iter()
+        .any(|scheme| url.starts_with(scheme))
+}
+
+fn sanitise(event: Event) -> Option<Event> {
+    match event {
+        Event::Html(html) => Some(Event::Text(html)),
+        Event::Start(Tag::Link(_, ref url, _) | Tag::Image(_, ref url, _))
+        | Event::End(Tag::Link(_, ref url, _) | Tag::Image(_, ref url, _))
+            if !is_safe_url(url) =>
+        {
+            None
+        }
+        event => Some(event),
+    }
+}
+
+/// Text buffers for the blocks that change how their lines are laid out.
+enum Block {
+    Document,
+    Item(String),
+    Quote,
+    Code,
+}
+
+fn plain_text(events: &[Event]) -> String {
+    let mut stack = vec![(Block::Document, String::new())];
+    let mut lists: Vec<Option<u64>> = Vec::new();
+    let mut links: Vec<String> = Vec::new();
+
+    for event in events {
+        let out = &mut stack.last_mut().unwrap().1;
+        match event {
+            Event::Start(Tag::Paragraph) => block_break(out),
+            Event::End(Tag::Paragraph) => out.push_str("\n\n"),
+            Event::Start(Tag::Heading(level, ..)) => {
+                block_break(out);
+                out.push_str(&format!("{} ", "#".repeat(*level as usize)));
+            }
+            Event::End(Tag::Heading(..)) => out.push_str("\n\n"),
+            Event::Start(Tag::List(start)) => {
+                lists.push(*start);
+                block_break(out);
+            }
+            Event::End(Tag::List(_)) => {
+                lists.pop();
+                out.push('\n');
+            }
+            Event::Start(Tag::Item) => {
+                let marker = match lists.last_mut() {
+                    Some(Some(n)) => {
+                        *n += 1;
+                        format!("{}. ", *n - 1)
+                    }
+                    _ => "- ".to_owned(),
+                };
+                stack.push((Block::Item(marker), String::new()));
+            }
+            Event::Start(Tag::BlockQuote) => {
+                block_break(out);
+                stack.push((Block::Quote, String::new()));
+            }
+            Event::Start(Tag::CodeBlock(_)) => {
+                block_break(out);
+                stack.push((Block::Code, String::new()));
+            }
+            Event::End(Tag::Item) | Event::End(Tag::BlockQuote) | Event::End(Tag::CodeBlock(_)) => {
+                let (block, inner) = stack.pop().unwrap();
+                let out = &mut stack.last_mut().unwrap().1;
+                let inner = inner.trim_end();
+                match block {
+                    Block::Item(marker) => {
+                        let indent = " ".repeat(marker.len());
+                        for (i, line) in inner.lines().enumerate() {
+                            let prefix = if i == 0 { &marker } else { &indent };
+                            out.push_str(format!("{}{}", prefix, line).trim_end());
+                            out.push('\n');
+                        }
+                    }
+                    Block::Quote => {
+                        for line in inner.lines() {
+                            out.push_str(format!("> {}", line).trim_end());
+                            out.push('\n');
+                        }
+                        out.push('\n');
+                    }
+                    Block::Code => {
+                        for line in inner.lines() {
+                            out.push_str(format!("    {}", line).trim_end());
+                            out.push('\n');
+                        }
+                        out.push('\n');
+                    }
+                    Block::Document => unreachable!("the document is never closed"),
+                }
+            }
+            Event::Start(Tag::Link(_, url, _)) => {
+                links.push(url.to_string());
+            }
+            Event::End(Tag::Link(..)) => {
+                let url = links.pop().unwrap_or_default();
+                let shown = url.strip_prefix("mailto:").unwrap_or(&url);
+                if !out.ends_with(shown) {
+                    out.push_str(&format!(" ({})", shown));
+                }
+            }
+            Event::Text(text) | Event::Code(text) => out.push_str(text),
+            Event::SoftBreak | Event::HardBreak => out.push('\n'),
+            Event::Rule => {
+                block_break(out);
+                out.push_str("---\n\n");
+            }
+            Event::TaskListMarker(done) => out.push_str(if *done { "[x] " } else { "[ ] " }),
+            Event::FootnoteReference(label) => out.push_str(&format!("[{}]", label)),
+            _ => {}
+        }
+    }
+    let (_, text) = stack.pop().unwrap();
+    text.trim_end().to_owned()
+}
+
+/// Starts a new block on a fresh line, a blank line after the previous one.
+fn block_break(out: &mut String) {
+    if out.is_empty() {
+        return;
+    }
+    while !out.ends_with("\n\n") {
+        out.push('\n');
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::render;
+
+    #[test]
+    fn markdown_becomes_html_and_text() {
+        let rendered = render("# Hello\n\nSome *emphasis* and a [link](https://example.com).");
+        assert_eq!(
+            rendered.html,
+            "<h1>Hello</h1>\n<p>Some <em>emphasis</em> and a <a href=\"https://example.com\">link</a>.</p>\n"
+        );
+        assert_eq!(
+            rendered.text,
+            "# Hello\n\nSome emphasis and a link (https://example.com)."
+        );
+    }
+
+    #[test]
+    fn raw_html_is_shown_as_text() {
+        let rendered = render("<script>alert(1)</script>\n\nHi <b>there</b>");
+        assert!(!rendered.html.contains("<script>"));
+        assert!(!rendered.html.contains("<b>"));
+        assert!(rendered.html.contains("&lt;script&gt;"));
+    }
+
+    #[test]
+    fn unsafe_links_lose_their_target() {
+        let rendered = render("[click](javascript:alert(1)) ![x](data:image/png;base64,AAAA)");
+        assert!(!rendered.html.contains("javascript:"));
+        assert!(!rendered.html.contains("data:"));
+        assert!(rendered.html.contains("click"));
+    }
+
+    #[test]
+    fn lists_and_quotes_are_laid_out_in_text() {
+        let rendered = render("Intro\n\n- one\n- two\n\n1. first\n2. second\n\n> quoted\n> text");
+        assert_eq!(
+            rendered.text,
+            "Intro\n\n- one\n- two\n\n1. first\n2. second\n\n> quoted\n> text"
+        );
+    }
+
+    #[test]
+    fn merge_tags_survive_rendering() {
+        let rendered = render(r#"Hi **{{ custom.nickname | default: "friend" }}**"#);
+        assert_eq!(
+            rendered.html,
+            "<p>Hi <strong>{{ custom.nickname | default: \"friend\" }}</strong></p>\n"
+        );
+        assert_eq!(
+            rendered.text,
+            r#"Hi {{ custom.nickname | default: "friend" }}"#
+        );
+    }
+
+    #[test]
+    fn text_that_looks_like_a_placeholder_is_left_alone() {
+        let rendered = render("MERGETAG0END {{ name }} \u{E000}0\u{E001}");
+        assert_eq!(rendered.html, "<p>MERGETAG0END {{ name }} 0</p>\n");
+    }
+}
@@ -0,0 +1,225 @@
+//! Per-recipient personalisation of newsletter bodies.
+//!
+//! `{{ name }}`, `{{ email }}` and `{{ custom.<key> }}` are replaced with the
+//! recipient's details; `{{ custom.company | default: "friend" }}` falls back
+//! to the quoted text when the recipient has no value.
+use std::collections::{BTreeSet, HashMap};
+
+#[derive(Debug, PartialEq, Eq)]
+enum Variable {
+    Name,
+    Email,
+    Custom(String),
+}
+
+#[derive(Debug, PartialEq, Eq)]
+enum Part {
+    Literal(String),
+    Tag {
+        variable: Variable,
+        default: Option<String>,
+    },
+}
+
+#[derive(Debug, PartialEq, Eq)]
+pub struct MergeTemplate(Vec<Part>);
+
+/// What a single recipient's tags are filled in with.
+pub struct MergeValues<'a> {
+    pub name: &'a str,
+    pub email: &'a str,
+    pub custom: &'a HashMap<String, String>,
+}
+
+impl MergeTemplate {
+    pub fn parse(s: &str) -> Result<MergeTemplate, String> {
+        let mut parts = Vec::new();
+        let mut rest = s;
+        while let Some(start) = rest.find("{{") {
+            if start > 0 {
+                parts.push(Part::Literal(rest[..start].to_owned()));
+            }
+            let after = &rest[start + 2..];
+            let end = after
+                .find("}}")
+                .ok_or("A merge tag is missing its closing `}}`")?;
+            parts.push(parse_tag(&after[..end])?);
+            rest = &after[end + 2..];
+        }
+        if !rest.is_empty() {
+            parts.push(Part::Literal(rest.to_owned()));
+        }
+        Ok(Self(parts))
+    }
+
+    /// The `custom.<key>` fields the template refers to.
+    pub fn custom_keys(&self) -> BTreeSet<&str> {
+        self.0
+            .iter()
+            .filter_map(|part| match part {
+                Part::Tag {
+                    variable: Variable::Custom(key),
+                    ..
+                } => Some(key.as_str()),
+                _ => None,
+            })
+            .collect()
+    }
+
+    /// Substituted values are HTML-escaped when `escape_html` is set; the
+    /// template's own text is left untouched.
+    pub fn render(&self, values: &MergeValues, escape_html: bool) -> String {
+        let mut output = String::new();
+        for part in &self.0 {
+            match part {
+                Part::Literal(text) => output.push_str(text),
+                Part::Tag { variable, default } => {
+                    let value = match variable {
+                        Variable::Name => Some(values.name),
+                        Variable::Email => Some(values.email),
+                        Variable::Custom(key) => values.custom.get(key).map(String::as_str),
+                    }
+                    .filter(|v| !v.is_empty())
+                    .or(default.as_deref())
+                    .unwrap_or_default();
+                    if escape_html {
+                        push_escaped(&mut output, value);
+                    } else {
+                        output.push_str(value);
+                    }
+                }
+            }
+        }
+        output
+    }
+}
+
+fn parse_tag(tag: &str) -> Result<Part, String> {
+    let (variable, filter) = match tag.split_once('|') {
+        Some((variable, filter)) => (variable.trim(), Some(filter.trim())),
+        None => (tag.trim(), None),
+    };
+    let variable = match variable {
+        "name" => Variable::Name,
+        "email" => Variable::Email,
+        _ => match variable.strip_prefix("custom.") {
+            Some(key) if !key.is_empty() => Variable::Custom(key.to_owned()),
+            _ => return Err(format!("Unknown merge tag `{{{{ {} }}}}`", variable)),
+        },
+    };
+    let default = filter
+        .map(|filter| {
+            filter
+                .strip_prefix("default:")
+                .map(str::trim)
+                .and_then(|s| s.strip_prefix('"'))
+                .and_then(|s| s.strip_suffix('"'))
+                .map(str::to_owned)
+                .ok_or_else(|| {
+                    format!(
+                        "Only `default: \"...\"` is supported after `|`, found `{}`",
+                        filter
+                    )
+                })
+        })
+        .transpose()?;
+    Ok(Part::Tag { variable, default })
+}
+
+fn push_escaped(output: &mut String, value: &str) {
+    for c in value.chars() {
+        match c {
+            '&' => output.push_str("&amp;"),
+            '<' => output.push_str("&lt;"),
+            '>' => output.push_str("&gt;"),
+            '"' => output.push_str("&quot;"),
+            '\'' => output.push_str("&#x27;"),
+            c => output.push(c),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{MergeTemplate, MergeValues};
+    use claims::assert_err;
+    use std::collections::HashMap;
+
+    fn render(template: &str, custom: &[(&str, &str)], escape_html: bool) -> String {
+        let custom: HashMap<String, String> = custom
+            .iter()
+            .map(|(k, v)| (k.to_string(), v.to_string()))
+            .collect();
+        let values = MergeValues {
+            name: "Ursula <3",
+            email: "ursula@example.com",
+            custom: &custom,
+        };
+        MergeTemplate::parse(template)
+            .unwrap()
+            .render(&values, escape_html)
+    }
+
+    #[test]
+    fn text_without_tags_is_unchanged() {
+        assert_eq!(render("Hello { there }", &[], false), "Hello { there }");
+    }
+
+    #[test]
+    fn name_and_custom_fields_are_substituted() {
+        assert_eq!(
+            render(
+                "Hi {{name}} from {{ custom.company }}!",
+                &[("company", "Acme")],
+                false
+            ),
+            "Hi Ursula <3 from Acme!"
+        );
+    }
+
+    #[test]
+    fn defaults_fill_in_missing_values() {
+        assert_eq!(
+            render(
+                r#"Hi {{ custom.nickname | default: "friend" }}"#,
+                &[],
+                false
+            ),
+            "Hi friend"
+        );
+    }
+
+    #[test]
+    fn values_are_escaped_in_html() {
+        assert_eq!(
+            render("<p>{{ name }}</p>", &[], true),
+            "<p>Ursula &lt;3</p>"
+        );
+    }
+
+    #[test]
+    fn custom_keys_are_listed() {
+        let template = MergeTemplate::parse("{{ custom.a }} {{ name }} {{ custom.b }}").unwrap();
+        assert_eq!(
+            template.custom_keys().into_iter().collect::<Vec<_>>(),
+            ["a", "b"]
+        );
+    }
+
+    #[test]
+    fn malformed_tags_are_rejected() {
+        for template in [
+            "{{ name",
+            "{{ surname }}",
+            "{{ custom. }}",
+            "{{ name | upcase }}",
+            "{{ name | default: friend }}",
+        ] {
+            assert_err!(
+                MergeTemplate::parse(template),
+                "{} should be rejected",
+                template
+            );
+        }
+    }
+}
@@ -0,0 +1,453 @@
+//! Webhooks we send to endpoints admins register, like a CRM.
+//!
+//! Events are queued in `webhook_deliveries` and sent by a background
+//! worker, which retries failed deliveries with a growing delay.
+//!
+//! Endpoints must be public `https` URLs: they are checked when they are
+//! added and again, after a fresh DNS lookup, before every delivery, so an
+//! endpoint can't be used to reach the app's own network.
+use crate::configuration::{OutboundWebhookSettings, Settings};
+use crate::startup::get_connection_pool;
+use anyhow::Context;
+use chrono::{Duration, Utc};
+use hmac::{Hmac, Mac};
+use reqwest::redirect::Policy;
+use reqwest::Url;
+use sha2::Sha256;
+use sqlx::{PgExecutor, PgPool, Postgres, Transaction};
+use std::net::{IpAddr, Ipv4Addr, Ipv6Addr, SocketAddr};
+use std::time::Duration as StdDuration;
+use uuid::Uuid;
+
+/// How many times a delivery is tried before it is given up on.
+pub const MAX_ATTEMPTS: i32 = 8;
+
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum WebhookEvent {
+    SubscriberCreated,
+    SubscriberConfirmed,
+    SubscriberUnsubscribed,
+    IssueSent,
+}
+
+impl WebhookEvent {
+    pub const ALL: [WebhookEvent; 4] = [
+        WebhookEvent::SubscriberCreated,
+        WebhookEvent::SubscriberConfirmed,
+        WebhookEvent::SubscriberUnsubscribed,
+        WebhookEvent::IssueSent,
+    ];
+
+    pub fn parse(s: &str) -> Result<WebhookEvent, String> {
+        Self::ALL
+            .into_iter()
+            .find(|event| event.as_str() == s)
+            .ok_or_else(|| format!("{} is not a webhook event", s))
+    }
+
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            WebhookEvent::SubscriberCreated => "subscriber.created",
+            WebhookEvent::SubscriberConfirmed => "subscriber.confirmed",
+            WebhookEvent::SubscriberUnsubscribed => "subscriber.unsubscribed",
+            WebhookEvent::IssueSent => "issue.sent",
+        }
+    }
+}
+
+/// The `X-Webhook-Signature` header value for a payload:
+/// `sha256=` followed by the hex HMAC-SHA256 of the body.
+pub fn signature(secret: &str, payload: &str) -> String {
+    let mut mac =
+        Hmac::<Sha256>::new_from_slice(secret.as_bytes()).expect("HMAC accepts keys of any length");
+    mac.update(payload.as_bytes());
+    format!("sha256={}", hex::encode(mac.finalize().into_bytes()))
+}
+
+/// An endpoint URL that passed [`check_endpoint`], with the addresses it
+/// resolved to.
+#[derive(Debug)]
+pub struct CheckedEndpoint {
+    pub url: Url,
+    addresses: Vec<SocketAddr>,
+}
+
+/// Checks that `url` is somewhere webhooks may be sent and looks it up.
+pub async fn check_endpoint(
+    url: &str,
+    settings: &OutboundWebhookSettings,
+) -> Result<CheckedEndpoint, String> {
+    let url = Url::parse(url).map_err(|_| "Webhook URLs must be absolute URLs".to_owned())?;
+    match url.scheme() {
+        "https" => {}
+        "http" if settings.allow_local_targets => {}
+        _ if settings.allow_local_targets => {
+            return Err("Webhook URLs must start with http:// or https://".into())
+        }
+        _ => return Err("Webhook URLs must start with https://".into()),
+    }
+    let host = url
+        .host_str()
+        .ok_or_else(|| "Webhook URLs need a host".to_owned())?;
+    let port = url.port_or_known_default().unwrap_or(443);
+    let addresses: Vec<SocketAddr> = tokio::net::lookup_host((host, port))
+        .await
+        .map_err(|_| format!("Couldn't look up {}", host))?
+        .collect();
+    if addresses.is_empty() {
+        return Err(format!("Couldn't look up {}", host));
+    }
+    if !settings.allow_local_targets && !addresses.iter().all(|a| is_public(a.ip())) {
+        return Err("Webhook URLs must point to a public address".into());
+    }
+    Ok(CheckedEndpoint { url, addresses })
+}
+
+/// Whether `ip` is reachable on the public internet, rather than loopback,
+/// private, link-local or otherwise reserved.
+fn is_public(ip: IpAddr) -> bool {
+    match ip {
+        IpAddr::V4(ip) => is_public_v4(ip),
+        IpAddr::V6(ip) => match ip.to_ipv4_mapped() {
+            Some(ip) => is_public_v4(ip),
+            None => is_public_v6(ip),
+        },
+    }
+}
+
+fn is_public_v4(ip: Ipv4Addr) -> bool {
+    let [a, b, ..] = ip.octets();
+    !(ip.is_unspecified()
+        || ip.is_loopback()
+        || ip.is_private()
+        || ip.is_link_local()
+        || ip.is_broadcast()
+        || ip.is_documentation()
+        || ip.is_multicast()
+        || a == 0
+        // Shared address space, used for carrier-grade NAT.
+        || (a == 100 && (64..128).contains(&b))
+        // Reserved for future use.
+        || a >= 240)
+}
+
+fn is_public_v6(ip: Ipv6Addr) -> bool {
+    let first = ip.segments()[0];
+    !(ip.is_unspecified()
+        || ip.is_loopback()
+        || ip.is_multicast()
+        // Unique local addresses, fc00::/7.
+        || (first & 0xfe00) == 0xfc00
+        // Link-local addresses, fe80::/10.
+        || (first & 0xffc0) == 0xfe80)
+}
+
+/// A client that only connects to the addresses the endpoint was checked
+/// against, so the name can't be pointed elsewhere in between, and that
+/// doesn't follow redirects for the same reason.
+fn pinned_client(endpoint: &CheckedEndpoint) -> Result<reqwest::Client, reqwest::Error> {
+    let builder = reqwest::Client::builder()
+        .redirect(Policy::none())
+        .timeout(StdDuration::from_secs(10));
+    let builder = match endpoint.url.domain() {
+        Some(domain) => builder.resolve_to_addrs(domain, &endpoint.addresses),
+        None => builder,
+    };
+    builder.build()
+}
+
+/// Queues `data` for every endpoint listening for `event`.
+#[tracing::instrument(name = "Queue webhook deliveries", skip(transaction, data))]
+pub async fn enqueue(
+    transaction: &mut Transaction<'_, Postgres>,
+    event: WebhookEvent,
+    data: serde_json::Value,
+) -> Result<(), anyhow::Error> {
+    let endpoint_ids: Vec<Uuid> = sqlx::query!(
+        r#"SELECT id FROM webhook_endpoints WHERE $1 = ANY(events)"#,
+        event.as_str()
+    )
+    .fetch_all(&mut **transaction)
+    .await
+    .context("Failed to find webhook endpoints")?
+    .into_iter()
+    .map(|r| r.id)
+    .collect();
+    if endpoint_ids.is_empty() {
+        return Ok(());
+    }
+    // One id per event, shared by its deliveries, so receivers can spot
+    // the same event arriving twice.
+    let payload = serde_json::json!({
+        "id": Uuid::new_v4(),
+        "event": event.as_str(),
+        "occurred_at": Utc::now(),
+        "data": data,
+    })
+    .to_string();
+    let delivery_ids: Vec<Uuid> = endpoint_ids.iter().map(|_| Uuid::new_v4()).collect();
+    sqlx::query!(
+        r#"
+        INSERT INTO webhook_deliveries
+            (id, endpoint_id, event, payload, status, attempts, next_attempt_at, created_at)
+        SELECT id, endpoint_id, $3, $4, 'pending', 0, now(), now()
+        FROM UNNEST($1::uuid[], $2::uuid[]) AS t(id, endpoint_id)
+        "#,
+        &delivery_ids,
+        &endpoint_ids,
+        event.as_str(),
+        payload,
+    )
+    .execute(&mut **transaction)
+    .await
+    .context("Failed to queue webhook deliveries")?;
+    Ok(())
+}
+
+/// Queues a subscriber event with the subscriber as they are now, as part of
+/// the transaction that changed them.
+pub async fn enqueue_for_subscriber(
+    transaction: &mut Transaction<'_, Postgres>,
+    event: WebhookEvent,
+    subscriber_id: Uuid,
+) -> Result<(), anyhow::Error> {
+    match subscriber_data(&mut **transaction, subscriber_id).await? {
+        Some(subscriber) => enqueue(transaction, event, subscriber).await,
+        None => Ok(()),
+    }
+}
+
+async fn subscriber_data(
+    executor: impl PgExecutor<'_>,
+    subscriber_id: Uuid,
+) -> Result<Option<serde_json::Value>, anyhow::Error> {
+    let row = sqlx::query!(
+        r#"SELECT id, email, name, status, subscribed_at FROM subscriptions WHERE id = $1"#,
+        subscriber_id
+    )
+    .fetch_optional(executor)
+    .await
+    .context("Failed to retrieve the subscriber")?;
+    Ok(row.map(|r| {
+        serde_json::json!({
+            "id": r.id,
+            "email": r.email,
+            "name": r.name,
+            "status": r.status,
+            "subscribed_at": r.subscribed_at,
+        })
+    }))
+}
+
+pub enum ExecutionOutcome {
+    TaskCompleted,
+    EmptyQueue,
+}
+
+/// Sends the next delivery that is due, if there is one.
+#[tracing::instrument(
+    skip_all,
+    fields(delivery_id=tracing::field::Empty, event=tracing::field::Empty),
+    err
+)]
+pub async fn try_execute_task(
+    pool: &PgPool,
+    settings: &OutboundWebhookSettings,
+) -> Result<ExecutionOutcome, anyhow::Error> {
+    let mut transaction = pool
+        .begin()
+        .await
+        .context("Failed to acquire a Postgres connection from the pool")?;
+    let Some(task) = sqlx::query_as!(
+        Delivery,
+        r#"
+        SELECT d.id, d.event, d.payload, d.attempts, e.url, e.secret
+        FROM webhook_deliveries d
+        JOIN webhook_endpoints e ON e.id = d.endpoint_id
+        WHERE d.status = 'pending' AND d.next_attempt_at <= now()
+        ORDER BY d.next_attempt_at
+        FOR UPDATE OF d SKIP LOCKED
+        LIMIT 1
+        "#
+    )
+    .fetch_optional(&mut *transaction)
+    .await
+    .context("Failed to dequeue a webhook delivery")?
+    else {
+        return Ok(ExecutionOutcome::EmptyQueue);
+    };
+    tracing::Span::current()
+        .record("delivery_id", tracing::field::display(task.id))
+        .record("event", tracing::field::display(&task.event));
+
+    let (response_status, error) = match deliver(&task, settings).await {
+        Ok(response) if response.status().is_success() => {
+            (Some(response.status().as_u16() as i32), None)
+        }
+        Ok(response) => (
+            Some(response.status().as_u16() as i32),
+            Some(format!("The endpoint responded with {}", response.status())),
+        ),
+        Err(e) => (None, Some(e)),
+    };
+    let attempts = task.attempts + 1;
+    let status = match &error {
+        None => "delivered",
+        Some(_) if attempts >= MAX_ATTEMPTS => "failed",
+        Some(_) => "pending",
+    };
+    if let Some(error) = &error {
+        tracing::warn!(error, attempts, "Failed to deliver a webhook");
+    }
+    sqlx::query!(
+        r#"
+        UPDATE webhook_deliveries
+        SET status = $2, attempts = $3, next_attempt_at = $4, last_attempt_at = now(),
+            last_response_status = $5, last_error = $6
+        WHERE id = $1
+        "#,
+        task.id,
+        status,
+        attempts,
+        Utc::now() + retry_delay(attempts),
+        response_status,
+        error,
+    )
+    .execute(&mut *transaction)
+    .await
+    .context("Failed to record the webhook delivery")?;
+    transaction
+        .commit()
+        .await
+        .context("Failed to commit transaction")?;
+    Ok(ExecutionOutcome::TaskCompleted)
+}
+
+struct Delivery {
+    id: Uuid,
+    event: String,
+    payload: String,
+    attempts: i32,
+    url: String,
+    secret: String,
+}
+
+async fn deliver(
+    task: &Delivery,
+    settings: &OutboundWebhookSettings,
+) -> Result<reqwest::Response, String> {
+    let endpoint = check_endpoint(&task.url, settings).await?;
+    let client = pinned_client(&endpoint).map_err(|e| e.to_string())?;
+    client
+        .post(endpoint.url)
+        .header("Content-Type", "application/json")
+        .header("X-Webhook-Event", &task.event)
+        .header("X-Webhook-Delivery", task.id.to_string())
+        .header(
+            "X-Webhook-Signature",
+            signature(&task.secret, &task.payload),
+        )
+        .body(task.payload.clone())
+        .send()
+        .await
+        .map_err(|e| e.to_string())
+}
+
+/// 30 seconds after the first failure, doubling each time after that.
+fn retry_delay(attempts: i32) -> Duration {
+    Duration::seconds(30 * 2_i64.pow(attempts.clamp(1, MAX_ATTEMPTS) as u32 - 1))
+}
+
+async fn worker_loop(pool: PgPool, settings: OutboundWebhookSettings) -> Result<(), anyhow::Error> {
+    loop {
+        match try_execute_task(&pool, &settings).await {
+            Ok(ExecutionOutcome::EmptyQueue) => {
+                tokio::time::sleep(StdDuration::from_secs(10)).await;
+            }
+            Err(_) => {
+                tokio::time::sleep(StdDuration::from_secs(1)).await;
+            }
+            Ok(ExecutionOutcome::TaskCompleted) => {}
+        }
+    }
+}
+
+pub async fn run_worker_until_stopped(configuration: Settings) -> Result<(), anyhow::Error> {
+    let pool = get_connection_pool(&configuration.database);
+    worker_loop(pool, configuration.outbound_webhooks).await
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{check_endpoint, is_public, retry_delay, signature, WebhookEvent};
+    use crate::configuration::OutboundWebhookSettings;
+    use chrono::Duration;
+    use claims::{assert_err, assert_ok, assert_ok_eq};
+
+    #[test]
+    fn every_event_round_trips() {
+        for event in WebhookEvent::ALL {
+            assert_ok_eq!(WebhookEvent::parse(event.as_str()), event);
+        }
+        assert_err!(WebhookEvent::parse("subscriber.deleted"));
+    }
+
+    #[test]
+    fn signatures_are_hex_hmac_sha256() {
+        // From RFC 4231, test case 2.
+        assert_eq!(
+            signature("Jefe", "what do ya want for nothing?"),
+            "sha256=5bdcc146bf60754e6a042426089575c75a003f089d2739839dec58b964ec3843"
+        );
+    }
+
+    #[test]
+    fn retries_back_off() {
+        assert_eq!(retry_delay(1), Duration::seconds(30));
+        assert_eq!(retry_delay(2), Duration::seconds(60));
+        assert_eq!(retry_delay(8), Duration::seconds(30 * 128));
+    }
+
+    #[test]
+    fn only_public_addresses_are_public() {
+        for ip in [
+            "127.0.0.1",
+            "10.1.2.3",
+            "172.16.0.1",
+            "192.168.1.1",
+            "169.254.169.254",
+            "100.64.0.1",
+            "0.0.0.0",
+            "::1",
+            "fd00::1",
+            "fe80::1",
+            "::ffff:127.0.0.1",
+        ] {
+            assert!(!is_public(ip.parse().unwrap()), "{} is not public", ip);
+        }
+        for ip in ["93.184.216.34", "2606:2800:220:1::1"] {
+            assert!(is_public(ip.parse().unwrap()), "{} is public", ip);
+        }
+    }
+
+    #[tokio::test]
+    async fn endpoints_must_be_public_https_urls() {
+        let settings = OutboundWebhookSettings::default();
+
+        assert_err!(check_endpoint("http://93.184.216.34/hooks", &settings).await);
+        assert_err!(check_endpoint("https://127.0.0.1/hooks", &settings).await);
+        assert_err!(check_endpoint("https://[::1]/hooks", &settings).await);
+        assert_err!(check_endpoint("https://169.254.169.254/latest", &settings).await);
+        assert_ok!(check_endpoint("https://93.184.216.34/hooks", &settings).await);
+    }
+
+    #[tokio::test]
+    async fn local_targets_can_be_allowed_for_development() {
+        let settings = OutboundWebhookSettings {
+            allow_local_targets: true,
+        };
+
+        assert_ok!(check_endpoint("http://127.0.0.1:8080/hooks", &settings).await);
+        assert_err!(check_endpoint("ftp://127.0.0.1/hooks", &settings).await);
+    }
+}
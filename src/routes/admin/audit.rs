@@ -0,0 +1,216 @@
+use super::filters::{date_range, non_empty, parse_date};
+use crate::audit::AuditAction;
+use crate::utils::{e500, render};
+use actix_web::error::ErrorNotFound;
+use actix_web::http::header::{ContentDisposition, DispositionParam, DispositionType};
+use actix_web::web;
+use actix_web::HttpResponse;
+use actix_web_flash_messages::IncomingFlashMessages;
+use anyhow::Context;
+use askama::Template;
+use chrono::{DateTime, NaiveDate, Utc};
+use sqlx::PgPool;
+
+const PAGE_SIZE: i64 = 50;
+
+#[derive(serde::Deserialize)]
+pub struct AuditQuery {
+    actor: Option<String>,
+    action: Option<String>,
+    from: Option<String>,
+    to: Option<String>,
+    page: Option<i64>,
+}
+
+struct AuditFilter {
+    actor: Option<String>,
+    action: Option<String>,
+    from: Option<NaiveDate>,
+    to: Option<NaiveDate>,
+}
+
+struct AuditRow {
+    occurred_at: DateTime<Utc>,
+    username: Option<String>,
+    action: String,
+    target: Option<String>,
+    ip_address: Option<String>,
+}
+
+impl AuditQuery {
+    fn filter(&self) -> Result<AuditFilter, actix_web::Error> {
+        Ok(AuditFilter {
+            actor: non_empty(&self.actor),
+            action: non_empty(&self.action),
+            from: parse_date(&self.from)?,
+            to: parse_date(&self.to)?,
+        })
+    }
+}
+
+impl AuditFilter {
+    fn query_string(&self, page: Option<i64>) -> String {
+        let mut params = Vec::new();
+        if let Some(actor) = &self.actor {
+            params.push(format!("actor={}", urlencoding::encode(actor)));
+        }
+        if let Some(action) = &self.action {
+            params.push(format!("action={}", urlencoding::encode(action)));
+        }
+        if let Some(from) = &self.from {
+            params.push(format!("from={}", from));
+        }
+        if let Some(to) = &self.to {
+            params.push(format!("to={}", to));
+        }
+        if let Some(page) = page {
+            params.push(format!("page={}", page));
+        }
+        params.join("&")
+    }
+}
+
+#[derive(Template)]
+#[template(path = "admin/audit.html")]
+struct AuditLogTemplate<'a> {
+    flash_messages: &'a IncomingFlashMessages,
+    actor: &'a str,
+    action: &'a str,
+    actions: &'static [AuditAction],
+    from: String,
+    to: String,
+    export_query: String,
+    newer_query: Option<String>,
+    older_query: Option<String>,
+    events: &'a [AuditRow],
+}
+
+pub async fn audit_log(
+    flash_messages: IncomingFlashMessages,
+    query: web::Query<AuditQuery>,
+    pool: web::Data<PgPool>,
+) -> Result<HttpResponse, actix_web::Error> {
+    let filter = query.filter()?;
+    let page = query.page.unwrap_or(1).max(1);
+    let offset = (page - 1)
+        .checked_mul(PAGE_SIZE)
+        .ok_or_else(|| ErrorNotFound("There is no such page"))?;
+
+    let mut events = get_audit_events(&pool, &filter, Some(PAGE_SIZE + 1), offset)
+        .await
+        .map_err(e500)?;
+    let has_next = events.len() as i64 > PAGE_SIZE;
+    events.truncate(PAGE_SIZE as usize);
+
+    render(&AuditLogTemplate {
+        flash_messages: &flash_messages,
+        actor: filter.actor.as_deref().unwrap_or(""),
+        action: filter.action.as_deref().unwrap_or(""),
+        actions: &AuditAction::ALL,
+        from: filter.from.map(|d| d.to_string()).unwrap_or_default(),
+        to: filter.to.map(|d| d.to_string()).unwrap_or_default(),
+        export_query: filter.query_string(None),
+        newer_query: (page > 1).then(|| filter.query_string(Some(page - 1))),
+        older_query: has_next.then(|| filter.query_string(Some(page + 1))),
+        events: &events,
+    })
+}
+
+pub async fn export_audit_log(
+    query: web::Query<AuditQuery>,
+    pool: web::Data<PgPool>,
+) -> Result<HttpResponse, actix_web::Error> {
+    let filter = query.filter()?;
+    let events = get_audit_events(&pool, &filter, None, 0)
+        .await
+        .map_err(e500)?;
+
+    let mut writer = csv::Writer::from_writer(Vec::new());
+    writer
+        .write_record(["occurred_at", "actor", "action", "target", "ip_address"])
+        .map_err(e500)?;
+    for e in events {
+        writer
+            .write_record([
+                e.occurred_at.to_rfc3339(),
+                spreadsheet_safe(e.username.unwrap_or_default()),
+                e.action,
+                spreadsheet_safe(e.target.unwrap_or_default()),
+                e.ip_address.unwrap_or_default(),
+            ])
+            .map_err(e500)?;
+    }
+    let body = writer.into_inner().map_err(e500)?;
+
+    Ok(HttpResponse::Ok()
+        .content_type("text/csv; charset=utf-8")
+        .insert_header(ContentDisposition {
+            disposition: DispositionType::Attachment,
+            parameters: vec![DispositionParam::Filename("audit.csv".into())],
+        })
+        .body(body))
+}
+
+/// Targets and usernames can be typed by anyone (a failed login records
+/// whatever username was tried), so cells a spreadsheet would read as a
+/// formula are quoted.
+fn spreadsheet_safe(value: String) -> String {
+    if value.starts_with(['=', '+', '-', '@', '\t', '\r']) {
+        format!("'{}", value)
+    } else {
+        value
+    }
+}
+
+#[tracing::instrument(name = "Get audit events", skip(pool, filter))]
+async fn get_audit_events(
+    pool: &PgPool,
+    filter: &AuditFilter,
+    limit: Option<i64>,
+    offset: i64,
+) -> Result<Vec<AuditRow>, anyhow::Error> {
+    let (from, to) = date_range(filter.from, filter.to);
+
+    let rows = sqlx::query_as!(
+        AuditRow,
+        r#"
+        SELECT e.occurred_at, u.username AS "username?", e.action, e.target, e.ip_address
+        FROM audit_events e
+        LEFT JOIN users u ON u.user_id = e.actor_user_id
+        WHERE ($1::text IS NULL OR u.username = $1)
+        AND ($2::text IS NULL OR e.action = $2)
+        AND ($3::timestamptz IS NULL OR e.occurred_at >= $3)
+        AND ($4::timestamptz IS NULL OR e.occurred_at < $4)
+        ORDER BY e.occurred_at DESC
+        LIMIT $5 OFFSET $6
+        "#,
+        filter.actor,
+        filter.action,
+        from,
+        to,
+        limit,
+        offset,
+    )
+    .fetch_all(pool)
+    .await
+    .context("Failed to retrieve audit events")?;
+    Ok(rows)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::spreadsheet_safe;
+
+    #[test]
+    fn formulas_are_quoted() {
+        for value in ["=1+1", "+1", "-1", "@SUM(A1)", "\tx", "\rx"] {
+            assert_eq!(spreadsheet_safe(value.into()), format!("'{}", value));
+        }
+    }
+
+    #[test]
+    fn other_values_are_left_alone() {
+        assert_eq!(spreadsheet_safe("admin".into()), "admin");
+        assert_eq!(spreadsheet_safe("".into()), "");
+    }
+}
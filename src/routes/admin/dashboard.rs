@@ -1,16 +1,136 @@
+use crate::authentication::OidcClient;
+use crate::configuration::OidcProviderSettings;
+use crate::domain::SubscriptionStatus;
 use crate::session_state::TypedSession;
-use crate::utils::e500;
-use actix_web::http::header::ContentType;
+use crate::utils::{e500, render};
+use actix_web::error::ErrorBadRequest;
 use actix_web::web;
 use actix_web::HttpResponse;
+use actix_web_flash_messages::IncomingFlashMessages;
 use anyhow::Context;
+use askama::Template;
+use chrono::{DateTime, NaiveDate, Utc};
 use reqwest::header::LOCATION;
 use sqlx::PgPool;
 use uuid::Uuid;
 
+/// The growth periods the dashboard can show, in days.
+const PERIODS: [i32; 3] = [30, 90, 365];
+const RECENT_ISSUES: i64 = 5;
+
+#[derive(serde::Deserialize)]
+pub struct DashboardQuery {
+    days: Option<i32>,
+}
+
+struct StatusCount {
+    status: SubscriptionStatus,
+    count: i64,
+}
+
+struct DailyCount {
+    day: NaiveDate,
+    signups: i64,
+    confirmations: i64,
+}
+
+struct RecentIssue {
+    id: Uuid,
+    title: String,
+    published_at: DateTime<Utc>,
+    recipients: i64,
+    delivered: i64,
+    failed: i64,
+    bounced: i64,
+}
+
+/// A bar chart laid out for an SVG `viewBox` of
+/// `BarChart::WIDTH` by `BarChart::HEIGHT`.
+struct BarChart {
+    bars: Vec<Bar>,
+    max: i64,
+}
+
+struct Bar {
+    label: String,
+    value: i64,
+    x: String,
+    y: String,
+    width: String,
+    height: String,
+}
+
+impl BarChart {
+    const WIDTH: f64 = 730.0;
+    const HEIGHT: f64 = 120.0;
+
+    fn new(values: impl ExactSizeIterator<Item = (String, i64)>) -> Self {
+        let slot = Self::WIDTH / values.len().max(1) as f64;
+        let values: Vec<(String, i64)> = values.collect();
+        let max = values.iter().map(|(_, v)| *v).max().unwrap_or(0);
+        let bars = values
+            .into_iter()
+            .enumerate()
+            .map(|(i, (label, value))| {
+                let height = if max == 0 {
+                    0.0
+                } else {
+                    value as f64 / max as f64 * Self::HEIGHT
+                };
+                Bar {
+                    label,
+                    value,
+                    x: format!("{:.2}", i as f64 * slot),
+                    y: format!("{:.2}", Self::HEIGHT - height),
+                    // Leave a gap between bars while there is room for one.
+                    width: format!("{:.2}", if slot > 3.0 { slot - 1.0 } else { slot }),
+                    height: format!("{:.2}", height),
+                }
+            })
+            .collect();
+        Self { bars, max }
+    }
+
+    fn width(&self) -> f64 {
+        Self::WIDTH
+    }
+
+    fn height(&self) -> f64 {
+        Self::HEIGHT
+    }
+}
+
+#[derive(Template)]
+#[template(path = "admin/dashboard.html")]
+struct DashboardTemplate<'a> {
+    flash_messages: &'a IncomingFlashMessages,
+    username: &'a str,
+    /// Providers the user can link a single sign-on identity from.
+    providers: &'a [OidcProviderSettings],
+    days: i32,
+    periods: &'a [i32],
+    statuses: Vec<StatusCount>,
+    signups: i64,
+    confirmations: i64,
+    /// Of the period's signups, the share that has confirmed.
+    conversion_rate: Option<String>,
+    signups_chart: BarChart,
+    confirmations_chart: BarChart,
+    recent_issues: Vec<RecentIssue>,
+}
+
+impl DashboardTemplate<'_> {
+    fn is_selected(&self, period: &i32) -> bool {
+        *period == self.days
+    }
+}
+
 pub async fn admin_dashboard(
+    flash_messages: IncomingFlashMessages,
+    query: web::Query<DashboardQuery>,
     session: TypedSession,
     pool: web::Data<PgPool>,
+    oidc_client: web::Data<OidcClient>,
 ) -> Result<HttpResponse, actix_web::Error> {
     let username = if let Some(user_id) = session.get_user_id().map_err(e500)? {
         get_username(user_id, &pool).await.map_err(e500)?
@@ -19,30 +139,31 @@ pub async fn admin_dashboard(
             .insert_header((LOCATION, "/login"))
             .finish());
     };
-    Ok(HttpResponse::Ok()
-        .content_type(ContentType::html())
-        .body(format!(
-            r#"<!DOCTYPE html>
-        <html lang="en">
-        <head>
-            <meta http-equiv="content-type" content="text/html; charset=utf-8">
-            <title>Admin Dashboard</title>
-        </head>
-        <body>
-            <p>Welcome {username}</p>
-            <p>Available Actions</p>
-            <ol>
-                <li><a href="/admin/password">Change Password</a></li>
-                <li><a href="/admin/newsletters">Change Password</a></li>
-                <li>
-                    <form name="logoutForm" action="/action/login" method="post">
-                        <input type="submit" value="Logout">
-                    </form>
-                </li>
-            </ol>
-        </body>
-        </html>"#
-        )))
+    let days = query.days.unwrap_or(PERIODS[0]);
+    if !PERIODS.contains(&days) {
+        return Err(ErrorBadRequest("The period must be 30, 90 or 365 days"));
+    }
+    let statuses = get_status_counts(&pool).await.map_err(e500)?;
+    let daily = get_daily_counts(&pool, days).await.map_err(e500)?;
+    let (signed_up, converted) = get_conversion(&pool, days).await.map_err(e500)?;
+    let recent_issues = get_recent_issues(&pool).await.map_err(e500)?;
+
+    let label = |d: &DailyCount| d.day.format("%Y-%m-%d").to_string();
+    render(&DashboardTemplate {
+        flash_messages: &flash_messages,
+        username: &username,
+        providers: oidc_client.providers(),
+        days,
+        periods: &PERIODS,
+        statuses,
+        signups: daily.iter().map(|d| d.signups).sum(),
+        confirmations: daily.iter().map(|d| d.confirmations).sum(),
+        conversion_rate: (signed_up > 0)
+            .then(|| format!("{:.1}%", converted as f64 / signed_up as f64 * 100.0)),
+        signups_chart: BarChart::new(daily.iter().map(|d| (label(d), d.signups))),
+        confirmations_chart: BarChart::new(daily.iter().map(|d| (label(d), d.confirmations))),
+        recent_issues,
+    })
 }
 
 #[tracing::instrument(name = "Get username", skip(pool))]
@@ -53,3 +174,141 @@ pub async fn get_username(user_id: Uuid, pool: &PgPool) -> Result<String, anyhow
         .context("Failed to get username")?;
     Ok(row.username)
 }
+
+/// Every status, including those nobody has.
+#[tracing::instrument(name = "Count subscribers by status", skip(pool))]
+async fn get_status_counts(pool: &PgPool) -> Result<Vec<StatusCount>, anyhow::Error> {
+    let rows =
+        sqlx::query!(r#"SELECT status, count(*) AS "count!" FROM subscriptions GROUP BY status"#)
+            .fetch_all(pool)
+            .await
+            .context("Failed to count subscribers")?;
+    Ok(SubscriptionStatus::ALL
+        .into_iter()
+        .map(|status| StatusCount {
+            status,
+            count: rows
+                .iter()
+                .find(|r| r.status == status.as_str())
+                .map_or(0, |r| r.count),
+        })
+        .collect())
+}
+
+/// One row per day of the period, ending today.
+#[tracing::instrument(name = "Count signups and confirmations per day", skip(pool))]
+async fn get_daily_counts(pool: &PgPool, days: i32) -> Result<Vec<DailyCount>, anyhow::Error> {
+    let rows = sqlx::query_as!(
+        DailyCount,
+        r#"
+        WITH days AS (
+            SELECT generate_series(current_date - ($1::int - 1), current_date, '1 day')::date
+                AS day
+        ),
+        signups AS (
+            SELECT subscribed_at::date AS day, count(*) AS n
+            FROM subscriptions
+            WHERE subscribed_at >= current_date - ($1::int - 1)
+            GROUP BY 1
+        ),
+        confirmations AS (
+            SELECT confirmed_at::date AS day, count(*) AS n
+            FROM subscriptions
+            WHERE confirmed_at >= current_date - ($1::int - 1)
+            GROUP BY 1
+        )
+        SELECT
+            days.day AS "day!",
+            coalesce(s.n, 0) AS "signups!",
+            coalesce(c.n, 0) AS "confirmations!"
+        FROM days
+        LEFT JOIN signups s USING (day)
+        LEFT JOIN confirmations c USING (day)
+        ORDER BY days.day
+        "#,
+        days
+    )
+    .fetch_all(pool)
+    .await
+    .context("Failed to count signups per day")?;
+    Ok(rows)
+}
+
+/// How many signed up during the period, and how many of those confirmed.
+#[tracing::instrument(name = "Count confirmed signups", skip(pool))]
+async fn get_conversion(pool: &PgPool, days: i32) -> Result<(i64, i64), anyhow::Error> {
+    let row = sqlx::query!(
+        r#"
+        SELECT
+            count(*) AS "signed_up!",
+            count(*) FILTER (WHERE confirmed_at IS NOT NULL) AS "converted!"
+        FROM subscriptions
+        WHERE subscribed_at >= current_date - ($1::int - 1)
+        "#,
+        days
+    )
+    .fetch_one(pool)
+    .await
+    .context("Failed to count confirmed signups")?;
+    Ok((row.signed_up, row.converted))
+}
+
+#[tracing::instrument(name = "Get recent issues", skip(pool))]
+async fn get_recent_issues(pool: &PgPool) -> Result<Vec<RecentIssue>, anyhow::Error> {
+    let rows = sqlx::query_as!(
+        RecentIssue,
+        r#"
+        SELECT
+            i.id,
+            i.title,
+            i.published_at,
+            count(d.subscriber_id) AS "recipients!",
+            count(*) FILTER (WHERE d.status = 'delivered') AS "delivered!",
+            count(*) FILTER (WHERE d.status = 'failed') AS "failed!",
+            count(*) FILTER (WHERE d.status = 'bounced') AS "bounced!"
+        FROM (
+            SELECT id, title, published_at
+            FROM newsletter_issues
+            ORDER BY published_at DESC
+            LIMIT $1
+        ) i
+        LEFT JOIN issue_deliveries d ON d.issue_id = i.id
+        GROUP BY i.id, i.title, i.published_at
+        ORDER BY i.published_at DESC
+        "#,
+        RECENT_ISSUES
+    )
+    .fetch_all(pool)
+    .await
+    .context("Failed to retrieve recent issues")?;
+    Ok(rows)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::BarChart;
+
+    #[test]
+    fn bars_are_scaled_to_the_largest_value() {
+        let chart = BarChart::new(vec![("a".into(), 2), ("b".into(), 4)].into_iter());
+        assert_eq!(chart.max, 4);
+        assert_eq!(chart.bars[0].height, "60.00");
+        assert_eq!(chart.bars[0].y, "60.00");
+        assert_eq!(chart.bars[1].height, "120.00");
+        assert_eq!(chart.bars[1].x, "365.00");
+        assert_eq!(chart.bars[1].width, "364.00");
+    }
+
+    #[test]
+    fn a_chart_of_zeroes_is_flat() {
+        let chart = BarChart::new(vec![("a".into(), 0); 30].into_iter());
+        assert!(chart.bars.iter().all(|b| b.height == "0.00"));
+    }
+
+    #[test]
+    fn an_empty_chart_has_no_bars() {
+        let chart = BarChart::new(Vec::new().into_iter());
+        assert!(chart.bars.is_empty());
+        assert_eq!(chart.max, 0);
+    }
+}
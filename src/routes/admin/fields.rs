@@ -0,0 +1,122 @@
+use crate::audit::{AuditAction, AuditEvent};
+use crate::authentication::UserId;
+use crate::custom_fields::{get_custom_fields, CustomField};
+use crate::domain::{CustomFieldKey, CustomFieldKind};
+use crate::utils::{e500, render, see_other};
+use actix_web::web;
+use actix_web::{HttpRequest, HttpResponse};
+use actix_web_flash_messages::{FlashMessage, IncomingFlashMessages};
+use anyhow::Context;
+use askama::Template;
+use sqlx::PgPool;
+use uuid::Uuid;
+
+#[derive(Template)]
+#[template(path = "admin/fields.html")]
+struct FieldsTemplate<'a> {
+    flash_messages: &'a IncomingFlashMessages,
+    fields: Vec<CustomField>,
+    kinds: &'static [CustomFieldKind],
+}
+
+pub async fn custom_fields(
+    flash_messages: IncomingFlashMessages,
+    pool: web::Data<PgPool>,
+) -> Result<HttpResponse, actix_web::Error> {
+    let fields = get_custom_fields(&pool).await.map_err(e500)?;
+    render(&FieldsTemplate {
+        flash_messages: &flash_messages,
+        fields,
+        kinds: &CustomFieldKind::ALL,
+    })
+}
+
+#[derive(serde::Deserialize)]
+pub struct NewFieldForm {
+    label: String,
+    key: String,
+    kind: String,
+    /// Comma separated; only used by choice fields.
+    #[serde(default)]
+    choices: String,
+}
+
+pub async fn create_custom_field(
+    form: web::Form<NewFieldForm>,
+    pool: web::Data<PgPool>,
+    user_id: web::ReqData<UserId>,
+    request: HttpRequest,
+) -> Result<HttpResponse, actix_web::Error> {
+    let label = form.label.trim();
+    if label.is_empty() {
+        FlashMessage::error("A field needs a label").send();
+        return Ok(see_other("/admin/fields"));
+    }
+    let Ok(key) = CustomFieldKey::parse(form.key.trim().to_owned()) else {
+        FlashMessage::error(
+            "Keys must start with a lowercase letter and contain only lowercase letters, digits and underscores",
+        )
+        .send();
+        return Ok(see_other("/admin/fields"));
+    };
+    let kind = CustomFieldKind::parse(&form.kind).map_err(actix_web::error::ErrorBadRequest)?;
+    let choices: Vec<String> = form
+        .choices
+        .split(',')
+        .map(str::trim)
+        .filter(|c| !c.is_empty())
+        .map(str::to_owned)
+        .collect();
+    if kind == CustomFieldKind::Choice && choices.is_empty() {
+        FlashMessage::error("A choice field needs at least one choice").send();
+        return Ok(see_other("/admin/fields"));
+    }
+    let choices = if kind == CustomFieldKind::Choice {
+        choices
+    } else {
+        Vec::new()
+    };
+
+    if !insert_custom_field(&pool, label, &key, kind, &choices)
+        .await
+        .map_err(e500)?
+    {
+        FlashMessage::error("A field with that key already exists").send();
+        return Ok(see_other("/admin/fields"));
+    }
+    AuditEvent::new(AuditAction::CustomFieldCreated, &request)
+        .actor(*user_id.into_inner())
+        .target(key.as_ref())
+        .record(&pool)
+        .await
+        .map_err(e500)?;
+    FlashMessage::info("Field created").send();
+    Ok(see_other("/admin/fields"))
+}
+
+/// Returns `false` if the key is taken.
+#[tracing::instrument(name = "Create custom field", skip(pool))]
+async fn insert_custom_field(
+    pool: &PgPool,
+    label: &str,
+    key: &CustomFieldKey,
+    kind: CustomFieldKind,
+    choices: &[String],
+) -> Result<bool, anyhow::Error> {
+    let inserted = sqlx::query!(
+        r#"
+        INSERT INTO custom_fields (id, key, label, kind, choices, created_at)
+        VALUES ($1, $2, $3, $4, $5, now())
+        ON CONFLICT (key) DO NOTHING
+        "#,
+        Uuid::new_v4(),
+        key.as_ref(),
+        label,
+        kind.as_str(),
+        choices,
+    )
+    .execute(pool)
+    .await
+    .context("Failed to create custom field")?;
+    Ok(inserted.rows_affected() > 0)
+}
@@ -0,0 +1,30 @@
+use actix_web::error::ErrorBadRequest;
+use chrono::{DateTime, Days, NaiveDate, Utc};
+
+pub(super) fn non_empty(value: &Option<String>) -> Option<String> {
+    value
+        .as_deref()
+        .map(str::trim)
+        .filter(|v| !v.is_empty())
+        .map(str::to_owned)
+}
+
+pub(super) fn parse_date(value: &Option<String>) -> Result<Option<NaiveDate>, actix_web::Error> {
+    non_empty(value)
+        .map(|v| NaiveDate::parse_from_str(&v, "%Y-%m-%d"))
+        .transpose()
+        .map_err(ErrorBadRequest)
+}
+
+/// Turns an inclusive range of days into a half-open range of instants.
+pub(super) fn date_range(
+    from: Option<NaiveDate>,
+    to: Option<NaiveDate>,
+) -> (Option<DateTime<Utc>>, Option<DateTime<Utc>>) {
+    let start_of = |d: NaiveDate| d.and_hms_opt(0, 0, 0).unwrap().and_utc();
+    (
+        from.map(start_of),
+        to.and_then(|d| d.checked_add_days(Days::new(1)))
+            .map(start_of),
+    )
+}
@@ -0,0 +1,87 @@
+use crate::utils::{e500, render};
+use actix_web::web;
+use actix_web::HttpResponse;
+use actix_web_flash_messages::IncomingFlashMessages;
+use anyhow::Context;
+use askama::Template;
+use chrono::{DateTime, Utc};
+use sqlx::PgPool;
+use uuid::Uuid;
+
+const PAGE_SIZE: i64 = 50;
+
+#[derive(serde::Deserialize)]
+pub struct IssuesQuery {
+    page: Option<i64>,
+}
+
+struct IssueRow {
+    id: Uuid,
+    slug: String,
+    title: String,
+    published_at: DateTime<Utc>,
+    hidden_from_archive: bool,
+    /// `None` when opens weren't tracked for the issue.
+    opens: Option<i64>,
+    /// How many recipients clicked a link; `None` when clicks weren't
+    /// tracked.
+    clickers: Option<i64>,
+}
+
+#[derive(Template)]
+#[template(path = "admin/issues.html")]
+struct IssuesTemplate<'a> {
+    flash_messages: &'a IncomingFlashMessages,
+    issues: Vec<IssueRow>,
+    newer_page: Option<i64>,
+    older_page: Option<i64>,
+}
+
+pub async fn sent_issues(
+    flash_messages: IncomingFlashMessages,
+    query: web::Query<IssuesQuery>,
+    pool: web::Data<PgPool>,
+) -> Result<HttpResponse, actix_web::Error> {
+    let page = query.page.unwrap_or(1).max(1);
+    let mut issues = get_issues(&pool, PAGE_SIZE + 1, (page - 1) * PAGE_SIZE)
+        .await
+        .map_err(e500)?;
+    let has_next = issues.len() as i64 > PAGE_SIZE;
+    issues.truncate(PAGE_SIZE as usize);
+    render(&IssuesTemplate {
+        flash_messages: &flash_messages,
+        issues,
+        newer_page: (page > 1).then_some(page - 1),
+        older_page: has_next.then_some(page + 1),
+    })
+}
+
+#[tracing::instrument(name = "Get sent issues", skip(pool))]
+async fn get_issues(
+    pool: &PgPool,
+    limit: i64,
+    offset: i64,
+) -> Result<Vec<IssueRow>, anyhow::Error> {
+    let rows = sqlx::query_as!(
+        IssueRow,
+        r#"
+        SELECT id, slug, title, published_at, hidden_from_archive,
+            CASE WHEN track_opens
+                THEN (SELECT count(*) FROM issue_opens o WHERE o.issue_id = i.id)
+            END AS opens,
+            CASE WHEN track_clicks
+                THEN (SELECT count(DISTINCT subscriber_id) FROM issue_clicks c
+                    WHERE c.issue_id = i.id)
+            END AS clickers
+        FROM newsletter_issues i
+        ORDER BY published_at DESC, id
+        LIMIT $1 OFFSET $2
+        "#,
+        limit,
+        offset
+    )
+    .fetch_all(pool)
+    .await
+    .context("Failed to retrieve issues")?;
+    Ok(rows)
+}
@@ -0,0 +1,7 @@
+mod get;
+mod post;
+mod report;
+
+pub use get::sent_issues;
+pub use post::set_issue_visibility;
+pub use report::{issue_report, issue_report_json};
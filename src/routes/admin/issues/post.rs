@@ -0,0 +1,52 @@
+use crate::audit::{AuditAction, AuditEvent};
+use crate::authentication::UserId;
+use crate::utils::{e500, see_other};
+use actix_web::error::ErrorNotFound;
+use actix_web::web;
+use actix_web::{HttpRequest, HttpResponse};
+use actix_web_flash_messages::FlashMessage;
+use anyhow::Context;
+use sqlx::PgPool;
+use uuid::Uuid;
+
+#[derive(serde::Deserialize)]
+pub struct VisibilityForm {
+    hidden: bool,
+}
+
+pub async fn set_issue_visibility(
+    issue_id: web::Path<Uuid>,
+    form: web::Form<VisibilityForm>,
+    pool: web::Data<PgPool>,
+    user_id: web::ReqData<UserId>,
+    request: HttpRequest,
+) -> Result<HttpResponse, actix_web::Error> {
+    let updated = sqlx::query!(
+        r#"
+        UPDATE newsletter_issues SET hidden_from_archive = $2, updated_at = now()
+        WHERE id = $1
+        "#,
+        *issue_id,
+        form.hidden
+    )
+    .execute(pool.get_ref())
+    .await
+    .context("Failed to update the issue")
+    .map_err(e500)?;
+    if updated.rows_affected() == 0 {
+        return Err(ErrorNotFound("Unknown issue"));
+    }
+    let (action, message) = if form.hidden {
+        (AuditAction::IssueHidden, "Issue hidden from the archive")
+    } else {
+        (AuditAction::IssueShown, "Issue shown in the archive")
+    };
+    AuditEvent::new(action, &request)
+        .actor(*user_id.into_inner())
+        .target(issue_id.to_string())
+        .record(&pool)
+        .await
+        .map_err(e500)?;
+    FlashMessage::info(message).send();
+    Ok(see_other("/admin/issues"))
+}
@@ -0,0 +1,175 @@
+use crate::utils::{e500, render};
+use actix_web::error::ErrorNotFound;
+use actix_web::web;
+use actix_web::HttpResponse;
+use actix_web_flash_messages::IncomingFlashMessages;
+use anyhow::Context;
+use askama::Template;
+use chrono::{DateTime, Utc};
+use sqlx::PgPool;
+use uuid::Uuid;
+
+/// How an issue's sending went and what readers did with it.
+#[derive(serde::Serialize)]
+struct IssueReport {
+    id: Uuid,
+    title: String,
+    slug: String,
+    published_at: DateTime<Utc>,
+    recipients: i64,
+    delivered: i64,
+    failed: i64,
+    bounced: i64,
+    /// `None` when opens weren't tracked for the issue.
+    opens: Option<Opens>,
+    /// `None` when clicks weren't tracked for the issue.
+    links: Option<Vec<LinkClicks>>,
+    /// Recipients who unsubscribed themselves while this was the latest
+    /// issue they had been sent.
+    unsubscribes: i64,
+}
+
+#[derive(serde::Serialize)]
+struct Opens {
+    total: i64,
+    unique: i64,
+}
+
+#[derive(serde::Serialize)]
+struct LinkClicks {
+    url: String,
+    total: i64,
+    unique: i64,
+}
+
+#[derive(Template)]
+#[template(path = "admin/issue_report.html")]
+struct IssueReportTemplate<'a> {
+    flash_messages: &'a IncomingFlashMessages,
+    report: IssueReport,
+}
+
+pub async fn issue_report(
+    flash_messages: IncomingFlashMessages,
+    issue_id: web::Path<Uuid>,
+    pool: web::Data<PgPool>,
+) -> Result<HttpResponse, actix_web::Error> {
+    let report = get_issue_report(&pool, *issue_id)
+        .await
+        .map_err(e500)?
+        .ok_or_else(|| ErrorNotFound("Unknown issue"))?;
+    render(&IssueReportTemplate {
+        flash_messages: &flash_messages,
+        report,
+    })
+}
+
+pub async fn issue_report_json(
+    issue_id: web::Path<Uuid>,
+    pool: web::Data<PgPool>,
+) -> Result<HttpResponse, actix_web::Error> {
+    let report = get_issue_report(&pool, *issue_id)
+        .await
+        .map_err(e500)?
+        .ok_or_else(|| ErrorNotFound("Unknown issue"))?;
+    Ok(HttpResponse::Ok().json(report))
+}
+
+#[tracing::instrument(name = "Build an issue report", skip(pool))]
+async fn get_issue_report(
+    pool: &PgPool,
+    issue_id: Uuid,
+) -> Result<Option<IssueReport>, anyhow::Error> {
+    let Some(issue) = sqlx::query!(
+        r#"
+        SELECT title, slug, published_at, track_opens, track_clicks
+        FROM newsletter_issues
+        WHERE id = $1
+        "#,
+        issue_id
+    )
+    .fetch_optional(pool)
+    .await
+    .context("Failed to retrieve the issue")?
+    else {
+        return Ok(None);
+    };
+
+    let deliveries = sqlx::query!(
+        r#"
+        SELECT
+            count(*) AS "recipients!",
+            count(*) FILTER (WHERE status = 'delivered') AS "delivered!",
+            count(*) FILTER (WHERE status = 'failed') AS "failed!",
+            count(*) FILTER (WHERE status = 'bounced') AS "bounced!"
+        FROM issue_deliveries
+        WHERE issue_id = $1
+        "#,
+        issue_id
+    )
+    .fetch_one(pool)
+    .await
+    .context("Failed to count deliveries")?;
+
+    let opens = if issue.track_opens {
+        let row = sqlx::query!(
+            r#"
+            SELECT coalesce(sum(open_count), 0) AS "total!", count(*) AS "unique!"
+            FROM issue_opens
+            WHERE issue_id = $1
+            "#,
+            issue_id
+        )
+        .fetch_one(pool)
+        .await
+        .context("Failed to count opens")?;
+        Some(Opens {
+            total: row.total,
+            unique: row.unique,
+        })
+    } else {
+        None
+    };
+
+    let links = if issue.track_clicks {
+        let links = sqlx::query_as!(
+            LinkClicks,
+            r#"
+            SELECT url, count(*) AS "total!", count(DISTINCT subscriber_id) AS "unique!"
+            FROM issue_clicks
+            WHERE issue_id = $1
+            GROUP BY url
+            ORDER BY 2 DESC, url
+            "#,
+            issue_id
+        )
+        .fetch_all(pool)
+        .await
+        .context("Failed to count clicks")?;
+        Some(links)
+    } else {
+        None
+    };
+
+    let unsubscribes = sqlx::query_scalar!(
+        r#"SELECT count(*) AS "count!" FROM issue_unsubscribes WHERE issue_id = $1"#,
+        issue_id
+    )
+    .fetch_one(pool)
+    .await
+    .context("Failed to count unsubscribes")?;
+
+    Ok(Some(IssueReport {
+        id: issue_id,
+        title: issue.title,
+        slug: issue.slug,
+        published_at: issue.published_at,
+        recipients: deliveries.recipients,
+        delivered: deliveries.delivered,
+        failed: deliveries.failed,
+        bounced: deliveries.bounced,
+        opens,
+        links,
+        unsubscribes,
+    }))
+}
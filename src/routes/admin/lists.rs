@@ -0,0 +1,116 @@
+use crate::audit::{AuditAction, AuditEvent};
+use crate::authentication::UserId;
+use crate::domain::ListSlug;
+use crate::utils::{e500, render, see_other};
+use actix_web::web;
+use actix_web::{HttpRequest, HttpResponse};
+use actix_web_flash_messages::{FlashMessage, IncomingFlashMessages};
+use anyhow::Context;
+use askama::Template;
+use sqlx::PgPool;
+use uuid::Uuid;
+
+struct ListRow {
+    slug: String,
+    name: String,
+    confirmed: i64,
+    pending: i64,
+}
+
+#[derive(Template)]
+#[template(path = "admin/lists.html")]
+struct ListsTemplate<'a> {
+    flash_messages: &'a IncomingFlashMessages,
+    lists: Vec<ListRow>,
+}
+
+pub async fn lists(
+    flash_messages: IncomingFlashMessages,
+    pool: web::Data<PgPool>,
+) -> Result<HttpResponse, actix_web::Error> {
+    let lists = get_list_counts(&pool).await.map_err(e500)?;
+    render(&ListsTemplate {
+        flash_messages: &flash_messages,
+        lists,
+    })
+}
+
+#[derive(serde::Deserialize)]
+pub struct NewListForm {
+    name: String,
+    slug: String,
+}
+
+pub async fn create_list(
+    form: web::Form<NewListForm>,
+    pool: web::Data<PgPool>,
+    user_id: web::ReqData<UserId>,
+    request: HttpRequest,
+) -> Result<HttpResponse, actix_web::Error> {
+    let name = form.name.trim();
+    if name.is_empty() {
+        FlashMessage::error("A list needs a name").send();
+        return Ok(see_other("/admin/lists"));
+    }
+    let slug = match ListSlug::parse(form.slug.trim().to_owned()) {
+        Ok(slug) => slug,
+        Err(_) => {
+            FlashMessage::error("Slugs may only contain lowercase letters, digits and dashes")
+                .send();
+            return Ok(see_other("/admin/lists"));
+        }
+    };
+    if !insert_list(&pool, name, &slug).await.map_err(e500)? {
+        FlashMessage::error("A list with that slug already exists").send();
+        return Ok(see_other("/admin/lists"));
+    }
+    AuditEvent::new(AuditAction::ListCreated, &request)
+        .actor(*user_id.into_inner())
+        .target(slug.as_ref())
+        .record(&pool)
+        .await
+        .map_err(e500)?;
+    FlashMessage::info("List created").send();
+    Ok(see_other("/admin/lists"))
+}
+
+#[tracing::instrument(name = "Get list member counts", skip(pool))]
+async fn get_list_counts(pool: &PgPool) -> Result<Vec<ListRow>, anyhow::Error> {
+    let rows = sqlx::query_as!(
+        ListRow,
+        r#"
+        SELECT
+            l.slug,
+            l.name,
+            count(m.subscriber_id) FILTER (WHERE m.status = 'confirmed') AS "confirmed!",
+            count(m.subscriber_id) FILTER (WHERE m.status = 'pending_confirmation') AS "pending!"
+        FROM lists l
+        LEFT JOIN list_memberships m ON m.list_id = l.id
+        GROUP BY l.id
+        ORDER BY l.name, l.slug
+        "#
+    )
+    .fetch_all(pool)
+    .await
+    .context("Failed to retrieve lists")?;
+    Ok(rows)
+}
+
+/// Returns `false` if the slug is taken.
+#[tracing::instrument(name = "Create list", skip(pool))]
+async fn insert_list(pool: &PgPool, name: &str, slug: &ListSlug) -> Result<bool, anyhow::Error> {
+    let inserted = sqlx::query!(
+        r#"
+        INSERT INTO lists (id, slug, name, created_at)
+        VALUES ($1, $2, $3, now())
+        ON CONFLICT (slug) DO NOTHING
+        "#,
+        Uuid::new_v4(),
+        slug.as_ref(),
+        name,
+    )
+    .execute(pool)
+    .await
+    .context("Failed to create list")?;
+    Ok(inserted.rows_affected() > 0)
+}
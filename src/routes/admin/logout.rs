@@ -1,14 +1,26 @@
+use crate::audit::{AuditAction, AuditEvent};
 use crate::session_state::TypedSession;
 use crate::utils::{e500, see_other};
-use actix_web::HttpResponse;
+use actix_web::{web, HttpRequest, HttpResponse};
 use actix_web_flash_messages::FlashMessage;
+use sqlx::PgPool;
 
-pub async fn log_out(session: TypedSession) -> Result<HttpResponse, actix_web::Error> {
-    if session.get_user_id().map_err(e500)?.is_none() {
-        Ok(see_other("/login"))
-    } else {
-        session.log_out();
-        FlashMessage::info("Logged out").send();
-        Ok(see_other("/login"))
+pub async fn log_out(
+    session: TypedSession,
+    pool: web::Data<PgPool>,
+    request: HttpRequest,
+) -> Result<HttpResponse, actix_web::Error> {
+    match session.get_user_id().map_err(e500)? {
+        None => Ok(see_other("/login")),
+        Some(user_id) => {
+            session.log_out();
+            AuditEvent::new(AuditAction::LoggedOut, &request)
+                .actor(user_id)
+                .record(&pool)
+                .await
+                .map_err(e500)?;
+            FlashMessage::info("Logged out").send();
+            Ok(see_other("/login"))
+        }
     }
 }
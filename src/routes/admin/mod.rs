@@ -1,7 +1,22 @@
+mod audit;
 mod dashboard;
+mod fields;
+mod filters;
+mod issues;
+mod lists;
 mod logout;
+mod passkeys;
 mod password;
+mod subscribers;
+mod webhooks;
 
+pub use audit::{audit_log, export_audit_log};
 pub use dashboard::admin_dashboard;
+pub use fields::{create_custom_field, custom_fields};
+pub use issues::*;
+pub use lists::{create_list, lists};
 pub use logout::log_out;
+pub use passkeys::*;
 pub use password::*;
+pub use subscribers::*;
+pub use webhooks::*;
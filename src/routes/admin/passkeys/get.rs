@@ -0,0 +1,28 @@
+use crate::authentication::{get_passkeys, passkey_required, StoredPasskey, UserId};
+use crate::utils::{e500, render};
+use actix_web::web;
+use actix_web::HttpResponse;
+use actix_web_flash_messages::IncomingFlashMessages;
+use askama::Template;
+use sqlx::PgPool;
+
+#[derive(Template)]
+#[template(path = "admin/passkeys.html")]
+struct PasskeysTemplate<'a> {
+    flash_messages: &'a IncomingFlashMessages,
+    passkeys: Vec<StoredPasskey>,
+    passkey_required: bool,
+}
+
+pub async fn passkeys_form(
+    flash_messages: IncomingFlashMessages,
+    pool: web::Data<PgPool>,
+    user_id: web::ReqData<UserId>,
+) -> Result<HttpResponse, actix_web::Error> {
+    let user_id = user_id.into_inner();
+    render(&PasskeysTemplate {
+        flash_messages: &flash_messages,
+        passkeys: get_passkeys(*user_id, &pool).await.map_err(e500)?,
+        passkey_required: passkey_required(*user_id, &pool).await.map_err(e500)?,
+    })
+}
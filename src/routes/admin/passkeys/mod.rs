@@ -0,0 +1,8 @@
+mod get;
+mod post;
+
+pub use get::passkeys_form;
+pub use post::{
+    finish_passkey_registration, remove_passkey, start_passkey_registration,
+    update_passkey_requirement,
+};
@@ -0,0 +1,141 @@
+use crate::audit::{AuditAction, AuditEvent};
+use crate::authentication::{
+    delete_passkey, get_passkeys, set_passkey_required, store_passkey, UserId,
+};
+use crate::routes::admin::dashboard::get_username;
+use crate::session_state::TypedSession;
+use crate::utils::{e500, see_other};
+use actix_web::error::ErrorBadRequest;
+use actix_web::web;
+use actix_web::{HttpRequest, HttpResponse};
+use actix_web_flash_messages::FlashMessage;
+use sqlx::PgPool;
+use uuid::Uuid;
+use webauthn_rs::prelude::RegisterPublicKeyCredential;
+use webauthn_rs::Webauthn;
+
+#[derive(serde::Deserialize)]
+pub struct RegisterPasskeyData {
+    name: String,
+    credential: RegisterPublicKeyCredential,
+}
+
+#[derive(serde::Deserialize)]
+pub struct PasskeyRequirementFormData {
+    required: bool,
+}
+
+#[tracing::instrument(skip(pool, webauthn, session))]
+pub async fn start_passkey_registration(
+    pool: web::Data<PgPool>,
+    webauthn: web::Data<Webauthn>,
+    session: TypedSession,
+    user_id: web::ReqData<UserId>,
+) -> Result<HttpResponse, actix_web::Error> {
+    let user_id = user_id.into_inner();
+    let username = get_username(*user_id, &pool).await.map_err(e500)?;
+    let existing = get_passkeys(*user_id, &pool)
+        .await
+        .map_err(e500)?
+        .into_iter()
+        .map(|p| p.passkey.cred_id().clone())
+        .collect();
+
+    let (challenge, state) = webauthn
+        .start_passkey_registration(*user_id, &username, &username, Some(existing))
+        .map_err(e500)?;
+    session.insert_passkey_registration(state).map_err(e500)?;
+
+    Ok(HttpResponse::Ok().json(challenge))
+}
+
+#[tracing::instrument(skip(body, pool, webauthn, session, request))]
+pub async fn finish_passkey_registration(
+    body: web::Json<RegisterPasskeyData>,
+    pool: web::Data<PgPool>,
+    webauthn: web::Data<Webauthn>,
+    session: TypedSession,
+    user_id: web::ReqData<UserId>,
+    request: HttpRequest,
+) -> Result<HttpResponse, actix_web::Error> {
+    let user_id = user_id.into_inner();
+    let state = session
+        .take_passkey_registration()
+        .map_err(e500)?
+        .ok_or_else(|| ErrorBadRequest("No passkey registration in progress"))?;
+
+    let passkey = webauthn
+        .finish_passkey_registration(&body.credential, &state)
+        .map_err(ErrorBadRequest)?;
+    let name = match body.name.trim() {
+        "" => "Passkey",
+        name => name,
+    };
+    store_passkey(*user_id, name, &passkey, &pool)
+        .await
+        .map_err(e500)?;
+    AuditEvent::new(AuditAction::PasskeyRegistered, &request)
+        .actor(*user_id)
+        .target(name)
+        .record(&pool)
+        .await
+        .map_err(e500)?;
+
+    FlashMessage::info("Passkey registered").send();
+    Ok(HttpResponse::Ok().finish())
+}
+
+pub async fn remove_passkey(
+    passkey_id: web::Path<Uuid>,
+    pool: web::Data<PgPool>,
+    user_id: web::ReqData<UserId>,
+    request: HttpRequest,
+) -> Result<HttpResponse, actix_web::Error> {
+    let user_id = user_id.into_inner();
+    let passkey_id = passkey_id.into_inner();
+    delete_passkey(*user_id, passkey_id, &pool)
+        .await
+        .map_err(e500)?;
+    AuditEvent::new(AuditAction::PasskeyRemoved, &request)
+        .actor(*user_id)
+        .target(passkey_id.to_string())
+        .record(&pool)
+        .await
+        .map_err(e500)?;
+    FlashMessage::info("Passkey removed").send();
+    Ok(see_other("/admin/passkeys"))
+}
+
+pub async fn update_passkey_requirement(
+    form: web::Form<PasskeyRequirementFormData>,
+    pool: web::Data<PgPool>,
+    user_id: web::ReqData<UserId>,
+    request: HttpRequest,
+) -> Result<HttpResponse, actix_web::Error> {
+    let user_id = user_id.into_inner();
+    if form.required
+        && get_passkeys(*user_id, &pool)
+            .await
+            .map_err(e500)?
+            .is_empty()
+    {
+        FlashMessage::error("Register a passkey first").send();
+        return Ok(see_other("/admin/passkeys"));
+    }
+
+    set_passkey_required(*user_id, form.required, &pool)
+        .await
+        .map_err(e500)?;
+    AuditEvent::new(AuditAction::PasskeyRequirementChanged, &request)
+        .actor(*user_id)
+        .target(if form.required {
+            "required"
+        } else {
+            "not required"
+        })
+        .record(&pool)
+        .await
+        .map_err(e500)?;
+    FlashMessage::info("Passkey requirement updated").send();
+    Ok(see_other("/admin/passkeys"))
+}
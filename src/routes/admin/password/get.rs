@@ -1,9 +1,14 @@
 use crate::session_state::TypedSession;
-use crate::utils::{e500, see_other};
-use actix_web::http::header::ContentType;
+use crate::utils::{e500, render, see_other};
 use actix_web::HttpResponse;
 use actix_web_flash_messages::IncomingFlashMessages;
-use std::fmt::Write;
+use askama::Template;
+
+#[derive(Template)]
+#[template(path = "admin/password.html")]
+struct ChangePasswordTemplate<'a> {
+    flash_messages: &'a IncomingFlashMessages,
+}
 
 pub async fn change_password_form(
     session: TypedSession,
@@ -13,51 +18,7 @@ pub async fn change_password_form(
         return Ok(see_other("/login"));
     }
 
-    let mut msg_html = String::new();
-    for m in flash_messages.iter() {
-        writeln!(msg_html, "<p><i>{}</i></p>", m.content()).unwrap();
-    }
-
-    Ok(HttpResponse::Ok()
-        .content_type(ContentType::html())
-        .body(format!(
-            r#"<!DOCTYPE html>
-        <html lang="en">
-            <head>
-                <meta http-equiv="content-type" content="text/html; charset=utf-8">
-                <title>Change Password</title>
-            </head>
-            <body>
-                {msg_html}
-                <form action="/admin/password" method="post">
-                    <label>Current password
-                    <input
-                        type="password"
-                        placeholder="Current Password"
-                        name="current_password"
-                    >
-                    </label>
-                    <br>
-                    <label>New Password
-                    <input
-                        type="password"
-                        placeholder="Current Password"
-                        name="new_password"
-                    >
-                    </label>
-                    <br>
-                    <label>New Password Again
-                    <input
-                        type="password"
-                        placeholder="New Password"
-                        name="new_password_check"
-                    >
-                    </label>
-                    <br>
-                    <button type="submit">Change Password</button>
-                </form>
-                <p><a href="/admin/dashboard">&lt;- Back</a></p>
-            </body>
-        </html>"#
-        )))
+    render(&ChangePasswordTemplate {
+        flash_messages: &flash_messages,
+    })
 }
@@ -1,8 +1,9 @@
+use crate::audit::{AuditAction, AuditEvent};
 use crate::authentication::UserId;
-use crate::authentication::{validate_credentials, AuthError, Credentials};
+use crate::authentication::{validate_credentials, AuthError, Credentials, PasswordPolicy};
 use crate::routes::admin::dashboard::get_username;
 use crate::utils::{e500, see_other};
-use actix_web::{web, HttpResponse};
+use actix_web::{web, HttpRequest, HttpResponse};
 use actix_web_flash_messages::FlashMessage;
 use secrecy::ExposeSecret;
 use secrecy::Secret;
@@ -19,6 +20,8 @@ pub async fn change_password(
     form: web::Form<FormData>,
     pool: web::Data<PgPool>,
     user_id: web::ReqData<UserId>,
+    password_policy: web::Data<PasswordPolicy>,
+    request: HttpRequest,
 ) -> Result<HttpResponse, actix_web::Error> {
     let user_id = user_id.into_inner();
 
@@ -29,8 +32,8 @@ pub async fn change_password(
 
     let username = get_username(*user_id, &pool).await.map_err(e500)?;
     let credentials = Credentials {
-        username,
-        password: form.0.current_password,
+        username: username.clone(),
+        password: form.current_password.clone(),
     };
     if let Err(e) = validate_credentials(credentials, &pool).await {
         return match e {
@@ -42,9 +45,26 @@ pub async fn change_password(
         };
     }
 
+    if let Err(violations) = password_policy.validate(
+        &form.new_password,
+        Some(&form.current_password),
+        &[&username],
+    ) {
+        for violation in violations {
+            FlashMessage::error(violation.to_string()).send();
+        }
+        return Ok(see_other("/admin/password"));
+    }
+
     crate::authentication::change_password(*user_id, form.0.new_password, &pool)
         .await
         .map_err(e500)?;
+    AuditEvent::new(AuditAction::PasswordChanged, &request)
+        .actor(*user_id)
+        .target(username)
+        .record(&pool)
+        .await
+        .map_err(e500)?;
     FlashMessage::error("Password changed").send();
     Ok(see_other("/admin/password"))
 }
@@ -0,0 +1,288 @@
+use crate::domain::SubscriptionStatus;
+use crate::routes::admin::filters::{date_range, non_empty, parse_date};
+use crate::utils::{e500, render};
+use actix_web::error::{ErrorBadRequest, ErrorNotFound};
+use actix_web::web;
+use actix_web::HttpResponse;
+use actix_web_flash_messages::IncomingFlashMessages;
+use anyhow::Context;
+use askama::Template;
+use chrono::{DateTime, NaiveDate, Utc};
+use sqlx::PgPool;
+use uuid::Uuid;
+
+const PAGE_SIZE: i64 = 50;
+
+#[derive(serde::Deserialize)]
+pub struct SubscribersQuery {
+    q: Option<String>,
+    status: Option<String>,
+    from: Option<String>,
+    to: Option<String>,
+    page: Option<i64>,
+}
+
+pub(super) struct SubscriberFilter {
+    pub search: Option<String>,
+    pub status: Option<SubscriptionStatus>,
+    pub from: Option<NaiveDate>,
+    pub to: Option<NaiveDate>,
+}
+
+pub(super) struct SubscriberRow {
+    pub id: Uuid,
+    pub email: String,
+    pub name: String,
+    pub subscribed_at: DateTime<Utc>,
+    pub status: String,
+    pub paused_until: Option<DateTime<Utc>>,
+}
+
+pub(super) struct MembershipRow {
+    pub list_id: Uuid,
+    pub list_slug: String,
+    pub list_name: String,
+    pub status: String,
+    pub subscribed_at: DateTime<Utc>,
+}
+
+impl SubscribersQuery {
+    fn filter(&self) -> Result<SubscriberFilter, actix_web::Error> {
+        SubscriberFilter::parse(&self.q, &self.status, &self.from, &self.to)
+    }
+}
+
+impl SubscriberFilter {
+    pub(super) fn parse(
+        search: &Option<String>,
+        status: &Option<String>,
+        from: &Option<String>,
+        to: &Option<String>,
+    ) -> Result<Self, actix_web::Error> {
+        Ok(Self {
+            search: non_empty(search),
+            status: non_empty(status)
+                .map(|s| SubscriptionStatus::parse(&s))
+                .transpose()
+                .map_err(ErrorBadRequest)?,
+            from: parse_date(from)?,
+            to: parse_date(to)?,
+        })
+    }
+
+    fn query_string(&self, page: Option<i64>) -> String {
+        let mut params = Vec::new();
+        if let Some(search) = &self.search {
+            params.push(format!("q={}", urlencoding::encode(search)));
+        }
+        if let Some(status) = &self.status {
+            params.push(format!("status={}", status));
+        }
+        if let Some(from) = &self.from {
+            params.push(format!("from={}", from));
+        }
+        if let Some(to) = &self.to {
+            params.push(format!("to={}", to));
+        }
+        if let Some(page) = page {
+            params.push(format!("page={}", page));
+        }
+        params.join("&")
+    }
+}
+
+#[derive(Template)]
+#[template(path = "admin/subscribers.html")]
+struct SubscribersTemplate<'a> {
+    flash_messages: &'a IncomingFlashMessages,
+    search: &'a str,
+    status: &'a str,
+    statuses: &'static [SubscriptionStatus],
+    from: String,
+    to: String,
+    export_query: String,
+    newer_query: Option<String>,
+    older_query: Option<String>,
+    subscribers: &'a [SubscriberRow],
+}
+
+pub async fn subscribers_list(
+    flash_messages: IncomingFlashMessages,
+    query: web::Query<SubscribersQuery>,
+    pool: web::Data<PgPool>,
+) -> Result<HttpResponse, actix_web::Error> {
+    let filter = query.filter()?;
+    let page = query.page.unwrap_or(1).max(1);
+    let offset = (page - 1)
+        .checked_mul(PAGE_SIZE)
+        .ok_or_else(|| ErrorNotFound("There is no such page"))?;
+
+    let mut subscribers = get_subscribers(&pool, &filter, PAGE_SIZE + 1, offset)
+        .await
+        .map_err(e500)?;
+    let has_next = subscribers.len() as i64 > PAGE_SIZE;
+    subscribers.truncate(PAGE_SIZE as usize);
+
+    render(&SubscribersTemplate {
+        flash_messages: &flash_messages,
+        search: filter.search.as_deref().unwrap_or(""),
+        status: filter.status.map(|s| s.as_str()).unwrap_or(""),
+        statuses: &SubscriptionStatus::ALL,
+        from: filter.from.map(|d| d.to_string()).unwrap_or_default(),
+        to: filter.to.map(|d| d.to_string()).unwrap_or_default(),
+        export_query: filter.query_string(None),
+        newer_query: (page > 1).then(|| filter.query_string(Some(page - 1))),
+        older_query: has_next.then(|| filter.query_string(Some(page + 1))),
+        subscribers: &subscribers,
+    })
+}
+
+#[derive(Template)]
+#[template(path = "admin/subscriber.html")]
+struct SubscriberTemplate<'a> {
+    flash_messages: &'a IncomingFlashMessages,
+    subscriber: SubscriberRow,
+    memberships: Vec<MembershipRow>,
+    tags: Vec<String>,
+    fields: Vec<FieldValueRow>,
+}
+
+struct FieldValueRow {
+    label: String,
+    value: String,
+}
+
+pub async fn subscriber_details(
+    flash_messages: IncomingFlashMessages,
+    subscriber_id: web::Path<Uuid>,
+    pool: web::Data<PgPool>,
+) -> Result<HttpResponse, actix_web::Error> {
+    let subscriber = get_subscriber(&pool, *subscriber_id)
+        .await
+        .map_err(e500)?
+        .ok_or_else(|| ErrorNotFound("Unknown subscriber"))?;
+    let memberships = get_memberships(&pool, subscriber.id).await.map_err(e500)?;
+    let tags = get_tags(&pool, subscriber.id).await.map_err(e500)?;
+    let fields = get_field_values(&pool, subscriber.id).await.map_err(e500)?;
+    render(&SubscriberTemplate {
+        flash_messages: &flash_messages,
+        subscriber,
+        memberships,
+        tags,
+        fields,
+    })
+}
+
+#[tracing::instrument(name = "Get subscribers", skip(pool, filter))]
+async fn get_subscribers(
+    pool: &PgPool,
+    filter: &SubscriberFilter,
+    limit: i64,
+    offset: i64,
+) -> Result<Vec<SubscriberRow>, anyhow::Error> {
+    let (from, to) = date_range(filter.from, filter.to);
+    let rows = sqlx::query_as!(
+        SubscriberRow,
+        r#"
+        SELECT id, email, name, subscribed_at, status, paused_until
+        FROM subscriptions
+        WHERE ($1::text IS NULL
+            OR strpos(lower(email), lower($1)) > 0
+            OR strpos(lower(name), lower($1)) > 0)
+        AND ($2::text IS NULL OR status = $2)
+        AND ($3::timestamptz IS NULL OR subscribed_at >= $3)
+        AND ($4::timestamptz IS NULL OR subscribed_at < $4)
+        ORDER BY subscribed_at DESC, id
+        LIMIT $5 OFFSET $6
+        "#,
+        filter.search,
+        filter.status.map(|s| s.as_str()),
+        from,
+        to,
+        limit,
+        offset,
+    )
+    .fetch_all(pool)
+    .await
+    .context("Failed to retrieve subscribers")?;
+    Ok(rows)
+}
+
+#[tracing::instrument(name = "Get subscriber", skip(pool))]
+pub(super) async fn get_subscriber(
+    pool: &PgPool,
+    subscriber_id: Uuid,
+) -> Result<Option<SubscriberRow>, anyhow::Error> {
+    let row = sqlx::query_as!(
+        SubscriberRow,
+        r#"
+        SELECT id, email, name, subscribed_at, status, paused_until
+        FROM subscriptions
+        WHERE id = $1
+        "#,
+        subscriber_id
+    )
+    .fetch_optional(pool)
+    .await
+    .context("Failed to retrieve subscriber")?;
+    Ok(row)
+}
+
+#[tracing::instrument(name = "Get list memberships", skip(pool))]
+pub(super) async fn get_memberships(
+    pool: &PgPool,
+    subscriber_id: Uuid,
+) -> Result<Vec<MembershipRow>, anyhow::Error> {
+    let rows = sqlx::query_as!(
+        MembershipRow,
+        r#"
+        SELECT l.id AS list_id, l.slug AS list_slug, l.name AS list_name, m.status, m.subscribed_at
+        FROM list_memberships m
+        JOIN lists l ON l.id = m.list_id
+        WHERE m.subscriber_id = $1
+        ORDER BY l.name, l.slug
+        "#,
+        subscriber_id
+    )
+    .fetch_all(pool)
+    .await
+    .context("Failed to retrieve list memberships")?;
+    Ok(rows)
+}
+
+#[tracing::instrument(name = "Get subscriber tags", skip(pool))]
+async fn get_tags(pool: &PgPool, subscriber_id: Uuid) -> Result<Vec<String>, anyhow::Error> {
+    let tags = sqlx::query!(
+        r#"SELECT tag FROM subscriber_tags WHERE subscriber_id = $1 ORDER BY tag"#,
+        subscriber_id
+    )
+    .fetch_all(pool)
+    .await
+    .context("Failed to retrieve tags")?
+    .into_iter()
+    .map(|r| r.tag)
+    .collect();
+    Ok(tags)
+}
+
+#[tracing::instrument(name = "Get subscriber field values", skip(pool))]
+async fn get_field_values(
+    pool: &PgPool,
+    subscriber_id: Uuid,
+) -> Result<Vec<FieldValueRow>, anyhow::Error> {
+    let rows = sqlx::query_as!(
+        FieldValueRow,
+        r#"
+        SELECT f.label, v.value
+        FROM subscriber_field_values v
+        JOIN custom_fields f ON f.id = v.field_id
+        WHERE v.subscriber_id = $1
+        ORDER BY f.created_at, f.key
+        "#,
+        subscriber_id
+    )
+    .fetch_all(pool)
+    .await
+    .context("Failed to retrieve custom field values")?;
+    Ok(rows)
+}
@@ -0,0 +1,465 @@
+use crate::audit::{AuditAction, AuditEvent};
+use crate::authentication::UserId;
+use crate::confirmation_emails::queue_confirmation_emails;
+use crate::domain::{NewSubscriber, SubscriberEmail, SubscriberName, SubscriptionStatus};
+use crate::lists::{get_default_list, MailingList};
+use crate::outbound_webhooks::{enqueue_for_subscriber, WebhookEvent};
+use crate::routes::generate_subscription_token;
+use crate::utils::{e500, render, see_other};
+use actix_multipart::{Field, Multipart};
+use actix_web::error::{ErrorBadRequest, ErrorNotFound};
+use actix_web::http::header::{ContentDisposition, DispositionParam, DispositionType};
+use actix_web::web;
+use actix_web::{HttpRequest, HttpResponse};
+use actix_web_flash_messages::{FlashMessage, IncomingFlashMessages};
+use anyhow::Context;
+use askama::Template;
+use chrono::{DateTime, Utc};
+use futures_util::{future, stream, StreamExt, TryStreamExt};
+use sqlx::PgPool;
+use std::io;
+use tokio::io::AsyncRead;
+use tokio_util::io::StreamReader;
+use uuid::Uuid;
+
+const BATCH_SIZE: usize = 500;
+
+struct Rejection {
+    row_number: i64,
+    email: String,
+    name: String,
+    reason: String,
+}
+
+struct ImportSummary {
+    created_at: DateTime<Utc>,
+    pre_confirmed: bool,
+    imported_count: i32,
+    rejected_count: i32,
+}
+
+#[derive(Template)]
+#[template(path = "admin/subscriber_import_form.html")]
+struct ImportFormTemplate<'a> {
+    flash_messages: &'a IncomingFlashMessages,
+}
+
+pub async fn import_subscribers_form(
+    flash_messages: IncomingFlashMessages,
+) -> Result<HttpResponse, actix_web::Error> {
+    render(&ImportFormTemplate {
+        flash_messages: &flash_messages,
+    })
+}
+
+/// Accepts a multipart form with a `mode` field (`pre_confirmed` or
+/// `double_opt_in`) followed by a `file` field holding the CSV.
+///
+/// Confirmation emails are queued rather than sent, so the upload returns as
+/// soon as the rows are in.
+#[tracing::instrument(name = "Import subscribers", skip(payload, pool, user_id, request))]
+pub async fn import_subscribers(
+    mut payload: Multipart,
+    pool: web::Data<PgPool>,
+    user_id: web::ReqData<UserId>,
+    request: HttpRequest,
+) -> Result<HttpResponse, actix_web::Error> {
+    let user_id = user_id.into_inner();
+    let mut pre_confirmed = None;
+    let mut outcome = None;
+    while let Some(mut field) = payload.try_next().await.map_err(ErrorBadRequest)? {
+        match field.name() {
+            "mode" => {
+                let mut mode = Vec::new();
+                while let Some(chunk) = field.try_next().await.map_err(ErrorBadRequest)? {
+                    mode.extend_from_slice(&chunk);
+                }
+                pre_confirmed = match mode.as_slice() {
+                    b"pre_confirmed" => Some(true),
+                    b"double_opt_in" => Some(false),
+                    _ => return Err(ErrorBadRequest("Unknown import mode")),
+                };
+            }
+            "file" if outcome.is_none() => {
+                let pre_confirmed = pre_confirmed
+                    .ok_or_else(|| ErrorBadRequest("The import mode must precede the file"))?;
+                outcome = Some((
+                    pre_confirmed,
+                    import_csv(field, pre_confirmed, &pool).await?,
+                ));
+            }
+            _ => while field.try_next().await.map_err(ErrorBadRequest)?.is_some() {},
+        }
+    }
+    let Some((pre_confirmed, (imported_count, rejections))) = outcome else {
+        FlashMessage::error("Choose a CSV file to import").send();
+        return Ok(see_other("/admin/subscribers/import"));
+    };
+
+    let import_id = store_import(&pool, *user_id, pre_confirmed, imported_count, &rejections)
+        .await
+        .map_err(e500)?;
+    AuditEvent::new(AuditAction::SubscribersImported, &request)
+        .actor(*user_id)
+        .target(import_id.to_string())
+        .record(&pool)
+        .await
+        .map_err(e500)?;
+    Ok(see_other(&format!(
+        "/admin/subscribers/imports/{}",
+        import_id
+    )))
+}
+
+/// Streams the CSV, inserting valid rows `BATCH_SIZE` at a time.
+///
+/// Returns how many subscribers were added and the rows that weren't.
+async fn import_csv(
+    mut field: Field,
+    pre_confirmed: bool,
+    pool: &PgPool,
+) -> Result<(i32, Vec<Rejection>), actix_web::Error> {
+    // The CSV reader needs a `Send` source, which a multipart field isn't,
+    // so its chunks are handed over through a channel.
+    let (sender, mut receiver) = tokio::sync::mpsc::channel(16);
+    let forward = async move {
+        while let Some(chunk) = field.next().await {
+            let chunk =
+                chunk.map_err(|e| io::Error::new(io::ErrorKind::InvalidData, e.to_string()));
+            if sender.send(chunk).await.is_err() {
+                break;
+            }
+        }
+    };
+    let body = StreamReader::new(stream::poll_fn(move |cx| receiver.poll_recv(cx)));
+    let (_, outcome) = future::join(forward, import_records(body, pre_confirmed, pool)).await;
+    outcome
+}
+
+async fn import_records(
+    body: impl AsyncRead + Unpin + Send,
+    pre_confirmed: bool,
+    pool: &PgPool,
+) -> Result<(i32, Vec<Rejection>), actix_web::Error> {
+    let mut reader = csv_async::AsyncReaderBuilder::new()
+        .flexible(true)
+        .trim(csv_async::Trim::All)
+        .create_reader(body);
+
+    let headers = reader.headers().await.map_err(ErrorBadRequest)?.clone();
+    let column = |name: &str| headers.iter().position(|h| h.eq_ignore_ascii_case(name));
+    let (Some(email_column), Some(name_column)) = (column("email"), column("name")) else {
+        return Err(ErrorBadRequest(
+            "The CSV must have a header row with `email` and `name` columns",
+        ));
+    };
+
+    let list = get_default_list(pool).await.map_err(e500)?;
+    let mut imported = 0;
+    let mut rejections = Vec::new();
+    let mut batch = Vec::with_capacity(BATCH_SIZE);
+    let mut records = reader.records();
+    while let Some(record) = records.next().await {
+        let record = match record {
+            Ok(record) => record,
+            Err(e) if matches!(e.kind(), csv_async::ErrorKind::Io(_)) => {
+                return Err(ErrorBadRequest(e))
+            }
+            Err(e) => {
+                rejections.push(Rejection {
+                    row_number: e.position().map_or(0, |p| p.line() as i64),
+                    email: String::new(),
+                    name: String::new(),
+                    reason: e.to_string(),
+                });
+                continue;
+            }
+        };
+        let row_number = record.position().map_or(0, |p| p.line() as i64);
+        let email = record.get(email_column).unwrap_or_default().to_owned();
+        let name = record.get(name_column).unwrap_or_default().to_owned();
+        let parsed = SubscriberEmail::parse(email.clone())
+            .and_then(|email| Ok((email, SubscriberName::parse(name.clone())?)));
+        match parsed {
+            Ok((email, name)) => batch.push((row_number, NewSubscriber { email, name })),
+            Err(reason) => rejections.push(Rejection {
+                row_number,
+                email,
+                name,
+                reason,
+            }),
+        }
+        if batch.len() == BATCH_SIZE {
+            imported += import_batch(
+                std::mem::take(&mut batch),
+                pre_confirmed,
+                &list,
+                pool,
+                &mut rejections,
+            )
+            .await
+            .map_err(e500)?;
+        }
+    }
+    imported += import_batch(batch, pre_confirmed, &list, pool, &mut rejections)
+        .await
+        .map_err(e500)?;
+    Ok((imported, rejections))
+}
+
+#[tracing::instrument(
+    name = "Import a batch of subscribers",
+    skip_all,
+    fields(batch_size = batch.len())
+)]
+async fn import_batch(
+    batch: Vec<(i64, NewSubscriber)>,
+    pre_confirmed: bool,
+    list: &MailingList,
+    pool: &PgPool,
+    rejections: &mut Vec<Rejection>,
+) -> Result<i32, anyhow::Error> {
+    if batch.is_empty() {
+        return Ok(0);
+    }
+    let status = if pre_confirmed {
+        SubscriptionStatus::Confirmed
+    } else {
+        SubscriptionStatus::PendingConfirmation
+    };
+    let ids: Vec<Uuid> = batch.iter().map(|_| Uuid::new_v4()).collect();
+    let emails: Vec<&str> = batch.iter().map(|(_, s)| s.email.as_ref()).collect();
+    let names: Vec<&str> = batch.iter().map(|(_, s)| s.name.as_ref()).collect();
+
+    let mut transaction = pool
+        .begin()
+        .await
+        .context("Failed to acquire a Postgres connection from the pool")?;
+    let inserted: Vec<Uuid> = sqlx::query!(
+        r#"
+        INSERT INTO subscriptions (id, email, name, subscribed_at, status, confirmed_at)
+        SELECT id, email, name, now(), $4, CASE WHEN $4 = 'confirmed' THEN now() END
+        FROM UNNEST($1::uuid[], $2::text[], $3::text[]) AS t(id, email, name)
+        ON CONFLICT (email) DO NOTHING
+        RETURNING id
+        "#,
+        &ids,
+        &emails as &[&str],
+        &names as &[&str],
+        status.as_str(),
+    )
+    .fetch_all(&mut *transaction)
+    .await
+    .context("Failed to insert subscribers")?
+    .into_iter()
+    .map(|r| r.id)
+    .collect();
+
+    sqlx::query!(
+        r#"
+        INSERT INTO list_memberships (list_id, subscriber_id, status, subscribed_at)
+        SELECT $1, subscriber_id, $3, now() FROM UNNEST($2::uuid[]) AS t(subscriber_id)
+        "#,
+        list.id,
+        &inserted,
+        status.as_str(),
+    )
+    .execute(&mut *transaction)
+    .await
+    .context("Failed to add subscribers to the list")?;
+    for subscriber_id in &inserted {
+        enqueue_for_subscriber(
+            &mut transaction,
+            WebhookEvent::SubscriberCreated,
+            *subscriber_id,
+        )
+        .await?;
+    }
+
+    let mut to_confirm = Vec::new();
+    for (id, (row_number, subscriber)) in ids.iter().zip(batch) {
+        if !inserted.contains(id) {
+            rejections.push(Rejection {
+                row_number,
+                email: subscriber.email.as_ref().to_owned(),
+                name: subscriber.name.as_ref().to_owned(),
+                reason: "Already subscribed".into(),
+            });
+        } else if !pre_confirmed {
+            to_confirm.push((*id, generate_subscription_token()));
+        }
+    }
+
+    let subscriber_ids: Vec<Uuid> = to_confirm.iter().map(|(id, _)| *id).collect();
+    let tokens: Vec<&str> = to_confirm.iter().map(|(_, t)| t.as_str()).collect();
+    sqlx::query!(
+        r#"
+        INSERT INTO subscription_tokens (subscription_token, subscriber_id, list_id)
+        SELECT token, subscriber_id, $3 FROM UNNEST($1::text[], $2::uuid[]) AS t(token, subscriber_id)
+        "#,
+        &tokens as &[&str],
+        &subscriber_ids,
+        list.id,
+    )
+    .execute(&mut *transaction)
+    .await
+    .context("Failed to store confirmation tokens")?;
+    queue_confirmation_emails(&mut transaction, &tokens)
+        .await
+        .context("Failed to queue confirmation emails")?;
+    transaction
+        .commit()
+        .await
+        .context("Failed to commit transaction")?;
+    Ok(inserted.len() as i32)
+}
+
+#[tracing::instrument(name = "Store import report", skip(pool, rejections))]
+async fn store_import(
+    pool: &PgPool,
+    user_id: Uuid,
+    pre_confirmed: bool,
+    imported_count: i32,
+    rejections: &[Rejection],
+) -> Result<Uuid, anyhow::Error> {
+    let import_id = Uuid::new_v4();
+    let mut transaction = pool
+        .begin()
+        .await
+        .context("Failed to acquire a Postgres connection from the pool")?;
+    sqlx::query!(
+        r#"
+        INSERT INTO subscriber_imports
+            (id, created_at, imported_by, pre_confirmed, imported_count, rejected_count)
+        VALUES ($1, now(), $2, $3, $4, $5)
+        "#,
+        import_id,
+        user_id,
+        pre_confirmed,
+        imported_count,
+        rejections.len() as i32,
+    )
+    .execute(&mut *transaction)
+    .await
+    .context("Failed to store import")?;
+    let row_numbers: Vec<i64> = rejections.iter().map(|r| r.row_number).collect();
+    let emails: Vec<&str> = rejections.iter().map(|r| r.email.as_str()).collect();
+    let names: Vec<&str> = rejections.iter().map(|r| r.name.as_str()).collect();
+    let reasons: Vec<&str> = rejections.iter().map(|r| r.reason.as_str()).collect();
+    sqlx::query!(
+        r#"
+        INSERT INTO subscriber_import_rejections (import_id, row_number, email, name, reason)
+        SELECT $1, * FROM UNNEST($2::bigint[], $3::text[], $4::text[], $5::text[])
+        "#,
+        import_id,
+        &row_numbers,
+        &emails as &[&str],
+        &names as &[&str],
+        &reasons as &[&str],
+    )
+    .execute(&mut *transaction)
+    .await
+    .context("Failed to store rejected rows")?;
+    transaction
+        .commit()
+        .await
+        .context("Failed to commit transaction")?;
+    Ok(import_id)
+}
+
+#[derive(Template)]
+#[template(path = "admin/subscriber_import.html")]
+struct ImportReportTemplate<'a> {
+    flash_messages: &'a IncomingFlashMessages,
+    import_id: Uuid,
+    summary: ImportSummary,
+    rejections: Vec<Rejection>,
+}
+
+pub async fn subscriber_import_report(
+    flash_messages: IncomingFlashMessages,
+    import_id: web::Path<Uuid>,
+    pool: web::Data<PgPool>,
+) -> Result<HttpResponse, actix_web::Error> {
+    let import_id = import_id.into_inner();
+    let summary = get_import(&pool, import_id)
+        .await
+        .map_err(e500)?
+        .ok_or_else(|| ErrorNotFound("Unknown import"))?;
+    let rejections = get_rejections(&pool, import_id).await.map_err(e500)?;
+    render(&ImportReportTemplate {
+        flash_messages: &flash_messages,
+        import_id,
+        summary,
+        rejections,
+    })
+}
+
+pub async fn export_import_rejections(
+    import_id: web::Path<Uuid>,
+    pool: web::Data<PgPool>,
+) -> Result<HttpResponse, actix_web::Error> {
+    let import_id = import_id.into_inner();
+    get_import(&pool, import_id)
+        .await
+        .map_err(e500)?
+        .ok_or_else(|| ErrorNotFound("Unknown import"))?;
+    let rejections = get_rejections(&pool, import_id).await.map_err(e500)?;
+
+    let mut writer = csv::Writer::from_writer(Vec::new());
+    writer
+        .write_record(["row", "email", "name", "reason"])
+        .map_err(e500)?;
+    for r in rejections {
+        writer
+            .write_record([r.row_number.to_string(), r.email, r.name, r.reason])
+            .map_err(e500)?;
+    }
+    let body = writer.into_inner().map_err(e500)?;
+
+    Ok(HttpResponse::Ok()
+        .content_type("text/csv; charset=utf-8")
+        .insert_header(ContentDisposition {
+            disposition: DispositionType::Attachment,
+            parameters: vec![DispositionParam::Filename("rejected.csv".into())],
+        })
+        .body(body))
+}
+
+#[tracing::instrument(name = "Get import", skip(pool))]
+async fn get_import(
+    pool: &PgPool,
+    import_id: Uuid,
+) -> Result<Option<ImportSummary>, anyhow::Error> {
+    let row = sqlx::query_as!(
+        ImportSummary,
+        r#"
+        SELECT created_at, pre_confirmed, imported_count, rejected_count
+        FROM subscriber_imports
+        WHERE id = $1
+        "#,
+        import_id
+    )
+    .fetch_optional(pool)
+    .await
+    .context("Failed to retrieve import")?;
+    Ok(row)
+}
+
+#[tracing::instrument(name = "Get rejected import rows", skip(pool))]
+async fn get_rejections(pool: &PgPool, import_id: Uuid) -> Result<Vec<Rejection>, anyhow::Error> {
+    let rows = sqlx::query_as!(
+        Rejection,
+        r#"
+        SELECT row_number, email, name, reason
+        FROM subscriber_import_rejections
+        WHERE import_id = $1
+        ORDER BY row_number
+        "#,
+        import_id
+    )
+    .fetch_all(pool)
+    .await
+    .context("Failed to retrieve rejected rows")?;
+    Ok(rows)
+}
@@ -0,0 +1,14 @@
+mod export;
+mod get;
+mod import;
+mod post;
+
+pub use export::export_subscribers;
+pub use get::{subscriber_details, subscribers_list};
+pub use import::{
+    export_import_rejections, import_subscribers, import_subscribers_form, subscriber_import_report,
+};
+pub use post::{
+    add_tag, confirm_subscriber_manually, delete_subscriber, remove_tag, resend_confirmation,
+    unsubscribe_subscriber,
+};
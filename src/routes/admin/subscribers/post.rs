@@ -0,0 +1,336 @@
+use super::get::{get_memberships, get_subscriber};
+use crate::audit::{AuditAction, AuditEvent};
+use crate::authentication::UserId;
+use crate::domain::{
+    NewSubscriber, SubscriberEmail, SubscriberName, SubscriberTag, SubscriptionStatus,
+};
+use crate::email_client::EmailClient;
+use crate::lists::MailingList;
+use crate::outbound_webhooks::{enqueue_for_subscriber, WebhookEvent};
+use crate::routes::{generate_subscription_token, send_confirmation_email, store_token};
+use crate::startup::ApplicationBaseUrl;
+use crate::utils::{e500, see_other};
+use actix_web::error::ErrorNotFound;
+use actix_web::web;
+use actix_web::{HttpRequest, HttpResponse};
+use actix_web_flash_messages::FlashMessage;
+use anyhow::Context;
+use sqlx::PgPool;
+use uuid::Uuid;
+
+pub async fn confirm_subscriber_manually(
+    subscriber_id: web::Path<Uuid>,
+    pool: web::Data<PgPool>,
+    user_id: web::ReqData<UserId>,
+    request: HttpRequest,
+) -> Result<HttpResponse, actix_web::Error> {
+    let subscriber_id = subscriber_id.into_inner();
+    if !set_status(
+        &pool,
+        subscriber_id,
+        SubscriptionStatus::Confirmed,
+        WebhookEvent::SubscriberConfirmed,
+    )
+    .await
+    .map_err(e500)?
+    {
+        return Err(ErrorNotFound("Unknown subscriber"));
+    }
+    AuditEvent::new(AuditAction::SubscriberConfirmed, &request)
+        .actor(*user_id.into_inner())
+        .target(subscriber_id.to_string())
+        .record(&pool)
+        .await
+        .map_err(e500)?;
+    FlashMessage::info("Subscriber confirmed").send();
+    Ok(see_other(&format!("/admin/subscribers/{}", subscriber_id)))
+}
+
+pub async fn resend_confirmation(
+    subscriber_id: web::Path<Uuid>,
+    pool: web::Data<PgPool>,
+    email_client: web::Data<EmailClient>,
+    base_url: web::Data<ApplicationBaseUrl>,
+    user_id: web::ReqData<UserId>,
+    request: HttpRequest,
+) -> Result<HttpResponse, actix_web::Error> {
+    let subscriber_id = subscriber_id.into_inner();
+    let location = format!("/admin/subscribers/{}", subscriber_id);
+    let subscriber = get_subscriber(&pool, subscriber_id)
+        .await
+        .map_err(e500)?
+        .ok_or_else(|| ErrorNotFound("Unknown subscriber"))?;
+    let pending: Vec<MailingList> = get_memberships(&pool, subscriber_id)
+        .await
+        .map_err(e500)?
+        .into_iter()
+        .filter(|m| m.status == SubscriptionStatus::PendingConfirmation.as_str())
+        .map(|m| MailingList {
+            id: m.list_id,
+            slug: m.list_slug,
+            name: m.list_name,
+        })
+        .collect();
+    if subscriber.status != SubscriptionStatus::PendingConfirmation.as_str() || pending.is_empty() {
+        FlashMessage::error("Only pending subscribers can be sent a confirmation email").send();
+        return Ok(see_other(&location));
+    }
+
+    let mut transaction = pool
+        .begin()
+        .await
+        .context("Failed to acquire a Postgres connection from the pool")
+        .map_err(e500)?;
+    let mut tokens = Vec::with_capacity(pending.len());
+    for list in &pending {
+        let subscription_token = generate_subscription_token();
+        store_token(
+            &mut transaction,
+            subscriber_id,
+            list.id,
+            &subscription_token,
+        )
+        .await
+        .map_err(e500)?;
+        tokens.push(subscription_token);
+    }
+    transaction
+        .commit()
+        .await
+        .context("Failed to commit transaction")
+        .map_err(e500)?;
+    // One email per pending list, each with a link that confirms that list only.
+    for (list, subscription_token) in pending.iter().zip(tokens) {
+        let new_subscriber = NewSubscriber {
+            email: SubscriberEmail::parse(subscriber.email.clone()).map_err(e500)?,
+            name: SubscriberName::parse(subscriber.name.clone()).map_err(e500)?,
+        };
+        send_confirmation_email(
+            &email_client,
+            new_subscriber,
+            list,
+            &base_url.0,
+            &subscription_token,
+        )
+        .await
+        .context("Failed to send confirmation email")
+        .map_err(e500)?;
+    }
+
+    AuditEvent::new(AuditAction::ConfirmationResent, &request)
+        .actor(*user_id.into_inner())
+        .target(subscriber_id.to_string())
+        .record(&pool)
+        .await
+        .map_err(e500)?;
+    FlashMessage::info("Confirmation email sent").send();
+    Ok(see_other(&location))
+}
+
+pub async fn unsubscribe_subscriber(
+    subscriber_id: web::Path<Uuid>,
+    pool: web::Data<PgPool>,
+    user_id: web::ReqData<UserId>,
+    request: HttpRequest,
+) -> Result<HttpResponse, actix_web::Error> {
+    let subscriber_id = subscriber_id.into_inner();
+    if !set_status(
+        &pool,
+        subscriber_id,
+        SubscriptionStatus::Unsubscribed,
+        WebhookEvent::SubscriberUnsubscribed,
+    )
+    .await
+    .map_err(e500)?
+    {
+        return Err(ErrorNotFound("Unknown subscriber"));
+    }
+    AuditEvent::new(AuditAction::SubscriberUnsubscribed, &request)
+        .actor(*user_id.into_inner())
+        .target(subscriber_id.to_string())
+        .record(&pool)
+        .await
+        .map_err(e500)?;
+    FlashMessage::info("Subscriber unsubscribed").send();
+    Ok(see_other(&format!("/admin/subscribers/{}", subscriber_id)))
+}
+
+pub async fn delete_subscriber(
+    subscriber_id: web::Path<Uuid>,
+    pool: web::Data<PgPool>,
+    user_id: web::ReqData<UserId>,
+    request: HttpRequest,
+) -> Result<HttpResponse, actix_web::Error> {
+    let subscriber_id = subscriber_id.into_inner();
+    if !remove_subscriber(&pool, subscriber_id)
+        .await
+        .map_err(e500)?
+    {
+        return Err(ErrorNotFound("Unknown subscriber"));
+    }
+    AuditEvent::new(AuditAction::SubscriberDeleted, &request)
+        .actor(*user_id.into_inner())
+        .target(subscriber_id.to_string())
+        .record(&pool)
+        .await
+        .map_err(e500)?;
+    FlashMessage::info("Subscriber deleted").send();
+    Ok(see_other("/admin/subscribers"))
+}
+
+#[derive(serde::Deserialize)]
+pub struct TagForm {
+    tag: String,
+}
+
+pub async fn add_tag(
+    subscriber_id: web::Path<Uuid>,
+    form: web::Form<TagForm>,
+    pool: web::Data<PgPool>,
+    user_id: web::ReqData<UserId>,
+    request: HttpRequest,
+) -> Result<HttpResponse, actix_web::Error> {
+    let subscriber_id = subscriber_id.into_inner();
+    let location = format!("/admin/subscribers/{}", subscriber_id);
+    let Ok(tag) = SubscriberTag::parse(form.0.tag.trim().to_owned()) else {
+        FlashMessage::error(
+            "Tags may only contain lowercase letters, digits, colons, underscores and dashes",
+        )
+        .send();
+        return Ok(see_other(&location));
+    };
+    if get_subscriber(&pool, subscriber_id)
+        .await
+        .map_err(e500)?
+        .is_none()
+    {
+        return Err(ErrorNotFound("Unknown subscriber"));
+    }
+    sqlx::query!(
+        r#"
+        INSERT INTO subscriber_tags (subscriber_id, tag) VALUES ($1, $2)
+        ON CONFLICT DO NOTHING
+        "#,
+        subscriber_id,
+        tag.as_ref()
+    )
+    .execute(pool.get_ref())
+    .await
+    .context("Failed to tag subscriber")
+    .map_err(e500)?;
+    AuditEvent::new(AuditAction::SubscriberTagged, &request)
+        .actor(*user_id.into_inner())
+        .target(subscriber_id.to_string())
+        .record(&pool)
+        .await
+        .map_err(e500)?;
+    Ok(see_other(&location))
+}
+
+pub async fn remove_tag(
+    subscriber_id: web::Path<Uuid>,
+    form: web::Form<TagForm>,
+    pool: web::Data<PgPool>,
+    user_id: web::ReqData<UserId>,
+    request: HttpRequest,
+) -> Result<HttpResponse, actix_web::Error> {
+    let subscriber_id = subscriber_id.into_inner();
+    let removed = sqlx::query!(
+        r#"DELETE FROM subscriber_tags WHERE subscriber_id = $1 AND tag = $2"#,
+        subscriber_id,
+        form.tag
+    )
+    .execute(pool.get_ref())
+    .await
+    .context("Failed to untag subscriber")
+    .map_err(e500)?;
+    if removed.rows_affected() > 0 {
+        AuditEvent::new(AuditAction::SubscriberUntagged, &request)
+            .actor(*user_id.into_inner())
+            .target(subscriber_id.to_string())
+            .record(&pool)
+            .await
+            .map_err(e500)?;
+    }
+    Ok(see_other(&format!("/admin/subscribers/{}", subscriber_id)))
+}
+
+/// Returns `false` if there is no such subscriber.
+///
+/// The subscriber's list memberships follow, except those they already left.
+/// Outstanding confirmation tokens are dropped, so an old link can't undo
+/// the change. `event` is queued for webhooks along with it.
+#[tracing::instrument(name = "Set subscription status", skip(pool))]
+async fn set_status(
+    pool: &PgPool,
+    subscriber_id: Uuid,
+    status: SubscriptionStatus,
+    event: WebhookEvent,
+) -> Result<bool, anyhow::Error> {
+    let mut transaction = pool
+        .begin()
+        .await
+        .context("Failed to acquire a Postgres connection from the pool")?;
+    let updated = sqlx::query!(
+        r#"
+        UPDATE subscriptions
+        SET status = $1,
+            confirmed_at = CASE WHEN $1 = 'confirmed' THEN coalesce(confirmed_at, now())
+                ELSE confirmed_at END
+        WHERE id = $2
+        "#,
+        status.as_str(),
+        subscriber_id
+    )
+    .execute(&mut *transaction)
+    .await
+    .context("Failed to update subscription status")?;
+    sqlx::query!(
+        r#"
+        UPDATE list_memberships SET status = $1
+        WHERE subscriber_id = $2 AND status <> 'unsubscribed'
+        "#,
+        status.as_str(),
+        subscriber_id
+    )
+    .execute(&mut *transaction)
+    .await
+    .context("Failed to update list memberships")?;
+    sqlx::query!(
+        r#"DELETE FROM subscription_tokens WHERE subscriber_id = $1"#,
+        subscriber_id
+    )
+    .execute(&mut *transaction)
+    .await
+    .context("Failed to delete subscription tokens")?;
+    enqueue_for_subscriber(&mut transaction, event, subscriber_id).await?;
+    transaction
+        .commit()
+        .await
+        .context("Failed to commit transaction")?;
+    Ok(updated.rows_affected() > 0)
+}
+
+#[tracing::instrument(name = "Delete subscriber", skip(pool))]
+async fn remove_subscriber(pool: &PgPool, subscriber_id: Uuid) -> Result<bool, anyhow::Error> {
+    let mut transaction = pool
+        .begin()
+        .await
+        .context("Failed to acquire a Postgres connection from the pool")?;
+    sqlx::query!(
+        r#"DELETE FROM subscription_tokens WHERE subscriber_id = $1"#,
+        subscriber_id
+    )
+    .execute(&mut *transaction)
+    .await
+    .context("Failed to delete subscription tokens")?;
+    let deleted = sqlx::query!(r#"DELETE FROM subscriptions WHERE id = $1"#, subscriber_id)
+        .execute(&mut *transaction)
+        .await
+        .context("Failed to delete subscriber")?;
+    transaction
+        .commit()
+        .await
+        .context("Failed to commit transaction")?;
+    Ok(deleted.rows_affected() > 0)
+}
@@ -0,0 +1,250 @@
+use crate::audit::{AuditAction, AuditEvent};
+use crate::authentication::UserId;
+use crate::configuration::OutboundWebhookSettings;
+use crate::outbound_webhooks::{check_endpoint, WebhookEvent};
+use crate::utils::{e500, render, see_other};
+use actix_web::error::ErrorNotFound;
+use actix_web::web;
+use actix_web::{HttpRequest, HttpResponse};
+use actix_web_flash_messages::{FlashMessage, IncomingFlashMessages};
+use actix_web_lab::extract::UrlEncodedForm;
+use anyhow::Context;
+use askama::Template;
+use chrono::{DateTime, Utc};
+use rand::distributions::Alphanumeric;
+use rand::{thread_rng, Rng};
+use sqlx::PgPool;
+use uuid::Uuid;
+
+struct EndpointRow {
+    id: Uuid,
+    url: String,
+    events: Vec<String>,
+    secret: String,
+    created_at: DateTime<Utc>,
+}
+
+struct DeliveryRow {
+    id: Uuid,
+    event: String,
+    status: String,
+    attempts: i32,
+    last_attempt_at: Option<DateTime<Utc>>,
+    last_response_status: Option<i32>,
+    last_error: Option<String>,
+    created_at: DateTime<Utc>,
+}
+
+#[derive(Template)]
+#[template(path = "admin/webhooks.html")]
+struct WebhooksTemplate<'a> {
+    flash_messages: &'a IncomingFlashMessages,
+    endpoints: Vec<EndpointRow>,
+    events: &'a [WebhookEvent],
+}
+
+#[derive(Template)]
+#[template(path = "admin/webhook.html")]
+struct WebhookTemplate<'a> {
+    flash_messages: &'a IncomingFlashMessages,
+    endpoint: EndpointRow,
+    deliveries: Vec<DeliveryRow>,
+}
+
+pub async fn webhook_endpoints(
+    flash_messages: IncomingFlashMessages,
+    pool: web::Data<PgPool>,
+) -> Result<HttpResponse, actix_web::Error> {
+    let endpoints = get_endpoints(&pool).await.map_err(e500)?;
+    render(&WebhooksTemplate {
+        flash_messages: &flash_messages,
+        endpoints,
+        events: &WebhookEvent::ALL,
+    })
+}
+
+pub async fn webhook_endpoint(
+    flash_messages: IncomingFlashMessages,
+    endpoint_id: web::Path<Uuid>,
+    pool: web::Data<PgPool>,
+) -> Result<HttpResponse, actix_web::Error> {
+    let endpoint = get_endpoint(&pool, *endpoint_id)
+        .await
+        .map_err(e500)?
+        .ok_or_else(|| ErrorNotFound("Unknown webhook endpoint"))?;
+    let deliveries = get_recent_deliveries(&pool, endpoint.id)
+        .await
+        .map_err(e500)?;
+    render(&WebhookTemplate {
+        flash_messages: &flash_messages,
+        endpoint,
+        deliveries,
+    })
+}
+
+#[derive(serde::Deserialize)]
+pub struct NewEndpointForm {
+    url: String,
+    #[serde(default)]
+    events: Vec<String>,
+}
+
+pub async fn create_webhook_endpoint(
+    form: UrlEncodedForm<NewEndpointForm>,
+    pool: web::Data<PgPool>,
+    settings: web::Data<OutboundWebhookSettings>,
+    user_id: web::ReqData<UserId>,
+    request: HttpRequest,
+) -> Result<HttpResponse, actix_web::Error> {
+    let url = form.url.trim();
+    if let Err(e) = check_endpoint(url, &settings).await {
+        FlashMessage::error(e).send();
+        return Ok(see_other("/admin/webhooks"));
+    }
+    let events = match form
+        .events
+        .iter()
+        .map(|e| WebhookEvent::parse(e))
+        .collect::<Result<Vec<_>, _>>()
+    {
+        Ok(events) if !events.is_empty() => events,
+        Ok(_) => {
+            FlashMessage::error("Choose at least one event").send();
+            return Ok(see_other("/admin/webhooks"));
+        }
+        Err(e) => {
+            FlashMessage::error(e).send();
+            return Ok(see_other("/admin/webhooks"));
+        }
+    };
+    let endpoint_id = insert_endpoint(&pool, url, &events).await.map_err(e500)?;
+    AuditEvent::new(AuditAction::WebhookEndpointCreated, &request)
+        .actor(*user_id.into_inner())
+        .target(url)
+        .record(&pool)
+        .await
+        .map_err(e500)?;
+    FlashMessage::info("Webhook endpoint added").send();
+    Ok(see_other(&format!("/admin/webhooks/{}", endpoint_id)))
+}
+
+pub async fn delete_webhook_endpoint(
+    endpoint_id: web::Path<Uuid>,
+    pool: web::Data<PgPool>,
+    user_id: web::ReqData<UserId>,
+    request: HttpRequest,
+) -> Result<HttpResponse, actix_web::Error> {
+    let url = remove_endpoint(&pool, *endpoint_id)
+        .await
+        .map_err(e500)?
+        .ok_or_else(|| ErrorNotFound("Unknown webhook endpoint"))?;
+    AuditEvent::new(AuditAction::WebhookEndpointDeleted, &request)
+        .actor(*user_id.into_inner())
+        .target(url)
+        .record(&pool)
+        .await
+        .map_err(e500)?;
+    FlashMessage::info("Webhook endpoint deleted").send();
+    Ok(see_other("/admin/webhooks"))
+}
+
+/// The key endpoints use to check a delivery's signature.
+fn generate_secret() -> String {
+    let mut rng = thread_rng();
+    std::iter::repeat_with(|| rng.sample(Alphanumeric))
+        .map(char::from)
+        .take(32)
+        .collect()
+}
+
+#[tracing::instrument(name = "Get webhook endpoints", skip(pool))]
+async fn get_endpoints(pool: &PgPool) -> Result<Vec<EndpointRow>, anyhow::Error> {
+    let rows = sqlx::query_as!(
+        EndpointRow,
+        r#"SELECT id, url, events, secret, created_at FROM webhook_endpoints ORDER BY created_at"#
+    )
+    .fetch_all(pool)
+    .await
+    .context("Failed to retrieve webhook endpoints")?;
+    Ok(rows)
+}
+
+#[tracing::instrument(name = "Get webhook endpoint", skip(pool))]
+async fn get_endpoint(
+    pool: &PgPool,
+    endpoint_id: Uuid,
+) -> Result<Option<EndpointRow>, anyhow::Error> {
+    let row = sqlx::query_as!(
+        EndpointRow,
+        r#"SELECT id, url, events, secret, created_at FROM webhook_endpoints WHERE id = $1"#,
+        endpoint_id
+    )
+    .fetch_optional(pool)
+    .await
+    .context("Failed to retrieve the webhook endpoint")?;
+    Ok(row)
+}
+
+#[tracing::instrument(name = "Get recent webhook deliveries", skip(pool))]
+async fn get_recent_deliveries(
+    pool: &PgPool,
+    endpoint_id: Uuid,
+) -> Result<Vec<DeliveryRow>, anyhow::Error> {
+    let rows = sqlx::query_as!(
+        DeliveryRow,
+        r#"
+        SELECT id, event, status, attempts, last_attempt_at, last_response_status,
+            last_error, created_at
+        FROM webhook_deliveries
+        WHERE endpoint_id = $1
+        ORDER BY created_at DESC
+        LIMIT 50
+        "#,
+        endpoint_id
+    )
+    .fetch_all(pool)
+    .await
+    .context("Failed to retrieve webhook deliveries")?;
+    Ok(rows)
+}
+
+#[tracing::instrument(name = "Create webhook endpoint", skip(pool))]
+async fn insert_endpoint(
+    pool: &PgPool,
+    url: &str,
+    events: &[WebhookEvent],
+) -> Result<Uuid, anyhow::Error> {
+    let endpoint_id = Uuid::new_v4();
+    let events: Vec<String> = events.iter().map(|e| e.as_str().to_owned()).collect();
+    sqlx::query!(
+        r#"
+        INSERT INTO webhook_endpoints (id, url, secret, events, created_at)
+        VALUES ($1, $2, $3, $4, now())
+        "#,
+        endpoint_id,
+        url,
+        generate_secret(),
+        &events,
+    )
+    .execute(pool)
+    .await
+    .context("Failed to create webhook endpoint")?;
+    Ok(endpoint_id)
+}
+
+/// Returns the removed endpoint's URL, or `None` if there was no such
+/// endpoint. Its queued deliveries go with it.
+#[tracing::instrument(name = "Delete webhook endpoint", skip(pool))]
+async fn remove_endpoint(
+    pool: &PgPool,
+    endpoint_id: Uuid,
+) -> Result<Option<String>, anyhow::Error> {
+    let row = sqlx::query!(
+        r#"DELETE FROM webhook_endpoints WHERE id = $1 RETURNING url"#,
+        endpoint_id
+    )
+    .fetch_optional(pool)
+    .await
+    .context("Failed to delete webhook endpoint")?;
+    Ok(row.map(|r| r.url))
+}
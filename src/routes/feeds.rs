@@ -0,0 +1,217 @@
+use super::issues::{public_body, public_text};
+use crate::email_layout::EmailLayout;
+use crate::startup::ApplicationBaseUrl;
+use crate::utils::e500;
+use actix_web::http::header::{
+    self, EntityTag, Header, HttpDate, IfModifiedSince, IfNoneMatch, LastModified,
+};
+use actix_web::{web, HttpRequest, HttpResponse};
+use anyhow::Context;
+use askama::Template;
+use chrono::{DateTime, Utc};
+use sha2::{Digest, Sha256};
+use sqlx::PgPool;
+use std::time::SystemTime;
+
+const FEED_SIZE: i64 = 20;
+const SUMMARY_LENGTH: usize = 280;
+
+struct FeedEntry {
+    title: String,
+    permalink: String,
+    published_at: DateTime<Utc>,
+    updated_at: DateTime<Utc>,
+    summary: String,
+    content: String,
+}
+
+#[derive(Template)]
+#[template(path = "feeds/rss.xml")]
+struct RssTemplate<'a> {
+    title: &'a str,
+    archive_url: &'a str,
+    self_url: &'a str,
+    updated: Option<DateTime<Utc>>,
+    entries: &'a [FeedEntry],
+}
+
+#[derive(Template)]
+#[template(path = "feeds/atom.xml")]
+struct AtomTemplate<'a> {
+    title: &'a str,
+    archive_url: &'a str,
+    self_url: &'a str,
+    updated: Option<DateTime<Utc>>,
+    entries: &'a [FeedEntry],
+}
+
+#[tracing::instrument(name = "Get RSS feed", skip_all)]
+pub async fn rss_feed(
+    request: HttpRequest,
+    pool: web::Data<PgPool>,
+    base_url: web::Data<ApplicationBaseUrl>,
+    layout: web::Data<EmailLayout>,
+) -> Result<HttpResponse, actix_web::Error> {
+    let feed = Feed::load(&pool, &base_url.0).await.map_err(e500)?;
+    let self_url = format!("{}/feed.xml", base_url.0);
+    let body = RssTemplate {
+        title: &layout.brand_name,
+        archive_url: &feed.archive_url,
+        self_url: &self_url,
+        updated: feed.last_modified,
+        entries: &feed.entries,
+    }
+    .render()
+    .map_err(e500)?;
+    Ok(respond(
+        &request,
+        "application/rss+xml; charset=utf-8",
+        body,
+        feed.last_modified,
+    ))
+}
+
+#[tracing::instrument(name = "Get Atom feed", skip_all)]
+pub async fn atom_feed(
+    request: HttpRequest,
+    pool: web::Data<PgPool>,
+    base_url: web::Data<ApplicationBaseUrl>,
+    layout: web::Data<EmailLayout>,
+) -> Result<HttpResponse, actix_web::Error> {
+    let feed = Feed::load(&pool, &base_url.0).await.map_err(e500)?;
+    let self_url = format!("{}/atom.xml", base_url.0);
+    let body = AtomTemplate {
+        title: &layout.brand_name,
+        archive_url: &feed.archive_url,
+        self_url: &self_url,
+        updated: feed.last_modified,
+        entries: &feed.entries,
+    }
+    .render()
+    .map_err(e500)?;
+    Ok(respond(
+        &request,
+        "application/atom+xml; charset=utf-8",
+        body,
+        feed.last_modified,
+    ))
+}
+
+struct Feed {
+    archive_url: String,
+    entries: Vec<FeedEntry>,
+    /// Also moves when an issue is hidden or shown, not just when one is sent.
+    last_modified: Option<DateTime<Utc>>,
+}
+
+impl Feed {
+    async fn load(pool: &PgPool, base_url: &str) -> Result<Self, anyhow::Error> {
+        let entries = sqlx::query!(
+            r#"
+            SELECT slug, title, text_content, html_content, published_at, updated_at
+            FROM newsletter_issues
+            WHERE NOT hidden_from_archive
+            ORDER BY published_at DESC, id
+            LIMIT $1
+            "#,
+            FEED_SIZE
+        )
+        .fetch_all(pool)
+        .await
+        .context("Failed to retrieve issues for the feed")?
+        .into_iter()
+        .map(|r| FeedEntry {
+            permalink: format!("{}/issues/{}", base_url, r.slug),
+            summary: summarise(&public_text(&r.text_content)),
+            content: public_body(&r.html_content),
+            title: r.title,
+            published_at: r.published_at,
+            updated_at: r.updated_at,
+        })
+        .collect();
+        let last_modified = sqlx::query_scalar!(r#"SELECT max(updated_at) FROM newsletter_issues"#)
+            .fetch_one(pool)
+            .await
+            .context("Failed to retrieve when the feed last changed")?;
+        Ok(Self {
+            archive_url: format!("{}/issues", base_url),
+            entries,
+            last_modified,
+        })
+    }
+}
+
+/// The start of the text, cut at a word boundary.
+fn summarise(text: &str) -> String {
+    let text = text.split_whitespace().collect::<Vec<_>>().join(" ");
+    if text.chars().count() <= SUMMARY_LENGTH {
+        return text;
+    }
+    let cut: String = text.chars().take(SUMMARY_LENGTH).collect();
+    let cut = match cut.rfind(' ') {
+        Some(end) => &cut[..end],
+        None => &cut,
+    };
+    format!("{}…", cut)
+}
+
+/// Serves the feed, or `304 Not Modified` if the client's copy is current.
+/// `If-None-Match` wins over `If-Modified-Since` when both are sent.
+fn respond(
+    request: &HttpRequest,
+    content_type: &str,
+    body: String,
+    last_modified: Option<DateTime<Utc>>,
+) -> HttpResponse {
+    let etag = EntityTag::new_strong(hex::encode(Sha256::digest(body.as_bytes())));
+    let fresh = if request.headers().contains_key(header::IF_NONE_MATCH) {
+        match IfNoneMatch::parse(request) {
+            Ok(IfNoneMatch::Any) => true,
+            Ok(IfNoneMatch::Items(tags)) => tags.iter().any(|tag| tag.weak_eq(&etag)),
+            Err(_) => false,
+        }
+    } else {
+        match (IfModifiedSince::parse(request), last_modified) {
+            (Ok(IfModifiedSince(since)), Some(last_modified)) => {
+                last_modified.timestamp()
+                    <= DateTime::<Utc>::from(SystemTime::from(since)).timestamp()
+            }
+            _ => false,
+        }
+    };
+
+    let mut response = if fresh {
+        HttpResponse::NotModified()
+    } else {
+        HttpResponse::Ok()
+    };
+    response.insert_header(header::ETag(etag));
+    if let Some(last_modified) = last_modified {
+        response.insert_header(LastModified(HttpDate::from(SystemTime::from(
+            last_modified,
+        ))));
+    }
+    if fresh {
+        response.finish()
+    } else {
+        response.content_type(content_type).body(body)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::summarise;
+
+    #[test]
+    fn short_text_is_kept_whole() {
+        assert_eq!(summarise("Hello\n\n  there"), "Hello there");
+    }
+
+    #[test]
+    fn long_text_is_cut_at_a_word() {
+        let text = "word ".repeat(100);
+        let summary = summarise(&text);
+        assert!(summary.ends_with("word…"));
+        assert!(summary.chars().count() <= super::SUMMARY_LENGTH + 1);
+    }
+}
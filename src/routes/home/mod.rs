@@ -1,8 +1,11 @@
-use actix_web::http::header::ContentType;
+use crate::utils::render;
 use actix_web::HttpResponse;
+use askama::Template;
 
-pub async fn home() -> HttpResponse {
-    HttpResponse::Ok()
-        .content_type(ContentType::html())
-        .body(include_str!("home.html"))
+#[derive(Template)]
+#[template(path = "home.html")]
+struct HomeTemplate;
+
+pub async fn home() -> Result<HttpResponse, actix_web::Error> {
+    render(&HomeTemplate)
 }
@@ -0,0 +1,148 @@
+use crate::domain::NewsletterHtml;
+use crate::merge_tags::{MergeTemplate, MergeValues};
+use crate::utils::{e500, render};
+use actix_web::error::ErrorNotFound;
+use actix_web::web;
+use actix_web::HttpResponse;
+use anyhow::Context;
+use askama::Template;
+use chrono::{DateTime, Utc};
+use sqlx::PgPool;
+use std::collections::HashMap;
+
+const PAGE_SIZE: i64 = 20;
+
+#[derive(serde::Deserialize)]
+pub struct ArchiveQuery {
+    page: Option<i64>,
+}
+
+struct ArchivedIssue {
+    slug: String,
+    title: String,
+    published_at: DateTime<Utc>,
+}
+
+#[derive(Template)]
+#[template(path = "issues.html")]
+struct ArchiveTemplate {
+    issues: Vec<ArchivedIssue>,
+    newer_page: Option<i64>,
+    older_page: Option<i64>,
+}
+
+pub async fn issues_archive(
+    query: web::Query<ArchiveQuery>,
+    pool: web::Data<PgPool>,
+) -> Result<HttpResponse, actix_web::Error> {
+    let page = query.page.unwrap_or(1).max(1);
+    let offset = (page - 1)
+        .checked_mul(PAGE_SIZE)
+        .ok_or_else(|| ErrorNotFound("There is no such page"))?;
+    let mut issues = get_archived_issues(&pool, PAGE_SIZE + 1, offset)
+        .await
+        .map_err(e500)?;
+    let has_next = issues.len() as i64 > PAGE_SIZE;
+    issues.truncate(PAGE_SIZE as usize);
+    render(&ArchiveTemplate {
+        issues,
+        newer_page: (page > 1).then_some(page - 1),
+        older_page: has_next.then_some(page + 1),
+    })
+}
+
+struct IssueRow {
+    title: String,
+    html_content: String,
+    published_at: DateTime<Utc>,
+}
+
+#[derive(Template)]
+#[template(path = "issue.html")]
+struct IssueTemplate {
+    title: String,
+    published_at: DateTime<Utc>,
+    body: String,
+}
+
+pub async fn issue_page(
+    slug: web::Path<String>,
+    pool: web::Data<PgPool>,
+) -> Result<HttpResponse, actix_web::Error> {
+    let issue = get_archived_issue(&pool, &slug)
+        .await
+        .map_err(e500)?
+        .ok_or_else(|| ErrorNotFound("There is no such issue"))?;
+    render(&IssueTemplate {
+        body: public_body(&issue.html_content),
+        title: issue.title,
+        published_at: issue.published_at,
+    })
+}
+
+/// The stored HTML as the public sees it. It is sanitised again in case it
+/// was stored before sanitising existed.
+pub(super) fn public_body(html: &str) -> String {
+    NewsletterHtml::parse(&render_anonymously(html, true))
+        .as_ref()
+        .to_owned()
+}
+
+pub(super) fn public_text(text: &str) -> String {
+    render_anonymously(text, false)
+}
+
+/// Fills in merge tags for nobody in particular, so only their defaults show.
+fn render_anonymously(template: &str, escape_html: bool) -> String {
+    let no_values = HashMap::new();
+    let anonymous = MergeValues {
+        name: "",
+        email: "",
+        custom: &no_values,
+    };
+    match MergeTemplate::parse(template) {
+        Ok(template) => template.render(&anonymous, escape_html),
+        Err(_) => template.to_owned(),
+    }
+}
+
+#[tracing::instrument(name = "Get archived issues", skip(pool))]
+async fn get_archived_issues(
+    pool: &PgPool,
+    limit: i64,
+    offset: i64,
+) -> Result<Vec<ArchivedIssue>, anyhow::Error> {
+    let rows = sqlx::query_as!(
+        ArchivedIssue,
+        r#"
+        SELECT slug, title, published_at
+        FROM newsletter_issues
+        WHERE NOT hidden_from_archive
+        ORDER BY published_at DESC, id
+        LIMIT $1 OFFSET $2
+        "#,
+        limit,
+        offset
+    )
+    .fetch_all(pool)
+    .await
+    .context("Failed to retrieve archived issues")?;
+    Ok(rows)
+}
+
+#[tracing::instrument(name = "Get archived issue", skip(pool))]
+async fn get_archived_issue(pool: &PgPool, slug: &str) -> Result<Option<IssueRow>, anyhow::Error> {
+    let row = sqlx::query_as!(
+        IssueRow,
+        r#"
+        SELECT title, html_content, published_at
+        FROM newsletter_issues
+        WHERE slug = $1 AND NOT hidden_from_archive
+        "#,
+        slug
+    )
+    .fetch_optional(pool)
+    .await
+    .context("Failed to retrieve issue")?;
+    Ok(row)
+}
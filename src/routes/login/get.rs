@@ -1,15 +1,24 @@
-use actix_web::http::header::ContentType;
+use crate::authentication::OidcClient;
+use crate::configuration::OidcProviderSettings;
+use crate::utils::render;
+use actix_web::web;
 use actix_web::HttpResponse;
 use actix_web_flash_messages::IncomingFlashMessages;
-use std::fmt::Write;
+use askama::Template;
 
-pub async fn login_form(flash_messages: IncomingFlashMessages) -> HttpResponse {
-    let mut error_html = String::new();
-    for m in flash_messages.iter() {
-        writeln!(error_html, "<p><i>{}</i></p>", m.content()).unwrap();
-    }
+#[derive(Template)]
+#[template(path = "login.html")]
+struct LoginTemplate<'a> {
+    flash_messages: &'a IncomingFlashMessages,
+    providers: &'a [OidcProviderSettings],
+}
 
-    HttpResponse::Ok()
-        .content_type(ContentType::html())
-        .body(format!(include_str!("login.html"), error_html))
+pub async fn login_form(
+    flash_messages: IncomingFlashMessages,
+    oidc_client: web::Data<OidcClient>,
+) -> Result<HttpResponse, actix_web::Error> {
+    render(&LoginTemplate {
+        flash_messages: &flash_messages,
+        providers: oidc_client.providers(),
+    })
 }
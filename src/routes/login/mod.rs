@@ -1,4 +1,8 @@
 mod get;
+mod oidc;
+mod passkey;
 mod post;
 pub use get::login_form;
+pub use oidc::{oidc_callback, oidc_login};
+pub use passkey::{finish_passkey_login, passkey_login_form, start_passkey_login};
 pub use post::login;
@@ -0,0 +1,173 @@
+use super::post::{login_redirect, LoginError};
+use crate::audit::{AuditAction, AuditEvent};
+use crate::authentication::{
+    link_oidc_identity, passkey_required, resolve_oidc_identity, AuthError, OidcClient,
+    OidcLoginState,
+};
+use crate::session_state::TypedSession;
+use crate::utils::see_other;
+use actix_web::error::InternalError;
+use actix_web::web;
+use actix_web::{HttpRequest, HttpResponse};
+use actix_web_flash_messages::FlashMessage;
+use sqlx::PgPool;
+
+#[derive(serde::Deserialize)]
+pub struct OidcLoginParameters {
+    provider: Option<String>,
+}
+
+#[derive(serde::Deserialize)]
+pub struct OidcCallbackParameters {
+    code: Option<String>,
+    state: Option<String>,
+    error: Option<String>,
+}
+
+#[tracing::instrument(skip(parameters, oidc_client, session))]
+pub async fn oidc_login(
+    parameters: web::Query<OidcLoginParameters>,
+    oidc_client: web::Data<OidcClient>,
+    session: TypedSession,
+) -> Result<HttpResponse, InternalError<LoginError>> {
+    let provider = oidc_client
+        .provider(parameters.provider.as_deref())
+        .ok_or_else(|| {
+            login_redirect(LoginError::AuthError(anyhow::anyhow!(
+                "Unknown identity provider"
+            )))
+        })?;
+
+    // Starting the flow while logged in links the identity instead.
+    let link_to = session
+        .get_user_id()
+        .map_err(|e| login_redirect(LoginError::UnexpectedError(e.into())))?;
+    let state = OidcLoginState::generate(&provider.name, link_to);
+    let authorization_url = oidc_client
+        .authorization_url(provider, &state)
+        .map_err(|e| login_redirect(LoginError::UnexpectedError(e)))?;
+    session
+        .insert_oidc_login(state)
+        .map_err(|e| login_redirect(LoginError::UnexpectedError(e.into())))?;
+
+    Ok(see_other(authorization_url.as_str()))
+}
+
+#[tracing::instrument(
+    skip(parameters, pool, oidc_client, session, request),
+    fields(provider=tracing::field::Empty, user_id=tracing::field::Empty)
+)]
+pub async fn oidc_callback(
+    parameters: web::Query<OidcCallbackParameters>,
+    pool: web::Data<PgPool>,
+    oidc_client: web::Data<OidcClient>,
+    session: TypedSession,
+    request: HttpRequest,
+) -> Result<HttpResponse, InternalError<LoginError>> {
+    let login_state = session
+        .take_oidc_login()
+        .map_err(|e| login_redirect(LoginError::UnexpectedError(e.into())))?
+        .ok_or_else(|| {
+            login_redirect(LoginError::AuthError(anyhow::anyhow!(
+                "No OIDC login in progress"
+            )))
+        })?;
+    tracing::Span::current().record("provider", &login_state.provider);
+
+    if let Some(error) = &parameters.error {
+        return Err(login_redirect(LoginError::AuthError(anyhow::anyhow!(
+            "Identity provider returned {}",
+            error
+        ))));
+    }
+    let code = match (&parameters.code, &parameters.state) {
+        (Some(code), Some(state)) if *state == login_state.csrf_state => code,
+        _ => {
+            return Err(login_redirect(LoginError::AuthError(anyhow::anyhow!(
+                "OIDC state mismatch"
+            ))))
+        }
+    };
+
+    let provider = oidc_client
+        .provider(Some(&login_state.provider))
+        .ok_or_else(|| {
+            login_redirect(LoginError::AuthError(anyhow::anyhow!(
+                "Unknown identity provider"
+            )))
+        })?;
+    let claims = oidc_client
+        .exchange_code(provider, code, &login_state)
+        .await
+        .map_err(|e| login_redirect(LoginError::AuthError(e.into())))?;
+
+    if let Some(user_id) = login_state.link_to {
+        let logged_in = session
+            .get_user_id()
+            .map_err(|e| login_redirect(LoginError::UnexpectedError(e.into())))?;
+        if logged_in != Some(user_id) {
+            return Err(login_redirect(LoginError::AuthError(anyhow::anyhow!(
+                "The user who started linking is no longer logged in"
+            ))));
+        }
+        return match link_oidc_identity(provider, &claims, user_id, &pool).await {
+            Ok(()) => {
+                AuditEvent::new(AuditAction::IdentityLinked, &request)
+                    .actor(user_id)
+                    .target(format!("oidc:{}:{}", provider.name, claims.sub))
+                    .record(&pool)
+                    .await
+                    .map_err(|e| login_redirect(LoginError::UnexpectedError(e)))?;
+                FlashMessage::info(format!("You can now log in with {}", provider.display_name))
+                    .send();
+                Ok(see_other("/admin/dashboard"))
+            }
+            Err(AuthError::InvalidCredentials(_)) => {
+                FlashMessage::error("That identity is already linked to another user").send();
+                Ok(see_other("/admin/dashboard"))
+            }
+            Err(e) => Err(login_redirect(LoginError::UnexpectedError(e.into()))),
+        };
+    }
+
+    match resolve_oidc_identity(provider, &claims, &pool).await {
+        Ok(user_id) => {
+            tracing::Span::current().record("user_id", tracing::field::display(&user_id));
+            // Single sign-on stands in for the password, not for the passkey.
+            let passkey_required = passkey_required(user_id, &pool)
+                .await
+                .map_err(|e| login_redirect(LoginError::UnexpectedError(e)))?;
+            session.renew();
+            if passkey_required {
+                session
+                    .insert_pending_user_id(user_id)
+                    .map_err(|e| login_redirect(LoginError::UnexpectedError(e.into())))?;
+                return Ok(see_other("/login/passkey"));
+            }
+            session
+                .insert_user_id(user_id)
+                .map_err(|e| login_redirect(LoginError::UnexpectedError(e.into())))?;
+            AuditEvent::new(AuditAction::LoginSucceeded, &request)
+                .actor(user_id)
+                .target(format!("oidc:{}", provider.name))
+                .record(&pool)
+                .await
+                .map_err(|e| login_redirect(LoginError::UnexpectedError(e)))?;
+            Ok(see_other("/admin/dashboard"))
+        }
+        Err(e) => {
+            if let AuthError::InvalidCredentials(_) = e {
+                AuditEvent::new(AuditAction::LoginFailed, &request)
+                    .target(format!("oidc:{}:{}", provider.name, claims.sub))
+                    .record(&pool)
+                    .await
+                    .map_err(|e| login_redirect(LoginError::UnexpectedError(e)))?;
+            }
+            let e = match e {
+                AuthError::InvalidCredentials(_) => LoginError::AuthError(e.into()),
+                AuthError::UnexpectedError(_) => LoginError::UnexpectedError(e.into()),
+            };
+            Err(login_redirect(e))
+        }
+    }
+}
@@ -0,0 +1,141 @@
+use super::post::LoginError;
+use crate::audit::{AuditAction, AuditEvent};
+use crate::authentication::{get_passkeys, get_user_id, record_passkey_use};
+use crate::session_state::TypedSession;
+use crate::utils::{e500, render};
+use actix_web::error::InternalError;
+use actix_web::http::StatusCode;
+use actix_web::web;
+use actix_web::{HttpRequest, HttpResponse};
+use actix_web_flash_messages::{FlashMessage, IncomingFlashMessages};
+use askama::Template;
+use sqlx::PgPool;
+use webauthn_rs::prelude::PublicKeyCredential;
+use webauthn_rs::Webauthn;
+
+#[derive(serde::Deserialize)]
+pub struct StartPasskeyLogin {
+    username: Option<String>,
+}
+
+#[derive(Template)]
+#[template(path = "passkey_login.html")]
+struct PasskeyLoginTemplate<'a> {
+    flash_messages: &'a IncomingFlashMessages,
+    second_factor: bool,
+}
+
+pub async fn passkey_login_form(
+    flash_messages: IncomingFlashMessages,
+    session: TypedSession,
+) -> Result<HttpResponse, actix_web::Error> {
+    render(&PasskeyLoginTemplate {
+        flash_messages: &flash_messages,
+        second_factor: session.get_pending_user_id().map_err(e500)?.is_some(),
+    })
+}
+
+#[tracing::instrument(
+    skip(body, pool, webauthn, session),
+    fields(user_id=tracing::field::Empty)
+)]
+pub async fn start_passkey_login(
+    body: web::Json<StartPasskeyLogin>,
+    pool: web::Data<PgPool>,
+    webauthn: web::Data<Webauthn>,
+    session: TypedSession,
+) -> Result<HttpResponse, InternalError<LoginError>> {
+    let user_id = match &body.username {
+        Some(username) => get_user_id(username, &pool)
+            .await
+            .map_err(|e| passkey_login_error(LoginError::UnexpectedError(e)))?,
+        None => session
+            .get_pending_user_id()
+            .map_err(|e| passkey_login_error(LoginError::UnexpectedError(e.into())))?,
+    }
+    .ok_or_else(|| passkey_login_error(LoginError::AuthError(anyhow::anyhow!("Unknown user"))))?;
+    tracing::Span::current().record("user_id", tracing::field::display(&user_id));
+
+    let passkeys: Vec<_> = get_passkeys(user_id, &pool)
+        .await
+        .map_err(|e| passkey_login_error(LoginError::UnexpectedError(e)))?
+        .into_iter()
+        .map(|p| p.passkey)
+        .collect();
+    if passkeys.is_empty() {
+        return Err(passkey_login_error(LoginError::AuthError(anyhow::anyhow!(
+            "No passkeys registered"
+        ))));
+    }
+
+    let (challenge, state) = webauthn
+        .start_passkey_authentication(&passkeys)
+        .map_err(|e| passkey_login_error(LoginError::UnexpectedError(e.into())))?;
+    session
+        .insert_passkey_authentication(user_id, state)
+        .map_err(|e| passkey_login_error(LoginError::UnexpectedError(e.into())))?;
+
+    Ok(HttpResponse::Ok().json(challenge))
+}
+
+#[tracing::instrument(
+    skip(credential, pool, webauthn, session, request),
+    fields(user_id=tracing::field::Empty)
+)]
+pub async fn finish_passkey_login(
+    credential: web::Json<PublicKeyCredential>,
+    pool: web::Data<PgPool>,
+    webauthn: web::Data<Webauthn>,
+    session: TypedSession,
+    request: HttpRequest,
+) -> Result<HttpResponse, InternalError<LoginError>> {
+    let (user_id, state) = session
+        .take_passkey_authentication()
+        .map_err(|e| passkey_login_error(LoginError::UnexpectedError(e.into())))?
+        .ok_or_else(|| {
+            passkey_login_error(LoginError::AuthError(anyhow::anyhow!(
+                "No passkey login in progress"
+            )))
+        })?;
+    tracing::Span::current().record("user_id", tracing::field::display(&user_id));
+
+    let result = match webauthn.finish_passkey_authentication(&credential, &state) {
+        Ok(result) => result,
+        Err(e) => {
+            AuditEvent::new(AuditAction::LoginFailed, &request)
+                .target(format!("passkey:{}", user_id))
+                .record(&pool)
+                .await
+                .map_err(|e| passkey_login_error(LoginError::UnexpectedError(e)))?;
+            return Err(passkey_login_error(LoginError::AuthError(e.into())));
+        }
+    };
+    record_passkey_use(user_id, &result, &pool)
+        .await
+        .map_err(|e| passkey_login_error(LoginError::UnexpectedError(e)))?;
+
+    session.renew();
+    session.clear_pending_user_id();
+    session
+        .insert_user_id(user_id)
+        .map_err(|e| passkey_login_error(LoginError::UnexpectedError(e.into())))?;
+    AuditEvent::new(AuditAction::LoginSucceeded, &request)
+        .actor(user_id)
+        .target("passkey")
+        .record(&pool)
+        .await
+        .map_err(|e| passkey_login_error(LoginError::UnexpectedError(e)))?;
+    Ok(HttpResponse::Ok().json(serde_json::json!({ "redirect": "/admin/dashboard" })))
+}
+
+/// The passkey endpoints are called from JavaScript, so rather than a
+/// redirect we tell the page where to go next.
+fn passkey_login_error(e: LoginError) -> InternalError<LoginError> {
+    FlashMessage::error(e.to_string()).send();
+    let status = match e {
+        LoginError::AuthError(_) => StatusCode::UNAUTHORIZED,
+        LoginError::UnexpectedError(_) => StatusCode::INTERNAL_SERVER_ERROR,
+    };
+    let response = HttpResponse::build(status).json(serde_json::json!({ "redirect": "/login" }));
+    InternalError::from_response(e, response)
+}
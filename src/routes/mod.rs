@@ -1,15 +1,29 @@
 mod admin;
+mod feeds;
 mod health_check;
 mod home;
+mod issues;
 mod login;
 mod newsletters;
+mod preferences;
+mod privacy;
 mod subscriptions;
+mod subscriptions_api;
 mod subscriptions_confirm;
+mod tracking;
+mod webhooks;
 
 pub use admin::*;
+pub use feeds::*;
 pub use health_check::*;
 pub use home::*;
+pub use issues::*;
 pub use login::*;
 pub use newsletters::*;
+pub use preferences::*;
+pub use privacy::*;
 pub use subscriptions::*;
+pub use subscriptions_api::*;
 pub use subscriptions_confirm::*;
+pub use tracking::*;
+pub use webhooks::*;
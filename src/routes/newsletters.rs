@@ -1,177 +0,0 @@
-use crate::domain::SubscriberEmail;
-use crate::email_client::EmailClient;
-use crate::routes::error_chain_fmt;
-use crate::session_state::TypedSession;
-use crate::utils::e500;
-use crate::utils::see_other;
-use actix_web::http::header;
-use actix_web::http::header::ContentType;
-use actix_web::http::header::HeaderValue;
-use actix_web::http::StatusCode;
-use actix_web::web;
-use actix_web::HttpResponse;
-use actix_web::ResponseError;
-use actix_web_flash_messages::IncomingFlashMessages;
-use anyhow::Context;
-use sqlx::PgPool;
-use std::fmt::Write;
-
-struct ConfirmedSubscriber {
-    email: SubscriberEmail,
-}
-
-#[derive(serde::Deserialize)]
-pub struct NewsletterFormData {
-    title: String,
-    html: String,
-    text: String,
-}
-
-#[derive(thiserror::Error)]
-pub enum PublishError {
-    #[error(transparent)]
-    UnexpectedError(#[from] anyhow::Error),
-    #[error("Authentication Failed")]
-    AuthError(#[source] anyhow::Error),
-}
-
-impl std::fmt::Debug for PublishError {
-    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
-        error_chain_fmt(self, f)
-    }
-}
-
-impl ResponseError for PublishError {
-    fn error_response(&self) -> HttpResponse<actix_web::body::BoxBody> {
-        match self {
-            PublishError::UnexpectedError(_) => {
-                HttpResponse::new(StatusCode::INTERNAL_SERVER_ERROR)
-            }
-            PublishError::AuthError(_) => {
-                let mut response = HttpResponse::new(StatusCode::UNAUTHORIZED);
-                let header_value = HeaderValue::from_str(r#"Basic realm="publish""#).unwrap();
-                response
-                    .headers_mut()
-                    .insert(header::WWW_AUTHENTICATE, header_value);
-
-                response
-            }
-        }
-    }
-}
-
-pub async fn send_newsletter_form(
-    session: TypedSession,
-    flash_messages: IncomingFlashMessages,
-) -> Result<HttpResponse, actix_web::Error> {
-    if session.get_user_id().map_err(e500)?.is_none() {
-        return Ok(see_other("/login"));
-    }
-
-    let mut msg_html = String::new();
-    for m in flash_messages.iter() {
-        writeln!(msg_html, "<p><i>{}</i></p>", m.content()).unwrap();
-    }
-
-    Ok(HttpResponse::Ok()
-        .content_type(ContentType::html())
-        .body(format!(
-            r#"<!DOCTYPE html>
-        <html lang="en">
-            <head>
-                <meta http-equiv="content-type" content="text/html; charset=utf-8">
-                <title>Change Password</title>
-            </head>
-            <body>
-                {msg_html}
-                <form action="/admin/newsletter" method="post">
-                    <label>Title
-                    <input
-                        type="text"
-                        placeholder="Title"
-                        name="title"
-                    >
-                    </label>
-                    <br>
-                    <label>Content (Text)
-                    <input
-                        type="text"
-                        placeholder="Content Text"
-                        name="text"
-                    >
-                    </label>
-                    <br>
-                    <label>Content HTML
-                    <input
-                        type="Text"
-                        placeholder="Content HTML"
-                        name="html"
-                    >
-                    </label>
-                    <br>
-                    <button type="submit">Send Newsletter</button>
-                </form>
-                <p><a href="/admin/dashboard">&lt;- Back</a></p>
-            </body>
-        </html>"#
-        )))
-}
-
-#[tracing::instrument(
-    name = "Publish a newsletter issue",
-    skip(body, pool, email_client),
-    fields(username=tracing::field::Empty, user_id=tracing::field::Empty)
-)]
-pub async fn publish_newsletter(
-    body: web::Form<NewsletterFormData>,
-    pool: web::Data<PgPool>,
-    email_client: web::Data<EmailClient>,
-) -> Result<HttpResponse, PublishError> {
-    let subscribers = get_confirmed_subscribers(&pool).await?;
-
-    for subscriber in subscribers {
-        match subscriber {
-            Ok(subscriber) => {
-                email_client
-                    .send_email(&subscriber.email, &body.title, &body.html, &body.text)
-                    .await
-                    .with_context(|| format!("Failed to send email to {}", subscriber.email))?;
-            }
-            Err(error) => {
-                tracing::warn!(error.cause_chain = ?error,
-                "Skipping unconfirmed subscriber due to bad email");
-            }
-        }
-    }
-
-    Ok(HttpResponse::Ok().finish())
-}
-
-#[tracing::instrument(name = "Get confirmed subscribers", skip(pool))]
-async fn get_confirmed_subscribers(
-    pool: &PgPool,
-) -> Result<Vec<Result<ConfirmedSubscriber, anyhow::Error>>, anyhow::Error> {
-    struct Row {
-        email: String,
-    }
-    let rows = sqlx::query_as!(
-        Row,
-        r#"
-        SELECT email
-        FROM subscriptions
-        WHERE status = 'confirmed'
-        "#,
-    )
-    .fetch_all(pool)
-    .await?;
-
-    let confirmed_subscribers = rows
-        .into_iter()
-        .map(|r| match SubscriberEmail::parse(r.email) {
-            Ok(email) => Ok(ConfirmedSubscriber { email }),
-            Err(error) => Err(anyhow::anyhow!(error)),
-        })
-        .collect();
-
-    Ok(confirmed_subscribers)
-}
@@ -0,0 +1,117 @@
+use super::post::{IssueContent, PublishError};
+use super::recipients::Audience;
+use crate::configuration::TrackingSettings;
+use crate::lists::get_lists;
+use crate::session_state::TypedSession;
+use crate::utils::{e500, render, see_other};
+use actix_web::web;
+use actix_web::HttpResponse;
+use actix_web_flash_messages::IncomingFlashMessages;
+use actix_web_lab::extract::UrlEncodedForm;
+use askama::Template;
+use sqlx::PgPool;
+
+/// What the form was last filled in with, so "Check before sending" can
+/// re-render it without losing the draft. It is posted rather than put in the
+/// URL, where it would end up in logs and browser history.
+#[derive(serde::Deserialize, Default)]
+pub struct Draft {
+    #[serde(default)]
+    title: String,
+    #[serde(default)]
+    text: String,
+    #[serde(default)]
+    html: String,
+    #[serde(default)]
+    markdown: String,
+    #[serde(default)]
+    lists: Vec<String>,
+    #[serde(default)]
+    segment: String,
+    #[serde(default)]
+    disable_open_tracking: bool,
+    #[serde(default)]
+    disable_click_tracking: bool,
+    #[serde(default)]
+    publish_to_archive: bool,
+}
+
+struct ListOption {
+    slug: String,
+    name: String,
+    selected: bool,
+}
+
+#[derive(Template)]
+#[template(path = "admin/newsletter.html")]
+struct NewsletterFormTemplate<'a> {
+    flash_messages: &'a IncomingFlashMessages,
+    draft: &'a Draft,
+    lists: Vec<ListOption>,
+    recipients: Result<i64, String>,
+    warnings: Vec<String>,
+    /// Whether opens can be tracked at all.
+    track_opens: bool,
+    /// Whether clicks can be tracked at all.
+    track_clicks: bool,
+}
+
+pub async fn send_newsletter_form(
+    session: TypedSession,
+    flash_messages: IncomingFlashMessages,
+    pool: web::Data<PgPool>,
+    tracking: web::Data<TrackingSettings>,
+) -> Result<HttpResponse, actix_web::Error> {
+    if session.get_user_id().map_err(e500)?.is_none() {
+        return Ok(see_other("/login"));
+    }
+    render_form(&flash_messages, &Draft::default(), &pool, &tracking).await
+}
+
+/// "Check before sending": the form again, with the draft's recipient count
+/// and what sanitising its HTML would change.
+pub async fn check_newsletter(
+    flash_messages: IncomingFlashMessages,
+    draft: UrlEncodedForm<Draft>,
+    pool: web::Data<PgPool>,
+    tracking: web::Data<TrackingSettings>,
+) -> Result<HttpResponse, actix_web::Error> {
+    render_form(&flash_messages, &draft, &pool, &tracking).await
+}
+
+async fn render_form(
+    flash_messages: &IncomingFlashMessages,
+    draft: &Draft,
+    pool: &PgPool,
+    tracking: &TrackingSettings,
+) -> Result<HttpResponse, actix_web::Error> {
+    let lists = get_lists(pool)
+        .await
+        .map_err(e500)?
+        .into_iter()
+        .map(|list| ListOption {
+            selected: draft.lists.contains(&list.slug),
+            slug: list.slug,
+            name: list.name,
+        })
+        .collect();
+    let recipients = match Audience::resolve(pool, &draft.lists, &draft.segment).await {
+        Ok(audience) => Ok(audience.count(pool).await.map_err(e500)?),
+        Err(e @ (PublishError::UnknownList(_) | PublishError::InvalidSegment(_))) => {
+            Err(e.to_string())
+        }
+        Err(e) => return Err(e500(e)),
+    };
+    let warnings = IssueContent::new(&draft.html, &draft.text, &draft.markdown)
+        .map(|content| content.warnings)
+        .unwrap_or_default();
+    render(&NewsletterFormTemplate {
+        flash_messages,
+        draft,
+        lists,
+        recipients,
+        warnings,
+        track_opens: tracking.opens,
+        track_clicks: tracking.clicks,
+    })
+}
@@ -0,0 +1,6 @@
+mod get;
+mod post;
+mod recipients;
+
+pub use get::{check_newsletter, send_newsletter_form};
+pub use post::{publish_newsletter, PublishError};
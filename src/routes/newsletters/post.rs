@@ -0,0 +1,417 @@
+use super::recipients::Audience;
+use crate::audit::{AuditAction, AuditEvent};
+use crate::authentication::UserId;
+use crate::configuration::TrackingSettings;
+use crate::custom_fields::{get_custom_fields, get_field_values};
+use crate::digests::queue_for_digest;
+use crate::domain::{DigestFrequency, IssueSlug, NewsletterHtml};
+use crate::email_client::EmailClient;
+use crate::email_layout::EmailLayout;
+use crate::markdown;
+use crate::merge_tags::{MergeTemplate, MergeValues};
+use crate::outbound_webhooks::{enqueue, WebhookEvent};
+use crate::routes::{error_chain_fmt, preferences_link, with_open_pixel, with_tracked_links};
+use crate::startup::{ApplicationBaseUrl, HmacSecret};
+use actix_web::http::header;
+use actix_web::http::header::HeaderValue;
+use actix_web::http::StatusCode;
+use actix_web::web;
+use actix_web::ResponseError;
+use actix_web::{HttpRequest, HttpResponse};
+use actix_web_lab::extract::UrlEncodedForm;
+use anyhow::Context;
+use sqlx::PgPool;
+use std::collections::HashMap;
+use uuid::Uuid;
+
+#[derive(serde::Deserialize)]
+pub struct NewsletterFormData {
+    title: String,
+    #[serde(default)]
+    html: String,
+    #[serde(default)]
+    text: String,
+    /// Takes the place of `html` and `text` when given.
+    #[serde(default)]
+    markdown: String,
+    /// Slugs of the lists to send to; the default list if none are given.
+    #[serde(default)]
+    lists: Vec<String>,
+    #[serde(default)]
+    segment: String,
+    /// The editor has seen what sanitising the HTML removes.
+    #[serde(default)]
+    accept_sanitised: bool,
+    #[serde(default)]
+    disable_open_tracking: bool,
+    #[serde(default)]
+    disable_click_tracking: bool,
+    /// Publish the issue at `/issues/{slug}` and in the feeds.
+    #[serde(default)]
+    publish_to_archive: bool,
+}
+
+/// What an issue is sent with, however it was authored.
+pub(super) struct IssueContent {
+    pub html: String,
+    pub text: String,
+    pub markdown: Option<String>,
+    /// What sanitising hand-written HTML removed.
+    pub warnings: Vec<String>,
+}
+
+impl IssueContent {
+    pub fn new(html: &str, text: &str, markdown: &str) -> Result<Self, PublishError> {
+        if !markdown.trim().is_empty() {
+            let rendered = markdown::render(markdown);
+            return Ok(Self {
+                html: NewsletterHtml::parse(&rendered.html).as_ref().to_owned(),
+                text: rendered.text,
+                markdown: Some(markdown.to_owned()),
+                warnings: Vec::new(),
+            });
+        }
+        if html.trim().is_empty() || text.trim().is_empty() {
+            return Err(PublishError::MissingContent);
+        }
+        let html = NewsletterHtml::parse(html);
+        Ok(Self {
+            warnings: html.warnings().to_vec(),
+            html: html.as_ref().to_owned(),
+            text: text.to_owned(),
+            markdown: None,
+        })
+    }
+}
+
+#[derive(thiserror::Error)]
+pub enum PublishError {
+    #[error("An issue needs either Markdown or both HTML and text content")]
+    MissingContent,
+    #[error("Sanitising the HTML would change it: {0}")]
+    UnreviewedHtml(String),
+    #[error("There is no list called {0}")]
+    UnknownList(String),
+    #[error("Invalid segment: {0}")]
+    InvalidSegment(String),
+    #[error("Invalid merge tag: {0}")]
+    InvalidMergeTag(String),
+    #[error(transparent)]
+    UnexpectedError(#[from] anyhow::Error),
+    #[error("Authentication Failed")]
+    AuthError(#[source] anyhow::Error),
+}
+
+impl std::fmt::Debug for PublishError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        error_chain_fmt(self, f)
+    }
+}
+
+impl ResponseError for PublishError {
+    fn error_response(&self) -> HttpResponse<actix_web::body::BoxBody> {
+        match self {
+            PublishError::MissingContent
+            | PublishError::UnreviewedHtml(_)
+            | PublishError::UnknownList(_)
+            | PublishError::InvalidSegment(_)
+            | PublishError::InvalidMergeTag(_) => HttpResponse::new(StatusCode::BAD_REQUEST),
+            PublishError::UnexpectedError(_) => {
+                HttpResponse::new(StatusCode::INTERNAL_SERVER_ERROR)
+            }
+            PublishError::AuthError(_) => {
+                let mut response = HttpResponse::new(StatusCode::UNAUTHORIZED);
+                let header_value = HeaderValue::from_str(r#"Basic realm="publish""#).unwrap();
+                response
+                    .headers_mut()
+                    .insert(header::WWW_AUTHENTICATE, header_value);
+
+                response
+            }
+        }
+    }
+}
+
+#[allow(clippy::too_many_arguments)]
+#[tracing::instrument(
+    name = "Publish a newsletter issue",
+    skip(body, pool, email_client, layout, tracking, base_url, secret, request),
+    fields(username=tracing::field::Empty, user_id=tracing::field::Empty)
+)]
+pub async fn publish_newsletter(
+    body: UrlEncodedForm<NewsletterFormData>,
+    pool: web::Data<PgPool>,
+    email_client: web::Data<EmailClient>,
+    layout: web::Data<EmailLayout>,
+    tracking: web::Data<TrackingSettings>,
+    base_url: web::Data<ApplicationBaseUrl>,
+    secret: web::Data<HmacSecret>,
+    user_id: web::ReqData<UserId>,
+    request: HttpRequest,
+) -> Result<HttpResponse, PublishError> {
+    let audience = Audience::resolve(&pool, &body.lists, &body.segment).await?;
+    let content = IssueContent::new(&body.html, &body.text, &body.markdown)?;
+    if !content.warnings.is_empty() && !body.accept_sanitised {
+        return Err(PublishError::UnreviewedHtml(content.warnings.join("; ")));
+    }
+    let (html, text) = parse_merge_templates(&pool, &content.html, &content.text).await?;
+    let track_opens = tracking.opens && !body.disable_open_tracking;
+    let track_clicks = tracking.clicks && !body.disable_click_tracking;
+    let (issue_id, slug) = insert_newsletter_issue(
+        &pool,
+        &body.title,
+        &content,
+        &audience,
+        track_opens,
+        track_clicks,
+        body.publish_to_archive,
+        **user_id,
+    )
+    .await
+    .context("Failed to store newsletter issue details")?;
+    let archive_link = body
+        .publish_to_archive
+        .then(|| format!("{}/issues/{}", base_url.0, slug.as_ref()));
+    let (archive_html, archive_text) = match &archive_link {
+        Some(link) => (
+            format!("<a href=\"{}\">View in browser</a> | ", link),
+            format!("View in browser: {}\n", link),
+        ),
+        None => (String::new(), String::new()),
+    };
+    let subscribers = audience.subscribers(&pool).await?;
+    let subscriber_ids: Vec<Uuid> = subscribers.iter().flatten().map(|s| s.id).collect();
+    let field_values = get_field_values(&pool, &subscriber_ids)
+        .await
+        .context("Failed to retrieve custom field values")?;
+    let no_values = HashMap::new();
+    let mut recipients = 0;
+
+    for subscriber in subscribers {
+        match subscriber {
+            Ok(subscriber) if subscriber.digest_frequency != DigestFrequency::Immediate => {
+                queue_for_digest(pool.get_ref(), issue_id, subscriber.id)
+                    .await
+                    .context("Failed to queue the issue for a digest")?;
+            }
+            Ok(subscriber) => {
+                let values = MergeValues {
+                    name: &subscriber.name,
+                    email: subscriber.email.as_ref(),
+                    custom: field_values.get(&subscriber.id).unwrap_or(&no_values),
+                };
+                let link = preferences_link(
+                    &base_url.0,
+                    &secret,
+                    subscriber.id,
+                    subscriber.email.as_ref(),
+                );
+                let content_html = html.render(&values, true);
+                // Only the issue's own links; the footer's stay as they are.
+                let content_html = if track_clicks {
+                    with_tracked_links(&content_html, &base_url.0, &secret, issue_id, subscriber.id)
+                } else {
+                    content_html
+                };
+                let html_body = match content.markdown {
+                    Some(_) => layout
+                        .wrap(&body.title, &content_html, archive_link.as_deref(), &link)
+                        .context("Failed to render the email layout")?,
+                    None => format!(
+                        "{}<hr><p>{}<a href=\"{}\">Update your preferences or unsubscribe</a></p>",
+                        content_html, archive_html, link
+                    ),
+                };
+                let html_body = if track_opens {
+                    with_open_pixel(&html_body, &base_url.0, &secret, issue_id, subscriber.id)
+                } else {
+                    html_body
+                };
+                let text_body = format!(
+                    "{}\n\n--\n{}Update your preferences or unsubscribe: {}",
+                    text.render(&values, false),
+                    archive_text,
+                    link
+                );
+                let outcome = email_client
+                    .send_email_with_message_id(
+                        &subscriber.email,
+                        &body.title,
+                        &html_body,
+                        &text_body,
+                    )
+                    .await;
+                let (status, message_id) = match &outcome {
+                    Ok(message_id) => (DeliveryStatus::Delivered, message_id.as_deref()),
+                    Err(_) => (DeliveryStatus::Failed, None),
+                };
+                record_delivery(&pool, issue_id, subscriber.id, status, message_id)
+                    .await
+                    .context("Failed to record delivery")?;
+                outcome.with_context(|| format!("Failed to send email to {}", subscriber.email))?;
+                recipients += 1;
+            }
+            Err(error) => {
+                tracing::warn!(error.cause_chain = ?error,
+                "Skipping unconfirmed subscriber due to bad email");
+            }
+        }
+    }
+
+    let mut transaction = pool
+        .begin()
+        .await
+        .context("Failed to acquire a Postgres connection from the pool")?;
+    enqueue(
+        &mut transaction,
+        WebhookEvent::IssueSent,
+        serde_json::json!({
+            "id": issue_id,
+            "title": body.title,
+            "slug": slug.as_ref(),
+            "url": archive_link,
+            "recipients": recipients,
+        }),
+    )
+    .await?;
+    transaction
+        .commit()
+        .await
+        .context("Failed to commit transaction")?;
+
+    AuditEvent::new(AuditAction::NewsletterPublished, &request)
+        .actor(**user_id)
+        .target(&body.title)
+        .record(&pool)
+        .await?;
+
+    Ok(HttpResponse::Ok().finish())
+}
+
+/// Rejects tags that are malformed or name a custom field that doesn't exist,
+/// before anything is stored or sent.
+async fn parse_merge_templates(
+    pool: &PgPool,
+    html: &str,
+    text: &str,
+) -> Result<(MergeTemplate, MergeTemplate), PublishError> {
+    let html = MergeTemplate::parse(html).map_err(PublishError::InvalidMergeTag)?;
+    let text = MergeTemplate::parse(text).map_err(PublishError::InvalidMergeTag)?;
+    let keys: Vec<&str> = html
+        .custom_keys()
+        .into_iter()
+        .chain(text.custom_keys())
+        .collect();
+    if !keys.is_empty() {
+        let fields = get_custom_fields(pool)
+            .await
+            .context("Failed to load custom fields")?;
+        if let Some(key) = keys.iter().find(|k| !fields.iter().any(|f| f.key == **k)) {
+            return Err(PublishError::InvalidMergeTag(format!(
+                "There is no field called {}",
+                key
+            )));
+        }
+    }
+    Ok((html, text))
+}
+
+#[derive(Copy, Clone, Debug)]
+enum DeliveryStatus {
+    Delivered,
+    Failed,
+}
+
+impl DeliveryStatus {
+    fn as_str(&self) -> &'static str {
+        match self {
+            DeliveryStatus::Delivered => "delivered",
+            DeliveryStatus::Failed => "failed",
+        }
+    }
+}
+
+#[allow(clippy::too_many_arguments)]
+#[tracing::instrument(name = "Store newsletter issue", skip(pool, content))]
+async fn insert_newsletter_issue(
+    pool: &PgPool,
+    title: &str,
+    content: &IssueContent,
+    audience: &Audience,
+    track_opens: bool,
+    track_clicks: bool,
+    publish_to_archive: bool,
+    user_id: Uuid,
+) -> Result<(Uuid, IssueSlug), sqlx::Error> {
+    let issue_id = Uuid::new_v4();
+    let mut transaction = pool.begin().await?;
+    let slug = IssueSlug::from_title(title);
+    let taken: Vec<String> = sqlx::query!(
+        r#"SELECT slug FROM newsletter_issues WHERE slug = $1 OR slug LIKE $1 || '-%'"#,
+        slug.as_ref()
+    )
+    .fetch_all(&mut *transaction)
+    .await?
+    .into_iter()
+    .map(|r| r.slug)
+    .collect();
+    let slug = slug.first_free(&taken);
+    sqlx::query!(
+        r#"
+        INSERT INTO newsletter_issues
+            (id, slug, title, text_content, html_content, markdown_content,
+             published_at, updated_at, published_by, segment, track_opens,
+             track_clicks, hidden_from_archive)
+        VALUES ($1, $2, $3, $4, $5, $6, now(), now(), $7, $8, $9, $10, $11)
+        "#,
+        issue_id,
+        slug.as_ref(),
+        title,
+        content.text,
+        content.html,
+        content.markdown,
+        user_id,
+        audience.segment.as_ref().map(|s| s.to_string()),
+        track_opens,
+        track_clicks,
+        !publish_to_archive,
+    )
+    .execute(&mut *transaction)
+    .await?;
+    sqlx::query!(
+        r#"
+        INSERT INTO issue_lists (issue_id, list_id)
+        SELECT $1, list_id FROM UNNEST($2::uuid[]) AS t(list_id)
+        ON CONFLICT DO NOTHING
+        "#,
+        issue_id,
+        &audience.list_ids,
+    )
+    .execute(&mut *transaction)
+    .await?;
+    transaction.commit().await?;
+    Ok((issue_id, slug))
+}
+
+#[tracing::instrument(name = "Record newsletter delivery", skip(pool))]
+async fn record_delivery(
+    pool: &PgPool,
+    issue_id: Uuid,
+    subscriber_id: Uuid,
+    status: DeliveryStatus,
+    message_id: Option<&str>,
+) -> Result<(), sqlx::Error> {
+    sqlx::query!(
+        r#"
+        INSERT INTO issue_deliveries (issue_id, subscriber_id, status, attempted_at, message_id)
+        VALUES ($1, $2, $3, now(), $4)
+        "#,
+        issue_id,
+        subscriber_id,
+        status.as_str(),
+        message_id,
+    )
+    .execute(pool)
+    .await?;
+    Ok(())
+}
@@ -0,0 +1,162 @@
+use super::post::PublishError;
+use crate::domain::{DigestFrequency, Segment, SubscriberEmail};
+use crate::lists::{get_default_list, get_list_by_slug};
+use anyhow::Context;
+use chrono::NaiveDate;
+use sqlx::{PgPool, Postgres, QueryBuilder};
+use uuid::Uuid;
+
+pub(super) struct ConfirmedSubscriber {
+    pub id: Uuid,
+    pub email: SubscriberEmail,
+    pub name: String,
+    pub digest_frequency: DigestFrequency,
+}
+
+/// Who an issue goes to: everyone confirmed on one of the lists who also
+/// matches the segment, if there is one, and hasn't paused delivery. Addresses
+/// that bounced or complained are never mailed again.
+#[derive(Debug)]
+pub(super) struct Audience {
+    pub list_ids: Vec<Uuid>,
+    pub segment: Option<Segment>,
+}
+
+impl Audience {
+    /// No slugs means the default list; a blank segment means everyone.
+    pub async fn resolve(
+        pool: &PgPool,
+        slugs: &[String],
+        segment: &str,
+    ) -> Result<Self, PublishError> {
+        let segment = match segment.trim() {
+            "" => None,
+            segment => Some(Segment::parse(segment).map_err(PublishError::InvalidSegment)?),
+        };
+        if slugs.is_empty() {
+            let list = get_default_list(pool).await?;
+            return Ok(Self {
+                list_ids: vec![list.id],
+                segment,
+            });
+        }
+        let mut list_ids = Vec::with_capacity(slugs.len());
+        for slug in slugs {
+            let list = get_list_by_slug(pool, slug)
+                .await
+                .context("Failed to look up the list")?
+                .ok_or_else(|| PublishError::UnknownList(slug.clone()))?;
+            list_ids.push(list.id);
+        }
+        Ok(Self { list_ids, segment })
+    }
+
+    /// Someone on several of the lists is only counted, and sent to, once.
+    fn query(&self, select: &str) -> QueryBuilder<'static, Postgres> {
+        let mut query = QueryBuilder::new(select);
+        query.push(
+            " FROM subscriptions s WHERE EXISTS (\
+                SELECT 1 FROM list_memberships m \
+                WHERE m.subscriber_id = s.id AND m.status = 'confirmed' AND m.list_id = ANY(",
+        );
+        query.push_bind(self.list_ids.clone());
+        query.push(
+            ")) AND (s.paused_until IS NULL OR s.paused_until <= now()) \
+            AND s.status NOT IN ('bounced', 'complained')",
+        );
+        if let Some(segment) = &self.segment {
+            query.push(" AND ");
+            push_segment(&mut query, segment);
+        }
+        query
+    }
+
+    #[tracing::instrument(name = "Count recipients", skip(self, pool))]
+    pub async fn count(&self, pool: &PgPool) -> Result<i64, anyhow::Error> {
+        self.query("SELECT count(*)")
+            .build_query_scalar()
+            .fetch_one(pool)
+            .await
+            .context("Failed to count recipients")
+    }
+
+    #[tracing::instrument(name = "Get confirmed subscribers", skip(self, pool))]
+    pub async fn subscribers(
+        &self,
+        pool: &PgPool,
+    ) -> Result<Vec<Result<ConfirmedSubscriber, anyhow::Error>>, anyhow::Error> {
+        #[derive(sqlx::FromRow)]
+        struct Row {
+            id: Uuid,
+            email: String,
+            name: String,
+            digest_frequency: String,
+        }
+        let rows: Vec<Row> = self
+            .query("SELECT s.id, s.email, s.name, s.digest_frequency")
+            .build_query_as()
+            .fetch_all(pool)
+            .await
+            .context("Failed to retrieve recipients")?;
+
+        let confirmed_subscribers = rows
+            .into_iter()
+            .map(|r| {
+                let email = SubscriberEmail::parse(r.email).map_err(anyhow::Error::msg)?;
+                let digest_frequency =
+                    DigestFrequency::parse(&r.digest_frequency).map_err(anyhow::Error::msg)?;
+                Ok(ConfirmedSubscriber {
+                    id: r.id,
+                    email,
+                    name: r.name,
+                    digest_frequency,
+                })
+            })
+            .collect();
+
+        Ok(confirmed_subscribers)
+    }
+}
+
+fn push_segment(query: &mut QueryBuilder<'static, Postgres>, segment: &Segment) {
+    let start_of = |d: &NaiveDate| d.and_hms_opt(0, 0, 0).unwrap().and_utc();
+    match segment {
+        Segment::Tag(tag) => {
+            query.push(
+                "EXISTS (SELECT 1 FROM subscriber_tags t \
+                WHERE t.subscriber_id = s.id AND t.tag = ",
+            );
+            query.push_bind(tag.as_ref().to_owned());
+            query.push(")");
+        }
+        Segment::Status(status) => {
+            query.push("s.status = ");
+            query.push_bind(status.as_str());
+        }
+        Segment::SubscribedSince(date) => {
+            query.push("s.subscribed_at >= ");
+            query.push_bind(start_of(date));
+        }
+        Segment::SubscribedBefore(date) => {
+            query.push("s.subscribed_at < ");
+            query.push_bind(start_of(date));
+        }
+        Segment::Not(inner) => {
+            query.push("NOT (");
+            push_segment(query, inner);
+            query.push(")");
+        }
+        Segment::And(lhs, rhs) | Segment::Or(lhs, rhs) => {
+            let operator = if matches!(segment, Segment::And(..)) {
+                " AND "
+            } else {
+                " OR "
+            };
+            query.push("(");
+            push_segment(query, lhs);
+            query.push(operator);
+            push_segment(query, rhs);
+            query.push(")");
+        }
+    }
+}
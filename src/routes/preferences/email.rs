@@ -0,0 +1,269 @@
+use super::{get_email, invalid_link, preferences_page, subscriber_id};
+use crate::audit::{AuditAction, AuditEvent};
+use crate::domain::SubscriberEmail;
+use crate::email_client::EmailClient;
+use crate::startup::{ApplicationBaseUrl, HmacSecret};
+use crate::utils::{e500, see_other};
+use actix_web::web;
+use actix_web::{HttpRequest, HttpResponse};
+use actix_web_flash_messages::FlashMessage;
+use anyhow::Context;
+use chrono::{Duration, Utc};
+use sqlx::PgPool;
+use uuid::Uuid;
+
+const TOKEN_PURPOSE: &str = "email-change";
+
+/// How long the link sent to the new address stays valid.
+const LINK_LIFETIME_HOURS: i64 = 24;
+
+#[derive(serde::Deserialize)]
+pub struct EmailChangeForm {
+    token: String,
+    email: String,
+}
+
+#[derive(serde::Deserialize)]
+pub struct ConfirmParameters {
+    token: String,
+}
+
+/// A pending change from `old_email` to `new_email`.
+///
+/// The old address is part of the token so the link stops working once the
+/// address has changed, whichever way it changed.
+#[derive(Debug, PartialEq, Eq)]
+struct EmailChange {
+    subscriber_id: Uuid,
+    old_email: String,
+    new_email: String,
+}
+
+impl EmailChange {
+    fn sign(&self, secret: &HmacSecret) -> String {
+        let expires_at = (Utc::now() + Duration::hours(LINK_LIFETIME_HOURS)).timestamp();
+        secret.sign(&format!(
+            "{}\n{}\n{}\n{}\n{}",
+            TOKEN_PURPOSE, expires_at, self.subscriber_id, self.old_email, self.new_email
+        ))
+    }
+
+    /// Returns the change if the token is genuine and unexpired.
+    fn verify(secret: &HmacSecret, token: &str) -> Option<Self> {
+        let message = secret.verify(token)?;
+        let mut parts = message.splitn(5, '\n');
+        let (purpose, expires_at) = (parts.next()?, parts.next()?);
+        let expires_at: i64 = expires_at.parse().ok()?;
+        if purpose != TOKEN_PURPOSE || expires_at <= Utc::now().timestamp() {
+            return None;
+        }
+        Some(Self {
+            subscriber_id: parts.next()?.parse().ok()?,
+            old_email: parts.next()?.to_owned(),
+            new_email: parts.next()?.to_owned(),
+        })
+    }
+}
+
+/// Emails a confirmation link to the new address; nothing changes until it
+/// is followed.
+#[tracing::instrument(
+    name = "Request an email change",
+    skip(form, pool, email_client, base_url, secret)
+)]
+pub async fn request_email_change(
+    form: web::Form<EmailChangeForm>,
+    pool: web::Data<PgPool>,
+    email_client: web::Data<EmailClient>,
+    base_url: web::Data<ApplicationBaseUrl>,
+    secret: web::Data<HmacSecret>,
+) -> Result<HttpResponse, actix_web::Error> {
+    let subscriber_id = subscriber_id(&pool, &secret, &form.token).await?;
+    let page = preferences_page(&form.token);
+    let new_email = match SubscriberEmail::parse(form.email.trim().to_owned()) {
+        Ok(email) => email,
+        Err(e) => {
+            FlashMessage::error(e).send();
+            return Ok(see_other(&page));
+        }
+    };
+    let old_email = get_email(&pool, subscriber_id)
+        .await
+        .map_err(e500)?
+        .ok_or_else(invalid_link)?;
+    if old_email == new_email.as_ref() {
+        FlashMessage::error("That is already your address").send();
+        return Ok(see_other(&page));
+    }
+
+    let change = EmailChange {
+        subscriber_id,
+        old_email,
+        new_email: new_email.as_ref().to_owned(),
+    };
+    send_change_confirmation(
+        &email_client,
+        &new_email,
+        &base_url.0,
+        &change.sign(&secret),
+    )
+    .await
+    .context("Failed to send email change confirmation")
+    .map_err(e500)?;
+    FlashMessage::info(format!(
+        "We've emailed a link to {}. Follow it to finish the change.",
+        new_email.as_ref()
+    ))
+    .send();
+    Ok(see_other(&page))
+}
+
+/// Swaps the address in place, so deliveries and list history stay with the
+/// subscriber, then lets the old address know.
+///
+/// Responds the same way when the new address already belongs to another
+/// subscriber, so the page can't be used to find out who is subscribed.
+#[tracing::instrument(
+    name = "Confirm an email change",
+    skip(parameters, pool, email_client, secret, request)
+)]
+pub async fn confirm_email_change(
+    parameters: web::Query<ConfirmParameters>,
+    pool: web::Data<PgPool>,
+    email_client: web::Data<EmailClient>,
+    secret: web::Data<HmacSecret>,
+    request: HttpRequest,
+) -> Result<HttpResponse, actix_web::Error> {
+    let change = EmailChange::verify(&secret, &parameters.token).ok_or_else(|| {
+        actix_web::error::ErrorUnauthorized("This link is invalid or has expired")
+    })?;
+    let changed = HttpResponse::Ok().body(format!(
+        "Your address is now {}. Use the link in your next newsletter to manage your preferences.",
+        change.new_email
+    ));
+
+    match change_email(&pool, &change).await.map_err(e500)? {
+        ChangeOutcome::Changed => {}
+        ChangeOutcome::AddressTaken => return Ok(changed),
+        ChangeOutcome::Stale => {
+            return Err(actix_web::error::ErrorUnauthorized(
+                "This link is invalid or has expired",
+            ))
+        }
+    }
+    AuditEvent::new(AuditAction::SubscriberEmailChanged, &request)
+        .target(change.subscriber_id.to_string())
+        .record(&pool)
+        .await
+        .map_err(e500)?;
+    if let Ok(old_email) = SubscriberEmail::parse(change.old_email.clone()) {
+        if let Err(e) = send_change_notice(&email_client, &old_email, &change.new_email).await {
+            tracing::warn!(error.cause_chain = ?e, "Failed to notify the old address");
+        }
+    }
+    Ok(changed)
+}
+
+enum ChangeOutcome {
+    Changed,
+    AddressTaken,
+    /// The subscriber is gone or their address has changed since the link
+    /// was sent.
+    Stale,
+}
+
+#[tracing::instrument(name = "Change subscriber email", skip(pool))]
+async fn change_email(pool: &PgPool, change: &EmailChange) -> Result<ChangeOutcome, anyhow::Error> {
+    let result = sqlx::query!(
+        r#"UPDATE subscriptions SET email = $3 WHERE id = $1 AND email = $2"#,
+        change.subscriber_id,
+        change.old_email,
+        change.new_email,
+    )
+    .execute(pool)
+    .await;
+    match result {
+        Ok(r) if r.rows_affected() == 0 => Ok(ChangeOutcome::Stale),
+        Ok(_) => Ok(ChangeOutcome::Changed),
+        Err(sqlx::Error::Database(e)) if e.is_unique_violation() => Ok(ChangeOutcome::AddressTaken),
+        Err(e) => Err(e).context("Failed to change email"),
+    }
+}
+
+#[tracing::instrument(
+    name = "Send an email change confirmation",
+    skip(email_client, base_url, token)
+)]
+async fn send_change_confirmation(
+    email_client: &EmailClient,
+    new_email: &SubscriberEmail,
+    base_url: &str,
+    token: &str,
+) -> Result<(), reqwest::Error> {
+    let link = format!("{}/preferences/email/confirm?token={}", base_url, token);
+    let text_body = format!(
+        "Visit {} to move your subscription to this address.\nThe link expires in 24 hours. If you didn't ask for this, ignore this email.",
+        link
+    );
+    let html_body = format!(
+        "Click <a href=\"{}\">here</a> to move your subscription to this address.<br />\
+        The link expires in 24 hours. If you didn't ask for this, ignore this email.",
+        link
+    );
+    email_client
+        .send_email(
+            new_email,
+            "Confirm your new address",
+            &html_body,
+            &text_body,
+        )
+        .await
+}
+
+#[tracing::instrument(name = "Send an email change notice", skip(email_client))]
+async fn send_change_notice(
+    email_client: &EmailClient,
+    old_email: &SubscriberEmail,
+    new_email: &str,
+) -> Result<(), reqwest::Error> {
+    let body = format!(
+        "Your subscription has moved to {}. You won't get any more emails at this address.",
+        new_email
+    );
+    email_client
+        .send_email(old_email, "Your subscription has moved", &body, &body)
+        .await
+}
+
+#[cfg(test)]
+mod tests {
+    use super::EmailChange;
+    use crate::startup::HmacSecret;
+    use claims::{assert_none, assert_some_eq};
+    use secrecy::Secret;
+    use uuid::Uuid;
+
+    #[test]
+    fn a_signed_change_verifies() {
+        let secret = HmacSecret(Secret::new("secret".into()));
+        let change = EmailChange {
+            subscriber_id: Uuid::new_v4(),
+            old_email: "old@example.com".into(),
+            new_email: "new@example.com".into(),
+        };
+        let token = change.sign(&secret);
+
+        assert_some_eq!(EmailChange::verify(&secret, &token), change);
+    }
+
+    #[test]
+    fn an_expired_change_is_rejected() {
+        let secret = HmacSecret(Secret::new("secret".into()));
+        let token = secret.sign(&format!(
+            "email-change\n0\n{}\nold@example.com\nnew@example.com",
+            Uuid::new_v4()
+        ));
+
+        assert_none!(EmailChange::verify(&secret, &token));
+    }
+}
@@ -0,0 +1,116 @@
+use super::{invalid_link, subscriber_id, PreferencesToken};
+use crate::domain::DigestFrequency;
+use crate::startup::HmacSecret;
+use crate::utils::{e500, render};
+use actix_web::web;
+use actix_web::HttpResponse;
+use actix_web_flash_messages::IncomingFlashMessages;
+use anyhow::Context;
+use askama::Template;
+use chrono::{DateTime, Utc};
+use sqlx::PgPool;
+use uuid::Uuid;
+
+struct Preferences {
+    email: String,
+    name: String,
+    status: String,
+    digest_frequency: String,
+    paused_until: Option<DateTime<Utc>>,
+}
+
+struct ListChoice {
+    slug: String,
+    name: String,
+    /// `None` if the subscriber has never been on the list.
+    status: Option<String>,
+}
+
+impl ListChoice {
+    fn is_subscribed(&self) -> bool {
+        self.status.as_deref() == Some("confirmed")
+    }
+
+    fn is_pending(&self) -> bool {
+        self.status.as_deref() == Some("pending_confirmation")
+    }
+}
+
+#[derive(Template)]
+#[template(path = "preferences.html")]
+struct PreferencesTemplate<'a> {
+    flash_messages: &'a IncomingFlashMessages,
+    token: &'a str,
+    preferences: Preferences,
+    lists: Vec<ListChoice>,
+    frequencies: &'a [DigestFrequency],
+}
+
+impl PreferencesTemplate<'_> {
+    fn is_chosen(&self, frequency: &DigestFrequency) -> bool {
+        frequency.as_str() == self.preferences.digest_frequency
+    }
+}
+
+pub async fn preferences_form(
+    flash_messages: IncomingFlashMessages,
+    parameters: web::Query<PreferencesToken>,
+    pool: web::Data<PgPool>,
+    secret: web::Data<HmacSecret>,
+) -> Result<HttpResponse, actix_web::Error> {
+    let subscriber_id = subscriber_id(&pool, &secret, &parameters.token).await?;
+    let preferences = get_preferences(&pool, subscriber_id)
+        .await
+        .map_err(e500)?
+        .ok_or_else(invalid_link)?;
+    let lists = get_list_choices(&pool, subscriber_id).await.map_err(e500)?;
+    render(&PreferencesTemplate {
+        flash_messages: &flash_messages,
+        token: &parameters.token,
+        preferences,
+        lists,
+        frequencies: &DigestFrequency::ALL,
+    })
+}
+
+#[tracing::instrument(name = "Get subscriber preferences", skip(pool))]
+async fn get_preferences(
+    pool: &PgPool,
+    subscriber_id: Uuid,
+) -> Result<Option<Preferences>, anyhow::Error> {
+    let row = sqlx::query_as!(
+        Preferences,
+        r#"
+        SELECT email, name, status, digest_frequency,
+            CASE WHEN paused_until > now() THEN paused_until END AS paused_until
+        FROM subscriptions
+        WHERE id = $1
+        "#,
+        subscriber_id
+    )
+    .fetch_optional(pool)
+    .await
+    .context("Failed to retrieve subscriber")?;
+    Ok(row)
+}
+
+#[tracing::instrument(name = "Get list choices", skip(pool))]
+async fn get_list_choices(
+    pool: &PgPool,
+    subscriber_id: Uuid,
+) -> Result<Vec<ListChoice>, anyhow::Error> {
+    let rows = sqlx::query_as!(
+        ListChoice,
+        r#"
+        SELECT l.slug, l.name, m.status AS "status?"
+        FROM lists l
+        LEFT JOIN list_memberships m ON m.list_id = l.id AND m.subscriber_id = $1
+        ORDER BY l.name, l.slug
+        "#,
+        subscriber_id
+    )
+    .fetch_all(pool)
+    .await
+    .context("Failed to retrieve lists")?;
+    Ok(rows)
+}
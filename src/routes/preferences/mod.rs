@@ -0,0 +1,123 @@
+mod email;
+mod get;
+mod post;
+
+pub use email::{confirm_email_change, request_email_change};
+pub use get::preferences_form;
+pub use post::{pause_delivery, unsubscribe, update_preferences};
+
+use crate::startup::HmacSecret;
+use crate::utils::e500;
+use anyhow::Context;
+use sqlx::PgPool;
+use uuid::Uuid;
+
+const TOKEN_PURPOSE: &str = "preferences";
+
+/// The link in every newsletter footer.
+///
+/// It doesn't expire: it has to keep working for as long as the email sits
+/// in someone's inbox. It is tied to the address it was sent to, though, so
+/// it stops working once the subscriber moves to another one.
+pub fn preferences_link(
+    base_url: &str,
+    secret: &HmacSecret,
+    subscriber_id: Uuid,
+    email: &str,
+) -> String {
+    format!(
+        "{}{}",
+        base_url,
+        preferences_page(&preferences_token(secret, subscriber_id, email))
+    )
+}
+
+fn preferences_token(secret: &HmacSecret, subscriber_id: Uuid, email: &str) -> String {
+    secret.sign(&format!("{}\n{}\n{}", TOKEN_PURPOSE, subscriber_id, email))
+}
+
+/// Returns the subscriber id and the address the token was issued for.
+fn verify_preferences_token(secret: &HmacSecret, token: &str) -> Option<(Uuid, String)> {
+    let message = secret.verify(token)?;
+    let mut parts = message.splitn(3, '\n');
+    if parts.next()? != TOKEN_PURPOSE {
+        return None;
+    }
+    let subscriber_id = parts.next()?.parse().ok()?;
+    Some((subscriber_id, parts.next()?.to_owned()))
+}
+
+fn invalid_link() -> actix_web::Error {
+    actix_web::error::ErrorUnauthorized("This link is invalid")
+}
+
+#[derive(serde::Deserialize)]
+pub struct PreferencesToken {
+    token: String,
+}
+
+/// Rejects tokens for subscribers that are gone or have changed address
+/// since the token was issued.
+async fn subscriber_id(
+    pool: &PgPool,
+    secret: &HmacSecret,
+    token: &str,
+) -> Result<Uuid, actix_web::Error> {
+    let (subscriber_id, email) =
+        verify_preferences_token(secret, token).ok_or_else(invalid_link)?;
+    match get_email(pool, subscriber_id).await.map_err(e500)? {
+        Some(current) if current == email => Ok(subscriber_id),
+        _ => Err(invalid_link()),
+    }
+}
+
+#[tracing::instrument(name = "Get subscriber email", skip(pool))]
+async fn get_email(pool: &PgPool, subscriber_id: Uuid) -> Result<Option<String>, anyhow::Error> {
+    let row = sqlx::query!(
+        r#"SELECT email FROM subscriptions WHERE id = $1"#,
+        subscriber_id
+    )
+    .fetch_optional(pool)
+    .await
+    .context("Failed to retrieve subscriber")?;
+    Ok(row.map(|r| r.email))
+}
+
+fn preferences_page(token: &str) -> String {
+    format!("/preferences?token={}", token)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{preferences_link, verify_preferences_token};
+    use crate::startup::HmacSecret;
+    use claims::{assert_none, assert_some_eq};
+    use secrecy::Secret;
+    use uuid::Uuid;
+
+    #[test]
+    fn a_footer_link_carries_the_subscriber_id_and_address() {
+        let secret = HmacSecret(Secret::new("secret".into()));
+        let subscriber_id = Uuid::new_v4();
+        let link = preferences_link(
+            "http://localhost",
+            &secret,
+            subscriber_id,
+            "ursula@example.com",
+        );
+        let token = link.split_once("token=").unwrap().1;
+
+        assert_some_eq!(
+            verify_preferences_token(&secret, token),
+            (subscriber_id, "ursula@example.com".to_owned())
+        );
+    }
+
+    #[test]
+    fn tokens_signed_for_other_purposes_are_rejected() {
+        let secret = HmacSecret(Secret::new("secret".into()));
+        let token = secret.sign(&format!("access\n{}\nursula@example.com", Uuid::new_v4()));
+
+        assert_none!(verify_preferences_token(&secret, &token));
+    }
+}
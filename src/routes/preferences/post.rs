@@ -0,0 +1,329 @@
+use super::{invalid_link, preferences_page, subscriber_id, PreferencesToken};
+use crate::audit::{AuditAction, AuditEvent};
+use crate::domain::{DigestFrequency, SubscriberName};
+use crate::lists::get_lists;
+use crate::outbound_webhooks::{enqueue_for_subscriber, WebhookEvent};
+use crate::startup::HmacSecret;
+use crate::utils::{e500, see_other};
+use actix_web::web;
+use actix_web::{HttpRequest, HttpResponse};
+use actix_web_flash_messages::FlashMessage;
+use actix_web_lab::extract::UrlEncodedForm;
+use anyhow::Context;
+use chrono::{Duration, Utc};
+use sqlx::{PgPool, Postgres, Transaction};
+use uuid::Uuid;
+
+const MAX_PAUSE_WEEKS: i64 = 52;
+
+#[derive(serde::Deserialize)]
+pub struct PreferencesForm {
+    token: String,
+    name: String,
+    /// Slugs of the lists to be on; any other confirmed membership is left.
+    #[serde(default)]
+    lists: Vec<String>,
+    /// Left as it is if not given.
+    digest_frequency: Option<String>,
+}
+
+#[derive(serde::Deserialize)]
+pub struct PauseForm {
+    token: String,
+    weeks: i64,
+}
+
+/// Lists picked here are joined straight away: the link was emailed to the
+/// subscriber, so there is nothing left to confirm.
+#[tracing::instrument(
+    name = "Update subscriber preferences",
+    skip(form, pool, secret, request)
+)]
+pub async fn update_preferences(
+    form: UrlEncodedForm<PreferencesForm>,
+    pool: web::Data<PgPool>,
+    secret: web::Data<HmacSecret>,
+    request: HttpRequest,
+) -> Result<HttpResponse, actix_web::Error> {
+    let PreferencesForm {
+        token,
+        name,
+        lists: slugs,
+        digest_frequency,
+    } = form.into_inner();
+    let subscriber_id = subscriber_id(&pool, &secret, &token).await?;
+    let name = match SubscriberName::parse(name) {
+        Ok(name) => name,
+        Err(e) => {
+            FlashMessage::error(e).send();
+            return Ok(see_other(&preferences_page(&token)));
+        }
+    };
+    let digest_frequency = match digest_frequency.as_deref().map(DigestFrequency::parse) {
+        Some(Ok(frequency)) => Some(frequency),
+        Some(Err(e)) => {
+            FlashMessage::error(e).send();
+            return Ok(see_other(&preferences_page(&token)));
+        }
+        None => None,
+    };
+    let lists = get_lists(&pool).await.map_err(e500)?;
+    let mut list_ids = Vec::with_capacity(slugs.len());
+    for slug in &slugs {
+        match lists.iter().find(|l| &l.slug == slug) {
+            Some(list) => list_ids.push(list.id),
+            None => {
+                FlashMessage::error(format!("There is no list called {}", slug)).send();
+                return Ok(see_other(&preferences_page(&token)));
+            }
+        }
+    }
+
+    if !save_preferences(&pool, subscriber_id, &name, &list_ids, digest_frequency)
+        .await
+        .map_err(e500)?
+    {
+        return Err(invalid_link());
+    }
+    AuditEvent::new(AuditAction::PreferencesUpdated, &request)
+        .target(subscriber_id.to_string())
+        .record(&pool)
+        .await
+        .map_err(e500)?;
+    FlashMessage::info("Your preferences have been saved.").send();
+    Ok(see_other(&preferences_page(&token)))
+}
+
+#[tracing::instrument(name = "Pause delivery", skip(form, pool, secret, request), fields(weeks = form.weeks))]
+pub async fn pause_delivery(
+    form: web::Form<PauseForm>,
+    pool: web::Data<PgPool>,
+    secret: web::Data<HmacSecret>,
+    request: HttpRequest,
+) -> Result<HttpResponse, actix_web::Error> {
+    let subscriber_id = subscriber_id(&pool, &secret, &form.token).await?;
+    if !(0..=MAX_PAUSE_WEEKS).contains(&form.weeks) {
+        FlashMessage::error(format!(
+            "You can pause for between 0 and {} weeks",
+            MAX_PAUSE_WEEKS
+        ))
+        .send();
+        return Ok(see_other(&preferences_page(&form.token)));
+    }
+    let paused_until = (form.weeks > 0).then(|| Utc::now() + Duration::weeks(form.weeks));
+
+    let updated = sqlx::query!(
+        r#"UPDATE subscriptions SET paused_until = $2 WHERE id = $1"#,
+        subscriber_id,
+        paused_until
+    )
+    .execute(pool.get_ref())
+    .await
+    .context("Failed to pause delivery")
+    .map_err(e500)?;
+    if updated.rows_affected() == 0 {
+        return Err(invalid_link());
+    }
+    AuditEvent::new(AuditAction::PreferencesUpdated, &request)
+        .target(subscriber_id.to_string())
+        .record(&pool)
+        .await
+        .map_err(e500)?;
+    match paused_until {
+        Some(until) => FlashMessage::info(format!(
+            "Delivery is paused until {}.",
+            until.format("%Y-%m-%d")
+        )),
+        None => FlashMessage::info("Delivery has resumed."),
+    }
+    .send();
+    Ok(see_other(&preferences_page(&form.token)))
+}
+
+#[tracing::instrument(
+    name = "Unsubscribe from preferences",
+    skip(form, pool, secret, request)
+)]
+pub async fn unsubscribe(
+    form: web::Form<PreferencesToken>,
+    pool: web::Data<PgPool>,
+    secret: web::Data<HmacSecret>,
+    request: HttpRequest,
+) -> Result<HttpResponse, actix_web::Error> {
+    let subscriber_id = subscriber_id(&pool, &secret, &form.token).await?;
+    if !unsubscribe_everywhere(&pool, subscriber_id)
+        .await
+        .map_err(e500)?
+    {
+        return Err(invalid_link());
+    }
+    AuditEvent::new(AuditAction::SubscriberUnsubscribed, &request)
+        .target(subscriber_id.to_string())
+        .record(&pool)
+        .await
+        .map_err(e500)?;
+    FlashMessage::info("You have been unsubscribed.").send();
+    Ok(see_other(&preferences_page(&form.token)))
+}
+
+/// Returns `false` if the subscriber no longer exists.
+///
+/// Addresses that bounced or complained keep their status whatever lists are
+/// picked, so they are never mailed again. Webhooks only hear about it when
+/// the subscriber's status actually changes.
+#[tracing::instrument(name = "Save subscriber preferences", skip(pool, name))]
+async fn save_preferences(
+    pool: &PgPool,
+    subscriber_id: Uuid,
+    name: &SubscriberName,
+    list_ids: &[Uuid],
+    digest_frequency: Option<DigestFrequency>,
+) -> Result<bool, anyhow::Error> {
+    let mut transaction = pool
+        .begin()
+        .await
+        .context("Failed to acquire a Postgres connection from the pool")?;
+    let status = if list_ids.is_empty() {
+        "unsubscribed"
+    } else {
+        "confirmed"
+    };
+    let Some(updated) = sqlx::query!(
+        r#"
+        UPDATE subscriptions s
+        SET name = $2,
+            status = CASE WHEN s.status IN ('bounced', 'complained') THEN s.status ELSE $3 END,
+            digest_frequency = coalesce($4, s.digest_frequency)
+        FROM (SELECT id, status FROM subscriptions WHERE id = $1 FOR UPDATE) old
+        WHERE s.id = old.id
+        RETURNING old.status AS previous, s.status AS current
+        "#,
+        subscriber_id,
+        name.as_ref(),
+        status,
+        digest_frequency.map(|f| f.as_str()),
+    )
+    .fetch_optional(&mut *transaction)
+    .await
+    .context("Failed to update subscriber")?
+    else {
+        return Ok(false);
+    };
+    sqlx::query!(
+        r#"
+        INSERT INTO list_memberships (list_id, subscriber_id, status, subscribed_at)
+        SELECT list_id, $1, 'confirmed', now() FROM UNNEST($2::uuid[]) AS t(list_id)
+        ON CONFLICT (list_id, subscriber_id) DO UPDATE
+            SET status = 'confirmed', subscribed_at = now()
+            WHERE list_memberships.status <> 'confirmed'
+        "#,
+        subscriber_id,
+        list_ids
+    )
+    .execute(&mut *transaction)
+    .await
+    .context("Failed to join lists")?;
+    sqlx::query!(
+        r#"
+        UPDATE list_memberships SET status = 'unsubscribed'
+        WHERE subscriber_id = $1 AND status = 'confirmed' AND NOT (list_id = ANY($2))
+        "#,
+        subscriber_id,
+        list_ids
+    )
+    .execute(&mut *transaction)
+    .await
+    .context("Failed to leave lists")?;
+    let event = match updated.current.as_str() {
+        "confirmed" => Some(WebhookEvent::SubscriberConfirmed),
+        "unsubscribed" => Some(WebhookEvent::SubscriberUnsubscribed),
+        _ => None,
+    };
+    if let Some(event) = event.filter(|_| updated.current != updated.previous) {
+        enqueue_for_subscriber(&mut transaction, event, subscriber_id).await?;
+        if event == WebhookEvent::SubscriberUnsubscribed {
+            attribute_unsubscribe(&mut transaction, subscriber_id).await?;
+        }
+    }
+    transaction
+        .commit()
+        .await
+        .context("Failed to commit transaction")?;
+    Ok(true)
+}
+
+/// Returns `false` if the subscriber no longer exists.
+#[tracing::instrument(name = "Unsubscribe from every list", skip(pool))]
+async fn unsubscribe_everywhere(pool: &PgPool, subscriber_id: Uuid) -> Result<bool, anyhow::Error> {
+    let mut transaction = pool
+        .begin()
+        .await
+        .context("Failed to acquire a Postgres connection from the pool")?;
+    let Some(updated) = sqlx::query!(
+        r#"
+        UPDATE subscriptions s
+        SET status = CASE WHEN s.status IN ('bounced', 'complained') THEN s.status
+                ELSE 'unsubscribed' END,
+            paused_until = NULL
+        FROM (SELECT id, status FROM subscriptions WHERE id = $1 FOR UPDATE) old
+        WHERE s.id = old.id
+        RETURNING old.status AS previous, s.status AS current
+        "#,
+        subscriber_id
+    )
+    .fetch_optional(&mut *transaction)
+    .await
+    .context("Failed to unsubscribe")?
+    else {
+        return Ok(false);
+    };
+    sqlx::query!(
+        r#"
+        UPDATE list_memberships SET status = 'unsubscribed'
+        WHERE subscriber_id = $1 AND status <> 'unsubscribed'
+        "#,
+        subscriber_id
+    )
+    .execute(&mut *transaction)
+    .await
+    .context("Failed to leave lists")?;
+    if updated.current != updated.previous {
+        enqueue_for_subscriber(
+            &mut transaction,
+            WebhookEvent::SubscriberUnsubscribed,
+            subscriber_id,
+        )
+        .await?;
+        attribute_unsubscribe(&mut transaction, subscriber_id).await?;
+    }
+    transaction
+        .commit()
+        .await
+        .context("Failed to commit transaction")?;
+    Ok(true)
+}
+
+/// Puts the unsubscribe down to the last issue the subscriber was sent, for
+/// that issue's report.
+#[tracing::instrument(name = "Attribute unsubscribe to an issue", skip(transaction))]
+async fn attribute_unsubscribe(
+    transaction: &mut Transaction<'_, Postgres>,
+    subscriber_id: Uuid,
+) -> Result<(), anyhow::Error> {
+    sqlx::query!(
+        r#"
+        INSERT INTO issue_unsubscribes (issue_id, subscriber_id, unsubscribed_at)
+        SELECT issue_id, subscriber_id, now()
+        FROM issue_deliveries
+        WHERE subscriber_id = $1
+        ORDER BY attempted_at DESC
+        LIMIT 1
+        ON CONFLICT DO NOTHING
+        "#,
+        subscriber_id
+    )
+    .execute(&mut **transaction)
+    .await
+    .context("Failed to attribute the unsubscribe to an issue")?;
+    Ok(())
+}
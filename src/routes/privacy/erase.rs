@@ -0,0 +1,123 @@
+use super::{invalid_link, verify_privacy_token, PrivacyRequest};
+use crate::audit::{AuditAction, AuditEvent};
+use crate::startup::HmacSecret;
+use crate::utils::{e500, render, see_other};
+use actix_web::web;
+use actix_web::{HttpRequest, HttpResponse};
+use actix_web_flash_messages::FlashMessage;
+use anyhow::Context;
+use askama::Template;
+use sqlx::PgPool;
+use uuid::Uuid;
+
+#[derive(serde::Deserialize)]
+pub struct Parameters {
+    token: String,
+}
+
+#[derive(Template)]
+#[template(path = "privacy_erase.html")]
+struct EraseTemplate<'a> {
+    token: &'a str,
+}
+
+/// Link scanners follow GET links, so erasure needs one more click.
+pub async fn erase_data_form(
+    parameters: web::Query<Parameters>,
+    secret: web::Data<HmacSecret>,
+) -> Result<HttpResponse, actix_web::Error> {
+    verify_privacy_token(&secret, PrivacyRequest::Erasure, &parameters.token)
+        .ok_or_else(invalid_link)?;
+    render(&EraseTemplate {
+        token: &parameters.token,
+    })
+}
+
+pub async fn erase_data(
+    form: web::Form<Parameters>,
+    pool: web::Data<PgPool>,
+    secret: web::Data<HmacSecret>,
+    request: HttpRequest,
+) -> Result<HttpResponse, actix_web::Error> {
+    let email = verify_privacy_token(&secret, PrivacyRequest::Erasure, &form.token)
+        .ok_or_else(invalid_link)?;
+    if let Some(subscriber_id) = anonymise_subscriber(&pool, &email).await.map_err(e500)? {
+        AuditEvent::new(AuditAction::SubscriberErased, &request)
+            .target(subscriber_id.to_string())
+            .record(&pool)
+            .await
+            .map_err(e500)?;
+    }
+    FlashMessage::info("Your data has been erased.").send();
+    Ok(see_other("/privacy"))
+}
+
+/// Strips every personal detail tied to `email`.
+///
+/// The subscription row itself survives with placeholder values, so the
+/// deliveries and events that point at it still add up.
+#[tracing::instrument(name = "Anonymise subscriber", skip(pool, email))]
+async fn anonymise_subscriber(pool: &PgPool, email: &str) -> Result<Option<Uuid>, anyhow::Error> {
+    let mut transaction = pool
+        .begin()
+        .await
+        .context("Failed to acquire a Postgres connection from the pool")?;
+    let row = sqlx::query!(
+        r#"
+        UPDATE subscriptions
+        SET email = id || '@erased.invalid', name = 'erased', status = 'unsubscribed'
+        WHERE email = $1
+        RETURNING id
+        "#,
+        email
+    )
+    .fetch_optional(&mut *transaction)
+    .await
+    .context("Failed to anonymise subscription")?;
+    if let Some(row) = &row {
+        sqlx::query!(
+            r#"DELETE FROM subscription_tokens WHERE subscriber_id = $1"#,
+            row.id
+        )
+        .execute(&mut *transaction)
+        .await
+        .context("Failed to delete subscription tokens")?;
+        sqlx::query!(
+            r#"UPDATE list_memberships SET status = 'unsubscribed' WHERE subscriber_id = $1"#,
+            row.id
+        )
+        .execute(&mut *transaction)
+        .await
+        .context("Failed to leave lists")?;
+        sqlx::query!(
+            r#"DELETE FROM subscriber_tags WHERE subscriber_id = $1"#,
+            row.id
+        )
+        .execute(&mut *transaction)
+        .await
+        .context("Failed to delete tags")?;
+        sqlx::query!(
+            r#"DELETE FROM subscriber_field_values WHERE subscriber_id = $1"#,
+            row.id
+        )
+        .execute(&mut *transaction)
+        .await
+        .context("Failed to delete custom field values")?;
+    }
+    sqlx::query!(
+        r#"
+        UPDATE subscriber_import_rejections
+        SET email = 'erased', name = 'erased'
+        WHERE email = $1
+        "#,
+        email
+    )
+    .execute(&mut *transaction)
+    .await
+    .context("Failed to anonymise import rejections")?;
+    transaction
+        .commit()
+        .await
+        .context("Failed to commit transaction")?;
+    Ok(row.map(|r| r.id))
+}
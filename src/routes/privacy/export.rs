@@ -0,0 +1,273 @@
+use super::{invalid_link, verify_privacy_token, PrivacyRequest};
+use crate::startup::HmacSecret;
+use crate::utils::e500;
+use actix_web::error::ErrorNotFound;
+use actix_web::http::header::{ContentDisposition, DispositionParam, DispositionType};
+use actix_web::web;
+use actix_web::HttpResponse;
+use anyhow::Context;
+use chrono::{DateTime, Utc};
+use sqlx::PgPool;
+use uuid::Uuid;
+
+#[derive(serde::Deserialize)]
+pub struct Parameters {
+    token: String,
+}
+
+/// Everything we hold that is tied to one email address.
+#[derive(serde::Serialize)]
+struct SubjectData {
+    subscription: Subscription,
+    subscription_tokens: Vec<String>,
+    lists: Vec<Membership>,
+    tags: Vec<String>,
+    fields: Vec<FieldValue>,
+    deliveries: Vec<Delivery>,
+    opens: Vec<Open>,
+    clicks: Vec<Click>,
+    events: Vec<Event>,
+    import_rejections: Vec<ImportRejection>,
+}
+
+#[derive(serde::Serialize)]
+struct Subscription {
+    id: Uuid,
+    email: String,
+    name: String,
+    subscribed_at: DateTime<Utc>,
+    confirmed_at: Option<DateTime<Utc>>,
+    status: String,
+    paused_until: Option<DateTime<Utc>>,
+}
+
+#[derive(serde::Serialize)]
+struct Membership {
+    list: String,
+    status: String,
+    subscribed_at: DateTime<Utc>,
+}
+
+#[derive(serde::Serialize)]
+struct FieldValue {
+    field: String,
+    value: String,
+}
+
+#[derive(serde::Serialize)]
+struct Delivery {
+    issue_id: Uuid,
+    title: String,
+    status: String,
+    attempted_at: DateTime<Utc>,
+}
+
+#[derive(serde::Serialize)]
+struct Open {
+    issue_id: Uuid,
+    title: String,
+    first_opened_at: DateTime<Utc>,
+    last_opened_at: DateTime<Utc>,
+    open_count: i32,
+}
+
+#[derive(serde::Serialize)]
+struct Click {
+    issue_id: Uuid,
+    title: String,
+    url: String,
+    clicked_at: DateTime<Utc>,
+}
+
+#[derive(serde::Serialize)]
+struct Event {
+    occurred_at: DateTime<Utc>,
+    action: String,
+}
+
+#[derive(serde::Serialize)]
+struct ImportRejection {
+    imported_at: DateTime<Utc>,
+    name: String,
+    reason: String,
+}
+
+pub async fn export_data(
+    parameters: web::Query<Parameters>,
+    pool: web::Data<PgPool>,
+    secret: web::Data<HmacSecret>,
+) -> Result<HttpResponse, actix_web::Error> {
+    let email = verify_privacy_token(&secret, PrivacyRequest::Access, &parameters.token)
+        .ok_or_else(invalid_link)?;
+    let data = get_subject_data(&pool, &email)
+        .await
+        .map_err(e500)?
+        .ok_or_else(|| ErrorNotFound("No subscription matches this link"))?;
+    Ok(HttpResponse::Ok()
+        .insert_header(ContentDisposition {
+            disposition: DispositionType::Attachment,
+            parameters: vec![DispositionParam::Filename("my-data.json".into())],
+        })
+        .json(data))
+}
+
+#[tracing::instrument(name = "Collect a data subject's data", skip(pool))]
+async fn get_subject_data(
+    pool: &PgPool,
+    email: &str,
+) -> Result<Option<SubjectData>, anyhow::Error> {
+    let Some(subscription) = sqlx::query_as!(
+        Subscription,
+        r#"
+        SELECT id, email, name, subscribed_at, confirmed_at, status, paused_until
+        FROM subscriptions
+        WHERE email = $1
+        "#,
+        email
+    )
+    .fetch_optional(pool)
+    .await
+    .context("Failed to retrieve subscription")?
+    else {
+        return Ok(None);
+    };
+
+    let subscription_tokens = sqlx::query!(
+        r#"SELECT subscription_token FROM subscription_tokens WHERE subscriber_id = $1"#,
+        subscription.id
+    )
+    .fetch_all(pool)
+    .await
+    .context("Failed to retrieve subscription tokens")?
+    .into_iter()
+    .map(|r| r.subscription_token)
+    .collect();
+
+    let lists = sqlx::query_as!(
+        Membership,
+        r#"
+        SELECT l.name AS list, m.status, m.subscribed_at
+        FROM list_memberships m
+        JOIN lists l ON l.id = m.list_id
+        WHERE m.subscriber_id = $1
+        ORDER BY m.subscribed_at
+        "#,
+        subscription.id
+    )
+    .fetch_all(pool)
+    .await
+    .context("Failed to retrieve list memberships")?;
+
+    let tags = sqlx::query!(
+        r#"SELECT tag FROM subscriber_tags WHERE subscriber_id = $1 ORDER BY tag"#,
+        subscription.id
+    )
+    .fetch_all(pool)
+    .await
+    .context("Failed to retrieve tags")?
+    .into_iter()
+    .map(|r| r.tag)
+    .collect();
+
+    let fields = sqlx::query_as!(
+        FieldValue,
+        r#"
+        SELECT f.label AS field, v.value
+        FROM subscriber_field_values v
+        JOIN custom_fields f ON f.id = v.field_id
+        WHERE v.subscriber_id = $1
+        ORDER BY f.created_at, f.key
+        "#,
+        subscription.id
+    )
+    .fetch_all(pool)
+    .await
+    .context("Failed to retrieve custom field values")?;
+
+    let deliveries = sqlx::query_as!(
+        Delivery,
+        r#"
+        SELECT d.issue_id, i.title, d.status, d.attempted_at
+        FROM issue_deliveries d
+        JOIN newsletter_issues i ON i.id = d.issue_id
+        WHERE d.subscriber_id = $1
+        ORDER BY d.attempted_at
+        "#,
+        subscription.id
+    )
+    .fetch_all(pool)
+    .await
+    .context("Failed to retrieve deliveries")?;
+
+    let opens = sqlx::query_as!(
+        Open,
+        r#"
+        SELECT o.issue_id, i.title, o.first_opened_at, o.last_opened_at, o.open_count
+        FROM issue_opens o
+        JOIN newsletter_issues i ON i.id = o.issue_id
+        WHERE o.subscriber_id = $1
+        ORDER BY o.first_opened_at
+        "#,
+        subscription.id
+    )
+    .fetch_all(pool)
+    .await
+    .context("Failed to retrieve opens")?;
+
+    let clicks = sqlx::query_as!(
+        Click,
+        r#"
+        SELECT c.issue_id, i.title, c.url, c.clicked_at
+        FROM issue_clicks c
+        JOIN newsletter_issues i ON i.id = c.issue_id
+        WHERE c.subscriber_id = $1
+        ORDER BY c.clicked_at, c.id
+        "#,
+        subscription.id
+    )
+    .fetch_all(pool)
+    .await
+    .context("Failed to retrieve clicks")?;
+
+    let events = sqlx::query_as!(
+        Event,
+        r#"
+        SELECT occurred_at, action
+        FROM audit_events
+        WHERE target = $1
+        ORDER BY occurred_at
+        "#,
+        subscription.id.to_string()
+    )
+    .fetch_all(pool)
+    .await
+    .context("Failed to retrieve events")?;
+
+    let import_rejections = sqlx::query_as!(
+        ImportRejection,
+        r#"
+        SELECT i.created_at AS imported_at, r.name, r.reason
+        FROM subscriber_import_rejections r
+        JOIN subscriber_imports i ON i.id = r.import_id
+        WHERE r.email = $1
+        ORDER BY i.created_at
+        "#,
+        email
+    )
+    .fetch_all(pool)
+    .await
+    .context("Failed to retrieve import rejections")?;
+
+    Ok(Some(SubjectData {
+        subscription,
+        subscription_tokens,
+        lists,
+        tags,
+        fields,
+        deliveries,
+        opens,
+        clicks,
+        events,
+        import_rejections,
+    }))
+}
@@ -0,0 +1,89 @@
+mod erase;
+mod export;
+mod request;
+
+pub use erase::{erase_data, erase_data_form};
+pub use export::export_data;
+pub use request::{privacy_form, request_privacy_link};
+
+use crate::startup::HmacSecret;
+use chrono::{Duration, Utc};
+
+/// How long an emailed privacy link stays valid.
+const LINK_LIFETIME_HOURS: i64 = 24;
+
+#[derive(Copy, Clone, Debug, PartialEq, Eq, serde::Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum PrivacyRequest {
+    Access,
+    Erasure,
+}
+
+impl PrivacyRequest {
+    fn as_str(&self) -> &'static str {
+        match self {
+            PrivacyRequest::Access => "access",
+            PrivacyRequest::Erasure => "erasure",
+        }
+    }
+}
+
+/// A signed token proving control of `email`, scoped to one kind of request.
+fn privacy_token(secret: &HmacSecret, request: PrivacyRequest, email: &str) -> String {
+    let expires_at = (Utc::now() + Duration::hours(LINK_LIFETIME_HOURS)).timestamp();
+    secret.sign(&format!("{}\n{}\n{}", request.as_str(), expires_at, email))
+}
+
+/// Returns the email address the token was issued for, if it is genuine,
+/// unexpired and meant for `request`.
+fn verify_privacy_token(
+    secret: &HmacSecret,
+    request: PrivacyRequest,
+    token: &str,
+) -> Option<String> {
+    let message = secret.verify(token)?;
+    let mut parts = message.splitn(3, '\n');
+    let (purpose, expires_at, email) = (parts.next()?, parts.next()?, parts.next()?);
+    let expires_at: i64 = expires_at.parse().ok()?;
+    (purpose == request.as_str() && expires_at > Utc::now().timestamp()).then(|| email.to_owned())
+}
+
+fn invalid_link() -> actix_web::Error {
+    actix_web::error::ErrorUnauthorized("This link is invalid or has expired")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{privacy_token, verify_privacy_token, PrivacyRequest};
+    use crate::startup::HmacSecret;
+    use claims::{assert_none, assert_some_eq};
+    use secrecy::Secret;
+
+    #[test]
+    fn a_token_only_works_for_the_request_it_was_issued_for() {
+        let secret = HmacSecret(Secret::new("secret".into()));
+        let token = privacy_token(&secret, PrivacyRequest::Access, "ursula@example.com");
+
+        assert_some_eq!(
+            verify_privacy_token(&secret, PrivacyRequest::Access, &token),
+            "ursula@example.com".to_owned()
+        );
+        assert_none!(verify_privacy_token(
+            &secret,
+            PrivacyRequest::Erasure,
+            &token
+        ));
+    }
+
+    #[test]
+    fn an_expired_token_is_rejected() {
+        let secret = HmacSecret(Secret::new("secret".into()));
+        let token = secret.sign("access\n0\nursula@example.com");
+
+        assert_none!(verify_privacy_token(
+            &secret,
+            PrivacyRequest::Access,
+            &token
+        ));
+    }
+}
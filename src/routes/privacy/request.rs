@@ -0,0 +1,108 @@
+use super::{privacy_token, PrivacyRequest};
+use crate::domain::SubscriberEmail;
+use crate::email_client::EmailClient;
+use crate::startup::{ApplicationBaseUrl, HmacSecret};
+use crate::utils::{e500, render, see_other};
+use actix_web::web;
+use actix_web::HttpResponse;
+use actix_web_flash_messages::{FlashMessage, IncomingFlashMessages};
+use anyhow::Context;
+use askama::Template;
+use sqlx::PgPool;
+
+#[derive(serde::Deserialize)]
+pub struct FormData {
+    email: String,
+    request: PrivacyRequest,
+}
+
+#[derive(Template)]
+#[template(path = "privacy.html")]
+struct PrivacyTemplate<'a> {
+    flash_messages: &'a IncomingFlashMessages,
+}
+
+pub async fn privacy_form(
+    flash_messages: IncomingFlashMessages,
+) -> Result<HttpResponse, actix_web::Error> {
+    render(&PrivacyTemplate {
+        flash_messages: &flash_messages,
+    })
+}
+
+/// Emails a signed link to the address in the form, if it is subscribed.
+///
+/// The response is the same either way, so the form can't be used to find
+/// out who is on the list.
+#[tracing::instrument(
+    name = "Request a privacy link",
+    skip(form, pool, email_client, base_url, secret),
+    fields(request = ?form.request)
+)]
+pub async fn request_privacy_link(
+    form: web::Form<FormData>,
+    pool: web::Data<PgPool>,
+    email_client: web::Data<EmailClient>,
+    base_url: web::Data<ApplicationBaseUrl>,
+    secret: web::Data<HmacSecret>,
+) -> Result<HttpResponse, actix_web::Error> {
+    let FormData { email, request } = form.0;
+    if let Ok(email) = SubscriberEmail::parse(email.trim().to_owned()) {
+        if is_subscribed(&pool, email.as_ref()).await.map_err(e500)? {
+            let token = privacy_token(&secret, request, email.as_ref());
+            send_privacy_link(&email_client, &email, request, &base_url.0, &token)
+                .await
+                .context("Failed to send privacy link")
+                .map_err(e500)?;
+        }
+    }
+    FlashMessage::info(
+        "If that address is subscribed, we have emailed it a link to complete your request.",
+    )
+    .send();
+    Ok(see_other("/privacy"))
+}
+
+#[tracing::instrument(name = "Check for a subscription", skip(pool))]
+async fn is_subscribed(pool: &PgPool, email: &str) -> Result<bool, anyhow::Error> {
+    let row = sqlx::query!(r#"SELECT id FROM subscriptions WHERE email = $1"#, email)
+        .fetch_optional(pool)
+        .await
+        .context("Failed to look up subscription")?;
+    Ok(row.is_some())
+}
+
+#[tracing::instrument(name = "Send a privacy link", skip(email_client, base_url, token))]
+async fn send_privacy_link(
+    email_client: &EmailClient,
+    email: &SubscriberEmail,
+    request: PrivacyRequest,
+    base_url: &str,
+    token: &str,
+) -> Result<(), reqwest::Error> {
+    let (subject, path, action) = match request {
+        PrivacyRequest::Access => (
+            "Your data export",
+            "/privacy/export",
+            "download a copy of the data we hold about you",
+        ),
+        PrivacyRequest::Erasure => (
+            "Erase your data",
+            "/privacy/erase",
+            "erase the data we hold about you",
+        ),
+    };
+    let link = format!("{}{}?token={}", base_url, path, token);
+    let text_body = format!(
+        "Visit {} to {}.\nThe link expires in 24 hours. If you didn't ask for this, ignore this email.",
+        link, action
+    );
+    let html_body = format!(
+        "Click <a href=\"{}\">here</a> to {}.<br />\
+        The link expires in 24 hours. If you didn't ask for this, ignore this email.",
+        link, action
+    );
+    email_client
+        .send_email(email, subject, &html_body, &text_body)
+        .await
+}
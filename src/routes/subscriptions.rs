@@ -1,6 +1,9 @@
 use crate::{
+    custom_fields::{get_custom_fields, insert_field_values},
     domain::{NewSubscriber, SubscriberEmail, SubscriberName},
     email_client::EmailClient,
+    lists::{get_default_list, get_list_by_slug, MailingList},
+    outbound_webhooks::{enqueue_for_subscriber, WebhookEvent},
     startup::ApplicationBaseUrl,
 };
 use actix_web::http::StatusCode;
@@ -10,6 +13,7 @@ use chrono::Utc;
 use rand::distributions::Alphanumeric;
 use rand::{thread_rng, Rng};
 use sqlx::{PgPool, Postgres, Transaction};
+use std::collections::HashMap;
 use unicode_segmentation::UnicodeSegmentation;
 use uuid::Uuid;
 
@@ -17,6 +21,9 @@ use uuid::Uuid;
 pub struct FormData {
     email: String,
     name: String,
+    /// Custom field values, submitted as `custom.<key>`.
+    #[serde(flatten)]
+    custom: HashMap<String, String>,
 }
 
 impl TryFrom<FormData> for NewSubscriber {
@@ -29,7 +36,7 @@ impl TryFrom<FormData> for NewSubscriber {
     }
 }
 
-fn generate_subscription_token() -> String {
+pub fn generate_subscription_token() -> String {
     let mut rng = thread_rng();
     std::iter::repeat_with(|| rng.sample(Alphanumeric))
         .map(char::from)
@@ -51,41 +58,161 @@ pub async fn subscribe(
     email_client: web::Data<EmailClient>,
     base_url: web::Data<ApplicationBaseUrl>,
 ) -> Result<HttpResponse, SubscribeError> {
-    let new_subscriber = form.0.try_into().map_err(SubscribeError::ValidationError)?;
-    let mut transaction = connection
+    let form = form.into_inner();
+    let custom_values = parse_custom_values(&connection, custom_form_values(&form.custom)).await?;
+    let new_subscriber = form.try_into().map_err(SubscribeError::ValidationError)?;
+    let list = get_default_list(&connection).await?;
+    add_to_list(
+        &connection,
+        &email_client,
+        &base_url.0,
+        &list,
+        new_subscriber,
+        &custom_values,
+    )
+    .await?;
+    Ok(HttpResponse::Ok().finish())
+}
+
+#[tracing::instrument(
+    name = "Adding a new subscriber to a list",
+    skip(form, connection, email_client, base_url),
+    fields(
+        subscriber_email = %form.email,
+        subscriber_name = %form.name
+    )
+)]
+pub async fn subscribe_to_list(
+    slug: web::Path<String>,
+    form: web::Form<FormData>,
+    connection: web::Data<PgPool>,
+    email_client: web::Data<EmailClient>,
+    base_url: web::Data<ApplicationBaseUrl>,
+) -> Result<HttpResponse, SubscribeError> {
+    let form = form.into_inner();
+    let custom_values = parse_custom_values(&connection, custom_form_values(&form.custom)).await?;
+    let new_subscriber = form.try_into().map_err(SubscribeError::ValidationError)?;
+    let list = get_list_by_slug(&connection, &slug)
+        .await
+        .context("Failed to look up the list")?
+        .ok_or(SubscribeError::UnknownList)?;
+    add_to_list(
+        &connection,
+        &email_client,
+        &base_url.0,
+        &list,
+        new_subscriber,
+        &custom_values,
+    )
+    .await?;
+    Ok(HttpResponse::Ok().finish())
+}
+
+/// The `custom.<key>` values of a submitted form, keyed by `<key>`.
+fn custom_form_values(submitted: &HashMap<String, String>) -> Vec<(&str, &str)> {
+    submitted
+        .iter()
+        .filter_map(|(name, value)| Some((name.strip_prefix("custom.")?, value.as_str())))
+        .collect()
+}
+
+/// Checks submitted custom field values against the field definitions.
+/// Blank values are skipped.
+pub(super) async fn parse_custom_values(
+    pool: &PgPool,
+    submitted: Vec<(&str, &str)>,
+) -> Result<Vec<(Uuid, String)>, SubscribeError> {
+    if submitted.is_empty() {
+        return Ok(Vec::new());
+    }
+    let fields = get_custom_fields(pool)
+        .await
+        .context("Failed to load custom fields")?;
+    let mut values = Vec::new();
+    for (key, value) in submitted {
+        let field = fields.iter().find(|f| f.key == key).ok_or_else(|| {
+            SubscribeError::ValidationError(format!("There is no field called {}", key))
+        })?;
+        if value.trim().is_empty() {
+            continue;
+        }
+        let value = field
+            .kind()
+            .map_err(anyhow::Error::msg)?
+            .normalise(value, &field.choices)
+            .map_err(|e| SubscribeError::ValidationError(format!("{}: {}", field.label, e)))?;
+        values.push((field.id, value));
+    }
+    Ok(values)
+}
+
+/// Nothing is sent if the address is already confirmed on `list`.
+pub(super) async fn add_to_list(
+    pool: &PgPool,
+    email_client: &EmailClient,
+    base_url: &str,
+    list: &MailingList,
+    new_subscriber: NewSubscriber,
+    custom_values: &[(Uuid, String)],
+) -> Result<(), anyhow::Error> {
+    let mut transaction = pool
         .begin()
         .await
         .context("Failed to acquire a Postgres connection from the pool")?;
-    let subscriber_id = insert_subscriber(&mut transaction, &new_subscriber)
+    let (subscriber_id, created) = insert_subscriber(&mut transaction, &new_subscriber)
         .await
         .context("Failed to insert new subscriber")?;
+    if created {
+        enqueue_for_subscriber(
+            &mut transaction,
+            WebhookEvent::SubscriberCreated,
+            subscriber_id,
+        )
+        .await?;
+    }
+    insert_field_values(&mut transaction, subscriber_id, custom_values)
+        .await
+        .context("Failed to store custom field values")?;
+    let needs_confirmation = insert_membership(&mut transaction, list.id, subscriber_id)
+        .await
+        .context("Failed to add subscriber to the list")?;
     let subscription_token = generate_subscription_token();
-
-    store_token(&mut transaction, subscriber_id, &subscription_token)
+    if needs_confirmation {
+        store_token(
+            &mut transaction,
+            subscriber_id,
+            list.id,
+            &subscription_token,
+        )
         .await
         .context("Failed to store confirmation token")?;
+    }
 
     transaction
         .commit()
         .await
         .context("Failed to commit transaction")?;
 
-    send_confirmation_email(
-        &email_client,
-        new_subscriber,
-        &base_url.0,
-        &subscription_token,
-    )
-    .await
-    .context("Failed to send confirmation email")?;
-
-    Ok(HttpResponse::Ok().finish())
+    if needs_confirmation {
+        send_confirmation_email(
+            email_client,
+            new_subscriber,
+            list,
+            base_url,
+            &subscription_token,
+        )
+        .await
+        .context("Failed to send confirmation email")?;
+    }
+    Ok(())
 }
 
 #[derive(thiserror::Error)]
 pub enum SubscribeError {
     #[error("{0}")]
     ValidationError(String),
+    #[error("There is no such list")]
+    UnknownList,
     #[error(transparent)]
     UnexpectedError(#[from] anyhow::Error),
 }
@@ -94,6 +221,7 @@ impl ResponseError for SubscribeError {
     fn status_code(&self) -> StatusCode {
         match self {
             SubscribeError::ValidationError(_) => StatusCode::BAD_REQUEST,
+            SubscribeError::UnknownList => StatusCode::NOT_FOUND,
             SubscribeError::UnexpectedError(_) => StatusCode::INTERNAL_SERVER_ERROR,
         }
     }
@@ -125,6 +253,7 @@ pub fn error_chain_fmt(
 pub async fn send_confirmation_email(
     email_client: &EmailClient,
     new_subscriber: NewSubscriber,
+    list: &MailingList,
     base_url: &str,
     subscription_token: &str,
 ) -> Result<(), reqwest::Error> {
@@ -133,19 +262,21 @@ pub async fn send_confirmation_email(
         base_url, subscription_token
     );
     let text_body = format!(
-        "Welcome to the newsletter!\nVisit {} to confirm your subscription.",
-        confirmation_link
+        "Welcome to {}!\nVisit {} to confirm your subscription.",
+        list.name, confirmation_link
     );
     let html_body = format!(
-        "Welcome to the newsletter!<br />\
+        "Welcome to {}!<br />\
                 Click <a href=\"{}\">here</a> to confirm your subscription.",
-        confirmation_link
+        list.name, confirmation_link
     );
     email_client
         .send_email(&new_subscriber.email, "Welcome!", &html_body, &text_body)
         .await
 }
 
+/// Returns the existing subscriber's id if the address is already known,
+/// along with whether the subscriber is new.
 #[tracing::instrument(
     name = "Saving new subscriber details in the db",
     skip(new_subscriber, transaction)
@@ -153,22 +284,47 @@ pub async fn send_confirmation_email(
 pub async fn insert_subscriber(
     transaction: &mut Transaction<'_, Postgres>,
     new_subscriber: &NewSubscriber,
-) -> Result<Uuid, sqlx::Error> {
-    let subscriber_id = Uuid::new_v4();
-    sqlx::query!(
+) -> Result<(Uuid, bool), sqlx::Error> {
+    let row = sqlx::query!(
         r#"
         INSERT INTO subscriptions (id, email, name, subscribed_at, status)
         VALUES ($1, $2, $3, $4, 'pending_confirmation')
+        ON CONFLICT (email) DO UPDATE SET email = EXCLUDED.email
+        RETURNING id, xmax = 0 AS "created!"
         "#,
-        subscriber_id,
+        Uuid::new_v4(),
         new_subscriber.email.as_ref(),
         new_subscriber.name.as_ref(),
         Utc::now()
     )
     // Need the ** for sqlx 0.7 due to some traid changes...
-    .execute(&mut **transaction)
+    .fetch_one(&mut **transaction)
+    .await?;
+    Ok((row.id, row.created))
+}
+
+/// Returns `false` if the subscriber is already confirmed on the list.
+#[tracing::instrument(name = "Add list membership", skip(transaction))]
+pub async fn insert_membership(
+    transaction: &mut Transaction<'_, Postgres>,
+    list_id: Uuid,
+    subscriber_id: Uuid,
+) -> Result<bool, sqlx::Error> {
+    let pending = sqlx::query!(
+        r#"
+        INSERT INTO list_memberships (list_id, subscriber_id, status, subscribed_at)
+        VALUES ($1, $2, 'pending_confirmation', now())
+        ON CONFLICT (list_id, subscriber_id) DO UPDATE
+            SET status = 'pending_confirmation', subscribed_at = now()
+            WHERE list_memberships.status <> 'confirmed'
+        RETURNING list_id
+        "#,
+        list_id,
+        subscriber_id,
+    )
+    .fetch_optional(&mut **transaction)
     .await?;
-    Ok(subscriber_id)
+    Ok(pending.is_some())
 }
 
 pub fn is_valid_name(s: &str) -> bool {
@@ -188,13 +344,15 @@ pub fn is_valid_name(s: &str) -> bool {
 pub async fn store_token(
     transaction: &mut Transaction<'_, Postgres>,
     subscriber_id: Uuid,
+    list_id: Uuid,
     subscription_token: &str,
 ) -> Result<(), StoreTokenError> {
     sqlx::query!(
-        r#"INSERT INTO subscription_tokens (subscription_token, subscriber_id)
-    VALUES ($1, $2)"#,
+        r#"INSERT INTO subscription_tokens (subscription_token, subscriber_id, list_id)
+    VALUES ($1, $2, $3)"#,
         subscription_token,
-        subscriber_id
+        subscriber_id,
+        list_id
     )
     .execute(&mut **transaction)
     .await
@@ -0,0 +1,218 @@
+use super::subscriptions::{add_to_list, parse_custom_values, SubscribeError};
+use crate::domain::{NewSubscriber, SubscriberEmail, SubscriberName};
+use crate::email_client::EmailClient;
+use crate::lists::{get_default_list, get_list_by_slug};
+use crate::routes::error_chain_fmt;
+use crate::startup::ApplicationBaseUrl;
+use actix_web::error::JsonPayloadError;
+use actix_web::http::StatusCode;
+use actix_web::{web, HttpMessage, HttpRequest, HttpResponse, ResponseError};
+use anyhow::Context;
+use sqlx::PgPool;
+use std::collections::HashMap;
+use tracing_actix_web::RequestId;
+
+#[derive(serde::Deserialize)]
+pub struct SubscriptionRequest {
+    #[serde(default)]
+    email: String,
+    #[serde(default)]
+    name: String,
+    /// The slug of the list to join; the default list if not given.
+    list: Option<String>,
+    /// Custom field values, by field key.
+    #[serde(default)]
+    custom: HashMap<String, String>,
+}
+
+/// Like `POST /subscriptions`, for signup widgets and other API clients.
+#[tracing::instrument(
+    name = "Adding a new subscriber through the API",
+    skip(body, pool, email_client, base_url),
+    fields(
+        subscriber_email = %body.email,
+        subscriber_name = %body.name
+    )
+)]
+pub async fn subscribe_json(
+    body: web::Json<SubscriptionRequest>,
+    pool: web::Data<PgPool>,
+    email_client: web::Data<EmailClient>,
+    base_url: web::Data<ApplicationBaseUrl>,
+    request_id: RequestId,
+) -> Result<HttpResponse, ApiError> {
+    let body = body.into_inner();
+    let new_subscriber = parse_subscriber(&body).map_err(|e| ApiError::new(e, request_id))?;
+    let custom = body
+        .custom
+        .iter()
+        .map(|(key, value)| (key.as_str(), value.as_str()))
+        .collect();
+    let custom_values = parse_custom_values(&pool, custom)
+        .await
+        .map_err(|e| ApiError::new(e.into(), request_id))?;
+    let list = match &body.list {
+        Some(slug) => get_list_by_slug(&pool, slug)
+            .await
+            .context("Failed to look up the list")
+            .map_err(|e| ApiError::new(e.into(), request_id))?
+            .ok_or_else(|| ApiError::new(ApiErrorKind::UnknownList, request_id))?,
+        None => get_default_list(&pool)
+            .await
+            .map_err(|e| ApiError::new(e.into(), request_id))?,
+    };
+    add_to_list(
+        &pool,
+        &email_client,
+        &base_url.0,
+        &list,
+        new_subscriber,
+        &custom_values,
+    )
+    .await
+    .map_err(|e| ApiError::new(e.into(), request_id))?;
+    Ok(HttpResponse::Accepted().json(serde_json::json!({
+        "request_id": request_id.to_string(),
+    })))
+}
+
+/// Checks every field, so clients can show all the problems at once.
+fn parse_subscriber(body: &SubscriptionRequest) -> Result<NewSubscriber, ApiErrorKind> {
+    let name = SubscriberName::parse(body.name.clone());
+    let email = SubscriberEmail::parse(body.email.clone());
+    match (name, email) {
+        (Ok(name), Ok(email)) => Ok(NewSubscriber { email, name }),
+        (name, email) => {
+            let mut fields = Vec::new();
+            if let Err(message) = email {
+                fields.push(FieldError {
+                    field: "email".into(),
+                    message,
+                });
+            }
+            if let Err(message) = name {
+                fields.push(FieldError {
+                    field: "name".into(),
+                    message,
+                });
+            }
+            Err(ApiErrorKind::Validation(fields))
+        }
+    }
+}
+
+/// Turns malformed request bodies into the API's error format.
+pub fn json_error_handler(error: JsonPayloadError, request: &HttpRequest) -> actix_web::Error {
+    let request_id = request.extensions().get::<RequestId>().copied();
+    ApiError {
+        request_id,
+        kind: ApiErrorKind::InvalidBody(error.to_string()),
+    }
+    .into()
+}
+
+#[derive(serde::Serialize, Debug)]
+pub struct FieldError {
+    field: String,
+    message: String,
+}
+
+#[derive(thiserror::Error)]
+pub enum ApiErrorKind {
+    #[error("The request body is invalid: {0}")]
+    InvalidBody(String),
+    #[error("Some fields are invalid")]
+    Validation(Vec<FieldError>),
+    #[error("There is no such list")]
+    UnknownList,
+    #[error("Something went wrong")]
+    Unexpected(#[source] anyhow::Error),
+}
+
+impl ApiErrorKind {
+    fn code(&self) -> &'static str {
+        match self {
+            ApiErrorKind::InvalidBody(_) => "invalid_body",
+            ApiErrorKind::Validation(_) => "validation_failed",
+            ApiErrorKind::UnknownList => "unknown_list",
+            ApiErrorKind::Unexpected(_) => "internal_error",
+        }
+    }
+}
+
+impl From<anyhow::Error> for ApiErrorKind {
+    fn from(e: anyhow::Error) -> Self {
+        ApiErrorKind::Unexpected(e)
+    }
+}
+
+impl From<SubscribeError> for ApiErrorKind {
+    fn from(e: SubscribeError) -> Self {
+        match e {
+            SubscribeError::ValidationError(message) => {
+                ApiErrorKind::Validation(vec![FieldError {
+                    field: "custom".into(),
+                    message,
+                }])
+            }
+            SubscribeError::UnknownList => ApiErrorKind::UnknownList,
+            SubscribeError::UnexpectedError(e) => ApiErrorKind::Unexpected(e),
+        }
+    }
+}
+
+impl std::fmt::Debug for ApiErrorKind {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        error_chain_fmt(self, f)
+    }
+}
+
+/// Responds with
+/// `{"error": {"code", "message", "fields"}, "request_id"}`,
+/// where `request_id` matches the request's logs.
+#[derive(thiserror::Error)]
+#[error("{kind}")]
+pub struct ApiError {
+    request_id: Option<RequestId>,
+    kind: ApiErrorKind,
+}
+
+impl ApiError {
+    fn new(kind: ApiErrorKind, request_id: RequestId) -> Self {
+        Self {
+            request_id: Some(request_id),
+            kind,
+        }
+    }
+}
+
+impl std::fmt::Debug for ApiError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        std::fmt::Debug::fmt(&self.kind, f)
+    }
+}
+
+impl ResponseError for ApiError {
+    fn status_code(&self) -> StatusCode {
+        match self.kind {
+            ApiErrorKind::InvalidBody(_) | ApiErrorKind::Validation(_) => StatusCode::BAD_REQUEST,
+            ApiErrorKind::UnknownList => StatusCode::NOT_FOUND,
+            ApiErrorKind::Unexpected(_) => StatusCode::INTERNAL_SERVER_ERROR,
+        }
+    }
+
+    fn error_response(&self) -> HttpResponse {
+        let fields = match &self.kind {
+            ApiErrorKind::Validation(fields) => fields.as_slice(),
+            _ => &[],
+        };
+        HttpResponse::build(self.status_code()).json(serde_json::json!({
+            "error": {
+                "code": self.kind.code(),
+                "message": self.kind.to_string(),
+                "fields": fields,
+            },
+            "request_id": self.request_id.map(|id| id.to_string()),
+        }))
+    }
+}
@@ -1,4 +1,6 @@
+use crate::outbound_webhooks::{enqueue_for_subscriber, WebhookEvent};
 use actix_web::{web, HttpResponse};
+use anyhow::Context;
 use sqlx::PgPool;
 use uuid::Uuid;
 
@@ -9,15 +11,16 @@ pub struct Parameters {
 
 #[tracing::instrument(name = "Confirm a pending subscriber", skip(parameters, pool))]
 pub async fn confirm(parameters: web::Query<Parameters>, pool: web::Data<PgPool>) -> HttpResponse {
-    let id = match get_subscriber_id_from_token(&pool, &parameters.subscription_token).await {
-        Ok(id) => id,
+    let membership = match get_membership_from_token(&pool, &parameters.subscription_token).await {
+        Ok(membership) => membership,
         Err(_) => return HttpResponse::InternalServerError().finish(),
     };
 
-    match id {
+    match membership {
         None => HttpResponse::Unauthorized().finish(),
-        Some(subscriber_id) => {
-            if confirm_subscriber(&pool, subscriber_id).await.is_err() {
+        Some(membership) => {
+            if let Err(e) = confirm_subscriber(&pool, membership).await {
+                tracing::error!(error.cause_chain = ?e, "Failed to confirm subscriber");
                 return HttpResponse::InternalServerError().finish();
             }
             HttpResponse::Ok().finish()
@@ -25,28 +28,64 @@ pub async fn confirm(parameters: web::Query<Parameters>, pool: web::Data<PgPool>
     }
 }
 
-#[tracing::instrument(name = "Mark subscriber as confirmed", skip(subscriber_id, pool))]
-pub async fn confirm_subscriber(pool: &PgPool, subscriber_id: Uuid) -> Result<(), sqlx::Error> {
+pub struct Membership {
+    pub subscriber_id: Uuid,
+    pub list_id: Uuid,
+}
+
+/// Confirms the subscriber on the token's list, and the address itself
+/// unless it has bounced or complained since.
+#[tracing::instrument(name = "Mark subscriber as confirmed", skip(membership, pool))]
+pub async fn confirm_subscriber(
+    pool: &PgPool,
+    membership: Membership,
+) -> Result<(), anyhow::Error> {
+    let mut transaction = pool
+        .begin()
+        .await
+        .context("Failed to acquire a Postgres connection from the pool")?;
     sqlx::query!(
-        r#"UPDATE subscriptions SET status = 'confirmed' WHERE id = $1"#,
-        subscriber_id,
+        r#"UPDATE list_memberships SET status = 'confirmed' WHERE list_id = $1 AND subscriber_id = $2"#,
+        membership.list_id,
+        membership.subscriber_id,
     )
-    .execute(pool)
+    .execute(&mut *transaction)
     .await
-    .map_err(|e| {
-        tracing::error!("Failed to execute query: {:?}", e);
-        e
-    })?;
+    .context("Failed to confirm list membership")?;
+    let confirmed = sqlx::query!(
+        r#"
+        UPDATE subscriptions
+        SET status = 'confirmed', confirmed_at = coalesce(confirmed_at, now())
+        WHERE id = $1 AND status NOT IN ('bounced', 'complained')
+        "#,
+        membership.subscriber_id,
+    )
+    .execute(&mut *transaction)
+    .await
+    .context("Failed to confirm subscriber")?;
+    if confirmed.rows_affected() > 0 {
+        enqueue_for_subscriber(
+            &mut transaction,
+            WebhookEvent::SubscriberConfirmed,
+            membership.subscriber_id,
+        )
+        .await?;
+    }
+    transaction
+        .commit()
+        .await
+        .context("Failed to commit transaction")?;
     Ok(())
 }
 
-#[tracing::instrument(name = "Get subscriber_id from token", skip(subscription_token, pool))]
-pub async fn get_subscriber_id_from_token(
+#[tracing::instrument(name = "Get membership from token", skip(subscription_token, pool))]
+pub async fn get_membership_from_token(
     pool: &PgPool,
     subscription_token: &str,
-) -> Result<Option<Uuid>, sqlx::Error> {
-    let result = sqlx::query!(
-        r#"SELECT subscriber_id FROM subscription_tokens
+) -> Result<Option<Membership>, sqlx::Error> {
+    let result = sqlx::query_as!(
+        Membership,
+        r#"SELECT subscriber_id, list_id FROM subscription_tokens
     WHERE subscription_token = $1"#,
         subscription_token,
     )
@@ -56,5 +95,5 @@ pub async fn get_subscriber_id_from_token(
         tracing::error!("Failed to execute query: {:?}", e);
         e
     })?;
-    Ok(result.map(|r| r.subscriber_id))
+    Ok(result)
 }
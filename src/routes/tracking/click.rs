@@ -0,0 +1,60 @@
+use super::verify_click_token;
+use crate::configuration::TrackingSettings;
+use crate::startup::HmacSecret;
+use crate::utils::e500;
+use actix_web::error::ErrorBadRequest;
+use actix_web::http::header::LOCATION;
+use actix_web::{web, HttpResponse};
+use anyhow::Context;
+use sqlx::PgPool;
+use uuid::Uuid;
+
+/// Records a click and sends the reader on to the link's destination.
+///
+/// Only destinations we signed are followed, so this can't be used to
+/// redirect anyone anywhere else.
+#[tracing::instrument(name = "Track a click", skip_all)]
+pub async fn track_click(
+    token: web::Path<String>,
+    pool: web::Data<PgPool>,
+    secret: web::Data<HmacSecret>,
+    tracking: web::Data<TrackingSettings>,
+) -> Result<HttpResponse, actix_web::Error> {
+    let (issue_id, subscriber_id, url) = verify_click_token(&secret, &token)
+        .ok_or_else(|| ErrorBadRequest("This link is invalid"))?;
+    if tracking.clicks {
+        record_click(&pool, issue_id, subscriber_id, &url)
+            .await
+            .map_err(e500)?;
+    }
+    Ok(HttpResponse::Found()
+        .insert_header((LOCATION, url))
+        .finish())
+}
+
+/// Every click is kept, so repeat clicks on a link can be told apart from
+/// clicks by different readers. Clicks in issues sent without tracking are
+/// ignored.
+#[tracing::instrument(name = "Record a click", skip(pool))]
+async fn record_click(
+    pool: &PgPool,
+    issue_id: Uuid,
+    subscriber_id: Uuid,
+    url: &str,
+) -> Result<(), anyhow::Error> {
+    sqlx::query!(
+        r#"
+        INSERT INTO issue_clicks (issue_id, subscriber_id, url, clicked_at)
+        SELECT i.id, s.id, $3, now()
+        FROM newsletter_issues i, subscriptions s
+        WHERE i.id = $1 AND i.track_clicks AND s.id = $2
+        "#,
+        issue_id,
+        subscriber_id,
+        url
+    )
+    .execute(pool)
+    .await
+    .context("Failed to record a click")?;
+    Ok(())
+}
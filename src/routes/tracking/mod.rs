@@ -0,0 +1,228 @@
+mod click;
+mod open;
+
+pub use click::track_click;
+pub use open::track_open;
+
+use crate::startup::HmacSecret;
+use uuid::Uuid;
+
+const OPEN_PURPOSE: &str = "open";
+const CLICK_PURPOSE: &str = "click";
+
+/// Adds the open-tracking pixel for one recipient to an email's HTML.
+///
+/// It goes just before `</body>` when there is one, so it stays inside the
+/// document in clients that care.
+pub fn with_open_pixel(
+    html: &str,
+    base_url: &str,
+    secret: &HmacSecret,
+    issue_id: Uuid,
+    subscriber_id: Uuid,
+) -> String {
+    let token = secret.sign(&format!(
+        "{}\n{}\n{}",
+        OPEN_PURPOSE, issue_id, subscriber_id
+    ));
+    let pixel = format!(
+        r#"<img src="{}/t/o/{}.gif" width="1" height="1" alt="" style="display:none">"#,
+        base_url, token
+    );
+    match html.rfind("</body>") {
+        Some(end) => format!("{}{}{}", &html[..end], pixel, &html[end..]),
+        None => format!("{}{}", html, pixel),
+    }
+}
+
+/// The issue and recipient an open-tracking token was issued for.
+fn verify_open_token(secret: &HmacSecret, token: &str) -> Option<(Uuid, Uuid)> {
+    let message = secret.verify(token)?;
+    let mut parts = message.split('\n');
+    if parts.next()? != OPEN_PURPOSE {
+        return None;
+    }
+    let issue_id = parts.next()?.parse().ok()?;
+    let subscriber_id = parts.next()?.parse().ok()?;
+    match parts.next() {
+        Some(_) => None,
+        None => Some((issue_id, subscriber_id)),
+    }
+}
+
+/// Points every web link in an email's HTML at the click tracker.
+///
+/// Expects HTML as `NewsletterHtml` serialises it. Links to anything but
+/// http(s), like `mailto:`, are left alone.
+pub fn with_tracked_links(
+    html: &str,
+    base_url: &str,
+    secret: &HmacSecret,
+    issue_id: Uuid,
+    subscriber_id: Uuid,
+) -> String {
+    let mut output = String::with_capacity(html.len());
+    let mut rest = html;
+    while let Some(start) = rest.find("<a ") {
+        let Some(end) = rest[start..].find('>').map(|end| start + end) else {
+            break;
+        };
+        output.push_str(&rest[..start]);
+        let tag = &rest[start..end];
+        match href_range(tag) {
+            Some((from, to)) if is_web_url(&unescape(&tag[from..to])) => {
+                let token = secret.sign(&format!(
+                    "{}\n{}\n{}\n{}",
+                    CLICK_PURPOSE,
+                    issue_id,
+                    subscriber_id,
+                    unescape(&tag[from..to])
+                ));
+                output.push_str(&tag[..from]);
+                output.push_str(&format!("{}/t/c/{}", base_url, token));
+                output.push_str(&tag[to..]);
+            }
+            _ => output.push_str(tag),
+        }
+        rest = &rest[end..];
+    }
+    output.push_str(rest);
+    output
+}
+
+/// Where the value of a tag's `href` attribute starts and ends.
+fn href_range(tag: &str) -> Option<(usize, usize)> {
+    let from = tag.find(" href=\"")? + " href=\"".len();
+    let to = tag[from..].find('"')? + from;
+    Some((from, to))
+}
+
+fn unescape(value: &str) -> String {
+    value
+        .replace("&quot;", "\"")
+        .replace("&#x27;", "'")
+        .replace("&lt;", "<")
+        .replace("&gt;", ">")
+        .replace("&amp;", "&")
+}
+
+fn is_web_url(url: &str) -> bool {
+    url.starts_with("https://") || url.starts_with("http://")
+}
+
+/// The issue, recipient and destination a click-tracking token was issued
+/// for.
+fn verify_click_token(secret: &HmacSecret, token: &str) -> Option<(Uuid, Uuid, String)> {
+    let message = secret.verify(token)?;
+    let mut parts = message.splitn(4, '\n');
+    if parts.next()? != CLICK_PURPOSE {
+        return None;
+    }
+    let issue_id = parts.next()?.parse().ok()?;
+    let subscriber_id = parts.next()?.parse().ok()?;
+    let url = parts.next()?;
+    is_web_url(url).then(|| (issue_id, subscriber_id, url.to_owned()))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{verify_click_token, verify_open_token, with_open_pixel, with_tracked_links};
+    use crate::startup::HmacSecret;
+    use claims::{assert_none, assert_some_eq};
+    use secrecy::Secret;
+    use uuid::Uuid;
+
+    fn secret() -> HmacSecret {
+        HmacSecret(Secret::new("secret".to_owned()))
+    }
+
+    fn token(html: &str) -> &str {
+        let start = html.find("/t/o/").unwrap() + "/t/o/".len();
+        let end = html[start..].find(".gif").unwrap() + start;
+        &html[start..end]
+    }
+
+    #[test]
+    fn the_pixel_identifies_the_issue_and_recipient() {
+        let (issue_id, subscriber_id) = (Uuid::new_v4(), Uuid::new_v4());
+        let html = with_open_pixel(
+            "<p>Hi</p>",
+            "https://x.test",
+            &secret(),
+            issue_id,
+            subscriber_id,
+        );
+        assert!(html.starts_with(r#"<p>Hi</p><img src="https://x.test/t/o/"#));
+        assert_some_eq!(
+            verify_open_token(&secret(), token(&html)),
+            (issue_id, subscriber_id)
+        );
+    }
+
+    #[test]
+    fn the_pixel_goes_inside_the_body() {
+        let html = with_open_pixel(
+            "<html><body><p>Hi</p></body></html>",
+            "https://x.test",
+            &secret(),
+            Uuid::new_v4(),
+            Uuid::new_v4(),
+        );
+        assert!(html.ends_with(r#"style="display:none"></body></html>"#));
+    }
+
+    #[test]
+    fn tokens_for_other_purposes_are_rejected() {
+        let token = secret().sign(&format!("preferences\n{}", Uuid::new_v4()));
+        assert_none!(verify_open_token(&secret(), &token));
+    }
+
+    #[test]
+    fn web_links_are_sent_through_the_tracker() {
+        let (issue_id, subscriber_id) = (Uuid::new_v4(), Uuid::new_v4());
+        let html = with_tracked_links(
+            r#"<p><a href="https://x.test/?a=1&amp;b=2" rel="noopener noreferrer">X</a></p>"#,
+            "https://news.test",
+            &secret(),
+            issue_id,
+            subscriber_id,
+        );
+        assert!(html.starts_with(r#"<p><a href="https://news.test/t/c/"#));
+        assert!(html.ends_with(r#"" rel="noopener noreferrer">X</a></p>"#));
+        let start = html.find("/t/c/").unwrap() + "/t/c/".len();
+        let end = html[start..].find('"').unwrap() + start;
+        assert_some_eq!(
+            verify_click_token(&secret(), &html[start..end]),
+            (
+                issue_id,
+                subscriber_id,
+                "https://x.test/?a=1&b=2".to_owned()
+            )
+        );
+    }
+
+    #[test]
+    fn other_links_are_left_alone() {
+        let html = r#"<a href="mailto:hi@x.test">Mail</a> <a>No link</a>"#;
+        assert_eq!(
+            with_tracked_links(
+                html,
+                "https://news.test",
+                &secret(),
+                Uuid::new_v4(),
+                Uuid::new_v4()
+            ),
+            html
+        );
+    }
+
+    #[test]
+    fn click_tokens_only_lead_to_web_urls() {
+        let token = secret().sign(&format!(
+            "click\n{}\n{}\njavascript:alert(1)",
+            Uuid::new_v4(),
+            Uuid::new_v4()
+        ));
+        assert_none!(verify_click_token(&secret(), &token));
+    }
+}
@@ -0,0 +1,72 @@
+use super::verify_open_token;
+use crate::configuration::TrackingSettings;
+use crate::startup::HmacSecret;
+use crate::utils::e500;
+use actix_web::http::header::{CacheControl, CacheDirective};
+use actix_web::{web, HttpResponse};
+use anyhow::Context;
+use sqlx::PgPool;
+use uuid::Uuid;
+
+/// A transparent 1x1 GIF.
+const PIXEL: &[u8] = &[
+    0x47, 0x49, 0x46, 0x38, 0x39, 0x61, 0x01, 0x00, 0x01, 0x00, 0x80, 0x00, 0x00, 0x00, 0x00, 0x00,
+    0xff, 0xff, 0xff, 0x21, 0xf9, 0x04, 0x01, 0x00, 0x00, 0x00, 0x00, 0x2c, 0x00, 0x00, 0x00, 0x00,
+    0x01, 0x00, 0x01, 0x00, 0x00, 0x02, 0x02, 0x44, 0x01, 0x00, 0x3b,
+];
+
+/// Records that a recipient opened an issue and serves the pixel.
+///
+/// The pixel is served whatever the token says, so a broken link never
+/// shows up as a broken image.
+#[tracing::instrument(name = "Track an open", skip_all)]
+pub async fn track_open(
+    token: web::Path<String>,
+    pool: web::Data<PgPool>,
+    secret: web::Data<HmacSecret>,
+    tracking: web::Data<TrackingSettings>,
+) -> Result<HttpResponse, actix_web::Error> {
+    match verify_open_token(&secret, &token) {
+        Some((issue_id, subscriber_id)) if tracking.opens => {
+            record_open(&pool, issue_id, subscriber_id)
+                .await
+                .map_err(e500)?;
+        }
+        Some(_) => {}
+        None => tracing::warn!("Ignoring an open with an invalid token"),
+    }
+    Ok(HttpResponse::Ok()
+        .content_type("image/gif")
+        .insert_header(CacheControl(vec![
+            CacheDirective::NoStore,
+            CacheDirective::Private,
+        ]))
+        .body(PIXEL))
+}
+
+/// Counts each recipient once per issue, however often their client loads
+/// the pixel. Opens of issues sent without tracking are ignored.
+#[tracing::instrument(name = "Record an open", skip(pool))]
+async fn record_open(
+    pool: &PgPool,
+    issue_id: Uuid,
+    subscriber_id: Uuid,
+) -> Result<(), anyhow::Error> {
+    sqlx::query!(
+        r#"
+        INSERT INTO issue_opens
+            (issue_id, subscriber_id, first_opened_at, last_opened_at, open_count)
+        SELECT i.id, s.id, now(), now(), 1
+        FROM newsletter_issues i, subscriptions s
+        WHERE i.id = $1 AND i.track_opens AND s.id = $2
+        ON CONFLICT (issue_id, subscriber_id) DO UPDATE
+        SET last_opened_at = now(), open_count = issue_opens.open_count + 1
+        "#,
+        issue_id,
+        subscriber_id
+    )
+    .execute(pool)
+    .await
+    .context("Failed to record an open")?;
+    Ok(())
+}
@@ -0,0 +1,191 @@
+mod postmark;
+
+use crate::audit::{AuditAction, AuditEvent};
+use crate::authentication::basic_authentication;
+use crate::configuration::WebhookSettings;
+use crate::domain::SubscriptionStatus;
+use crate::routes::error_chain_fmt;
+use actix_web::http::header::{self, HeaderValue};
+use actix_web::http::StatusCode;
+use actix_web::web::{self, Bytes};
+use actix_web::{HttpRequest, HttpResponse, ResponseError};
+use anyhow::Context;
+use secrecy::ExposeSecret;
+use sha2::{Digest, Sha256};
+use sqlx::PgPool;
+use subtle::ConstantTimeEq;
+use uuid::Uuid;
+
+/// Something an email provider tells us happened to a message we sent.
+#[derive(Debug)]
+pub struct EmailEvent {
+    pub email: String,
+    /// The provider's id for the message the event is about.
+    pub message_id: Option<String>,
+    pub kind: EmailEventKind,
+}
+
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum EmailEventKind {
+    HardBounce,
+    /// A temporary failure; the address is kept.
+    SoftBounce,
+    Complaint,
+}
+
+#[derive(thiserror::Error)]
+pub enum WebhookError {
+    #[error("Invalid webhook credentials")]
+    Unauthorized,
+    #[error("There is no email provider called {0}")]
+    UnknownProvider(String),
+    #[error("The webhook payload could not be parsed")]
+    InvalidPayload(#[source] serde_json::Error),
+    #[error(transparent)]
+    UnexpectedError(#[from] anyhow::Error),
+}
+
+impl std::fmt::Debug for WebhookError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        error_chain_fmt(self, f)
+    }
+}
+
+impl ResponseError for WebhookError {
+    fn error_response(&self) -> HttpResponse<actix_web::body::BoxBody> {
+        match self {
+            WebhookError::Unauthorized => {
+                let mut response = HttpResponse::new(StatusCode::UNAUTHORIZED);
+                response.headers_mut().insert(
+                    header::WWW_AUTHENTICATE,
+                    HeaderValue::from_static(r#"Basic realm="webhooks""#),
+                );
+                response
+            }
+            WebhookError::UnknownProvider(_) => HttpResponse::new(StatusCode::NOT_FOUND),
+            WebhookError::InvalidPayload(_) => HttpResponse::new(StatusCode::BAD_REQUEST),
+            WebhookError::UnexpectedError(_) => {
+                HttpResponse::new(StatusCode::INTERNAL_SERVER_ERROR)
+            }
+        }
+    }
+}
+
+/// Takes bounce and spam-complaint notifications from an email provider.
+///
+/// Hard bounces and complaints take the subscriber out of every future
+/// issue. A hard bounce of an issue also marks that delivery as bounced.
+#[tracing::instrument(name = "Receive an email webhook", skip(body, pool, settings, request))]
+pub async fn receive_email_webhook(
+    provider: web::Path<String>,
+    body: Bytes,
+    pool: web::Data<PgPool>,
+    settings: web::Data<WebhookSettings>,
+    request: HttpRequest,
+) -> Result<HttpResponse, WebhookError> {
+    if !is_authorised(&request, &settings) {
+        return Err(WebhookError::Unauthorized);
+    }
+    let event = match provider.as_str() {
+        "postmark" => postmark::parse(&body).map_err(WebhookError::InvalidPayload)?,
+        _ => return Err(WebhookError::UnknownProvider(provider.into_inner())),
+    };
+    let Some(event) = event else {
+        return Ok(HttpResponse::Ok().finish());
+    };
+    tracing::info!(kind = ?event.kind, "Received an email event");
+
+    if let (EmailEventKind::HardBounce, Some(message_id)) = (event.kind, &event.message_id) {
+        mark_delivery_bounced(&pool, message_id)
+            .await
+            .context("Failed to mark the delivery as bounced")?;
+    }
+    let (status, action) = match event.kind {
+        EmailEventKind::HardBounce => (SubscriptionStatus::Bounced, AuditAction::SubscriberBounced),
+        EmailEventKind::Complaint => (
+            SubscriptionStatus::Complained,
+            AuditAction::SubscriberComplained,
+        ),
+        EmailEventKind::SoftBounce => return Ok(HttpResponse::Ok().finish()),
+    };
+    if let Some(subscriber_id) = set_status(&pool, &event.email, status)
+        .await
+        .context("Failed to update the subscriber's status")?
+    {
+        AuditEvent::new(action, &request)
+            .target(subscriber_id.to_string())
+            .record(&pool)
+            .await?;
+    }
+    Ok(HttpResponse::Ok().finish())
+}
+
+fn is_authorised(request: &HttpRequest, settings: &WebhookSettings) -> bool {
+    let expected = settings.secret.expose_secret();
+    if let Some(secret) = request.headers().get("X-Webhook-Secret") {
+        return same_secret(secret.as_bytes(), expected.as_bytes());
+    }
+    match basic_authentication(request.headers()) {
+        Ok(credentials) => {
+            // Both are checked either way, so a wrong username takes as
+            // long to reject as a wrong password.
+            let username = same_secret(
+                credentials.username.as_bytes(),
+                settings.username.as_bytes(),
+            );
+            let password = same_secret(
+                credentials.password.expose_secret().as_bytes(),
+                expected.as_bytes(),
+            );
+            username & password
+        }
+        Err(_) => false,
+    }
+}
+
+/// Compares fixed-length digests in constant time, so how long the
+/// comparison takes says nothing about the secret or its length.
+fn same_secret(given: &[u8], expected: &[u8]) -> bool {
+    Sha256::digest(given)
+        .as_slice()
+        .ct_eq(Sha256::digest(expected).as_slice())
+        .into()
+}
+
+/// A complaint wins over a bounce, so a later bounce doesn't hide that the
+/// subscriber reported us.
+#[tracing::instrument(name = "Set status from an email event", skip(pool, email))]
+async fn set_status(
+    pool: &PgPool,
+    email: &str,
+    status: SubscriptionStatus,
+) -> Result<Option<Uuid>, sqlx::Error> {
+    let row = sqlx::query!(
+        r#"
+        UPDATE subscriptions SET status = $2
+        WHERE email = $1 AND status <> 'complained'
+        RETURNING id
+        "#,
+        email,
+        status.as_str()
+    )
+    .fetch_optional(pool)
+    .await?;
+    Ok(row.map(|r| r.id))
+}
+
+/// Bounces of messages that weren't issues, like confirmation emails,
+/// match no delivery and change nothing.
+#[tracing::instrument(name = "Mark delivery bounced", skip(pool))]
+async fn mark_delivery_bounced(pool: &PgPool, message_id: &str) -> Result<(), sqlx::Error> {
+    sqlx::query!(
+        r#"
+        UPDATE issue_deliveries SET status = 'bounced'
+        WHERE message_id = $1 AND status = 'delivered'
+        "#,
+        message_id
+    )
+    .execute(pool)
+    .await?;
+    Ok(())
+}
@@ -0,0 +1,100 @@
+//! Postmark's bounce and spam-complaint webhooks.
+use super::{EmailEvent, EmailEventKind};
+
+#[derive(serde::Deserialize)]
+#[serde(rename_all = "PascalCase")]
+struct Payload {
+    record_type: String,
+    #[serde(default)]
+    r#type: String,
+    #[serde(default)]
+    email: String,
+    #[serde(rename = "MessageID")]
+    message_id: Option<String>,
+}
+
+/// Bounce types after which mail to the address will never arrive.
+const HARD_BOUNCES: [&str; 2] = ["HardBounce", "BadEmailAddress"];
+
+/// `None` for records we don't act on, like deliveries and opens.
+pub fn parse(body: &[u8]) -> Result<Option<EmailEvent>, serde_json::Error> {
+    let payload: Payload = serde_json::from_slice(body)?;
+    let kind = match payload.record_type.as_str() {
+        "Bounce" if HARD_BOUNCES.contains(&payload.r#type.as_str()) => EmailEventKind::HardBounce,
+        "Bounce" => EmailEventKind::SoftBounce,
+        "SpamComplaint" => EmailEventKind::Complaint,
+        _ => return Ok(None),
+    };
+    Ok(Some(EmailEvent {
+        email: payload.email,
+        message_id: payload.message_id,
+        kind,
+    }))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::parse;
+    use crate::routes::webhooks::EmailEventKind;
+    use claims::{assert_err, assert_none};
+
+    fn kind(body: serde_json::Value) -> EmailEventKind {
+        parse(body.to_string().as_bytes()).unwrap().unwrap().kind
+    }
+
+    #[test]
+    fn hard_bounces_are_told_apart_from_soft_ones() {
+        let bounce = |t| {
+            serde_json::json!({
+                "RecordType": "Bounce",
+                "Type": t,
+                "TypeCode": 1,
+                "Email": "ursula@example.com",
+            })
+        };
+        assert_eq!(kind(bounce("HardBounce")), EmailEventKind::HardBounce);
+        assert_eq!(kind(bounce("BadEmailAddress")), EmailEventKind::HardBounce);
+        assert_eq!(kind(bounce("SoftBounce")), EmailEventKind::SoftBounce);
+        assert_eq!(kind(bounce("Transient")), EmailEventKind::SoftBounce);
+    }
+
+    #[test]
+    fn the_bounced_message_is_identified() {
+        let body = serde_json::json!({
+            "RecordType": "Bounce",
+            "Type": "HardBounce",
+            "MessageID": "b7bc2f4a",
+            "Email": "ursula@example.com",
+        });
+        let event = parse(body.to_string().as_bytes()).unwrap().unwrap();
+        assert_eq!(event.message_id.as_deref(), Some("b7bc2f4a"));
+    }
+
+    #[test]
+    fn spam_complaints_are_recognised() {
+        let event = parse(
+            serde_json::json!({
+                "RecordType": "SpamComplaint",
+                "Type": "SpamComplaint",
+                "Email": "ursula@example.com",
+            })
+            .to_string()
+            .as_bytes(),
+        )
+        .unwrap()
+        .unwrap();
+        assert_eq!(event.kind, EmailEventKind::Complaint);
+        assert_eq!(event.email, "ursula@example.com");
+    }
+
+    #[test]
+    fn other_records_are_ignored() {
+        let body = serde_json::json!({"RecordType": "Delivery", "Recipient": "ursula@example.com"});
+        assert_none!(parse(body.to_string().as_bytes()).unwrap());
+    }
+
+    #[test]
+    fn malformed_payloads_are_rejected() {
+        assert_err!(parse(b"not json"));
+    }
+}
@@ -1,9 +1,11 @@
+use crate::authentication::OidcLoginState;
 use actix_session::SessionExt;
 use actix_session::{Session, SessionGetError, SessionInsertError};
 use actix_web::dev::Payload;
 use actix_web::{FromRequest, HttpRequest};
 use std::future::{ready, Ready};
 use uuid::Uuid;
+use webauthn_rs::prelude::{PasskeyAuthentication, PasskeyRegistration};
 
 pub struct TypedSession(Session);
 
@@ -19,6 +21,10 @@ impl FromRequest for TypedSession {
 
 impl TypedSession {
     const USER_ID_KEY: &'static str = "user_id";
+    const OIDC_LOGIN_KEY: &'static str = "oidc_login";
+    const PENDING_USER_ID_KEY: &'static str = "pending_user_id";
+    const PASSKEY_REGISTRATION_KEY: &'static str = "passkey_registration";
+    const PASSKEY_AUTHENTICATION_KEY: &'static str = "passkey_authentication";
 
     pub fn log_out(self) {
         self.0.purge()
@@ -35,4 +41,62 @@ impl TypedSession {
     pub fn get_user_id(&self) -> Result<Option<Uuid>, SessionGetError> {
         self.0.get(Self::USER_ID_KEY)
     }
+
+    pub fn insert_oidc_login(&self, state: OidcLoginState) -> Result<(), SessionInsertError> {
+        self.0.insert(Self::OIDC_LOGIN_KEY, state)
+    }
+
+    /// Returns the pending OIDC login, if any, and clears it so that it
+    /// cannot be replayed.
+    pub fn take_oidc_login(&self) -> Result<Option<OidcLoginState>, SessionGetError> {
+        let state = self.0.get(Self::OIDC_LOGIN_KEY)?;
+        self.0.remove(Self::OIDC_LOGIN_KEY);
+        Ok(state)
+    }
+
+    /// A user who passed the password check but still has to present a
+    /// passkey as their second factor.
+    pub fn insert_pending_user_id(&self, user_id: Uuid) -> Result<(), SessionInsertError> {
+        self.0.insert(Self::PENDING_USER_ID_KEY, user_id)
+    }
+
+    pub fn get_pending_user_id(&self) -> Result<Option<Uuid>, SessionGetError> {
+        self.0.get(Self::PENDING_USER_ID_KEY)
+    }
+
+    pub fn clear_pending_user_id(&self) {
+        self.0.remove(Self::PENDING_USER_ID_KEY);
+    }
+
+    pub fn insert_passkey_registration(
+        &self,
+        state: PasskeyRegistration,
+    ) -> Result<(), SessionInsertError> {
+        self.0.insert(Self::PASSKEY_REGISTRATION_KEY, state)
+    }
+
+    pub fn take_passkey_registration(
+        &self,
+    ) -> Result<Option<PasskeyRegistration>, SessionGetError> {
+        let state = self.0.get(Self::PASSKEY_REGISTRATION_KEY)?;
+        self.0.remove(Self::PASSKEY_REGISTRATION_KEY);
+        Ok(state)
+    }
+
+    pub fn insert_passkey_authentication(
+        &self,
+        user_id: Uuid,
+        state: PasskeyAuthentication,
+    ) -> Result<(), SessionInsertError> {
+        self.0
+            .insert(Self::PASSKEY_AUTHENTICATION_KEY, (user_id, state))
+    }
+
+    pub fn take_passkey_authentication(
+        &self,
+    ) -> Result<Option<(Uuid, PasskeyAuthentication)>, SessionGetError> {
+        let state = self.0.get(Self::PASSKEY_AUTHENTICATION_KEY)?;
+        self.0.remove(Self::PASSKEY_AUTHENTICATION_KEY);
+        Ok(state)
+    }
 }
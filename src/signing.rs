@@ -0,0 +1,71 @@
+use crate::startup::HmacSecret;
+use base64::engine::general_purpose::URL_SAFE_NO_PAD;
+use base64::Engine;
+use hmac::{Hmac, Mac};
+use secrecy::ExposeSecret;
+use sha2::Sha256;
+
+type HmacSha256 = Hmac<Sha256>;
+
+impl HmacSecret {
+    /// Produces a URL-safe token carrying `message` and its HMAC-SHA256 tag.
+    pub fn sign(&self, message: &str) -> String {
+        format!(
+            "{}.{}",
+            URL_SAFE_NO_PAD.encode(message),
+            hex::encode(self.mac(message.as_bytes()).finalize().into_bytes())
+        )
+    }
+
+    /// Returns the signed message if the token's tag is valid.
+    pub fn verify(&self, token: &str) -> Option<String> {
+        let (message, tag) = token.split_once('.')?;
+        let message = URL_SAFE_NO_PAD.decode(message).ok()?;
+        let tag = hex::decode(tag).ok()?;
+        self.mac(&message).verify_slice(&tag).ok()?;
+        String::from_utf8(message).ok()
+    }
+
+    fn mac(&self, message: &[u8]) -> HmacSha256 {
+        let mut mac = HmacSha256::new_from_slice(self.0.expose_secret().as_bytes())
+            .expect("HMAC accepts keys of any length");
+        mac.update(message);
+        mac
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::startup::HmacSecret;
+    use claims::{assert_none, assert_some_eq};
+    use secrecy::Secret;
+
+    fn secret(s: &str) -> HmacSecret {
+        HmacSecret(Secret::new(s.to_owned()))
+    }
+
+    #[test]
+    fn a_signed_message_verifies() {
+        let secret = secret("secret");
+        let token = secret.sign("hello:world");
+        assert_some_eq!(secret.verify(&token), "hello:world".to_owned());
+    }
+
+    #[test]
+    fn a_token_signed_with_another_secret_is_rejected() {
+        let token = secret("other").sign("hello");
+        assert_none!(secret("secret").verify(&token));
+    }
+
+    #[test]
+    fn a_tampered_message_is_rejected() {
+        let secret = secret("secret");
+        let tag = secret.sign("hello").split_once('.').unwrap().1.to_owned();
+        let forged = format!(
+            "{}.{}",
+            secret.sign("goodbye").split_once('.').unwrap().0,
+            tag
+        );
+        assert_none!(secret.verify(&forged));
+    }
+}
@@ -1,11 +1,21 @@
-use crate::authentication::reject_anonymous_users;
+use crate::authentication::{
+    build_webauthn, reject_anonymous_users, reject_unauthenticated_api_requests, OidcClient,
+    PasswordPolicy,
+};
+use crate::configuration::CorsSettings;
 use crate::configuration::DatabaseSettings;
+use crate::configuration::OutboundWebhookSettings;
 use crate::configuration::Settings;
+use crate::configuration::TrackingSettings;
+use crate::configuration::WebhookSettings;
 use crate::email_client::EmailClient;
+use crate::email_layout::EmailLayout;
 use crate::routes::*;
+use actix_cors::Cors;
 use actix_session::storage::RedisSessionStore;
 use actix_session::SessionMiddleware;
 use actix_web::cookie::Key;
+use actix_web::http::header::CONTENT_TYPE;
 use actix_web::{dev::Server, web, App, HttpServer};
 use actix_web_flash_messages::storage::CookieMessageStore;
 use actix_web_flash_messages::FlashMessagesFramework;
@@ -16,6 +26,7 @@ use sqlx::postgres::PgPoolOptions;
 use sqlx::PgPool;
 use std::net::TcpListener;
 use tracing_actix_web::TracingLogger;
+use webauthn_rs::Webauthn;
 
 pub struct Application {
     port: u16,
@@ -26,17 +37,15 @@ impl Application {
     pub async fn build(configuration: Settings) -> Result<Self, anyhow::Error> {
         let connection = get_connection_pool(&configuration.database);
 
-        let sender_email = configuration
-            .email_client
-            .sender()
-            .expect("Invalid Sender Email");
+        let email_client = configuration.email_client.client();
 
-        let email_client = EmailClient::new(
-            configuration.email_client.base_url,
-            sender_email,
-            configuration.email_client.authorization_token,
+        let oidc_client = OidcClient::new(
+            &configuration.application.base_url,
+            configuration.oidc_providers,
         );
 
+        let webauthn = build_webauthn(&configuration.webauthn)?;
+
         let address = format!(
             "{}:{}",
             configuration.application.host, configuration.application.port
@@ -48,9 +57,17 @@ impl Application {
             listener,
             connection,
             email_client,
+            oidc_client,
+            webauthn,
             configuration.application.base_url,
             configuration.application.hmac_secret,
             configuration.redis_uri,
+            configuration.password_policy,
+            configuration.email_layout,
+            configuration.tracking,
+            configuration.webhooks,
+            configuration.outbound_webhooks,
+            configuration.cors,
         )
         .await?;
 
@@ -74,17 +91,47 @@ pub fn get_connection_pool(configuration: &DatabaseSettings) -> PgPool {
 
 pub struct ApplicationBaseUrl(pub String);
 
+fn api_cors(settings: &CorsSettings) -> Cors {
+    let cors = Cors::default()
+        .allowed_methods(["POST"])
+        .allowed_header(CONTENT_TYPE)
+        .max_age(3600);
+    if settings.allowed_origins.iter().any(|o| o == "*") {
+        return cors.allow_any_origin();
+    }
+    settings
+        .allowed_origins
+        .iter()
+        .fold(cors, |cors, origin| cors.allowed_origin(origin))
+}
+
+#[allow(clippy::too_many_arguments)]
 async fn run(
     listener: TcpListener,
     connection: PgPool,
     email_client: EmailClient,
+    oidc_client: OidcClient,
+    webauthn: Webauthn,
     base_url: String,
     hmac_secret: Secret<String>,
     redis_uri: Secret<String>,
+    password_policy: PasswordPolicy,
+    email_layout: EmailLayout,
+    tracking: TrackingSettings,
+    webhooks: WebhookSettings,
+    outbound_webhooks: OutboundWebhookSettings,
+    cors: CorsSettings,
 ) -> Result<Server, anyhow::Error> {
     let connection = web::Data::new(connection);
     let email_client = web::Data::new(email_client);
+    let oidc_client = web::Data::new(oidc_client);
+    let webauthn = web::Data::new(webauthn);
     let base_url = web::Data::new(ApplicationBaseUrl(base_url));
+    let password_policy = web::Data::new(password_policy);
+    let email_layout = web::Data::new(email_layout);
+    let tracking = web::Data::new(tracking);
+    let webhooks = web::Data::new(webhooks);
+    let outbound_webhooks = web::Data::new(outbound_webhooks);
     let message_store =
         CookieMessageStore::builder(Key::from(hmac_secret.expose_secret().as_bytes())).build();
     let message_framework = FlashMessagesFramework::builder(message_store).build();
@@ -102,22 +149,154 @@ async fn run(
             .route("/health_check", web::get().to(health_check))
             .route("/subscriptions", web::post().to(subscribe))
             .route("/subscriptions/confirm", web::get().to(confirm))
+            .service(
+                web::resource("/api/v1/subscriptions")
+                    .wrap(api_cors(&cors))
+                    .app_data(web::JsonConfig::default().error_handler(json_error_handler))
+                    .route(web::post().to(subscribe_json)),
+            )
+            .route(
+                "/lists/{slug}/subscriptions",
+                web::post().to(subscribe_to_list),
+            )
             .route("/", web::get().to(home))
+            .route("/issues", web::get().to(issues_archive))
+            .route("/issues/{slug}", web::get().to(issue_page))
+            .route("/feed.xml", web::get().to(rss_feed))
+            .route("/atom.xml", web::get().to(atom_feed))
+            .route("/t/o/{token}.gif", web::get().to(track_open))
+            .route("/t/c/{token}", web::get().to(track_click))
+            .route(
+                "/webhooks/email/{provider}",
+                web::post().to(receive_email_webhook),
+            )
+            .route("/preferences", web::get().to(preferences_form))
+            .route("/preferences", web::post().to(update_preferences))
+            .route("/preferences/email", web::post().to(request_email_change))
+            .route(
+                "/preferences/email/confirm",
+                web::get().to(confirm_email_change),
+            )
+            .route("/preferences/pause", web::post().to(pause_delivery))
+            .route("/preferences/unsubscribe", web::post().to(unsubscribe))
+            .route("/privacy", web::get().to(privacy_form))
+            .route("/privacy", web::post().to(request_privacy_link))
+            .route("/privacy/export", web::get().to(export_data))
+            .route("/privacy/erase", web::get().to(erase_data_form))
+            .route("/privacy/erase", web::post().to(erase_data))
             .route("/login", web::get().to(login_form))
             .route("/login", web::post().to(login))
+            .route("/login/oidc", web::get().to(oidc_login))
+            .route("/login/oidc/callback", web::get().to(oidc_callback))
+            .route("/login/passkey", web::get().to(passkey_login_form))
+            .route("/login/passkey/start", web::post().to(start_passkey_login))
+            .route(
+                "/login/passkey/finish",
+                web::post().to(finish_passkey_login),
+            )
             .service(
                 web::scope("/admin")
                     .wrap(from_fn(reject_anonymous_users))
                     .route("/dashboard", web::get().to(admin_dashboard))
+                    .route("/audit", web::get().to(audit_log))
+                    .route("/audit/export.csv", web::get().to(export_audit_log))
                     .route("/password", web::get().to(change_password_form))
                     .route("/password", web::post().to(change_password))
                     .route("/logout", web::post().to(log_out))
+                    .route("/passkeys", web::get().to(passkeys_form))
+                    .route(
+                        "/passkeys/register/start",
+                        web::post().to(start_passkey_registration),
+                    )
+                    .route(
+                        "/passkeys/register/finish",
+                        web::post().to(finish_passkey_registration),
+                    )
+                    .route(
+                        "/passkeys/second-factor",
+                        web::post().to(update_passkey_requirement),
+                    )
+                    .route(
+                        "/passkeys/{passkey_id}/delete",
+                        web::post().to(remove_passkey),
+                    )
+                    .route("/fields", web::get().to(custom_fields))
+                    .route("/fields", web::post().to(create_custom_field))
+                    .route("/issues", web::get().to(sent_issues))
+                    .route("/issues/{issue_id}", web::get().to(issue_report))
+                    .route(
+                        "/issues/{issue_id}/visibility",
+                        web::post().to(set_issue_visibility),
+                    )
+                    .route("/lists", web::get().to(lists))
+                    .route("/lists", web::post().to(create_list))
+                    .route("/subscribers", web::get().to(subscribers_list))
+                    .route("/subscribers/export", web::get().to(export_subscribers))
+                    .route(
+                        "/subscribers/import",
+                        web::get().to(import_subscribers_form),
+                    )
+                    .route("/subscribers/import", web::post().to(import_subscribers))
+                    .route(
+                        "/subscribers/imports/{import_id}",
+                        web::get().to(subscriber_import_report),
+                    )
+                    .route(
+                        "/subscribers/imports/{import_id}/rejected.csv",
+                        web::get().to(export_import_rejections),
+                    )
+                    .route(
+                        "/subscribers/{subscriber_id}",
+                        web::get().to(subscriber_details),
+                    )
+                    .route(
+                        "/subscribers/{subscriber_id}/confirm",
+                        web::post().to(confirm_subscriber_manually),
+                    )
+                    .route(
+                        "/subscribers/{subscriber_id}/resend-confirmation",
+                        web::post().to(resend_confirmation),
+                    )
+                    .route(
+                        "/subscribers/{subscriber_id}/unsubscribe",
+                        web::post().to(unsubscribe_subscriber),
+                    )
+                    .route(
+                        "/subscribers/{subscriber_id}/delete",
+                        web::post().to(delete_subscriber),
+                    )
+                    .route("/subscribers/{subscriber_id}/tags", web::post().to(add_tag))
+                    .route(
+                        "/subscribers/{subscriber_id}/tags/remove",
+                        web::post().to(remove_tag),
+                    )
+                    .route("/webhooks", web::get().to(webhook_endpoints))
+                    .route("/webhooks", web::post().to(create_webhook_endpoint))
+                    .route("/webhooks/{endpoint_id}", web::get().to(webhook_endpoint))
+                    .route(
+                        "/webhooks/{endpoint_id}/delete",
+                        web::post().to(delete_webhook_endpoint),
+                    )
                     .route("/newsletters", web::post().to(publish_newsletter))
-                    .route("/newsletters", web::get().to(send_newsletter_form)),
+                    .route("/newsletters", web::get().to(send_newsletter_form))
+                    .route("/newsletters/check", web::post().to(check_newsletter)),
+            )
+            .service(
+                web::scope("/api/v1/admin")
+                    .wrap(from_fn(reject_unauthenticated_api_requests))
+                    .route("/subscribers/export", web::get().to(export_subscribers))
+                    .route("/issues/{issue_id}", web::get().to(issue_report_json)),
             )
             .app_data(connection.clone())
             .app_data(email_client.clone())
+            .app_data(oidc_client.clone())
+            .app_data(webauthn.clone())
             .app_data(base_url.clone())
+            .app_data(password_policy.clone())
+            .app_data(email_layout.clone())
+            .app_data(tracking.clone())
+            .app_data(webhooks.clone())
+            .app_data(outbound_webhooks.clone())
             .app_data(web::Data::new(HmacSecret(hmac_secret.clone())))
     })
     .listen(listener)?
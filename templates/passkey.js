@@ -0,0 +1,74 @@
+const b64url = {
+  decode: (s) =>
+    Uint8Array.from(atob(s.replace(/-/g, "+").replace(/_/g, "/")), (c) =>
+      c.charCodeAt(0)
+    ),
+  encode: (b) =>
+    btoa(String.fromCharCode(...new Uint8Array(b)))
+      .replace(/\+/g, "-")
+      .replace(/\//g, "_")
+      .replace(/=/g, ""),
+};
+
+async function postJson(url, body) {
+  return fetch(url, {
+    method: "POST",
+    headers: { "Content-Type": "application/json" },
+    body: JSON.stringify(body),
+  });
+}
+
+async function registerPasskey(name) {
+  const start = await fetch("/admin/passkeys/register/start", { method: "POST" });
+  const options = await start.json();
+  options.publicKey.challenge = b64url.decode(options.publicKey.challenge);
+  options.publicKey.user.id = b64url.decode(options.publicKey.user.id);
+  (options.publicKey.excludeCredentials || []).forEach((c) => {
+    c.id = b64url.decode(c.id);
+  });
+
+  const credential = await navigator.credentials.create(options);
+  await postJson("/admin/passkeys/register/finish", {
+    name,
+    credential: {
+      id: credential.id,
+      rawId: b64url.encode(credential.rawId),
+      type: credential.type,
+      response: {
+        attestationObject: b64url.encode(credential.response.attestationObject),
+        clientDataJSON: b64url.encode(credential.response.clientDataJSON),
+      },
+      extensions: credential.getClientExtensionResults(),
+    },
+  });
+  window.location.reload();
+}
+
+async function loginWithPasskey(username) {
+  const start = await postJson("/login/passkey/start", { username });
+  const options = await start.json();
+  if (!start.ok) {
+    window.location.href = options.redirect;
+    return;
+  }
+  options.publicKey.challenge = b64url.decode(options.publicKey.challenge);
+  (options.publicKey.allowCredentials || []).forEach((c) => {
+    c.id = b64url.decode(c.id);
+  });
+
+  const credential = await navigator.credentials.get(options);
+  const response = credential.response;
+  const finish = await postJson("/login/passkey/finish", {
+    id: credential.id,
+    rawId: b64url.encode(credential.rawId),
+    type: credential.type,
+    response: {
+      authenticatorData: b64url.encode(response.authenticatorData),
+      clientDataJSON: b64url.encode(response.clientDataJSON),
+      signature: b64url.encode(response.signature),
+      userHandle: response.userHandle ? b64url.encode(response.userHandle) : null,
+    },
+    extensions: credential.getClientExtensionResults(),
+  });
+  window.location.href = (await finish.json()).redirect;
+}
@@ -1,4 +1,6 @@
-use crate::helpers::{assert_is_redirect_to, spawn_app};
+use crate::helpers::{assert_is_redirect_to, spawn_app, ConfirmationLinks, TestApp};
+use wiremock::matchers::{method, path};
+use wiremock::{Mock, ResponseTemplate};
 
 #[tokio::test]
 async fn you_must_be_logged_in_to_see_the_dashboard() {
@@ -33,3 +35,126 @@ async fn logout_clears_session() {
     let response = app.get_admin_dashboard().await;
     assert_is_redirect_to(&response, "/login");
 }
+
+#[tokio::test]
+async fn dashboard_links_to_newsletters_and_logs_out_via_admin_logout() {
+    let app = spawn_app().await;
+    app.post_login(&serde_json::json!({
+        "username": &app.test_user.username,
+        "password": &app.test_user.password
+    }))
+    .await;
+
+    let html_page = app.get_admin_dashboard_html().await;
+
+    assert!(html_page.contains(r#"<a href="/admin/newsletters">Send Newsletter</a>"#));
+    assert!(html_page.contains(r#"action="/admin/logout""#));
+}
+
+async fn subscribe(app: &TestApp, name: &str, email: &str) -> ConfirmationLinks {
+    app.post_subscriptions(format!(
+        "name={}&email={}",
+        name,
+        urlencoding::encode(email)
+    ))
+    .await
+    .error_for_status()
+    .unwrap();
+    let email_request = app
+        .email_server
+        .received_requests()
+        .await
+        .unwrap()
+        .pop()
+        .unwrap();
+    app.get_confirmation_links(&email_request)
+}
+
+#[tokio::test]
+async fn dashboard_shows_subscriber_counts_and_growth() {
+    let app = spawn_app().await;
+    Mock::given(path("/email"))
+        .and(method("POST"))
+        .respond_with(ResponseTemplate::new(200))
+        .mount(&app.email_server)
+        .await;
+    let links = subscribe(&app, "ursula", "ursula@example.com").await;
+    reqwest::get(links.html)
+        .await
+        .unwrap()
+        .error_for_status()
+        .unwrap();
+    subscribe(&app, "le%20guin", "le_guin@example.com").await;
+    app.post_login(&serde_json::json!({
+        "username": &app.test_user.username,
+        "password": &app.test_user.password
+    }))
+    .await;
+
+    let html_page = app.get_admin_dashboard_html().await;
+
+    assert!(html_page.contains("<tr><td>confirmed</td><td>1</td></tr>"));
+    assert!(html_page.contains("<tr><td>pending_confirmation</td><td>1</td></tr>"));
+    assert!(html_page.contains("<tr><td>bounced</td><td>0</td></tr>"));
+    assert!(html_page.contains("<dt>New signups</dt><dd>2</dd>"));
+    assert!(html_page.contains("<dt>Confirmations</dt><dd>1</dd>"));
+    assert!(html_page.contains("<dt>Confirmation rate</dt><dd>50.0%</dd>"));
+    assert_eq!(html_page.matches("<svg").count(), 2);
+    // One bar a day.
+    assert_eq!(html_page.matches("<rect").count(), 60);
+}
+
+#[tokio::test]
+async fn dashboard_growth_covers_the_chosen_period() {
+    let app = spawn_app().await;
+    app.post_login(&serde_json::json!({
+        "username": &app.test_user.username,
+        "password": &app.test_user.password
+    }))
+    .await;
+
+    let html_page = app.get_html("/admin/dashboard?days=90").await;
+    assert!(html_page.contains("Growth over the last 90 days"));
+    assert_eq!(html_page.matches("<rect").count(), 180);
+    assert!(html_page.contains("<dt>Confirmation rate</dt><dd>No signups yet</dd>"));
+
+    let response = app.get_admin_dashboard_with_query("days=45").await;
+    assert_eq!(response.status().as_u16(), 400);
+}
+
+#[tokio::test]
+async fn dashboard_lists_recent_issues_with_their_delivery_status() {
+    let app = spawn_app().await;
+    Mock::given(path("/email"))
+        .and(method("POST"))
+        .respond_with(ResponseTemplate::new(200))
+        .mount(&app.email_server)
+        .await;
+    let links = subscribe(&app, "ursula", "ursula@example.com").await;
+    reqwest::get(links.html)
+        .await
+        .unwrap()
+        .error_for_status()
+        .unwrap();
+    app.post_login(&serde_json::json!({
+        "username": &app.test_user.username,
+        "password": &app.test_user.password
+    }))
+    .await;
+    let html_page = app.get_admin_dashboard_html().await;
+    assert!(html_page.contains("No issues have been sent yet."));
+
+    let response = app
+        .post_newsletters(&serde_json::json!({
+            "title": "Spring Update",
+            "text": "Hello",
+            "html": "<p>Hello</p>",
+        }))
+        .await;
+    assert_eq!(response.status().as_u16(), 200);
+
+    let html_page = app.get_admin_dashboard_html().await;
+    assert!(html_page.contains(">Spring Update</a></td>"));
+    // Recipients, delivered, failed, bounced.
+    assert!(html_page.contains("<td>1</td><td>1</td><td>0</td><td>0</td></tr>"));
+}
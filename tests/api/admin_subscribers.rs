@@ -0,0 +1,254 @@
+use crate::helpers::{assert_is_redirect_to, spawn_app, ConfirmationLinks, TestApp};
+use uuid::Uuid;
+use wiremock::matchers::{method, path};
+use wiremock::{Mock, ResponseTemplate};
+
+async fn create_unconfirmed_subscriber(app: &TestApp, name: &str, email: &str) -> Uuid {
+    let _mock_guard = Mock::given(path("/email"))
+        .and(method("POST"))
+        .respond_with(ResponseTemplate::new(200))
+        .expect(1)
+        .mount_as_scoped(&app.email_server)
+        .await;
+    let body = format!(
+        "name={}&email={}",
+        urlencoding::encode(name),
+        urlencoding::encode(email)
+    );
+    app.post_subscriptions(body)
+        .await
+        .error_for_status()
+        .unwrap();
+
+    sqlx::query!("SELECT id FROM subscriptions WHERE email = $1", email)
+        .fetch_one(&app.db_pool)
+        .await
+        .unwrap()
+        .id
+}
+
+async fn status_of(app: &TestApp, subscriber_id: Uuid) -> String {
+    sqlx::query!(
+        "SELECT status FROM subscriptions WHERE id = $1",
+        subscriber_id
+    )
+    .fetch_one(&app.db_pool)
+    .await
+    .unwrap()
+    .status
+}
+
+#[tokio::test]
+async fn you_must_be_logged_in_to_see_subscribers() {
+    let app = spawn_app().await;
+
+    let response = app.get_admin_subscribers("").await;
+
+    assert_is_redirect_to(&response, "/login");
+}
+
+#[tokio::test]
+async fn subscribers_can_be_searched_and_filtered_by_status() {
+    let app = spawn_app().await;
+    let ursula = create_unconfirmed_subscriber(&app, "ursula", "ursula@example.com").await;
+    create_unconfirmed_subscriber(&app, "tom", "tom@example.com").await;
+    app.log_in().await;
+    app.post_admin_subscriber_action(ursula, "confirm").await;
+
+    let html_page = app.get_admin_subscribers_html("q=URSULA").await;
+    assert!(html_page.contains("ursula@example.com"));
+    assert!(!html_page.contains("tom@example.com"));
+
+    let html_page = app
+        .get_admin_subscribers_html("status=pending_confirmation")
+        .await;
+    assert!(html_page.contains("tom@example.com"));
+    assert!(!html_page.contains("ursula@example.com"));
+}
+
+#[tokio::test]
+async fn subscribers_are_filtered_by_subscription_date() {
+    let app = spawn_app().await;
+    create_unconfirmed_subscriber(&app, "ursula", "ursula@example.com").await;
+    app.log_in().await;
+
+    let html_page = app
+        .get_admin_subscribers_html("from=2000-01-01&to=2000-12-31")
+        .await;
+
+    assert!(!html_page.contains("ursula@example.com"));
+}
+
+#[tokio::test]
+async fn an_unknown_status_filter_is_rejected() {
+    let app = spawn_app().await;
+    app.log_in().await;
+
+    let response = app.get_admin_subscribers("status=deleted").await;
+
+    assert_eq!(response.status().as_u16(), 400);
+}
+
+#[tokio::test]
+async fn an_unknown_subscriber_is_a_404() {
+    let app = spawn_app().await;
+    app.log_in().await;
+
+    let response = app.get_admin_subscriber(Uuid::new_v4()).await;
+
+    assert_eq!(response.status().as_u16(), 404);
+}
+
+#[tokio::test]
+async fn subscriber_details_are_shown() {
+    let app = spawn_app().await;
+    let subscriber_id = create_unconfirmed_subscriber(&app, "ursula", "ursula@example.com").await;
+    app.log_in().await;
+
+    let html_page = app
+        .get_admin_subscriber(subscriber_id)
+        .await
+        .text()
+        .await
+        .unwrap();
+
+    assert!(html_page.contains("<dd>ursula@example.com</dd>"));
+    assert!(html_page.contains("<dd>pending_confirmation</dd>"));
+    assert!(html_page.contains("<td>Newsletter</td><td>pending_confirmation</td>"));
+}
+
+#[tokio::test]
+async fn admins_can_confirm_a_subscriber_manually() {
+    let app = spawn_app().await;
+    let subscriber_id = create_unconfirmed_subscriber(&app, "ursula", "ursula@example.com").await;
+    app.log_in().await;
+
+    let response = app
+        .post_admin_subscriber_action(subscriber_id, "confirm")
+        .await;
+
+    assert_is_redirect_to(&response, &format!("/admin/subscribers/{}", subscriber_id));
+    assert_eq!(status_of(&app, subscriber_id).await, "confirmed");
+    let html_page = app.get_audit_log_html("action=subscriber_confirmed").await;
+    assert!(html_page.contains(&format!(
+        "<td>subscriber_confirmed</td><td>{}</td>",
+        subscriber_id
+    )));
+}
+
+#[tokio::test]
+async fn resending_confirmation_sends_a_working_link() {
+    let app = spawn_app().await;
+    let subscriber_id = create_unconfirmed_subscriber(&app, "ursula", "ursula@example.com").await;
+    app.log_in().await;
+
+    Mock::given(path("/email"))
+        .and(method("POST"))
+        .respond_with(ResponseTemplate::new(200))
+        .expect(1)
+        .mount(&app.email_server)
+        .await;
+    let response = app
+        .post_admin_subscriber_action(subscriber_id, "resend-confirmation")
+        .await;
+    assert_is_redirect_to(&response, &format!("/admin/subscribers/{}", subscriber_id));
+
+    let email_request = app
+        .email_server
+        .received_requests()
+        .await
+        .unwrap()
+        .pop()
+        .unwrap();
+    let ConfirmationLinks { html, .. } = app.get_confirmation_links(&email_request);
+    reqwest::get(html)
+        .await
+        .unwrap()
+        .error_for_status()
+        .unwrap();
+    assert_eq!(status_of(&app, subscriber_id).await, "confirmed");
+}
+
+#[tokio::test]
+async fn confirmation_is_only_resent_to_pending_subscribers() {
+    let app = spawn_app().await;
+    let subscriber_id = create_unconfirmed_subscriber(&app, "ursula", "ursula@example.com").await;
+    app.log_in().await;
+    app.post_admin_subscriber_action(subscriber_id, "confirm")
+        .await;
+
+    Mock::given(path("/email"))
+        .respond_with(ResponseTemplate::new(200))
+        .expect(0)
+        .mount(&app.email_server)
+        .await;
+    app.post_admin_subscriber_action(subscriber_id, "resend-confirmation")
+        .await;
+
+    let html_page = app
+        .get_admin_subscriber(subscriber_id)
+        .await
+        .text()
+        .await
+        .unwrap();
+    assert!(html_page
+        .contains("<p><i>Only pending subscribers can be sent a confirmation email</i></p>"));
+}
+
+#[tokio::test]
+async fn unsubscribing_invalidates_outstanding_confirmation_links() {
+    let app = spawn_app().await;
+    create_unconfirmed_subscriber(&app, "ursula", "ursula@example.com").await;
+    let email_request = app
+        .email_server
+        .received_requests()
+        .await
+        .unwrap()
+        .pop()
+        .unwrap();
+    let confirmation_links = app.get_confirmation_links(&email_request);
+    let subscriber_id = sqlx::query!("SELECT id FROM subscriptions")
+        .fetch_one(&app.db_pool)
+        .await
+        .unwrap()
+        .id;
+    app.log_in().await;
+
+    app.post_admin_subscriber_action(subscriber_id, "unsubscribe")
+        .await;
+
+    assert_eq!(status_of(&app, subscriber_id).await, "unsubscribed");
+    let response = reqwest::get(confirmation_links.html).await.unwrap();
+    assert_eq!(response.status().as_u16(), 401);
+    assert_eq!(status_of(&app, subscriber_id).await, "unsubscribed");
+}
+
+#[tokio::test]
+async fn admins_can_delete_a_subscriber() {
+    let app = spawn_app().await;
+    let subscriber_id = create_unconfirmed_subscriber(&app, "ursula", "ursula@example.com").await;
+    app.log_in().await;
+
+    let response = app
+        .post_admin_subscriber_action(subscriber_id, "delete")
+        .await;
+
+    assert_is_redirect_to(&response, "/admin/subscribers");
+    let remaining = sqlx::query!("SELECT id FROM subscriptions")
+        .fetch_all(&app.db_pool)
+        .await
+        .unwrap();
+    assert!(remaining.is_empty());
+}
+
+#[tokio::test]
+async fn pages_past_the_end_of_the_number_range_are_not_found() {
+    let app = spawn_app().await;
+    app.log_in().await;
+
+    let response = app
+        .get_admin_subscribers(&format!("page={}", i64::MAX))
+        .await;
+
+    assert_eq!(response.status().as_u16(), 404);
+}
@@ -0,0 +1,179 @@
+use crate::helpers::{assert_is_redirect_to, spawn_app, TestApp};
+use wiremock::matchers::{method, path};
+use wiremock::{Mock, ResponseTemplate};
+
+async fn publish(app: &TestApp, title: &str) {
+    let response = app
+        .post_newsletters(&serde_json::json!({
+            "title": title,
+            "text": "Hi {{ name }}",
+            "html": r#"<p>Hi {{ name | default: "reader" }}</p>"#,
+            "publish_to_archive": true,
+        }))
+        .await;
+    assert_eq!(response.status().as_u16(), 200);
+}
+
+async fn get_public(app: &TestApp, location: &str) -> reqwest::Response {
+    reqwest::get(format!("{}{}", app.address, location))
+        .await
+        .unwrap()
+}
+
+async fn last_email(app: &TestApp) -> serde_json::Value {
+    let email_request = app
+        .email_server
+        .received_requests()
+        .await
+        .unwrap()
+        .pop()
+        .unwrap();
+    serde_json::from_slice(&email_request.body).unwrap()
+}
+
+#[tokio::test]
+async fn sent_issues_get_a_public_permalink() {
+    let app = spawn_app().await;
+    app.log_in().await;
+    publish(&app, "Spring Update").await;
+    publish(&app, "Spring Update").await;
+
+    let index = get_public(&app, "/issues").await.text().await.unwrap();
+    assert!(index.contains(r#"<a href="/issues/spring-update">Spring Update</a>"#));
+    assert!(index.contains(r#"<a href="/issues/spring-update-2">Spring Update</a>"#));
+
+    let response = get_public(&app, "/issues/spring-update").await;
+    assert_eq!(response.status().as_u16(), 200);
+    let page = response.text().await.unwrap();
+    assert!(page.contains("<h1>Spring Update</h1>"));
+    assert!(page.contains("<p>Hi reader</p>"));
+}
+
+#[tokio::test]
+async fn emails_link_to_the_archived_copy() {
+    let app = spawn_app().await;
+    app.log_in().await;
+    let _mock_guard = Mock::given(path("/email"))
+        .and(method("POST"))
+        .respond_with(ResponseTemplate::new(200))
+        .mount_as_scoped(&app.email_server)
+        .await;
+    app.create_confirmed_subscriber("ursula@example.com").await;
+
+    publish(&app, "Spring Update").await;
+
+    let body = last_email(&app).await;
+    let text = body["TextBody"].as_str().unwrap();
+    assert!(text.contains("View in browser: http://127.0.0.1"));
+    assert!(text.contains("/issues/spring-update\n"));
+    assert!(body["HtmlBody"]
+        .as_str()
+        .unwrap()
+        .contains("/issues/spring-update\">View in browser</a>"));
+}
+
+#[tokio::test]
+async fn issues_are_left_out_of_the_archive_unless_published() {
+    let app = spawn_app().await;
+    app.log_in().await;
+    let _mock_guard = Mock::given(path("/email"))
+        .and(method("POST"))
+        .respond_with(ResponseTemplate::new(200))
+        .mount_as_scoped(&app.email_server)
+        .await;
+    app.create_confirmed_subscriber("ursula@example.com").await;
+
+    let response = app
+        .post_newsletters(&serde_json::json!({
+            "title": "Members Only",
+            "text": "Hi",
+            "html": "<p>Hi</p>",
+        }))
+        .await;
+    assert_eq!(response.status().as_u16(), 200);
+
+    let body = last_email(&app).await;
+    assert!(!body["TextBody"]
+        .as_str()
+        .unwrap()
+        .contains("View in browser"));
+    assert!(!body["HtmlBody"]
+        .as_str()
+        .unwrap()
+        .contains("View in browser"));
+
+    assert_eq!(
+        get_public(&app, "/issues/members-only")
+            .await
+            .status()
+            .as_u16(),
+        404
+    );
+    let index = get_public(&app, "/issues").await.text().await.unwrap();
+    assert!(!index.contains("Members Only"));
+}
+
+#[tokio::test]
+async fn admins_can_hide_an_issue_from_the_archive() {
+    let app = spawn_app().await;
+    app.log_in().await;
+    publish(&app, "Spring Update").await;
+    let issue_id = sqlx::query!("SELECT id FROM newsletter_issues")
+        .fetch_one(&app.db_pool)
+        .await
+        .unwrap()
+        .id;
+
+    let response = app.post_issue_visibility(issue_id, true).await;
+    assert_is_redirect_to(&response, "/admin/issues");
+    let html_page = app.get_html("/admin/issues").await;
+    assert!(html_page.contains("<p><i>Issue hidden from the archive</i></p>"));
+    assert_eq!(
+        get_public(&app, "/issues/spring-update")
+            .await
+            .status()
+            .as_u16(),
+        404
+    );
+    let index = get_public(&app, "/issues").await.text().await.unwrap();
+    assert!(!index.contains("Spring Update"));
+
+    app.post_issue_visibility(issue_id, false).await;
+    assert_eq!(
+        get_public(&app, "/issues/spring-update")
+            .await
+            .status()
+            .as_u16(),
+        200
+    );
+}
+
+#[tokio::test]
+async fn the_archive_is_paginated() {
+    let app = spawn_app().await;
+    app.log_in().await;
+    for n in 1..=21 {
+        publish(&app, &format!("Issue {}", n)).await;
+    }
+
+    let first = get_public(&app, "/issues").await.text().await.unwrap();
+    assert!(first.contains(r#"<a href="/issues?page=2">Older &gt;</a>"#));
+    assert!(!first.contains("Newer"));
+
+    let second = get_public(&app, "/issues?page=2")
+        .await
+        .text()
+        .await
+        .unwrap();
+    assert_eq!(second.matches("<li>").count(), 1);
+    assert!(second.contains(r#"<a href="/issues?page=1">&lt; Newer</a>"#));
+}
+
+#[tokio::test]
+async fn pages_past_the_end_of_the_number_range_are_not_found() {
+    let app = spawn_app().await;
+
+    let response = get_public(&app, &format!("/issues?page={}", i64::MAX)).await;
+
+    assert_eq!(response.status().as_u16(), 404);
+}
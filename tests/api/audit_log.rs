@@ -0,0 +1,137 @@
+use crate::helpers::{assert_is_redirect_to, spawn_app};
+use uuid::Uuid;
+
+#[tokio::test]
+async fn you_must_be_logged_in_to_see_the_audit_log() {
+    let app = spawn_app().await;
+
+    let response = app.get_audit_log("").await;
+
+    assert_is_redirect_to(&response, "/login");
+}
+
+#[tokio::test]
+async fn logins_and_password_changes_are_recorded() {
+    let app = spawn_app().await;
+    let new_pw = Uuid::new_v4().to_string();
+    app.log_in().await;
+
+    app.post_change_password(&serde_json::json!({
+        "current_password": &app.test_user.password,
+        "new_password": &new_pw,
+        "new_password_check": &new_pw,
+    }))
+    .await;
+
+    let html_page = app.get_audit_log_html("").await;
+    assert!(html_page.contains(&format!(
+        "<td>{}</td><td>login_succeeded</td><td>password</td>",
+        app.test_user.username
+    )));
+    assert!(html_page.contains(&format!(
+        "<td>{0}</td><td>password_changed</td><td>{0}</td>",
+        app.test_user.username
+    )));
+}
+
+#[tokio::test]
+async fn failed_logins_are_recorded_and_filterable() {
+    let app = spawn_app().await;
+    app.post_login(&serde_json::json!({
+        "username": "intruder",
+        "password": "guess",
+    }))
+    .await;
+    app.log_in().await;
+
+    let html_page = app.get_audit_log_html("action=login_failed").await;
+
+    assert!(html_page.contains("<td></td><td>login_failed</td><td>intruder</td>"));
+    assert!(!html_page.contains("<td>login_succeeded</td>"));
+}
+
+#[tokio::test]
+async fn the_audit_log_can_be_exported_as_csv() {
+    let app = spawn_app().await;
+    app.log_in().await;
+
+    let response = app.get_audit_log_csv("action=login_succeeded").await;
+    assert_eq!(response.status().as_u16(), 200);
+    assert_eq!(
+        response.headers().get("Content-Type").unwrap(),
+        "text/csv; charset=utf-8"
+    );
+
+    let body = response.text().await.unwrap();
+    let mut lines = body.lines();
+    assert_eq!(
+        lines.next(),
+        Some("occurred_at,actor,action,target,ip_address")
+    );
+    let row = lines.next().unwrap();
+    assert!(row.contains(&format!(
+        ",{},login_succeeded,password,",
+        app.test_user.username
+    )));
+    assert_eq!(lines.next(), None);
+}
+
+#[tokio::test]
+async fn audit_events_are_append_only() {
+    let app = spawn_app().await;
+    app.log_in().await;
+
+    let outcome = sqlx::query!("DELETE FROM audit_events")
+        .execute(&app.db_pool)
+        .await;
+
+    assert!(outcome.is_err());
+}
+
+#[tokio::test]
+async fn forwarding_headers_do_not_change_the_recorded_address() {
+    let app = spawn_app().await;
+    reqwest::Client::new()
+        .post(format!("{}/login", &app.address))
+        .header("X-Forwarded-For", "203.0.113.7")
+        .header("Forwarded", "for=203.0.113.7")
+        .form(&serde_json::json!({
+            "username": "intruder",
+            "password": "guess",
+        }))
+        .send()
+        .await
+        .expect("Failed to execute request.");
+
+    let saved = sqlx::query!("SELECT ip_address FROM audit_events WHERE action = 'login_failed'")
+        .fetch_one(&app.db_pool)
+        .await
+        .unwrap();
+    assert_eq!(saved.ip_address.as_deref(), Some("127.0.0.1"));
+}
+
+#[tokio::test]
+async fn exported_cells_are_not_read_as_formulas() {
+    let app = spawn_app().await;
+    app.post_login(&serde_json::json!({
+        "username": "=HYPERLINK(\"https://evil.example\")",
+        "password": "guess",
+    }))
+    .await;
+    app.log_in().await;
+
+    let response = app.get_audit_log_csv("action=login_failed").await;
+
+    let body = response.text().await.unwrap();
+    assert!(body.contains(",login_failed,\"'=HYPERLINK(\"\"https://evil.example\"\")\","));
+}
+
+#[tokio::test]
+async fn pages_past_the_end_of_the_number_range_are_not_found() {
+    let app = spawn_app().await;
+    app.log_in().await;
+
+    let response = app.get_audit_log(&format!("page={}", i64::MAX)).await;
+
+    assert_eq!(response.status().as_u16(), 404);
+}
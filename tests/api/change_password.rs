@@ -115,3 +115,56 @@ async fn change_pw_works() {
         .await;
     assert_is_redirect_to(&response, "/admin/dashboard");
 }
+
+#[tokio::test]
+async fn new_pw_must_satisfy_the_policy() {
+    let app = spawn_app().await;
+
+    app.post_login(&serde_json::json!({
+        "username": &app.test_user.username,
+        "password": &app.test_user.password,
+    }))
+    .await;
+
+    let response = app
+        .post_change_password(&serde_json::json!({
+            "current_password": &app.test_user.password,
+            "new_password": "password",
+            "new_password_check": "password",
+        }))
+        .await;
+    assert_is_redirect_to(&response, "/admin/password");
+
+    let html_page = app.get_change_password_html().await;
+    assert!(
+        html_page.contains("<p><i>The new password must be at least 12 characters long</i></p>")
+    );
+    assert!(html_page.contains("<p><i>The new password is too easy to guess</i></p>"));
+    assert!(html_page
+        .contains("<p><i>The new password appears in a list of breached passwords</i></p>"));
+}
+
+#[tokio::test]
+async fn new_pw_must_differ_from_current_pw() {
+    let app = spawn_app().await;
+
+    app.post_login(&serde_json::json!({
+        "username": &app.test_user.username,
+        "password": &app.test_user.password,
+    }))
+    .await;
+
+    let response = app
+        .post_change_password(&serde_json::json!({
+            "current_password": &app.test_user.password,
+            "new_password": &app.test_user.password,
+            "new_password_check": &app.test_user.password,
+        }))
+        .await;
+    assert_is_redirect_to(&response, "/admin/password");
+
+    let html_page = app.get_change_password_html().await;
+    assert!(
+        html_page.contains("<p><i>The new password must differ from the current password</i></p>")
+    );
+}
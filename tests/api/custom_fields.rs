@@ -0,0 +1,176 @@
+use crate::helpers::{assert_is_redirect_to, spawn_app, TestApp};
+use wiremock::matchers::{method, path};
+use wiremock::{Mock, ResponseTemplate};
+
+async fn create_fields(app: &TestApp) {
+    for (label, key, kind, choices) in [
+        ("Company", "company", "text", ""),
+        ("Nickname", "nickname", "text", ""),
+        ("Team size", "team_size", "choice", "small, large"),
+    ] {
+        let response = app
+            .post_admin_fields(&serde_json::json!({
+                "label": label,
+                "key": key,
+                "kind": kind,
+                "choices": choices,
+            }))
+            .await;
+        assert_is_redirect_to(&response, "/admin/fields");
+    }
+}
+
+async fn subscribe_and_confirm(app: &TestApp, body: &str) {
+    let _mock_guard = Mock::given(path("/email"))
+        .and(method("POST"))
+        .respond_with(ResponseTemplate::new(200))
+        .expect(1)
+        .mount_as_scoped(&app.email_server)
+        .await;
+    app.post_subscriptions(body.into())
+        .await
+        .error_for_status()
+        .unwrap();
+    let email_request = app
+        .email_server
+        .received_requests()
+        .await
+        .unwrap()
+        .pop()
+        .unwrap();
+    reqwest::get(app.get_confirmation_links(&email_request).html)
+        .await
+        .unwrap()
+        .error_for_status()
+        .unwrap();
+}
+
+#[tokio::test]
+async fn signup_stores_custom_field_values() {
+    let app = spawn_app().await;
+    app.log_in().await;
+    create_fields(&app).await;
+
+    subscribe_and_confirm(
+        &app,
+        "name=ursula&email=ursula%40example.com&custom.company=Acme&custom.team_size=large",
+    )
+    .await;
+
+    let subscriber_id = sqlx::query!("SELECT id FROM subscriptions")
+        .fetch_one(&app.db_pool)
+        .await
+        .unwrap()
+        .id;
+    let html_page = app
+        .get_html(&format!("/admin/subscribers/{}", subscriber_id))
+        .await;
+    assert!(html_page.contains("<dt>Company</dt><dd>Acme</dd>"));
+    assert!(html_page.contains("<dt>Team size</dt><dd>large</dd>"));
+}
+
+#[tokio::test]
+async fn signup_rejects_invalid_custom_values() {
+    let app = spawn_app().await;
+    app.log_in().await;
+    create_fields(&app).await;
+    let test_cases = vec![
+        ("custom.team_size=medium", "a value that isn't a choice"),
+        ("custom.shoe_size=9", "an unknown field"),
+    ];
+
+    for (custom, description) in test_cases {
+        let response = app
+            .post_subscriptions(format!("name=ursula&email=ursula%40example.com&{}", custom))
+            .await;
+
+        assert_eq!(
+            response.status().as_u16(),
+            400,
+            "The API did not fail with 400 Bad Request for {}",
+            description
+        );
+    }
+}
+
+#[tokio::test]
+async fn field_keys_are_validated() {
+    let app = spawn_app().await;
+    app.log_in().await;
+
+    app.post_admin_fields(&serde_json::json!({
+        "label": "Company",
+        "key": "Company Name",
+        "kind": "text",
+    }))
+    .await;
+
+    let html_page = app.get_html("/admin/fields").await;
+    assert!(html_page.contains("<p><i>Keys must start with a lowercase letter"));
+}
+
+#[tokio::test]
+async fn merge_tags_are_filled_in_per_recipient() {
+    let app = spawn_app().await;
+    app.log_in().await;
+    create_fields(&app).await;
+    subscribe_and_confirm(
+        &app,
+        "name=ursula&email=ursula%40example.com&custom.company=%3CAcme%3E",
+    )
+    .await;
+
+    Mock::given(path("/email"))
+        .and(method("POST"))
+        .respond_with(ResponseTemplate::new(200))
+        .expect(1)
+        .mount(&app.email_server)
+        .await;
+    let response = app
+        .post_newsletters(&serde_json::json!({
+            "title": "Issue #1",
+            "text": r#"Hi {{ custom.nickname | default: "friend" }} at {{ custom.company }}"#,
+            "html": "<p>Hi {{ name }} at {{ custom.company }}</p>",
+        }))
+        .await;
+
+    assert_eq!(response.status().as_u16(), 200);
+    let email_request = app
+        .email_server
+        .received_requests()
+        .await
+        .unwrap()
+        .pop()
+        .unwrap();
+    let body: serde_json::Value = serde_json::from_slice(&email_request.body).unwrap();
+    assert!(body["TextBody"]
+        .as_str()
+        .unwrap()
+        .starts_with("Hi friend at <Acme>\n"));
+    assert!(body["HtmlBody"]
+        .as_str()
+        .unwrap()
+        .starts_with("<p>Hi ursula at &lt;Acme&gt;</p>"));
+}
+
+#[tokio::test]
+async fn merge_tags_for_unknown_fields_are_rejected() {
+    let app = spawn_app().await;
+    app.log_in().await;
+    subscribe_and_confirm(&app, "name=ursula&email=ursula%40example.com").await;
+
+    Mock::given(path("/email"))
+        .respond_with(ResponseTemplate::new(200))
+        .expect(0)
+        .mount(&app.email_server)
+        .await;
+    let response = app
+        .post_newsletters(&serde_json::json!({
+            "title": "Issue #1",
+            "text": "Hi {{ custom.company }}",
+            "html": "<p>Hi</p>",
+        }))
+        .await;
+
+    assert_eq!(response.status().as_u16(), 400);
+}
@@ -0,0 +1,127 @@
+use crate::helpers::{spawn_app, TestApp};
+
+async fn publish(app: &TestApp, title: &str) {
+    let response = app
+        .post_newsletters(&serde_json::json!({
+            "title": title,
+            "text": "Hi {{ name }}, here is the news.",
+            "html": r#"<p>Hi {{ name | default: "reader" }} &amp; friends</p>"#,
+            "publish_to_archive": true,
+        }))
+        .await;
+    assert_eq!(response.status().as_u16(), 200);
+}
+
+fn get_feed(app: &TestApp, location: &str) -> reqwest::RequestBuilder {
+    reqwest::Client::new().get(format!("{}{}", app.address, location))
+}
+
+#[tokio::test]
+async fn the_rss_feed_lists_sent_issues() {
+    let app = spawn_app().await;
+    app.log_in().await;
+    publish(&app, "Spring Update").await;
+
+    let response = get_feed(&app, "/feed.xml").send().await.unwrap();
+
+    assert_eq!(response.status().as_u16(), 200);
+    assert_eq!(
+        response.headers()["Content-Type"],
+        "application/rss+xml; charset=utf-8"
+    );
+    let feed = response.text().await.unwrap();
+    assert!(feed.contains("<title>Spring Update</title>"));
+    assert!(
+        feed.contains(r#"<guid isPermaLink="true">http://127.0.0.1/issues/spring-update</guid>"#)
+    );
+    assert!(feed.contains("<description>Hi , here is the news.</description>"));
+    assert!(feed.contains(
+        "<content:encoded>&lt;p&gt;Hi reader &amp;amp; friends&lt;/p&gt;</content:encoded>"
+    ));
+    assert!(feed.contains("<pubDate>"));
+}
+
+#[tokio::test]
+async fn the_atom_feed_lists_sent_issues() {
+    let app = spawn_app().await;
+    app.log_in().await;
+    publish(&app, "Spring Update").await;
+
+    let response = get_feed(&app, "/atom.xml").send().await.unwrap();
+
+    assert_eq!(response.status().as_u16(), 200);
+    assert_eq!(
+        response.headers()["Content-Type"],
+        "application/atom+xml; charset=utf-8"
+    );
+    let feed = response.text().await.unwrap();
+    assert!(feed.contains("<id>http://127.0.0.1/issues/spring-update</id>"));
+    assert!(feed.contains(r#"<content type="html">&lt;p&gt;Hi reader"#));
+    assert!(feed.contains("<published>"));
+}
+
+#[tokio::test]
+async fn hidden_issues_are_left_out_of_the_feeds() {
+    let app = spawn_app().await;
+    app.log_in().await;
+    publish(&app, "Spring Update").await;
+    let issue_id = sqlx::query!("SELECT id FROM newsletter_issues")
+        .fetch_one(&app.db_pool)
+        .await
+        .unwrap()
+        .id;
+    app.post_issue_visibility(issue_id, true).await;
+
+    for location in ["/feed.xml", "/atom.xml"] {
+        let feed = get_feed(&app, location)
+            .send()
+            .await
+            .unwrap()
+            .text()
+            .await
+            .unwrap();
+        assert!(!feed.contains("Spring Update"));
+    }
+}
+
+#[tokio::test]
+async fn unchanged_feeds_are_not_sent_again() {
+    let app = spawn_app().await;
+    app.log_in().await;
+    publish(&app, "Spring Update").await;
+    let response = get_feed(&app, "/feed.xml").send().await.unwrap();
+    let etag = response.headers()["ETag"].to_str().unwrap().to_owned();
+    let last_modified = response.headers()["Last-Modified"]
+        .to_str()
+        .unwrap()
+        .to_owned();
+
+    let response = get_feed(&app, "/feed.xml")
+        .header("If-None-Match", &etag)
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(response.status().as_u16(), 304);
+    let response = get_feed(&app, "/feed.xml")
+        .header("If-Modified-Since", &last_modified)
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(response.status().as_u16(), 304);
+
+    // Make sure the next change lands in a later second than Last-Modified.
+    tokio::time::sleep(std::time::Duration::from_millis(1100)).await;
+    publish(&app, "Summer Update").await;
+    let response = get_feed(&app, "/feed.xml")
+        .header("If-None-Match", &etag)
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(response.status().as_u16(), 200);
+    let response = get_feed(&app, "/feed.xml")
+        .header("If-Modified-Since", &last_modified)
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(response.status().as_u16(), 200);
+}
@@ -2,12 +2,19 @@ use argon2::password_hash::SaltString;
 use argon2::{Argon2, PasswordHasher};
 use once_cell::sync::Lazy;
 use reqwest::redirect;
+use secrecy::Secret;
 use sqlx::{Connection, Executor, PgConnection, PgPool};
 use uuid::Uuid;
-use wiremock::MockServer;
+use wiremock::matchers::{method, path};
+use wiremock::{Mock, MockServer, ResponseTemplate};
 use zero2prod::configuration::get_configuration;
-use zero2prod::configuration::DatabaseSettings;
-use zero2prod::startup::{get_connection_pool, Application};
+use zero2prod::configuration::{DatabaseSettings, OidcProviderSettings, OutboundWebhookSettings};
+use zero2prod::confirmation_emails;
+use zero2prod::digests::send_due_digests;
+use zero2prod::email_client::EmailClient;
+use zero2prod::outbound_webhooks::{try_execute_task, ExecutionOutcome};
+use zero2prod::routes::preferences_link;
+use zero2prod::startup::{get_connection_pool, Application, HmacSecret};
 use zero2prod::telemetry::{get_subscriber, init_subscriber};
 
 static TRACING: Lazy<()> = Lazy::new(|| {
@@ -27,9 +34,13 @@ pub struct TestApp {
     pub address: String,
     pub db_pool: PgPool,
     pub email_server: MockServer,
+    pub idp_server: MockServer,
     pub port: u16,
     pub test_user: TestUser,
     api_client: reqwest::Client,
+    email_client: EmailClient,
+    base_url: String,
+    hmac_secret: HmacSecret,
 }
 
 pub struct ConfirmationLinks {
@@ -79,7 +90,7 @@ pub fn assert_is_redirect_to(response: &reqwest::Response, location: &str) {
 impl TestApp {
     pub async fn post_logout(&self) -> reqwest::Response {
         self.api_client
-            .post(&format!("{}/admin/logout", &self.address))
+            .post(format!("{}/admin/logout", &self.address))
             .send()
             .await
             .expect("Failed to get logout")
@@ -87,7 +98,7 @@ impl TestApp {
 
     pub async fn get_change_password(&self) -> reqwest::Response {
         self.api_client
-            .get(&format!("{}/admin/password", &self.address))
+            .get(format!("{}/admin/password", &self.address))
             .send()
             .await
             .expect("Failed to get password")
@@ -102,7 +113,7 @@ impl TestApp {
         Body: serde::Serialize,
     {
         self.api_client
-            .post(&format!("{}/admin/password", &self.address))
+            .post(format!("{}/admin/password", &self.address))
             .form(body)
             .send()
             .await
@@ -111,19 +122,27 @@ impl TestApp {
 
     pub async fn get_admin_dashboard(&self) -> reqwest::Response {
         self.api_client
-            .get(&format!("{}/admin/dashboard", &self.address))
+            .get(format!("{}/admin/dashboard", &self.address))
             .send()
             .await
             .expect("Failed to get dashboard")
     }
 
+    pub async fn get_admin_dashboard_with_query(&self, query: &str) -> reqwest::Response {
+        self.api_client
+            .get(format!("{}/admin/dashboard?{}", &self.address, query))
+            .send()
+            .await
+            .expect("Failed to execute request.")
+    }
+
     pub async fn get_admin_dashboard_html(&self) -> String {
         self.get_admin_dashboard().await.text().await.unwrap()
     }
 
     pub async fn get_login_html(&self) -> String {
         self.api_client
-            .get(&format!("{}/login", &self.address))
+            .get(format!("{}/login", &self.address))
             .send()
             .await
             .expect("Failed to get login")
@@ -137,13 +156,57 @@ impl TestApp {
         Body: serde::Serialize,
     {
         self.api_client
-            .post(&format!("{}/login", &self.address))
+            .post(format!("{}/login", &self.address))
             .form(body)
             .send()
             .await
             .expect("Failed to send login request")
     }
 
+    /// Logs in as the test user.
+    pub async fn log_in(&self) {
+        let response = self
+            .post_login(&serde_json::json!({
+                "username": &self.test_user.username,
+                "password": &self.test_user.password,
+            }))
+            .await;
+        assert_is_redirect_to(&response, "/admin/dashboard");
+    }
+
+    /// Subscribes `email`, named after its local part, and follows the link
+    /// in the confirmation email, returning the new subscriber's id.
+    pub async fn create_confirmed_subscriber(&self, email: &str) -> Uuid {
+        let _mock_guard = Mock::given(path("/email"))
+            .and(method("POST"))
+            .respond_with(ResponseTemplate::new(200))
+            .named("Confirmation email")
+            .mount_as_scoped(&self.email_server)
+            .await;
+        let name = email.split('@').next().unwrap();
+        self.post_subscriptions(format!("name={name}&email={}", urlencoding::encode(email)))
+            .await
+            .error_for_status()
+            .unwrap();
+        let email_request = self
+            .email_server
+            .received_requests()
+            .await
+            .unwrap()
+            .pop()
+            .unwrap();
+        reqwest::get(self.get_confirmation_links(&email_request).html)
+            .await
+            .unwrap()
+            .error_for_status()
+            .unwrap();
+        sqlx::query!("SELECT id FROM subscriptions WHERE email = $1", email)
+            .fetch_one(&self.db_pool)
+            .await
+            .unwrap()
+            .id
+    }
+
     pub fn get_confirmation_links(&self, email_request: &wiremock::Request) -> ConfirmationLinks {
         let body: serde_json::Value = serde_json::from_slice(&email_request.body).unwrap();
 
@@ -170,7 +233,26 @@ impl TestApp {
 
     pub async fn post_subscriptions(&self, body: String) -> reqwest::Response {
         self.api_client
-            .post(&format!("{}/subscriptions", &self.address))
+            .post(format!("{}/subscriptions", &self.address))
+            .header("Content-Type", "application/x-www-form-urlencoded")
+            .body(body)
+            .send()
+            .await
+            .expect("Failed to send request.")
+    }
+
+    pub async fn post_api_subscriptions(&self, body: &serde_json::Value) -> reqwest::Response {
+        self.api_client
+            .post(format!("{}/api/v1/subscriptions", &self.address))
+            .json(body)
+            .send()
+            .await
+            .expect("Failed to execute request.")
+    }
+
+    pub async fn post_list_subscriptions(&self, slug: &str, body: String) -> reqwest::Response {
+        self.api_client
+            .post(format!("{}/lists/{}/subscriptions", &self.address, slug))
             .header("Content-Type", "application/x-www-form-urlencoded")
             .body(body)
             .send()
@@ -178,29 +260,418 @@ impl TestApp {
             .expect("Failed to send request.")
     }
 
+    pub async fn post_admin_lists<Body>(&self, body: &Body) -> reqwest::Response
+    where
+        Body: serde::Serialize,
+    {
+        self.api_client
+            .post(format!("{}/admin/lists", &self.address))
+            .form(body)
+            .send()
+            .await
+            .expect("Failed to execute request.")
+    }
+
+    pub async fn post_admin_webhooks<Body>(&self, body: &Body) -> reqwest::Response
+    where
+        Body: serde::Serialize,
+    {
+        self.api_client
+            .post(format!("{}/admin/webhooks", &self.address))
+            .form(body)
+            .send()
+            .await
+            .expect("Failed to execute request.")
+    }
+
+    pub async fn post_delete_webhook_endpoint(&self, endpoint_id: Uuid) -> reqwest::Response {
+        self.api_client
+            .post(format!(
+                "{}/admin/webhooks/{}/delete",
+                &self.address, endpoint_id
+            ))
+            .send()
+            .await
+            .expect("Failed to execute request.")
+    }
+
+    pub async fn dispatch_all_pending_webhooks(&self) {
+        let settings = OutboundWebhookSettings {
+            allow_local_targets: true,
+        };
+        loop {
+            if let ExecutionOutcome::EmptyQueue =
+                try_execute_task(&self.db_pool, &settings).await.unwrap()
+            {
+                break;
+            }
+        }
+    }
+
+    pub async fn dispatch_all_pending_confirmation_emails(&self) {
+        loop {
+            if let ExecutionOutcome::EmptyQueue = confirmation_emails::try_execute_task(
+                &self.db_pool,
+                &self.email_client,
+                &self.base_url,
+            )
+            .await
+            .unwrap()
+            {
+                break;
+            }
+        }
+    }
+
+    /// The token in the subscriber's preferences link.
+    pub fn preferences_token(&self, subscriber_id: Uuid, email: &str) -> String {
+        let link = preferences_link(&self.base_url, &self.hmac_secret, subscriber_id, email);
+        link.split_once("token=").unwrap().1.to_owned()
+    }
+
+    /// Returns how many digests went out.
+    pub async fn send_due_digests(&self) -> usize {
+        send_due_digests(
+            &self.db_pool,
+            &self.email_client,
+            &self.base_url,
+            &self.hmac_secret,
+        )
+        .await
+        .unwrap()
+    }
+
+    pub async fn post_admin_fields<Body>(&self, body: &Body) -> reqwest::Response
+    where
+        Body: serde::Serialize,
+    {
+        self.api_client
+            .post(format!("{}/admin/fields", &self.address))
+            .form(body)
+            .send()
+            .await
+            .expect("Failed to execute request.")
+    }
+
+    pub async fn get_oidc_login(&self) -> reqwest::Response {
+        self.api_client
+            .get(format!("{}/login/oidc?provider=test", &self.address))
+            .send()
+            .await
+            .expect("Failed to get OIDC login")
+    }
+
+    pub async fn get_oidc_callback(&self, code: &str, state: &str) -> reqwest::Response {
+        self.api_client
+            .get(format!("{}/login/oidc/callback", &self.address))
+            .query(&[("code", code), ("state", state)])
+            .send()
+            .await
+            .expect("Failed to get OIDC callback")
+    }
+
+    pub async fn get_passkeys(&self) -> reqwest::Response {
+        self.api_client
+            .get(format!("{}/admin/passkeys", &self.address))
+            .send()
+            .await
+            .expect("Failed to get passkeys")
+    }
+
+    pub async fn get_passkeys_html(&self) -> String {
+        self.get_passkeys().await.text().await.unwrap()
+    }
+
+    pub async fn post_passkey_registration_start(&self) -> reqwest::Response {
+        self.api_client
+            .post(format!("{}/admin/passkeys/register/start", &self.address))
+            .send()
+            .await
+            .expect("Failed to start passkey registration")
+    }
+
+    pub async fn post_passkey_registration_finish(
+        &self,
+        body: &serde_json::Value,
+    ) -> reqwest::Response {
+        self.api_client
+            .post(format!("{}/admin/passkeys/register/finish", &self.address))
+            .json(body)
+            .send()
+            .await
+            .expect("Failed to finish passkey registration")
+    }
+
+    pub async fn post_passkey_requirement<Body>(&self, body: &Body) -> reqwest::Response
+    where
+        Body: serde::Serialize,
+    {
+        self.api_client
+            .post(format!("{}/admin/passkeys/second-factor", &self.address))
+            .form(body)
+            .send()
+            .await
+            .expect("Failed to update passkey requirement")
+    }
+
+    pub async fn post_passkey_login_start(&self, body: &serde_json::Value) -> reqwest::Response {
+        self.api_client
+            .post(format!("{}/login/passkey/start", &self.address))
+            .json(body)
+            .send()
+            .await
+            .expect("Failed to start passkey login")
+    }
+
+    pub async fn get_audit_log(&self, query: &str) -> reqwest::Response {
+        self.api_client
+            .get(format!("{}/admin/audit?{}", &self.address, query))
+            .send()
+            .await
+            .expect("Failed to get audit log")
+    }
+
+    pub async fn get_audit_log_html(&self, query: &str) -> String {
+        self.get_audit_log(query).await.text().await.unwrap()
+    }
+
+    pub async fn get_audit_log_csv(&self, query: &str) -> reqwest::Response {
+        self.api_client
+            .get(format!(
+                "{}/admin/audit/export.csv?{}",
+                &self.address, query
+            ))
+            .send()
+            .await
+            .expect("Failed to export audit log")
+    }
+
+    pub async fn get_admin_subscribers(&self, query: &str) -> reqwest::Response {
+        self.api_client
+            .get(format!("{}/admin/subscribers?{}", &self.address, query))
+            .send()
+            .await
+            .expect("Failed to get subscribers")
+    }
+
+    pub async fn get_admin_subscribers_html(&self, query: &str) -> String {
+        self.get_admin_subscribers(query)
+            .await
+            .text()
+            .await
+            .unwrap()
+    }
+
+    pub async fn get_admin_subscriber(&self, subscriber_id: Uuid) -> reqwest::Response {
+        self.api_client
+            .get(format!(
+                "{}/admin/subscribers/{}",
+                &self.address, subscriber_id
+            ))
+            .send()
+            .await
+            .expect("Failed to get subscriber")
+    }
+
+    pub async fn post_admin_subscriber_action(
+        &self,
+        subscriber_id: Uuid,
+        action: &str,
+    ) -> reqwest::Response {
+        self.api_client
+            .post(format!(
+                "{}/admin/subscribers/{}/{}",
+                &self.address, subscriber_id, action
+            ))
+            .send()
+            .await
+            .expect("Failed to execute subscriber action")
+    }
+
+    pub async fn post_admin_subscriber_tag(
+        &self,
+        subscriber_id: Uuid,
+        action: &str,
+        tag: &str,
+    ) -> reqwest::Response {
+        self.api_client
+            .post(format!(
+                "{}/admin/subscribers/{}/{}",
+                &self.address, subscriber_id, action
+            ))
+            .form(&[("tag", tag)])
+            .send()
+            .await
+            .expect("Failed to execute subscriber action")
+    }
+
+    pub async fn post_subscriber_import(&self, mode: &str, csv: &str) -> reqwest::Response {
+        let form = reqwest::multipart::Form::new()
+            .text("mode", mode.to_owned())
+            .part(
+                "file",
+                reqwest::multipart::Part::text(csv.to_owned())
+                    .file_name("subscribers.csv")
+                    .mime_str("text/csv")
+                    .unwrap(),
+            );
+        self.api_client
+            .post(format!("{}/admin/subscribers/import", &self.address))
+            .multipart(form)
+            .send()
+            .await
+            .expect("Failed to import subscribers")
+    }
+
+    pub async fn get_html(&self, location: &str) -> String {
+        self.api_client
+            .get(format!("{}{}", &self.address, location))
+            .send()
+            .await
+            .expect("Failed to execute request.")
+            .text()
+            .await
+            .unwrap()
+    }
+
+    pub async fn get_subscriber_export(&self, query: &str) -> reqwest::Response {
+        self.api_client
+            .get(format!(
+                "{}/admin/subscribers/export?{}",
+                &self.address, query
+            ))
+            .send()
+            .await
+            .expect("Failed to export subscribers")
+    }
+
+    pub async fn get_api_subscriber_export(
+        &self,
+        query: &str,
+        username: &str,
+        password: &str,
+    ) -> reqwest::Response {
+        reqwest::Client::new()
+            .get(format!(
+                "{}/api/v1/admin/subscribers/export?{}",
+                &self.address, query
+            ))
+            .basic_auth(username, Some(password))
+            .send()
+            .await
+            .expect("Failed to export subscribers")
+    }
+
+    pub async fn get_api_issue_report(&self, issue_id: Uuid) -> reqwest::Response {
+        reqwest::Client::new()
+            .get(format!(
+                "{}/api/v1/admin/issues/{}",
+                &self.address, issue_id
+            ))
+            .basic_auth(&self.test_user.username, Some(&self.test_user.password))
+            .send()
+            .await
+            .expect("Failed to execute request.")
+    }
+
+    pub async fn post_privacy_request<Body>(&self, body: &Body) -> reqwest::Response
+    where
+        Body: serde::Serialize,
+    {
+        self.api_client
+            .post(format!("{}/privacy", &self.address))
+            .form(body)
+            .send()
+            .await
+            .expect("Failed to execute request.")
+    }
+
+    pub async fn post_privacy_erasure(&self, token: &str) -> reqwest::Response {
+        self.api_client
+            .post(format!("{}/privacy/erase", &self.address))
+            .form(&[("token", token)])
+            .send()
+            .await
+            .expect("Failed to execute request.")
+    }
+
+    pub async fn post_preferences<Body>(&self, path: &str, body: &Body) -> reqwest::Response
+    where
+        Body: serde::Serialize,
+    {
+        self.api_client
+            .post(format!("{}/preferences{}", &self.address, path))
+            .form(body)
+            .send()
+            .await
+            .expect("Failed to execute request.")
+    }
+
+    pub async fn post_issue_visibility(&self, issue_id: Uuid, hidden: bool) -> reqwest::Response {
+        self.api_client
+            .post(format!(
+                "{}/admin/issues/{}/visibility",
+                &self.address, issue_id
+            ))
+            .form(&[("hidden", hidden.to_string())])
+            .send()
+            .await
+            .expect("Failed to execute request.")
+    }
+
     pub async fn post_newsletters<Body>(&self, body: &Body) -> reqwest::Response
     where
         Body: serde::Serialize,
     {
         self.api_client
-            .post(&format!("{}/admin/newsletters", &self.address))
+            .post(format!("{}/admin/newsletters", &self.address))
             .form(body)
             .send()
             .await
             .expect("Failed to send request.")
     }
+
+    pub async fn post_newsletter_check<Body>(&self, body: &Body) -> String
+    where
+        Body: serde::Serialize,
+    {
+        self.api_client
+            .post(format!("{}/admin/newsletters/check", &self.address))
+            .form(body)
+            .send()
+            .await
+            .expect("Failed to send request.")
+            .text()
+            .await
+            .unwrap()
+    }
 }
 
 pub async fn spawn_app() -> TestApp {
     Lazy::force(&TRACING);
 
     let email_server = MockServer::start().await;
+    let idp_server = MockServer::start().await;
     let configuration = {
         let mut c = get_configuration().expect("Failed to read config");
 
         c.database.database_name = Uuid::new_v4().to_string();
         c.email_client.base_url = email_server.uri();
         c.application.port = 0;
+        c.cors.allowed_origins = vec!["https://widget.example.com".into()];
+        c.oidc_providers = vec![OidcProviderSettings {
+            name: "test".into(),
+            display_name: "Test IdP".into(),
+            issuer: idp_server.uri(),
+            client_id: "zero2prod".into(),
+            client_secret: Secret::new("client-secret".into()),
+            authorization_endpoint: format!("{}/authorize", idp_server.uri()),
+            token_endpoint: format!("{}/token", idp_server.uri()),
+            userinfo_endpoint: format!("{}/userinfo", idp_server.uri()),
+            scopes: vec!["openid".into(), "profile".into()],
+            username_claim: "preferred_username".into(),
+            jit_provisioning: true,
+        }];
 
         c
     };
@@ -212,7 +683,7 @@ pub async fn spawn_app() -> TestApp {
         .expect("Failed to build App");
     let address = format!("http://localhost:{}", server.port());
     let application_port = server.port();
-    let _ = tokio::spawn(server.run_until_stopped());
+    tokio::spawn(server.run_until_stopped());
 
     let client = reqwest::Client::builder()
         .redirect(redirect::Policy::none())
@@ -224,9 +695,13 @@ pub async fn spawn_app() -> TestApp {
         address,
         db_pool: get_connection_pool(&configuration.database),
         email_server,
+        idp_server,
         port: application_port,
         test_user: TestUser::generate(),
         api_client: client,
+        email_client: configuration.email_client.client(),
+        base_url: configuration.application.base_url,
+        hmac_secret: HmacSecret(configuration.application.hmac_secret),
     };
     app.test_user.store(&app.db_pool).await;
     app
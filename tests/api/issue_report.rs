@@ -0,0 +1,190 @@
+use crate::helpers::{spawn_app, TestApp};
+use uuid::Uuid;
+use wiremock::matchers::{method, path};
+use wiremock::{Mock, ResponseTemplate};
+
+/// Sends an issue with one link and returns the bodies of the emails it
+/// went out in.
+async fn publish(app: &TestApp) -> Vec<serde_json::Value> {
+    let already_sent = app.email_server.received_requests().await.unwrap().len();
+    let _mock_guard = Mock::given(path("/email"))
+        .and(method("POST"))
+        .respond_with(ResponseTemplate::new(200))
+        .mount_as_scoped(&app.email_server)
+        .await;
+    let response = app
+        .post_newsletters(&serde_json::json!({
+            "title": "Spring Update",
+            "text": "Hello",
+            "html": r#"<p>Hello, read <a href="https://example.com/post">this</a></p>"#,
+        }))
+        .await;
+    assert_eq!(response.status().as_u16(), 200);
+    app.email_server.received_requests().await.unwrap()[already_sent..]
+        .iter()
+        .map(|request| serde_json::from_slice(&request.body).unwrap())
+        .collect()
+}
+
+/// The path of the first tracking URL of a kind in an email's HTML.
+fn tracking_path<'a>(email: &'a serde_json::Value, prefix: &str) -> &'a str {
+    let html = email["HtmlBody"].as_str().unwrap();
+    let start = html.find(prefix).unwrap();
+    let end = html[start..].find('"').unwrap() + start;
+    &html[start..end]
+}
+
+fn preferences_token(email: &serde_json::Value) -> String {
+    let link = linkify::LinkFinder::new()
+        .links(email["TextBody"].as_str().unwrap())
+        .last()
+        .unwrap()
+        .as_str()
+        .to_owned();
+    reqwest::Url::parse(&link)
+        .unwrap()
+        .query_pairs()
+        .find(|(k, _)| k == "token")
+        .unwrap()
+        .1
+        .into_owned()
+}
+
+async fn get_path(app: &TestApp, location: &str) {
+    reqwest::Client::builder()
+        .redirect(reqwest::redirect::Policy::none())
+        .build()
+        .unwrap()
+        .get(format!("{}{}", app.address, location))
+        .send()
+        .await
+        .unwrap();
+}
+
+async fn issue_id(app: &TestApp) -> Uuid {
+    sqlx::query!("SELECT id FROM newsletter_issues")
+        .fetch_one(&app.db_pool)
+        .await
+        .unwrap()
+        .id
+}
+
+#[tokio::test]
+async fn the_report_sums_up_deliveries_and_engagement() {
+    let app = spawn_app().await;
+    app.create_confirmed_subscriber("ursula@example.com").await;
+    app.create_confirmed_subscriber("ged@example.com").await;
+    app.log_in().await;
+    let emails = publish(&app).await;
+    assert_eq!(emails.len(), 2);
+    for _ in 0..2 {
+        get_path(&app, tracking_path(&emails[0], "/t/o/")).await;
+        get_path(&app, tracking_path(&emails[0], "/t/c/")).await;
+    }
+    get_path(&app, tracking_path(&emails[1], "/t/c/")).await;
+    app.post_preferences(
+        "/unsubscribe",
+        &[("token", preferences_token(&emails[1]).as_str())],
+    )
+    .await;
+
+    let report: serde_json::Value = app
+        .get_api_issue_report(issue_id(&app).await)
+        .await
+        .error_for_status()
+        .unwrap()
+        .json()
+        .await
+        .unwrap();
+
+    assert_eq!(report["title"], "Spring Update");
+    assert_eq!(report["recipients"], 2);
+    assert_eq!(report["delivered"], 2);
+    assert_eq!(report["failed"], 0);
+    assert_eq!(report["bounced"], 0);
+    assert_eq!(
+        report["opens"],
+        serde_json::json!({"total": 2, "unique": 1})
+    );
+    assert_eq!(
+        report["links"],
+        serde_json::json!([{"url": "https://example.com/post", "total": 3, "unique": 2}])
+    );
+    assert_eq!(report["unsubscribes"], 1);
+
+    let html_page = app
+        .get_html(&format!("/admin/issues/{}", issue_id(&app).await))
+        .await;
+    assert!(html_page.contains("<dt>Unique opens</dt><dd>1</dd>"));
+    assert!(html_page.contains("<tr><td>https://example.com/post</td><td>3</td><td>2</td></tr>"));
+}
+
+#[tokio::test]
+async fn unsubscribes_are_attributed_to_the_latest_issue_only() {
+    let app = spawn_app().await;
+    app.create_confirmed_subscriber("ursula@example.com").await;
+    app.log_in().await;
+    publish(&app).await;
+    let first_issue = issue_id(&app).await;
+    let emails = publish(&app).await;
+
+    app.post_preferences(
+        "/unsubscribe",
+        &[("token", preferences_token(&emails[0]).as_str())],
+    )
+    .await;
+
+    let report: serde_json::Value = app
+        .get_api_issue_report(first_issue)
+        .await
+        .json()
+        .await
+        .unwrap();
+    assert_eq!(report["unsubscribes"], 0);
+}
+
+#[tokio::test]
+async fn leaving_every_list_counts_as_an_unsubscribe() {
+    let app = spawn_app().await;
+    app.create_confirmed_subscriber("ursula@example.com").await;
+    app.log_in().await;
+    let emails = publish(&app).await;
+    let token = preferences_token(&emails[0]);
+
+    for _ in 0..2 {
+        app.post_preferences("", &[("token", token.as_str()), ("name", "Ursula")])
+            .await;
+    }
+
+    let report: serde_json::Value = app
+        .get_api_issue_report(issue_id(&app).await)
+        .await
+        .json()
+        .await
+        .unwrap();
+    assert_eq!(report["unsubscribes"], 1);
+}
+
+#[tokio::test]
+async fn reports_require_authentication() {
+    let app = spawn_app().await;
+
+    let response = reqwest::get(format!(
+        "{}/api/v1/admin/issues/{}",
+        app.address,
+        Uuid::new_v4()
+    ))
+    .await
+    .unwrap();
+
+    assert_eq!(response.status().as_u16(), 401);
+}
+
+#[tokio::test]
+async fn unknown_issues_are_not_found() {
+    let app = spawn_app().await;
+
+    let response = app.get_api_issue_report(Uuid::new_v4()).await;
+
+    assert_eq!(response.status().as_u16(), 404);
+}
@@ -0,0 +1,192 @@
+use crate::helpers::{assert_is_redirect_to, spawn_app, TestApp};
+use wiremock::matchers::{method, path};
+use wiremock::{Mock, ResponseTemplate};
+
+async fn create_list(app: &TestApp, name: &str, slug: &str) {
+    let response = app
+        .post_admin_lists(&serde_json::json!({ "name": name, "slug": slug }))
+        .await;
+    assert_is_redirect_to(&response, "/admin/lists");
+}
+
+/// Signs `email` up to the list and follows the confirmation link.
+async fn subscribe_and_confirm(app: &TestApp, slug: &str, email: &str) {
+    let _mock_guard = Mock::given(path("/email"))
+        .and(method("POST"))
+        .respond_with(ResponseTemplate::new(200))
+        .expect(1)
+        .mount_as_scoped(&app.email_server)
+        .await;
+    let body = format!("name=reader&email={}", urlencoding::encode(email));
+    app.post_list_subscriptions(slug, body)
+        .await
+        .error_for_status()
+        .unwrap();
+    let email_request = app
+        .email_server
+        .received_requests()
+        .await
+        .unwrap()
+        .pop()
+        .unwrap();
+    let confirmation_links = app.get_confirmation_links(&email_request);
+    reqwest::get(confirmation_links.html)
+        .await
+        .unwrap()
+        .error_for_status()
+        .unwrap();
+}
+
+async fn membership_status(app: &TestApp, slug: &str, email: &str) -> Option<String> {
+    sqlx::query!(
+        r#"
+        SELECT m.status
+        FROM list_memberships m
+        JOIN lists l ON l.id = m.list_id
+        JOIN subscriptions s ON s.id = m.subscriber_id
+        WHERE l.slug = $1 AND s.email = $2
+        "#,
+        slug,
+        email
+    )
+    .fetch_optional(&app.db_pool)
+    .await
+    .unwrap()
+    .map(|r| r.status)
+}
+
+#[tokio::test]
+async fn signing_up_to_an_unknown_list_is_a_404() {
+    let app = spawn_app().await;
+
+    let response = app
+        .post_list_subscriptions(
+            "no-such-list",
+            "name=ursula&email=ursula%40example.com".into(),
+        )
+        .await;
+
+    assert_eq!(response.status().as_u16(), 404);
+}
+
+#[tokio::test]
+async fn list_slugs_are_validated() {
+    let app = spawn_app().await;
+    app.log_in().await;
+
+    app.post_admin_lists(&serde_json::json!({ "name": "Weekly", "slug": "Weekly Digest" }))
+        .await;
+
+    let html_page = app.get_html("/admin/lists").await;
+    assert!(html_page
+        .contains("<p><i>Slugs may only contain lowercase letters, digits and dashes</i></p>"));
+}
+
+#[tokio::test]
+async fn one_address_can_join_several_lists_with_separate_confirmation() {
+    let app = spawn_app().await;
+    app.log_in().await;
+    create_list(&app, "Weekly Digest", "weekly").await;
+
+    subscribe_and_confirm(&app, "newsletter", "ursula@example.com").await;
+    Mock::given(path("/email"))
+        .and(method("POST"))
+        .respond_with(ResponseTemplate::new(200))
+        .expect(1)
+        .mount(&app.email_server)
+        .await;
+    app.post_list_subscriptions("weekly", "name=ursula&email=ursula%40example.com".into())
+        .await
+        .error_for_status()
+        .unwrap();
+
+    let subscribers = sqlx::query!("SELECT id FROM subscriptions")
+        .fetch_all(&app.db_pool)
+        .await
+        .unwrap();
+    assert_eq!(subscribers.len(), 1);
+    assert_eq!(
+        membership_status(&app, "newsletter", "ursula@example.com")
+            .await
+            .as_deref(),
+        Some("confirmed")
+    );
+    assert_eq!(
+        membership_status(&app, "weekly", "ursula@example.com")
+            .await
+            .as_deref(),
+        Some("pending_confirmation")
+    );
+}
+
+#[tokio::test]
+async fn signing_up_again_to_a_confirmed_list_sends_nothing() {
+    let app = spawn_app().await;
+    subscribe_and_confirm(&app, "newsletter", "ursula@example.com").await;
+
+    Mock::given(path("/email"))
+        .respond_with(ResponseTemplate::new(200))
+        .expect(0)
+        .mount(&app.email_server)
+        .await;
+    let response = app
+        .post_subscriptions("name=ursula&email=ursula%40example.com".into())
+        .await;
+
+    assert_eq!(response.status().as_u16(), 200);
+}
+
+#[tokio::test]
+async fn newsletters_only_go_to_the_targeted_lists() {
+    let app = spawn_app().await;
+    app.log_in().await;
+    create_list(&app, "Weekly Digest", "weekly").await;
+    create_list(&app, "Releases", "releases").await;
+    subscribe_and_confirm(&app, "newsletter", "ursula@example.com").await;
+    subscribe_and_confirm(&app, "weekly", "tom@example.com").await;
+    subscribe_and_confirm(&app, "releases", "tom@example.com").await;
+
+    Mock::given(path("/email"))
+        .and(method("POST"))
+        .respond_with(ResponseTemplate::new(200))
+        .expect(1)
+        .mount(&app.email_server)
+        .await;
+    let response = app
+        .post_newsletters(&[
+            ("title", "Issue #1"),
+            ("text", "Newsletter body as plain text"),
+            ("html", "<p>Newsletter body as HTML</p>"),
+            ("lists", "weekly"),
+            ("lists", "releases"),
+        ])
+        .await;
+
+    assert_eq!(response.status().as_u16(), 200);
+    let email_request = app
+        .email_server
+        .received_requests()
+        .await
+        .unwrap()
+        .pop()
+        .unwrap();
+    let body: serde_json::Value = serde_json::from_slice(&email_request.body).unwrap();
+    assert_eq!(body["To"], "tom@example.com");
+}
+
+#[tokio::test]
+async fn publishing_to_an_unknown_list_is_rejected() {
+    let app = spawn_app().await;
+    app.log_in().await;
+
+    let response = app
+        .post_newsletters(&[
+            ("title", "Issue #1"),
+            ("text", "Newsletter body as plain text"),
+            ("html", "<p>Newsletter body as HTML</p>"),
+            ("lists", "no-such-list"),
+        ])
+        .await;
+
+    assert_eq!(response.status().as_u16(), 400);
+}
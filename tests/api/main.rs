@@ -1,8 +1,26 @@
 mod admin_dashboard;
+mod admin_subscribers;
+mod archive;
+mod audit_log;
 mod change_password;
+mod custom_fields;
+mod feeds;
 mod health_check;
 mod helpers;
+mod issue_report;
+mod lists;
 mod login;
 mod newsletters;
+mod oidc_login;
+mod outbound_webhooks;
+mod passkeys;
+mod preferences;
+mod privacy;
+mod segments;
+mod subscriber_export;
+mod subscriber_import;
 mod subscriptions;
+mod subscriptions_api;
 mod subscriptions_confirm;
+mod tracking;
+mod webhooks;
@@ -33,7 +33,8 @@ async fn newsletters_dont_go_to_unconfirmed_subs() {
 #[tokio::test]
 async fn newsletters_go_to_unconfirmed_subs() {
     let app = spawn_app().await;
-    create_confirmed_subscriber(&app).await;
+    app.create_confirmed_subscriber("elliot@elliotcsmith.com")
+        .await;
 
     Mock::given(any())
         .respond_with(ResponseTemplate::new(200))
@@ -109,6 +110,127 @@ async fn requests_without_auth_are_redirected() {
     assert_eq!(303, response.status().as_u16());
 }
 
+#[tokio::test]
+async fn markdown_issues_are_rendered_into_the_branded_layout() {
+    let app = spawn_app().await;
+    app.create_confirmed_subscriber("elliot@elliotcsmith.com")
+        .await;
+    Mock::given(path("/email"))
+        .and(method("POST"))
+        .respond_with(ResponseTemplate::new(200))
+        .expect(1)
+        .mount(&app.email_server)
+        .await;
+    app.post_login(&serde_json::json!({
+        "username": &app.test_user.username,
+        "password": &app.test_user.password,
+    }))
+    .await;
+
+    let response = app
+        .post_newsletters(&serde_json::json!({
+            "title": "Issue #1",
+            "markdown": "# Hello {{ name }}\n\nRead [the post](https://example.com).\n\n<script>alert(1)</script>",
+            "disable_click_tracking": true,
+        }))
+        .await;
+
+    assert_eq!(response.status().as_u16(), 200);
+    let email_request = app
+        .email_server
+        .received_requests()
+        .await
+        .unwrap()
+        .pop()
+        .unwrap();
+    let body: serde_json::Value = serde_json::from_slice(&email_request.body).unwrap();
+    let html = body["HtmlBody"].as_str().unwrap();
+    assert!(html.contains(r#"<h1 style="margin:0 0 16px;font-size:24px">Hello elliot</h1>"#));
+    assert!(html.contains(r#"<a style="color:#1a73e8" href="https://example.com" rel="noopener noreferrer">the post</a>"#));
+    assert!(html.contains("&lt;script&gt;"));
+    assert!(!html.contains("<script>"));
+    assert!(html.contains("/preferences?token="));
+    let text = body["TextBody"].as_str().unwrap();
+    assert!(text.starts_with("# Hello elliot\n\nRead the post (https://example.com)."));
+}
+
+#[tokio::test]
+async fn unsafe_html_is_only_sent_once_the_editor_accepts_the_cleanup() {
+    let app = spawn_app().await;
+    app.create_confirmed_subscriber("elliot@elliotcsmith.com")
+        .await;
+    Mock::given(path("/email"))
+        .and(method("POST"))
+        .respond_with(ResponseTemplate::new(200))
+        .expect(1)
+        .mount(&app.email_server)
+        .await;
+    app.post_login(&serde_json::json!({
+        "username": &app.test_user.username,
+        "password": &app.test_user.password,
+    }))
+    .await;
+    let mut newsletter = serde_json::json!({
+        "title": "Issue #1",
+        "text": "Hello",
+        "html": r#"<p onclick="steal()">Hello</p><script>steal()</script>"#,
+    });
+
+    let response = app.post_newsletters(&newsletter).await;
+    assert_eq!(response.status().as_u16(), 400);
+
+    newsletter["accept_sanitised"] = true.into();
+    let response = app.post_newsletters(&newsletter).await;
+    assert_eq!(response.status().as_u16(), 200);
+    let email_request = app
+        .email_server
+        .received_requests()
+        .await
+        .unwrap()
+        .pop()
+        .unwrap();
+    let body: serde_json::Value = serde_json::from_slice(&email_request.body).unwrap();
+    let html = body["HtmlBody"].as_str().unwrap();
+    assert!(html.starts_with("<p>Hello</p><hr>"));
+}
+
+#[tokio::test]
+async fn sanitisation_warnings_are_shown_before_sending() {
+    let app = spawn_app().await;
+    app.post_login(&serde_json::json!({
+        "username": &app.test_user.username,
+        "password": &app.test_user.password,
+    }))
+    .await;
+
+    let html_page = app
+        .post_newsletter_check(&serde_json::json!({
+            "title": "Issue",
+            "text": "Hello",
+            "html": r#"<a href="javascript:steal()" onclick="steal()">Hi</a>"#,
+        }))
+        .await;
+
+    assert!(html_page.contains("<li>Removed the onclick attribute from &lt;a&gt;</li>"));
+    assert!(html_page.contains("only http, https and mailto URLs are allowed"));
+    assert!(html_page.contains(r#"name="accept_sanitised""#));
+}
+
+#[tokio::test]
+async fn drafts_are_checked_without_putting_them_in_the_url() {
+    let app = spawn_app().await;
+    app.post_login(&serde_json::json!({
+        "username": &app.test_user.username,
+        "password": &app.test_user.password,
+    }))
+    .await;
+
+    let html_page = app.get_html("/admin/newsletters?title=Leaked").await;
+    assert!(!html_page.contains("Leaked"));
+    assert!(html_page.contains(r#"formaction="/admin/newsletters/check""#));
+    assert!(!html_page.contains("formmethod"));
+}
+
 // Helper Functions
 
 async fn create_unconfirmed_subscriber(app: &TestApp) -> ConfirmationLinks {
@@ -136,13 +258,3 @@ async fn create_unconfirmed_subscriber(app: &TestApp) -> ConfirmationLinks {
 
     app.get_confirmation_links(email_request)
 }
-
-async fn create_confirmed_subscriber(app: &TestApp) {
-    let confirmation_link = create_unconfirmed_subscriber(app).await;
-
-    reqwest::get(confirmation_link.html)
-        .await
-        .unwrap()
-        .error_for_status()
-        .unwrap();
-}
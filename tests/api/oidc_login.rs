@@ -0,0 +1,228 @@
+use crate::helpers::{assert_is_redirect_to, spawn_app, TestApp};
+use uuid::Uuid;
+use wiremock::matchers::{body_string_contains, header, method, path};
+use wiremock::{Mock, ResponseTemplate};
+
+async fn start_oidc_login(app: &TestApp) -> reqwest::Url {
+    let response = app.get_oidc_login().await;
+    assert_eq!(response.status().as_u16(), 303);
+    let location = response
+        .headers()
+        .get("Location")
+        .unwrap()
+        .to_str()
+        .unwrap();
+    reqwest::Url::parse(location).unwrap()
+}
+
+fn query_param(url: &reqwest::Url, name: &str) -> String {
+    url.query_pairs()
+        .find(|(k, _)| k == name)
+        .map(|(_, v)| v.into_owned())
+        .unwrap()
+}
+
+async fn mount_idp(app: &TestApp, username: &str) {
+    Mock::given(path("/token"))
+        .and(method("POST"))
+        .and(body_string_contains("code=the-code"))
+        .and(body_string_contains("code_verifier="))
+        .respond_with(ResponseTemplate::new(200).set_body_json(serde_json::json!({
+            "access_token": "the-access-token",
+            "token_type": "Bearer",
+        })))
+        .expect(1)
+        .mount(&app.idp_server)
+        .await;
+    Mock::given(path("/userinfo"))
+        .and(method("GET"))
+        .and(header("Authorization", "Bearer the-access-token"))
+        .respond_with(ResponseTemplate::new(200).set_body_json(serde_json::json!({
+            "sub": "subject-1234",
+            "preferred_username": username,
+        })))
+        .expect(1)
+        .mount(&app.idp_server)
+        .await;
+}
+
+#[tokio::test]
+async fn oidc_login_redirects_to_the_provider_with_pkce() {
+    let app = spawn_app().await;
+
+    let url = start_oidc_login(&app).await;
+
+    assert!(url
+        .as_str()
+        .starts_with(&format!("{}/authorize", app.idp_server.uri())));
+    assert_eq!(query_param(&url, "response_type"), "code");
+    assert_eq!(query_param(&url, "client_id"), "zero2prod");
+    assert_eq!(query_param(&url, "code_challenge_method"), "S256");
+    assert!(!query_param(&url, "code_challenge").is_empty());
+    assert!(!query_param(&url, "state").is_empty());
+}
+
+#[tokio::test]
+async fn the_login_form_links_to_configured_providers() {
+    let app = spawn_app().await;
+
+    let html_page = app.get_login_html().await;
+
+    assert!(html_page.contains(r#"<a href="/login/oidc?provider=test">Log in with Test IdP</a>"#));
+}
+
+async fn link_identity(app: &TestApp, subject: &str) {
+    sqlx::query!(
+        "INSERT INTO user_identities (issuer, subject, user_id) VALUES ($1, $2, $3)",
+        app.idp_server.uri(),
+        subject,
+        app.test_user.user_id,
+    )
+    .execute(&app.db_pool)
+    .await
+    .expect("Failed to link identity");
+}
+
+#[tokio::test]
+async fn a_valid_callback_logs_a_linked_user_in() {
+    let app = spawn_app().await;
+    link_identity(&app, "subject-1234").await;
+    mount_idp(&app, "whatever-the-idp-says").await;
+
+    let url = start_oidc_login(&app).await;
+    let response = app
+        .get_oidc_callback("the-code", &query_param(&url, "state"))
+        .await;
+    assert_is_redirect_to(&response, "/admin/dashboard");
+
+    let html_page = app.get_admin_dashboard_html().await;
+    assert!(html_page.contains(&format!("Welcome {}", app.test_user.username)));
+}
+
+#[tokio::test]
+async fn a_required_passkey_is_asked_for_after_single_sign_on() {
+    let app = spawn_app().await;
+    link_identity(&app, "subject-1234").await;
+    mount_idp(&app, "whatever-the-idp-says").await;
+    sqlx::query!(
+        r#"
+        INSERT INTO passkeys (id, user_id, credential_id, name, passkey, created_at)
+        VALUES ($1, $2, $3, 'laptop', '{}', now())
+        "#,
+        Uuid::new_v4(),
+        app.test_user.user_id,
+        &[1u8, 2, 3][..],
+    )
+    .execute(&app.db_pool)
+    .await
+    .unwrap();
+    sqlx::query!(
+        "UPDATE users SET passkey_required = true WHERE user_id = $1",
+        app.test_user.user_id
+    )
+    .execute(&app.db_pool)
+    .await
+    .unwrap();
+
+    let url = start_oidc_login(&app).await;
+    let response = app
+        .get_oidc_callback("the-code", &query_param(&url, "state"))
+        .await;
+    assert_is_redirect_to(&response, "/login/passkey");
+
+    let response = app.get_admin_dashboard().await;
+    assert_is_redirect_to(&response, "/login");
+}
+
+#[tokio::test]
+async fn a_new_subject_with_a_colliding_username_is_rejected() {
+    let app = spawn_app().await;
+    mount_idp(&app, &app.test_user.username).await;
+
+    let url = start_oidc_login(&app).await;
+    let response = app
+        .get_oidc_callback("the-code", &query_param(&url, "state"))
+        .await;
+    assert_is_redirect_to(&response, "/login");
+
+    let response = app.get_admin_dashboard().await;
+    assert_is_redirect_to(&response, "/login");
+    let linked = sqlx::query!("SELECT user_id FROM user_identities")
+        .fetch_optional(&app.db_pool)
+        .await
+        .unwrap();
+    assert!(linked.is_none());
+}
+
+#[tokio::test]
+async fn a_logged_in_user_can_link_an_identity_to_their_account() {
+    let app = spawn_app().await;
+    mount_idp(&app, "someone-else").await;
+    app.post_login(&serde_json::json!({
+        "username": &app.test_user.username,
+        "password": &app.test_user.password
+    }))
+    .await;
+
+    let url = start_oidc_login(&app).await;
+    let response = app
+        .get_oidc_callback("the-code", &query_param(&url, "state"))
+        .await;
+    assert_is_redirect_to(&response, "/admin/dashboard");
+
+    let html_page = app.get_admin_dashboard_html().await;
+    assert!(html_page.contains("<p><i>You can now log in with Test IdP</i></p>"));
+    let linked = sqlx::query!("SELECT user_id FROM user_identities WHERE subject = 'subject-1234'")
+        .fetch_one(&app.db_pool)
+        .await
+        .unwrap();
+    assert_eq!(linked.user_id, app.test_user.user_id);
+}
+
+#[tokio::test]
+async fn a_callback_with_the_wrong_state_is_rejected() {
+    let app = spawn_app().await;
+    Mock::given(path("/token"))
+        .respond_with(ResponseTemplate::new(200))
+        .expect(0)
+        .mount(&app.idp_server)
+        .await;
+
+    start_oidc_login(&app).await;
+    let response = app.get_oidc_callback("the-code", "not-the-state").await;
+    assert_is_redirect_to(&response, "/login");
+
+    let html_page = app.get_login_html().await;
+    assert!(html_page.contains("<p><i>Authentication Failed</i></p>"));
+}
+
+#[tokio::test]
+async fn a_callback_without_a_pending_login_is_rejected() {
+    let app = spawn_app().await;
+
+    let response = app.get_oidc_callback("the-code", "some-state").await;
+
+    assert_is_redirect_to(&response, "/login");
+}
+
+#[tokio::test]
+async fn unknown_identities_are_provisioned_just_in_time() {
+    let app = spawn_app().await;
+    mount_idp(&app, "new-admin").await;
+
+    let url = start_oidc_login(&app).await;
+    let response = app
+        .get_oidc_callback("the-code", &query_param(&url, "state"))
+        .await;
+    assert_is_redirect_to(&response, "/admin/dashboard");
+
+    let saved = sqlx::query!(
+        r#"SELECT u.username
+        FROM user_identities i JOIN users u ON u.user_id = i.user_id
+        WHERE i.subject = 'subject-1234'"#
+    )
+    .fetch_one(&app.db_pool)
+    .await
+    .expect("Failed to fetch provisioned user");
+    assert_eq!(saved.username, "new-admin");
+}
@@ -0,0 +1,287 @@
+use crate::helpers::{assert_is_redirect_to, spawn_app, TestApp};
+use uuid::Uuid;
+use wiremock::matchers::{any, header_exists, method, path};
+use wiremock::{Mock, MockServer, ResponseTemplate};
+use zero2prod::outbound_webhooks::signature;
+
+/// Registers `receiver` for `events` and returns the endpoint's id.
+async fn add_endpoint(app: &TestApp, receiver: &MockServer, events: &[&str]) -> Uuid {
+    let url = format!("{}/hooks", receiver.uri());
+    let mut form = vec![("url", url.as_str())];
+    form.extend(events.iter().map(|e| ("events", *e)));
+    let response = app.post_admin_webhooks(&form).await;
+    assert_eq!(response.status().as_u16(), 303);
+    let location = response
+        .headers()
+        .get("Location")
+        .unwrap()
+        .to_str()
+        .unwrap();
+    location
+        .strip_prefix("/admin/webhooks/")
+        .expect("Expected a redirect to the new endpoint")
+        .parse()
+        .unwrap()
+}
+
+async fn subscribe(app: &TestApp) {
+    Mock::given(path("/email"))
+        .and(method("POST"))
+        .respond_with(ResponseTemplate::new(200))
+        .mount(&app.email_server)
+        .await;
+    app.post_subscriptions("name=le%20guin&email=ursula_le_guin%40gmail.com".into())
+        .await
+        .error_for_status()
+        .unwrap();
+}
+
+async fn endpoint_secret(app: &TestApp, endpoint_id: Uuid) -> String {
+    sqlx::query!(
+        "SELECT secret FROM webhook_endpoints WHERE id = $1",
+        endpoint_id
+    )
+    .fetch_one(&app.db_pool)
+    .await
+    .unwrap()
+    .secret
+}
+
+#[tokio::test]
+async fn you_must_be_logged_in_to_manage_webhooks() {
+    let app = spawn_app().await;
+
+    let response = app
+        .post_admin_webhooks(&[("url", "https://example.com"), ("events", "issue.sent")])
+        .await;
+
+    assert_is_redirect_to(&response, "/login");
+}
+
+#[tokio::test]
+async fn new_subscribers_are_sent_to_endpoints_with_a_signature() {
+    let app = spawn_app().await;
+    let receiver = MockServer::start().await;
+    app.log_in().await;
+    let endpoint_id = add_endpoint(&app, &receiver, &["subscriber.created"]).await;
+    Mock::given(path("/hooks"))
+        .and(method("POST"))
+        .and(header_exists("X-Webhook-Signature"))
+        .respond_with(ResponseTemplate::new(204))
+        .expect(1)
+        .mount(&receiver)
+        .await;
+
+    subscribe(&app).await;
+    app.dispatch_all_pending_webhooks().await;
+
+    let request = &receiver.received_requests().await.unwrap()[0];
+    let body = std::str::from_utf8(&request.body).unwrap();
+    let secret = endpoint_secret(&app, endpoint_id).await;
+    assert_eq!(
+        request.headers.get(&"X-Webhook-Signature".into()).unwrap(),
+        signature(&secret, body).as_str()
+    );
+    assert_eq!(
+        request.headers.get(&"X-Webhook-Event".into()).unwrap(),
+        "subscriber.created"
+    );
+    let payload: serde_json::Value = serde_json::from_str(body).unwrap();
+    assert_eq!(payload["event"], "subscriber.created");
+    assert_eq!(payload["data"]["email"], "ursula_le_guin@gmail.com");
+    assert_eq!(payload["data"]["status"], "pending_confirmation");
+}
+
+#[tokio::test]
+async fn endpoints_only_receive_the_events_they_listen_for() {
+    let app = spawn_app().await;
+    let receiver = MockServer::start().await;
+    app.log_in().await;
+    add_endpoint(&app, &receiver, &["issue.sent"]).await;
+    Mock::given(any())
+        .respond_with(ResponseTemplate::new(200))
+        .expect(0)
+        .mount(&receiver)
+        .await;
+
+    subscribe(&app).await;
+    app.dispatch_all_pending_webhooks().await;
+}
+
+#[tokio::test]
+async fn failed_deliveries_are_retried_later_and_logged() {
+    let app = spawn_app().await;
+    let receiver = MockServer::start().await;
+    app.log_in().await;
+    let endpoint_id = add_endpoint(&app, &receiver, &["subscriber.created"]).await;
+    Mock::given(path("/hooks"))
+        .respond_with(ResponseTemplate::new(500))
+        .up_to_n_times(1)
+        .expect(1)
+        .mount(&receiver)
+        .await;
+    Mock::given(path("/hooks"))
+        .respond_with(ResponseTemplate::new(200))
+        .expect(1)
+        .mount(&receiver)
+        .await;
+
+    subscribe(&app).await;
+    app.dispatch_all_pending_webhooks().await;
+
+    // The retry isn't due yet.
+    let html = app
+        .get_html(&format!("/admin/webhooks/{}", endpoint_id))
+        .await;
+    assert!(html.contains("<td>pending</td>"));
+    assert!(html.contains("<td>500</td>"));
+    assert_eq!(receiver.received_requests().await.unwrap().len(), 1);
+
+    sqlx::query!("UPDATE webhook_deliveries SET next_attempt_at = now()")
+        .execute(&app.db_pool)
+        .await
+        .unwrap();
+    app.dispatch_all_pending_webhooks().await;
+
+    let html = app
+        .get_html(&format!("/admin/webhooks/{}", endpoint_id))
+        .await;
+    assert!(html.contains("<td>delivered</td>"));
+    assert!(html.contains("<td>2</td>"));
+}
+
+#[tokio::test]
+async fn sending_an_issue_is_announced() {
+    let app = spawn_app().await;
+    let receiver = MockServer::start().await;
+    app.log_in().await;
+    add_endpoint(&app, &receiver, &["issue.sent"]).await;
+    Mock::given(path("/hooks"))
+        .respond_with(ResponseTemplate::new(200))
+        .expect(1)
+        .mount(&receiver)
+        .await;
+
+    let response = app
+        .post_newsletters(&serde_json::json!({
+            "title": "Spring Update",
+            "text": "Hello",
+            "html": "<p>Hello</p>",
+            "publish_to_archive": true,
+        }))
+        .await;
+    assert_eq!(response.status().as_u16(), 200);
+    app.dispatch_all_pending_webhooks().await;
+
+    let request = &receiver.received_requests().await.unwrap()[0];
+    let payload: serde_json::Value = serde_json::from_slice(&request.body).unwrap();
+    assert_eq!(payload["event"], "issue.sent");
+    assert_eq!(payload["data"]["title"], "Spring Update");
+    assert_eq!(
+        payload["data"]["url"],
+        "http://127.0.0.1/issues/spring-update"
+    );
+    assert_eq!(payload["data"]["recipients"], 0);
+}
+
+#[tokio::test]
+async fn saving_preferences_only_announces_real_unsubscribes() {
+    let app = spawn_app().await;
+    let receiver = MockServer::start().await;
+    app.log_in().await;
+    subscribe(&app).await;
+    let confirmation = &app.email_server.received_requests().await.unwrap()[0];
+    reqwest::get(app.get_confirmation_links(confirmation).html)
+        .await
+        .unwrap()
+        .error_for_status()
+        .unwrap();
+    let endpoint_id = add_endpoint(&app, &receiver, &["subscriber.unsubscribed"]).await;
+    let subscriber_id = sqlx::query!("SELECT id FROM subscriptions")
+        .fetch_one(&app.db_pool)
+        .await
+        .unwrap()
+        .id;
+    let token = app.preferences_token(subscriber_id, "ursula_le_guin@gmail.com");
+    let deliveries = || async {
+        sqlx::query!(
+            r#"SELECT count(*) AS "count!" FROM webhook_deliveries WHERE endpoint_id = $1"#,
+            endpoint_id
+        )
+        .fetch_one(&app.db_pool)
+        .await
+        .unwrap()
+        .count
+    };
+
+    // Renaming while staying on the list changes nothing webhooks care about.
+    let form = [
+        ("token", token.as_str()),
+        ("name", "Ursula"),
+        ("lists", "newsletter"),
+    ];
+    app.post_preferences("", &form).await;
+    assert_eq!(deliveries().await, 0);
+
+    // Leaving every list is an unsubscribe, announced once.
+    let form = [("token", token.as_str()), ("name", "Ursula")];
+    app.post_preferences("", &form).await;
+    app.post_preferences("", &form).await;
+    assert_eq!(deliveries().await, 1);
+}
+
+#[tokio::test]
+async fn endpoints_need_a_web_url_and_an_event() {
+    let app = spawn_app().await;
+    app.log_in().await;
+
+    let response = app
+        .post_admin_webhooks(&[("url", "ftp://example.com"), ("events", "issue.sent")])
+        .await;
+    assert_is_redirect_to(&response, "/admin/webhooks");
+    let html = app.get_html("/admin/webhooks").await;
+    assert!(html.contains("Webhook URLs must start with http:// or https://"));
+
+    let response = app
+        .post_admin_webhooks(&[("url", "https://127.0.0.1/hooks")])
+        .await;
+    assert_is_redirect_to(&response, "/admin/webhooks");
+    let html = app.get_html("/admin/webhooks").await;
+    assert!(html.contains("Choose at least one event"));
+
+    let count = sqlx::query!(r#"SELECT count(*) AS "count!" FROM webhook_endpoints"#)
+        .fetch_one(&app.db_pool)
+        .await
+        .unwrap()
+        .count;
+    assert_eq!(count, 0);
+}
+
+#[tokio::test]
+async fn deleting_an_endpoint_drops_its_queued_deliveries() {
+    let app = spawn_app().await;
+    let receiver = MockServer::start().await;
+    app.log_in().await;
+    let endpoint_id = add_endpoint(&app, &receiver, &["subscriber.created"]).await;
+    Mock::given(any())
+        .respond_with(ResponseTemplate::new(200))
+        .expect(0)
+        .mount(&receiver)
+        .await;
+    subscribe(&app).await;
+
+    let response = app
+        .get_html(&format!("/admin/webhooks/{}", endpoint_id))
+        .await;
+    assert!(response.contains("subscriber.created"));
+    let response = app.post_delete_webhook_endpoint(endpoint_id).await;
+    assert_is_redirect_to(&response, "/admin/webhooks");
+    app.dispatch_all_pending_webhooks().await;
+
+    let count = sqlx::query!(r#"SELECT count(*) AS "count!" FROM webhook_deliveries"#)
+        .fetch_one(&app.db_pool)
+        .await
+        .unwrap()
+        .count;
+    assert_eq!(count, 0);
+}
@@ -0,0 +1,113 @@
+use crate::helpers::{assert_is_redirect_to, spawn_app};
+use uuid::Uuid;
+
+#[tokio::test]
+async fn you_must_be_logged_in_to_manage_passkeys() {
+    let app = spawn_app().await;
+
+    let response = app.get_passkeys().await;
+
+    assert_is_redirect_to(&response, "/login");
+}
+
+#[tokio::test]
+async fn registration_start_returns_a_challenge_for_the_user() {
+    let app = spawn_app().await;
+    app.log_in().await;
+
+    let response = app.post_passkey_registration_start().await;
+    assert_eq!(response.status().as_u16(), 200);
+
+    let body: serde_json::Value = response.json().await.unwrap();
+    assert_eq!(body["publicKey"]["rp"]["id"], "localhost");
+    assert_eq!(body["publicKey"]["user"]["name"], app.test_user.username);
+    assert!(body["publicKey"]["challenge"].is_string());
+}
+
+#[tokio::test]
+async fn registration_finish_without_a_challenge_is_rejected() {
+    let app = spawn_app().await;
+    app.log_in().await;
+
+    let response = app
+        .post_passkey_registration_finish(&serde_json::json!({
+            "name": "laptop",
+            "credential": {
+                "id": "AAAA",
+                "rawId": "AAAA",
+                "type": "public-key",
+                "response": {
+                    "attestationObject": "AAAA",
+                    "clientDataJSON": "AAAA"
+                },
+                "extensions": {}
+            }
+        }))
+        .await;
+
+    assert_eq!(response.status().as_u16(), 400);
+}
+
+#[tokio::test]
+async fn a_passkey_cannot_be_required_before_one_is_registered() {
+    let app = spawn_app().await;
+    app.log_in().await;
+
+    let response = app
+        .post_passkey_requirement(&serde_json::json!({ "required": true }))
+        .await;
+    assert_is_redirect_to(&response, "/admin/passkeys");
+
+    let html_page = app.get_passkeys_html().await;
+    assert!(html_page.contains("<p><i>Register a passkey first</i></p>"));
+    assert!(html_page.contains("A passkey is not required"));
+}
+
+#[tokio::test]
+async fn passkey_login_for_an_unknown_user_fails() {
+    let app = spawn_app().await;
+
+    let response = app
+        .post_passkey_login_start(&serde_json::json!({ "username": "nobody" }))
+        .await;
+
+    assert_eq!(response.status().as_u16(), 401);
+    let body: serde_json::Value = response.json().await.unwrap();
+    assert_eq!(body["redirect"], "/login");
+}
+
+#[tokio::test]
+async fn a_required_passkey_is_asked_for_after_the_password() {
+    let app = spawn_app().await;
+    sqlx::query!(
+        r#"
+        INSERT INTO passkeys (id, user_id, credential_id, name, passkey, created_at)
+        VALUES ($1, $2, $3, 'laptop', '{}', now())
+        "#,
+        Uuid::new_v4(),
+        app.test_user.user_id,
+        &[1u8, 2, 3][..],
+    )
+    .execute(&app.db_pool)
+    .await
+    .unwrap();
+    sqlx::query!(
+        "UPDATE users SET passkey_required = true WHERE user_id = $1",
+        app.test_user.user_id
+    )
+    .execute(&app.db_pool)
+    .await
+    .unwrap();
+
+    let response = app
+        .post_login(&serde_json::json!({
+            "username": &app.test_user.username,
+            "password": &app.test_user.password,
+        }))
+        .await;
+    assert_is_redirect_to(&response, "/login/passkey");
+
+    // The password alone does not grant access to the admin area.
+    let response = app.get_admin_dashboard().await;
+    assert_is_redirect_to(&response, "/login");
+}
@@ -0,0 +1,449 @@
+use crate::helpers::{assert_is_redirect_to, spawn_app, TestApp};
+use wiremock::matchers::{method, path};
+use wiremock::{Mock, ResponseTemplate};
+
+/// Publishes an issue and returns how many emails went out.
+async fn publish(app: &TestApp) -> usize {
+    let before = app.email_server.received_requests().await.unwrap().len();
+    let _mock_guard = Mock::given(path("/email"))
+        .and(method("POST"))
+        .respond_with(ResponseTemplate::new(200))
+        .mount_as_scoped(&app.email_server)
+        .await;
+    let response = app
+        .post_newsletters(&serde_json::json!({
+            "title": "Newsletter title",
+            "text": "Newsletter body as plain text",
+            "html": "<p>Newsletter body as HTML</p>",
+        }))
+        .await;
+    assert_eq!(response.status().as_u16(), 200);
+    app.email_server.received_requests().await.unwrap().len() - before
+}
+
+/// The token from the preferences link in the footer of the last email.
+async fn preferences_token(app: &TestApp) -> String {
+    let email_request = app
+        .email_server
+        .received_requests()
+        .await
+        .unwrap()
+        .pop()
+        .unwrap();
+    let body: serde_json::Value = serde_json::from_slice(&email_request.body).unwrap();
+    let link = linkify::LinkFinder::new()
+        .links(body["TextBody"].as_str().unwrap())
+        .last()
+        .unwrap()
+        .as_str()
+        .to_owned();
+    let link = reqwest::Url::parse(&link).unwrap();
+    assert_eq!(link.path(), "/preferences");
+    link.query_pairs()
+        .find(|(k, _)| k == "token")
+        .unwrap()
+        .1
+        .into_owned()
+}
+
+async fn set_up(app: &TestApp) -> String {
+    app.log_in().await;
+    app.create_confirmed_subscriber("ursula@example.com").await;
+    assert_eq!(publish(app).await, 1);
+    preferences_token(app).await
+}
+
+#[tokio::test]
+async fn newsletters_link_to_the_preference_page() {
+    let app = spawn_app().await;
+    let token = set_up(&app).await;
+
+    let html_page = app.get_html(&format!("/preferences?token={}", token)).await;
+
+    assert!(html_page.contains("Preferences for ursula@example.com"));
+    assert!(html_page.contains(r#"value="ursula""#));
+    assert!(html_page.contains(r#"value="newsletter" checked"#));
+}
+
+#[tokio::test]
+async fn a_forged_token_is_rejected() {
+    let app = spawn_app().await;
+
+    let response = reqwest::get(format!("{}/preferences?token=abc.def", app.address))
+        .await
+        .unwrap();
+
+    assert_eq!(response.status().as_u16(), 401);
+}
+
+#[tokio::test]
+async fn subscribers_can_rename_themselves_and_switch_lists() {
+    let app = spawn_app().await;
+    let token = set_up(&app).await;
+    app.post_admin_lists(&serde_json::json!({"name": "Weekly", "slug": "weekly"}))
+        .await;
+
+    let response = app
+        .post_preferences(
+            "",
+            &[
+                ("token", token.as_str()),
+                ("name", "Ursula K"),
+                ("lists", "weekly"),
+            ],
+        )
+        .await;
+    assert_is_redirect_to(&response, &format!("/preferences?token={}", token));
+
+    let html_page = app.get_html(&format!("/preferences?token={}", token)).await;
+    assert!(html_page.contains("<p><i>Your preferences have been saved.</i></p>"));
+    let memberships = sqlx::query!(
+        r#"
+        SELECT l.slug, m.status
+        FROM list_memberships m JOIN lists l ON l.id = m.list_id
+        ORDER BY l.slug
+        "#
+    )
+    .fetch_all(&app.db_pool)
+    .await
+    .unwrap();
+    let memberships: Vec<_> = memberships
+        .iter()
+        .map(|m| (m.slug.as_str(), m.status.as_str()))
+        .collect();
+    assert_eq!(
+        memberships,
+        [("newsletter", "unsubscribed"), ("weekly", "confirmed")]
+    );
+    let name = sqlx::query!("SELECT name FROM subscriptions")
+        .fetch_one(&app.db_pool)
+        .await
+        .unwrap()
+        .name;
+    assert_eq!(name, "Ursula K");
+}
+
+#[tokio::test]
+async fn invalid_names_are_rejected() {
+    let app = spawn_app().await;
+    let token = set_up(&app).await;
+
+    app.post_preferences(
+        "",
+        &[
+            ("token", token.as_str()),
+            ("name", "<script>"),
+            ("lists", "newsletter"),
+        ],
+    )
+    .await;
+
+    let html_page = app.get_html(&format!("/preferences?token={}", token)).await;
+    assert!(html_page.contains("is not a valid SubscriberName"));
+    let name = sqlx::query!("SELECT name FROM subscriptions")
+        .fetch_one(&app.db_pool)
+        .await
+        .unwrap()
+        .name;
+    assert_eq!(name, "ursula");
+}
+
+#[tokio::test]
+async fn paused_subscribers_are_skipped_until_they_resume() {
+    let app = spawn_app().await;
+    let token = set_up(&app).await;
+
+    let response = app
+        .post_preferences("/pause", &[("token", token.as_str()), ("weeks", "4")])
+        .await;
+    assert_is_redirect_to(&response, &format!("/preferences?token={}", token));
+    assert_eq!(publish(&app).await, 0);
+
+    app.post_preferences("/pause", &[("token", token.as_str()), ("weeks", "0")])
+        .await;
+    assert_eq!(publish(&app).await, 1);
+}
+
+#[tokio::test]
+async fn pauses_are_limited_to_a_year() {
+    let app = spawn_app().await;
+    let token = set_up(&app).await;
+
+    app.post_preferences("/pause", &[("token", token.as_str()), ("weeks", "53")])
+        .await;
+
+    let html_page = app.get_html(&format!("/preferences?token={}", token)).await;
+    assert!(html_page.contains("<p><i>You can pause for between 0 and 52 weeks</i></p>"));
+    assert_eq!(publish(&app).await, 1);
+}
+
+#[tokio::test]
+async fn subscribers_can_unsubscribe() {
+    let app = spawn_app().await;
+    let token = set_up(&app).await;
+
+    let response = app
+        .post_preferences("/unsubscribe", &[("token", token.as_str())])
+        .await;
+    assert_is_redirect_to(&response, &format!("/preferences?token={}", token));
+
+    let html_page = app.get_html(&format!("/preferences?token={}", token)).await;
+    assert!(html_page.contains("<p><i>You have been unsubscribed.</i></p>"));
+    let status = sqlx::query!("SELECT status FROM subscriptions")
+        .fetch_one(&app.db_pool)
+        .await
+        .unwrap()
+        .status;
+    assert_eq!(status, "unsubscribed");
+    assert_eq!(publish(&app).await, 0);
+}
+
+#[tokio::test]
+async fn picking_lists_does_not_lift_a_bounce() {
+    let app = spawn_app().await;
+    let token = set_up(&app).await;
+    sqlx::query!("UPDATE subscriptions SET status = 'bounced'")
+        .execute(&app.db_pool)
+        .await
+        .unwrap();
+
+    app.post_preferences(
+        "",
+        &[
+            ("token", token.as_str()),
+            ("name", "ursula"),
+            ("lists", "newsletter"),
+        ],
+    )
+    .await;
+    app.post_preferences("/unsubscribe", &[("token", token.as_str())])
+        .await;
+    app.post_preferences(
+        "",
+        &[
+            ("token", token.as_str()),
+            ("name", "ursula"),
+            ("lists", "newsletter"),
+        ],
+    )
+    .await;
+
+    let status = sqlx::query!("SELECT status FROM subscriptions")
+        .fetch_one(&app.db_pool)
+        .await
+        .unwrap()
+        .status;
+    assert_eq!(status, "bounced");
+    assert_eq!(publish(&app).await, 0);
+}
+
+#[tokio::test]
+async fn digest_subscribers_get_their_issues_together_once_due() {
+    let app = spawn_app().await;
+    let token = set_up(&app).await;
+
+    let response = app
+        .post_preferences(
+            "",
+            &[
+                ("token", token.as_str()),
+                ("name", "ursula"),
+                ("lists", "newsletter"),
+                ("digest_frequency", "weekly"),
+            ],
+        )
+        .await;
+    assert_is_redirect_to(&response, &format!("/preferences?token={}", token));
+    let html_page = app.get_html(&format!("/preferences?token={}", token)).await;
+    assert!(html_page.contains(r#"<option value="weekly" selected>"#));
+    assert_eq!(publish(&app).await, 0);
+    assert_eq!(publish(&app).await, 0);
+
+    let _mock_guard = Mock::given(path("/email"))
+        .and(method("POST"))
+        .respond_with(ResponseTemplate::new(200))
+        .mount_as_scoped(&app.email_server)
+        .await;
+    assert_eq!(app.send_due_digests().await, 0);
+    sqlx::query!("UPDATE digest_items SET queued_at = now() - interval '8 days'")
+        .execute(&app.db_pool)
+        .await
+        .unwrap();
+    assert_eq!(app.send_due_digests().await, 1);
+
+    let email_request = app
+        .email_server
+        .received_requests()
+        .await
+        .unwrap()
+        .pop()
+        .unwrap();
+    let body: serde_json::Value = serde_json::from_slice(&email_request.body).unwrap();
+    assert_eq!(body["Subject"], "Your weekly digest");
+    let text = body["TextBody"].as_str().unwrap();
+    assert_eq!(text.matches("Newsletter body as plain text").count(), 2);
+    assert!(text.contains("/preferences?token="));
+    let deliveries = sqlx::query!(r#"SELECT count(*) AS "count!" FROM issue_deliveries"#)
+        .fetch_one(&app.db_pool)
+        .await
+        .unwrap()
+        .count;
+    // One from `set_up`, and the two in the digest.
+    assert_eq!(deliveries, 3);
+    // Nothing is left to send.
+    assert_eq!(app.send_due_digests().await, 0);
+}
+
+#[tokio::test]
+async fn unknown_digest_frequencies_are_rejected() {
+    let app = spawn_app().await;
+    let token = set_up(&app).await;
+
+    app.post_preferences(
+        "",
+        &[
+            ("token", token.as_str()),
+            ("name", "ursula"),
+            ("lists", "newsletter"),
+            ("digest_frequency", "hourly"),
+        ],
+    )
+    .await;
+
+    let html_page = app.get_html(&format!("/preferences?token={}", token)).await;
+    assert!(html_page.contains("hourly is not a valid digest frequency"));
+}
+
+#[tokio::test]
+async fn an_email_change_takes_effect_once_the_new_address_confirms_it() {
+    let app = spawn_app().await;
+    let token = set_up(&app).await;
+    let _mock_guard = Mock::given(path("/email"))
+        .and(method("POST"))
+        .respond_with(ResponseTemplate::new(200))
+        .mount_as_scoped(&app.email_server)
+        .await;
+
+    let response = app
+        .post_preferences(
+            "/email",
+            &[
+                ("token", token.as_str()),
+                ("email", "ursula@new.example.com"),
+            ],
+        )
+        .await;
+    assert_is_redirect_to(&response, &format!("/preferences?token={}", token));
+    let email_request = app
+        .email_server
+        .received_requests()
+        .await
+        .unwrap()
+        .pop()
+        .unwrap();
+    let body: serde_json::Value = serde_json::from_slice(&email_request.body).unwrap();
+    assert_eq!(body["To"], "ursula@new.example.com");
+    let email = sqlx::query!("SELECT email FROM subscriptions")
+        .fetch_one(&app.db_pool)
+        .await
+        .unwrap()
+        .email;
+    assert_eq!(email, "ursula@example.com");
+
+    let confirmation_link = app.get_confirmation_links(&email_request).html;
+    let response = reqwest::get(confirmation_link.clone()).await.unwrap();
+    assert_eq!(response.status().as_u16(), 200);
+
+    let subscriber = sqlx::query!("SELECT email, status FROM subscriptions")
+        .fetch_one(&app.db_pool)
+        .await
+        .unwrap();
+    assert_eq!(subscriber.email, "ursula@new.example.com");
+    assert_eq!(subscriber.status, "confirmed");
+    let notice = app
+        .email_server
+        .received_requests()
+        .await
+        .unwrap()
+        .pop()
+        .unwrap();
+    let body: serde_json::Value = serde_json::from_slice(&notice.body).unwrap();
+    assert_eq!(body["To"], "ursula@example.com");
+
+    // The link is spent once the address has moved.
+    let response = reqwest::get(confirmation_link).await.unwrap();
+    assert_eq!(response.status().as_u16(), 401);
+    // So are preferences links sent to the old address.
+    let response = reqwest::get(format!("{}/preferences?token={}", app.address, token))
+        .await
+        .unwrap();
+    assert_eq!(response.status().as_u16(), 401);
+}
+
+#[tokio::test]
+async fn moving_to_a_taken_address_looks_like_a_successful_change() {
+    let app = spawn_app().await;
+    let token = set_up(&app).await;
+    let _mock_guard = Mock::given(path("/email"))
+        .and(method("POST"))
+        .respond_with(ResponseTemplate::new(200))
+        .mount_as_scoped(&app.email_server)
+        .await;
+    app.post_subscriptions("name=le%20guin&email=ursula%40new.example.com".into())
+        .await
+        .error_for_status()
+        .unwrap();
+
+    app.post_preferences(
+        "/email",
+        &[
+            ("token", token.as_str()),
+            ("email", "ursula@new.example.com"),
+        ],
+    )
+    .await;
+    let email_request = app
+        .email_server
+        .received_requests()
+        .await
+        .unwrap()
+        .pop()
+        .unwrap();
+    let confirmation_link = app.get_confirmation_links(&email_request).html;
+    let response = reqwest::get(confirmation_link).await.unwrap();
+
+    assert_eq!(response.status().as_u16(), 200);
+    assert_eq!(
+        response.text().await.unwrap(),
+        "Your address is now ursula@new.example.com. \
+        Use the link in your next newsletter to manage your preferences."
+    );
+    let emails: Vec<String> = sqlx::query!("SELECT email FROM subscriptions ORDER BY email")
+        .fetch_all(&app.db_pool)
+        .await
+        .unwrap()
+        .into_iter()
+        .map(|r| r.email)
+        .collect();
+    assert_eq!(emails, ["ursula@example.com", "ursula@new.example.com"]);
+}
+
+#[tokio::test]
+async fn an_invalid_new_address_is_rejected() {
+    let app = spawn_app().await;
+    let token = set_up(&app).await;
+    let sent = app.email_server.received_requests().await.unwrap().len();
+
+    app.post_preferences(
+        "/email",
+        &[("token", token.as_str()), ("email", "not-an-email")],
+    )
+    .await;
+
+    let html_page = app.get_html(&format!("/preferences?token={}", token)).await;
+    assert!(html_page.contains("is not a valid subscriber email"));
+    assert_eq!(
+        app.email_server.received_requests().await.unwrap().len(),
+        sent
+    );
+}
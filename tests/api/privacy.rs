@@ -0,0 +1,186 @@
+use crate::helpers::{assert_is_redirect_to, spawn_app, TestApp};
+use wiremock::matchers::{any, method, path};
+use wiremock::{Mock, ResponseTemplate};
+
+/// Subscribes and confirms ursula, then sends her one issue.
+async fn create_subscriber_with_a_delivery(app: &TestApp) {
+    Mock::given(path("/email"))
+        .and(method("POST"))
+        .respond_with(ResponseTemplate::new(200))
+        .mount(&app.email_server)
+        .await;
+    app.post_subscriptions("name=ursula&email=ursula%40example.com".into())
+        .await
+        .error_for_status()
+        .unwrap();
+    let email_request = app
+        .email_server
+        .received_requests()
+        .await
+        .unwrap()
+        .pop()
+        .unwrap();
+    let confirmation_links = app.get_confirmation_links(&email_request);
+    reqwest::get(confirmation_links.html)
+        .await
+        .unwrap()
+        .error_for_status()
+        .unwrap();
+
+    app.post_login(&serde_json::json!({
+        "username": &app.test_user.username,
+        "password": &app.test_user.password
+    }))
+    .await;
+    app.post_newsletters(&serde_json::json!({
+        "title": "Issue #1",
+        "text": "Newsletter body as plain text",
+        "html": "<p>Newsletter body as HTML</p>",
+    }))
+    .await
+    .error_for_status()
+    .unwrap();
+    app.post_logout().await;
+}
+
+async fn request_link(app: &TestApp, request: &str) -> reqwest::Url {
+    let response = app
+        .post_privacy_request(&serde_json::json!({
+            "email": "ursula@example.com",
+            "request": request,
+        }))
+        .await;
+    assert_is_redirect_to(&response, "/privacy");
+    let email_request = app
+        .email_server
+        .received_requests()
+        .await
+        .unwrap()
+        .pop()
+        .unwrap();
+    app.get_confirmation_links(&email_request).html
+}
+
+#[tokio::test]
+async fn unknown_addresses_get_the_same_answer_but_no_email() {
+    let app = spawn_app().await;
+    Mock::given(any())
+        .respond_with(ResponseTemplate::new(200))
+        .expect(0)
+        .mount(&app.email_server)
+        .await;
+
+    let response = app
+        .post_privacy_request(&serde_json::json!({
+            "email": "nobody@example.com",
+            "request": "access",
+        }))
+        .await;
+
+    assert_is_redirect_to(&response, "/privacy");
+    let html_page = app.get_html("/privacy").await;
+    assert!(html_page.contains("If that address is subscribed, we have emailed it a link"));
+}
+
+#[tokio::test]
+async fn the_access_link_exports_everything_tied_to_the_address() {
+    let app = spawn_app().await;
+    create_subscriber_with_a_delivery(&app).await;
+
+    let link = request_link(&app, "access").await;
+    let response = reqwest::get(link).await.unwrap();
+
+    assert_eq!(response.status().as_u16(), 200);
+    let data: serde_json::Value = response.json().await.unwrap();
+    assert_eq!(data["subscription"]["email"], "ursula@example.com");
+    assert_eq!(data["subscription"]["status"], "confirmed");
+    assert_eq!(data["subscription_tokens"].as_array().unwrap().len(), 1);
+    assert_eq!(data["deliveries"][0]["title"], "Issue #1");
+    assert_eq!(data["deliveries"][0]["status"], "delivered");
+}
+
+#[tokio::test]
+async fn a_tampered_link_is_rejected() {
+    let app = spawn_app().await;
+    create_subscriber_with_a_delivery(&app).await;
+
+    let mut link = request_link(&app, "access").await;
+    let token = link
+        .query_pairs()
+        .find(|(k, _)| k == "token")
+        .unwrap()
+        .1
+        .into_owned();
+    let (_, tag) = token.split_once('.').unwrap();
+    let forged = format!(
+        "{}.{}",
+        base64::Engine::encode(
+            &base64::engine::general_purpose::URL_SAFE_NO_PAD,
+            "access\n99999999999\ntom@example.com"
+        ),
+        tag
+    );
+    link.query_pairs_mut().clear().append_pair("token", &forged);
+
+    let response = reqwest::get(link).await.unwrap();
+
+    assert_eq!(response.status().as_u16(), 401);
+}
+
+#[tokio::test]
+async fn an_access_link_cannot_erase_data() {
+    let app = spawn_app().await;
+    create_subscriber_with_a_delivery(&app).await;
+
+    let mut link = request_link(&app, "access").await;
+    link.set_path("/privacy/erase");
+    let response = reqwest::get(link).await.unwrap();
+
+    assert_eq!(response.status().as_u16(), 401);
+}
+
+#[tokio::test]
+async fn erasure_anonymises_the_subscriber_but_keeps_counts() {
+    let app = spawn_app().await;
+    create_subscriber_with_a_delivery(&app).await;
+    let access_link = request_link(&app, "access").await;
+    let erase_link = request_link(&app, "erasure").await;
+
+    let html_page = reqwest::get(erase_link.clone())
+        .await
+        .unwrap()
+        .text()
+        .await
+        .unwrap();
+    assert!(html_page.contains(r#"<form action="/privacy/erase" method="post">"#));
+    let token = erase_link
+        .query_pairs()
+        .find(|(k, _)| k == "token")
+        .unwrap()
+        .1
+        .into_owned();
+    let response = app.post_privacy_erasure(&token).await;
+    assert_is_redirect_to(&response, "/privacy");
+
+    let subscriptions = sqlx::query!("SELECT email, name, status FROM subscriptions")
+        .fetch_all(&app.db_pool)
+        .await
+        .unwrap();
+    assert_eq!(subscriptions.len(), 1);
+    assert!(subscriptions[0].email.ends_with("@erased.invalid"));
+    assert_eq!(subscriptions[0].name, "erased");
+    assert_eq!(subscriptions[0].status, "unsubscribed");
+    let deliveries = sqlx::query!("SELECT status FROM issue_deliveries")
+        .fetch_all(&app.db_pool)
+        .await
+        .unwrap();
+    assert_eq!(deliveries.len(), 1);
+    let tokens = sqlx::query!("SELECT subscription_token FROM subscription_tokens")
+        .fetch_all(&app.db_pool)
+        .await
+        .unwrap();
+    assert!(tokens.is_empty());
+
+    let response = reqwest::get(access_link).await.unwrap();
+    assert_eq!(response.status().as_u16(), 404);
+}
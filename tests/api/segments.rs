@@ -0,0 +1,119 @@
+use crate::helpers::{assert_is_redirect_to, spawn_app, TestApp};
+use uuid::Uuid;
+use wiremock::matchers::{method, path};
+use wiremock::{Mock, ResponseTemplate};
+
+/// Two confirmed subscribers, only ursula tagged `beta`.
+async fn create_tagged_audience(app: &TestApp) -> Uuid {
+    let ursula = app.create_confirmed_subscriber("ursula@example.com").await;
+    app.create_confirmed_subscriber("tom@example.com").await;
+    app.log_in().await;
+    let response = app.post_admin_subscriber_tag(ursula, "tags", "beta").await;
+    assert_is_redirect_to(&response, &format!("/admin/subscribers/{}", ursula));
+    ursula
+}
+
+async fn publish_to_segment(app: &TestApp, segment: &str) -> reqwest::Response {
+    app.post_newsletters(&[
+        ("title", "Issue #1"),
+        ("text", "Newsletter body as plain text"),
+        ("html", "<p>Newsletter body as HTML</p>"),
+        ("segment", segment),
+    ])
+    .await
+}
+
+#[tokio::test]
+async fn tags_can_be_added_and_removed() {
+    let app = spawn_app().await;
+    let ursula = create_tagged_audience(&app).await;
+
+    let html_page = app
+        .get_html(&format!("/admin/subscribers/{}", ursula))
+        .await;
+    assert!(html_page.contains(r#"<input type="hidden" name="tag" value="beta">"#));
+
+    app.post_admin_subscriber_tag(ursula, "tags/remove", "beta")
+        .await;
+    let html_page = app
+        .get_html(&format!("/admin/subscribers/{}", ursula))
+        .await;
+    assert!(!html_page.contains(r#"value="beta""#));
+}
+
+#[tokio::test]
+async fn invalid_tags_are_rejected() {
+    let app = spawn_app().await;
+    let ursula = app.create_confirmed_subscriber("ursula@example.com").await;
+    app.log_in().await;
+
+    app.post_admin_subscriber_tag(ursula, "tags", "Paid Plan")
+        .await;
+
+    let html_page = app
+        .get_html(&format!("/admin/subscribers/{}", ursula))
+        .await;
+    assert!(html_page.contains("<p><i>Tags may only contain lowercase letters"));
+}
+
+#[tokio::test]
+async fn newsletters_only_go_to_the_segment() {
+    let app = spawn_app().await;
+    create_tagged_audience(&app).await;
+
+    Mock::given(path("/email"))
+        .and(method("POST"))
+        .respond_with(ResponseTemplate::new(200))
+        .expect(1)
+        .mount(&app.email_server)
+        .await;
+    let response = publish_to_segment(&app, "not tag:beta and status:confirmed").await;
+
+    assert_eq!(response.status().as_u16(), 200);
+    let email_request = app
+        .email_server
+        .received_requests()
+        .await
+        .unwrap()
+        .pop()
+        .unwrap();
+    let body: serde_json::Value = serde_json::from_slice(&email_request.body).unwrap();
+    assert_eq!(body["To"], "tom@example.com");
+}
+
+#[tokio::test]
+async fn the_form_shows_the_recipient_count() {
+    let app = spawn_app().await;
+    create_tagged_audience(&app).await;
+
+    let html_page = app.get_html("/admin/newsletters").await;
+    assert!(html_page.contains("This issue will go to 2 subscribers."));
+
+    let html_page = app
+        .post_newsletter_check(&serde_json::json!({
+            "title": "Hello",
+            "segment": "tag:beta or subscribed_before:2000-01-01",
+        }))
+        .await;
+    assert!(html_page.contains("This issue will go to 1 subscribers."));
+    assert!(html_page.contains(r#"value="Hello""#));
+}
+
+#[tokio::test]
+async fn an_invalid_segment_is_reported_and_not_sent() {
+    let app = spawn_app().await;
+    create_tagged_audience(&app).await;
+
+    let html_page = app
+        .post_newsletter_check(&serde_json::json!({"segment": "tag:beta and"}))
+        .await;
+    assert!(html_page.contains("<p><i>Invalid segment: The segment ends too early</i></p>"));
+
+    Mock::given(path("/email"))
+        .respond_with(ResponseTemplate::new(200))
+        .expect(0)
+        .mount(&app.email_server)
+        .await;
+    let response = publish_to_segment(&app, "colour:red").await;
+    assert_eq!(response.status().as_u16(), 400);
+}
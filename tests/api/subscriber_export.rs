@@ -0,0 +1,139 @@
+use crate::helpers::{assert_is_redirect_to, spawn_app, TestApp};
+use uuid::Uuid;
+
+async fn insert_subscriber(app: &TestApp, email: &str, status: &str, subscribed_at: &str) {
+    sqlx::query!(
+        r#"
+        INSERT INTO subscriptions (id, email, name, subscribed_at, status)
+        VALUES ($1, $2, 'name', $3::text::timestamptz, $4)
+        "#,
+        Uuid::new_v4(),
+        email,
+        subscribed_at,
+        status
+    )
+    .execute(&app.db_pool)
+    .await
+    .unwrap();
+}
+
+#[tokio::test]
+async fn you_must_be_logged_in_to_export_subscribers() {
+    let app = spawn_app().await;
+
+    let response = app.get_subscriber_export("").await;
+
+    assert_is_redirect_to(&response, "/login");
+}
+
+#[tokio::test]
+async fn csv_export_honours_status_and_date_filters() {
+    let app = spawn_app().await;
+    insert_subscriber(&app, "old@example.com", "confirmed", "2020-01-01T10:00:00Z").await;
+    insert_subscriber(&app, "new@example.com", "confirmed", "2023-06-01T10:00:00Z").await;
+    insert_subscriber(
+        &app,
+        "pending@example.com",
+        "pending_confirmation",
+        "2023-06-02T10:00:00Z",
+    )
+    .await;
+    app.log_in().await;
+
+    let response = app
+        .get_subscriber_export("format=csv&status=confirmed&from=2023-01-01")
+        .await;
+
+    assert_eq!(response.status().as_u16(), 200);
+    assert_eq!(
+        response.headers()["Content-Type"],
+        "text/csv; charset=utf-8"
+    );
+    let body = response.text().await.unwrap();
+    let lines: Vec<_> = body.lines().collect();
+    assert_eq!(lines[0], "id,email,name,subscribed_at,status");
+    assert_eq!(lines.len(), 2);
+    assert!(lines[1].contains(",new@example.com,name,2023-06-01T10:00:00Z,confirmed"));
+}
+
+#[tokio::test]
+async fn an_empty_export_is_still_well_formed() {
+    let app = spawn_app().await;
+    app.log_in().await;
+
+    let csv = app
+        .get_subscriber_export("format=csv")
+        .await
+        .text()
+        .await
+        .unwrap();
+    let json = app
+        .get_subscriber_export("format=json")
+        .await
+        .text()
+        .await
+        .unwrap();
+
+    assert_eq!(csv, "id,email,name,subscribed_at,status\n");
+    assert_eq!(
+        serde_json::from_str::<serde_json::Value>(&json).unwrap(),
+        serde_json::json!([])
+    );
+}
+
+#[tokio::test]
+async fn json_export_contains_every_subscriber() {
+    let app = spawn_app().await;
+    insert_subscriber(&app, "a@example.com", "confirmed", "2023-06-01T10:00:00Z").await;
+    insert_subscriber(
+        &app,
+        "b@example.com",
+        "unsubscribed",
+        "2023-06-02T10:00:00Z",
+    )
+    .await;
+    app.log_in().await;
+
+    let response = app.get_subscriber_export("format=json").await;
+
+    assert_eq!(response.headers()["Content-Type"], "application/json");
+    let body: Vec<serde_json::Value> = response.json().await.unwrap();
+    assert_eq!(body.len(), 2);
+    assert_eq!(body[0]["email"], "a@example.com");
+    assert_eq!(body[1]["status"], "unsubscribed");
+    assert!(body[0]["id"].is_string());
+    assert_eq!(body[0]["subscribed_at"], "2023-06-01T10:00:00Z");
+}
+
+#[tokio::test]
+async fn the_api_export_requires_valid_credentials() {
+    let app = spawn_app().await;
+
+    let response = app
+        .get_api_subscriber_export("", &app.test_user.username, "wrong-password")
+        .await;
+
+    assert_eq!(response.status().as_u16(), 401);
+    assert_eq!(
+        response.headers()["WWW-Authenticate"],
+        r#"Basic realm="api""#
+    );
+}
+
+#[tokio::test]
+async fn the_api_export_accepts_basic_auth() {
+    let app = spawn_app().await;
+    insert_subscriber(&app, "a@example.com", "confirmed", "2023-06-01T10:00:00Z").await;
+
+    let response = app
+        .get_api_subscriber_export(
+            "format=json",
+            &app.test_user.username,
+            &app.test_user.password,
+        )
+        .await;
+
+    assert_eq!(response.status().as_u16(), 200);
+    let body: Vec<serde_json::Value> = response.json().await.unwrap();
+    assert_eq!(body[0]["email"], "a@example.com");
+}
@@ -0,0 +1,143 @@
+use crate::helpers::{assert_is_redirect_to, spawn_app, TestApp};
+use wiremock::matchers::{method, path};
+use wiremock::{Mock, ResponseTemplate};
+
+async fn statuses(app: &TestApp) -> Vec<(String, String)> {
+    sqlx::query!("SELECT email, status FROM subscriptions ORDER BY email")
+        .fetch_all(&app.db_pool)
+        .await
+        .unwrap()
+        .into_iter()
+        .map(|r| (r.email, r.status))
+        .collect()
+}
+
+#[tokio::test]
+async fn you_must_be_logged_in_to_import_subscribers() {
+    let app = spawn_app().await;
+
+    let response = app
+        .post_subscriber_import("pre_confirmed", "email,name\n")
+        .await;
+
+    assert_is_redirect_to(&response, "/login");
+}
+
+#[tokio::test]
+async fn pre_confirmed_rows_are_imported_and_invalid_rows_reported() {
+    let app = spawn_app().await;
+    app.log_in().await;
+    Mock::given(path("/email"))
+        .respond_with(ResponseTemplate::new(200))
+        .expect(0)
+        .mount(&app.email_server)
+        .await;
+    let csv = "Name,Email,Source\n\
+               ursula,ursula@example.com,old\n\
+               tom,not-an-email,old\n\
+               ursula again,ursula@example.com,old\n\
+               ,nameless@example.com,old\n";
+
+    let response = app.post_subscriber_import("pre_confirmed", csv).await;
+
+    assert_eq!(response.status().as_u16(), 303);
+    let report = response
+        .headers()
+        .get("Location")
+        .unwrap()
+        .to_str()
+        .unwrap();
+    assert_eq!(
+        statuses(&app).await,
+        vec![("ursula@example.com".into(), "confirmed".into())]
+    );
+
+    let html_page = app.get_html(report).await;
+    assert!(html_page.contains("Imported 1 subscribers, rejected 3 rows."));
+    assert!(html_page.contains("<td>3</td><td>not-an-email</td>"));
+    assert!(html_page.contains("<td>Already subscribed</td>"));
+
+    let rejected_csv = app.get_html(&format!("{}/rejected.csv", report)).await;
+    let lines: Vec<_> = rejected_csv.lines().collect();
+    assert_eq!(lines[0], "row,email,name,reason");
+    assert_eq!(lines.len(), 4);
+    assert!(lines[1].starts_with("3,not-an-email,tom,"));
+    assert_eq!(
+        lines[2],
+        "4,ursula@example.com,ursula again,Already subscribed"
+    );
+    assert!(lines[3].starts_with("5,nameless@example.com,,"));
+}
+
+#[tokio::test]
+async fn double_opt_in_imports_queue_confirmation_emails() {
+    let app = spawn_app().await;
+    app.log_in().await;
+    Mock::given(path("/email"))
+        .and(method("POST"))
+        .respond_with(ResponseTemplate::new(200))
+        .expect(2)
+        .mount(&app.email_server)
+        .await;
+    let csv = "email,name\nursula@example.com,ursula\ntom@example.com,tom\n";
+
+    let response = app.post_subscriber_import("double_opt_in", csv).await;
+
+    assert_eq!(response.status().as_u16(), 303);
+    assert_eq!(
+        statuses(&app).await,
+        vec![
+            ("tom@example.com".into(), "pending_confirmation".into()),
+            ("ursula@example.com".into(), "pending_confirmation".into()),
+        ]
+    );
+    assert!(app
+        .email_server
+        .received_requests()
+        .await
+        .unwrap()
+        .is_empty());
+
+    app.dispatch_all_pending_confirmation_emails().await;
+
+    let email_request = app
+        .email_server
+        .received_requests()
+        .await
+        .unwrap()
+        .pop()
+        .unwrap();
+    let confirmation_links = app.get_confirmation_links(&email_request);
+    reqwest::get(confirmation_links.html)
+        .await
+        .unwrap()
+        .error_for_status()
+        .unwrap();
+}
+
+#[tokio::test]
+async fn a_csv_without_email_and_name_columns_is_rejected() {
+    let app = spawn_app().await;
+    app.log_in().await;
+
+    let response = app
+        .post_subscriber_import("pre_confirmed", "address\nursula@example.com\n")
+        .await;
+
+    assert_eq!(response.status().as_u16(), 400);
+}
+
+#[tokio::test]
+async fn large_imports_are_inserted_in_batches() {
+    let app = spawn_app().await;
+    app.log_in().await;
+    let mut csv = String::from("email,name\n");
+    for i in 0..1234 {
+        csv.push_str(&format!("subscriber{}@example.com,subscriber {}\n", i, i));
+    }
+
+    let response = app.post_subscriber_import("pre_confirmed", &csv).await;
+
+    assert_eq!(response.status().as_u16(), 303);
+    assert_eq!(statuses(&app).await.len(), 1234);
+}
@@ -0,0 +1,148 @@
+use crate::helpers::spawn_app;
+use wiremock::matchers::{method, path};
+use wiremock::{Mock, ResponseTemplate};
+
+#[tokio::test]
+async fn subscribe_json_returns_a_202_and_sends_a_confirmation_email() {
+    let app = spawn_app().await;
+    Mock::given(path("/email"))
+        .and(method("POST"))
+        .respond_with(ResponseTemplate::new(200))
+        .expect(1)
+        .mount(&app.email_server)
+        .await;
+
+    let response = app
+        .post_api_subscriptions(&serde_json::json!({
+            "name": "le guin",
+            "email": "ursula_le_guin@gmail.com",
+        }))
+        .await;
+
+    assert_eq!(response.status().as_u16(), 202);
+    let body: serde_json::Value = response.json().await.unwrap();
+    assert!(body["request_id"].is_string());
+    let saved = sqlx::query!("SELECT email, name, status FROM subscriptions")
+        .fetch_one(&app.db_pool)
+        .await
+        .expect("Failed to fetch saved subscription.");
+    assert_eq!(saved.email, "ursula_le_guin@gmail.com");
+    assert_eq!(saved.name, "le guin");
+    assert_eq!(saved.status, "pending_confirmation");
+}
+
+#[tokio::test]
+async fn subscribe_json_reports_every_invalid_field() {
+    let app = spawn_app().await;
+
+    let response = app
+        .post_api_subscriptions(&serde_json::json!({
+            "name": "",
+            "email": "not-an-email",
+        }))
+        .await;
+
+    assert_eq!(response.status().as_u16(), 400);
+    let body: serde_json::Value = response.json().await.unwrap();
+    assert_eq!(body["error"]["code"], "validation_failed");
+    let fields = body["error"]["fields"].as_array().unwrap();
+    assert_eq!(fields.len(), 2);
+    assert_eq!(fields[0]["field"], "email");
+    assert_eq!(
+        fields[0]["message"],
+        "not-an-email is not a valid subscriber email."
+    );
+    assert_eq!(fields[1]["field"], "name");
+    assert!(body["request_id"].is_string());
+}
+
+#[tokio::test]
+async fn subscribe_json_treats_missing_fields_as_invalid() {
+    let app = spawn_app().await;
+
+    let response = app
+        .post_api_subscriptions(&serde_json::json!({ "name": "le guin" }))
+        .await;
+
+    assert_eq!(response.status().as_u16(), 400);
+    let body: serde_json::Value = response.json().await.unwrap();
+    assert_eq!(body["error"]["code"], "validation_failed");
+    assert_eq!(body["error"]["fields"][0]["field"], "email");
+}
+
+#[tokio::test]
+async fn subscribe_json_rejects_malformed_bodies_with_a_structured_error() {
+    let app = spawn_app().await;
+
+    let response = reqwest::Client::new()
+        .post(format!("{}/api/v1/subscriptions", app.address))
+        .header("Content-Type", "application/json")
+        .body("{\"name\": ")
+        .send()
+        .await
+        .unwrap();
+
+    assert_eq!(response.status().as_u16(), 400);
+    let body: serde_json::Value = response.json().await.unwrap();
+    assert_eq!(body["error"]["code"], "invalid_body");
+    assert_eq!(body["error"]["fields"], serde_json::json!([]));
+    assert!(body["request_id"].is_string());
+}
+
+#[tokio::test]
+async fn subscribe_json_returns_a_404_for_an_unknown_list() {
+    let app = spawn_app().await;
+
+    let response = app
+        .post_api_subscriptions(&serde_json::json!({
+            "name": "le guin",
+            "email": "ursula_le_guin@gmail.com",
+            "list": "no-such-list",
+        }))
+        .await;
+
+    assert_eq!(response.status().as_u16(), 404);
+    let body: serde_json::Value = response.json().await.unwrap();
+    assert_eq!(body["error"]["code"], "unknown_list");
+}
+
+#[tokio::test]
+async fn configured_origins_may_call_the_api_from_the_browser() {
+    let app = spawn_app().await;
+    let client = reqwest::Client::new();
+
+    let response = client
+        .request(
+            reqwest::Method::OPTIONS,
+            format!("{}/api/v1/subscriptions", app.address),
+        )
+        .header("Origin", "https://widget.example.com")
+        .header("Access-Control-Request-Method", "POST")
+        .header("Access-Control-Request-Headers", "content-type")
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(response.status().as_u16(), 200);
+    assert_eq!(
+        response
+            .headers()
+            .get("Access-Control-Allow-Origin")
+            .unwrap(),
+        "https://widget.example.com"
+    );
+
+    let response = client
+        .request(
+            reqwest::Method::OPTIONS,
+            format!("{}/api/v1/subscriptions", app.address),
+        )
+        .header("Origin", "https://elsewhere.example.com")
+        .header("Access-Control-Request-Method", "POST")
+        .send()
+        .await
+        .unwrap();
+    assert!(response
+        .headers()
+        .get("Access-Control-Allow-Origin")
+        .is_none());
+}
@@ -0,0 +1,184 @@
+use crate::helpers::{spawn_app, TestApp};
+use base64::engine::general_purpose::URL_SAFE_NO_PAD;
+use base64::Engine;
+use wiremock::matchers::{method, path};
+use wiremock::{Mock, ResponseTemplate};
+
+/// Sends an issue to the one subscriber and returns the HTML they got.
+async fn publish(app: &TestApp, extra: serde_json::Value) -> String {
+    let _mock_guard = Mock::given(path("/email"))
+        .and(method("POST"))
+        .respond_with(ResponseTemplate::new(200))
+        .expect(1)
+        .mount_as_scoped(&app.email_server)
+        .await;
+    let mut newsletter = serde_json::json!({
+        "title": "Spring Update",
+        "text": "Hello",
+        "html": r#"<p>Hello, read <a href="https://example.com/post?a=1&amp;b=2">this</a></p>"#,
+    });
+    newsletter
+        .as_object_mut()
+        .unwrap()
+        .extend(extra.as_object().unwrap().clone());
+    let response = app.post_newsletters(&newsletter).await;
+    assert_eq!(response.status().as_u16(), 200);
+    let email_request = app
+        .email_server
+        .received_requests()
+        .await
+        .unwrap()
+        .pop()
+        .unwrap();
+    let body: serde_json::Value = serde_json::from_slice(&email_request.body).unwrap();
+    body["HtmlBody"].as_str().unwrap().to_owned()
+}
+
+/// The path of the first tracking URL of a kind, which the test server serves
+/// on another port than the configured base URL.
+fn tracking_path<'a>(html: &'a str, prefix: &str) -> Option<&'a str> {
+    let start = html.find(prefix)?;
+    let end = html[start..].find('"')? + start;
+    Some(&html[start..end])
+}
+
+fn pixel_path(html: &str) -> Option<&str> {
+    tracking_path(html, "/t/o/")
+}
+
+fn no_redirects() -> reqwest::Client {
+    reqwest::Client::builder()
+        .redirect(reqwest::redirect::Policy::none())
+        .build()
+        .unwrap()
+}
+
+async fn get_pixel(app: &TestApp, pixel_path: &str) -> reqwest::Response {
+    reqwest::get(format!("{}{}", app.address, pixel_path))
+        .await
+        .unwrap()
+}
+
+#[tokio::test]
+async fn opens_are_counted_once_per_recipient() {
+    let app = spawn_app().await;
+    app.create_confirmed_subscriber("ursula@example.com").await;
+    app.log_in().await;
+    let html = publish(&app, serde_json::json!({})).await;
+    let pixel_path = pixel_path(&html).expect("No tracking pixel in the email");
+
+    for _ in 0..2 {
+        let response = get_pixel(&app, pixel_path).await;
+        assert_eq!(response.status().as_u16(), 200);
+        assert_eq!(response.headers()["Content-Type"], "image/gif");
+    }
+
+    let open = sqlx::query!("SELECT open_count FROM issue_opens")
+        .fetch_one(&app.db_pool)
+        .await
+        .unwrap();
+    assert_eq!(open.open_count, 2);
+    let html_page = app.get_html("/admin/issues").await;
+    assert!(html_page.contains("<td>1</td>"));
+}
+
+#[tokio::test]
+async fn opens_are_not_tracked_when_the_issue_opts_out() {
+    let app = spawn_app().await;
+    app.create_confirmed_subscriber("ursula@example.com").await;
+    app.log_in().await;
+
+    let html = publish(&app, serde_json::json!({"disable_open_tracking": true})).await;
+
+    assert!(pixel_path(&html).is_none());
+    let html_page = app.get_html("/admin/issues").await;
+    assert!(html_page.contains("<td>Not tracked</td>"));
+}
+
+#[tokio::test]
+async fn forged_pixels_still_load_but_record_nothing() {
+    let app = spawn_app().await;
+
+    let response = get_pixel(&app, "/t/o/bm9wZQ.00.gif").await;
+
+    assert_eq!(response.status().as_u16(), 200);
+    assert_eq!(response.headers()["Content-Type"], "image/gif");
+    let opens = sqlx::query!("SELECT count(*) AS count FROM issue_opens")
+        .fetch_one(&app.db_pool)
+        .await
+        .unwrap();
+    assert_eq!(opens.count, Some(0));
+}
+
+#[tokio::test]
+async fn clicks_are_recorded_and_redirect_to_the_link() {
+    let app = spawn_app().await;
+    app.create_confirmed_subscriber("ursula@example.com").await;
+    app.log_in().await;
+    let html = publish(&app, serde_json::json!({})).await;
+    let click_path = tracking_path(&html, "/t/c/").expect("The link isn't tracked");
+    assert!(html.contains(r#"/preferences?token="#));
+
+    let response = no_redirects()
+        .get(format!("{}{}", app.address, click_path))
+        .send()
+        .await
+        .unwrap();
+
+    assert_eq!(response.status().as_u16(), 302);
+    assert_eq!(
+        response.headers()["Location"],
+        "https://example.com/post?a=1&b=2"
+    );
+    let click = sqlx::query!("SELECT url FROM issue_clicks")
+        .fetch_one(&app.db_pool)
+        .await
+        .unwrap();
+    assert_eq!(click.url, "https://example.com/post?a=1&b=2");
+    let html_page = app.get_html("/admin/issues").await;
+    assert!(html_page.contains("<td>0</td>\n            <td>1</td>"));
+}
+
+#[tokio::test]
+async fn links_are_left_alone_when_the_issue_opts_out() {
+    let app = spawn_app().await;
+    app.create_confirmed_subscriber("ursula@example.com").await;
+    app.log_in().await;
+
+    let html = publish(&app, serde_json::json!({"disable_click_tracking": true})).await;
+
+    assert!(tracking_path(&html, "/t/c/").is_none());
+    assert!(html.contains(r#"href="https://example.com/post?a=1&amp;b=2""#));
+}
+
+#[tokio::test]
+async fn unsigned_click_links_are_not_followed() {
+    let app = spawn_app().await;
+    app.create_confirmed_subscriber("ursula@example.com").await;
+    app.log_in().await;
+    let html = publish(&app, serde_json::json!({})).await;
+    let click_path = tracking_path(&html, "/t/c/").unwrap();
+    let (_, tag) = click_path.rsplit_once('.').unwrap();
+    // The same signature on a message pointing somewhere else.
+    let forged = format!(
+        "/t/c/{}.{}",
+        URL_SAFE_NO_PAD.encode(
+            "click\n00000000-0000-0000-0000-000000000000\n00000000-0000-0000-0000-000000000000\nhttps://evil.example"
+        ),
+        tag
+    );
+
+    let response = no_redirects()
+        .get(format!("{}{}", app.address, forged))
+        .send()
+        .await
+        .unwrap();
+
+    assert_eq!(response.status().as_u16(), 400);
+    assert!(response.headers().get("Location").is_none());
+    let clicks = sqlx::query!("SELECT count(*) AS count FROM issue_clicks")
+        .fetch_one(&app.db_pool)
+        .await
+        .unwrap();
+    assert_eq!(clicks.count, Some(0));
+}
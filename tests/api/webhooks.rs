@@ -0,0 +1,183 @@
+use crate::helpers::{spawn_app, TestApp};
+use wiremock::matchers::{any, path};
+use wiremock::{Mock, ResponseTemplate};
+
+const EMAIL: &str = "ursula@example.com";
+
+async fn delivery_status(app: &TestApp) -> String {
+    sqlx::query!("SELECT status FROM issue_deliveries")
+        .fetch_one(&app.db_pool)
+        .await
+        .unwrap()
+        .status
+}
+
+/// Publishes an issue, which the email provider accepts as
+/// `ISSUE_MESSAGE_ID`.
+async fn publish_issue(app: &TestApp) {
+    let _mock_guard = Mock::given(path("/email"))
+        .respond_with(ResponseTemplate::new(200).set_body_json(serde_json::json!({
+            "MessageID": ISSUE_MESSAGE_ID,
+            "ErrorCode": 0,
+        })))
+        .expect(1)
+        .mount_as_scoped(&app.email_server)
+        .await;
+    publish(app).await;
+}
+
+async fn publish(app: &TestApp) {
+    app.post_login(&serde_json::json!({
+        "username": &app.test_user.username,
+        "password": &app.test_user.password
+    }))
+    .await;
+    let response = app
+        .post_newsletters(&serde_json::json!({
+            "title": "Spring Update",
+            "text": "Hello",
+            "html": "<p>Hello</p>",
+        }))
+        .await;
+    assert_eq!(response.status().as_u16(), 200);
+}
+
+async fn post_webhook(app: &TestApp, provider: &str, body: serde_json::Value) -> reqwest::Response {
+    reqwest::Client::new()
+        .post(format!("{}/webhooks/email/{}", app.address, provider))
+        .basic_auth("postmark", Some("a-shared-webhook-secret"))
+        .json(&body)
+        .send()
+        .await
+        .unwrap()
+}
+
+const ISSUE_MESSAGE_ID: &str = "b7bc2f4a-0a9e-4b4e-a3c5-62b6e1d9f0a1";
+
+fn bounce(bounce_type: &str, message_id: &str) -> serde_json::Value {
+    serde_json::json!({
+        "RecordType": "Bounce",
+        "Type": bounce_type,
+        "TypeCode": 1,
+        "MessageID": message_id,
+        "Email": EMAIL,
+        "BouncedAt": "2026-10-19T12:00:00Z",
+    })
+}
+
+async fn status(app: &TestApp) -> String {
+    sqlx::query!("SELECT status FROM subscriptions WHERE email = $1", EMAIL)
+        .fetch_one(&app.db_pool)
+        .await
+        .unwrap()
+        .status
+}
+
+#[tokio::test]
+async fn hard_bounced_subscribers_are_not_mailed_again() {
+    let app = spawn_app().await;
+    app.create_confirmed_subscriber(EMAIL).await;
+    publish_issue(&app).await;
+
+    let response = post_webhook(&app, "postmark", bounce("HardBounce", ISSUE_MESSAGE_ID)).await;
+
+    assert_eq!(response.status().as_u16(), 200);
+    assert_eq!(status(&app).await, "bounced");
+    assert_eq!(delivery_status(&app).await, "bounced");
+    Mock::given(any())
+        .respond_with(ResponseTemplate::new(200))
+        .expect(0)
+        .mount(&app.email_server)
+        .await;
+    publish(&app).await;
+}
+
+#[tokio::test]
+async fn soft_bounces_keep_the_subscriber() {
+    let app = spawn_app().await;
+    app.create_confirmed_subscriber(EMAIL).await;
+
+    publish_issue(&app).await;
+
+    let response = post_webhook(&app, "postmark", bounce("SoftBounce", ISSUE_MESSAGE_ID)).await;
+
+    assert_eq!(response.status().as_u16(), 200);
+    assert_eq!(status(&app).await, "confirmed");
+    assert_eq!(delivery_status(&app).await, "delivered");
+}
+
+#[tokio::test]
+async fn bounces_of_other_messages_leave_issue_deliveries_alone() {
+    let app = spawn_app().await;
+    app.create_confirmed_subscriber(EMAIL).await;
+    publish_issue(&app).await;
+
+    // Say, the confirmation email bouncing late.
+    let response = post_webhook(&app, "postmark", bounce("HardBounce", "another-message")).await;
+
+    assert_eq!(response.status().as_u16(), 200);
+    assert_eq!(status(&app).await, "bounced");
+    assert_eq!(delivery_status(&app).await, "delivered");
+}
+
+#[tokio::test]
+async fn complaints_can_be_sent_with_the_secret_header() {
+    let app = spawn_app().await;
+    app.create_confirmed_subscriber(EMAIL).await;
+
+    let response = reqwest::Client::new()
+        .post(format!("{}/webhooks/email/postmark", app.address))
+        .header("X-Webhook-Secret", "a-shared-webhook-secret")
+        .json(&serde_json::json!({
+            "RecordType": "SpamComplaint",
+            "Type": "SpamComplaint",
+            "Email": EMAIL,
+        }))
+        .send()
+        .await
+        .unwrap();
+
+    assert_eq!(response.status().as_u16(), 200);
+    assert_eq!(status(&app).await, "complained");
+    // A bounce afterwards doesn't hide the complaint.
+    post_webhook(&app, "postmark", bounce("HardBounce", ISSUE_MESSAGE_ID)).await;
+    assert_eq!(status(&app).await, "complained");
+}
+
+#[tokio::test]
+async fn webhooks_without_the_secret_are_rejected() {
+    let app = spawn_app().await;
+    app.create_confirmed_subscriber(EMAIL).await;
+
+    for request in [
+        reqwest::Client::new().post(format!("{}/webhooks/email/postmark", app.address)),
+        reqwest::Client::new()
+            .post(format!("{}/webhooks/email/postmark", app.address))
+            .basic_auth("postmark", Some("wrong")),
+        reqwest::Client::new()
+            .post(format!("{}/webhooks/email/postmark", app.address))
+            .basic_auth("mailgun", Some("a-shared-webhook-secret")),
+        reqwest::Client::new()
+            .post(format!("{}/webhooks/email/postmark", app.address))
+            .header("X-Webhook-Secret", "wrong"),
+    ] {
+        let response = request
+            .json(&bounce("HardBounce", ISSUE_MESSAGE_ID))
+            .send()
+            .await
+            .unwrap();
+        assert_eq!(response.status().as_u16(), 401);
+    }
+    assert_eq!(status(&app).await, "confirmed");
+}
+
+#[tokio::test]
+async fn unknown_providers_and_bad_payloads_are_rejected() {
+    let app = spawn_app().await;
+
+    let response = post_webhook(&app, "mailgun", bounce("HardBounce", ISSUE_MESSAGE_ID)).await;
+    assert_eq!(response.status().as_u16(), 404);
+
+    let response = post_webhook(&app, "postmark", serde_json::json!({"Email": EMAIL})).await;
+    assert_eq!(response.status().as_u16(), 400);
+}